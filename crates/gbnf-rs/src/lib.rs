@@ -1,28 +1,94 @@
 use schemars::Schema;
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::collections::{BTreeMap, HashMap};
 use std::fmt::{self, Write};
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-#[derive(Clone, Debug)]
+mod matcher;
+mod parse;
+mod pattern;
+mod targets;
+mod validate;
+
+pub use matcher::Matcher;
+pub use parse::{ParseError, parse};
+pub use targets::RegexError;
+pub use validate::GrammarError;
+
+/// llama.cpp's `json.gbnf`, which defines the `ws`, `string`, `number` and
+/// `value` rules that generated grammars refer to.
+pub const JSON_GRAMMAR: &str = include_str!("json.gbnf");
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Expr {
     Literal(String),
     Ref(String),
+    /// A character class, given as inclusive ranges.
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
     Seq(Vec<Expr>),
     Choice(Vec<Expr>),
     Repeat(Box<Expr>),
     Optional(Box<Expr>),
+    /// Between `min` and `max` (unbounded when `None`) repetitions.
+    Bounded {
+        expr: Box<Expr>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+impl Expr {
+    /// Repeats `expr` between `min` and `max` times, using the shortest
+    /// notation.
+    pub fn repeat(expr: Expr, min: usize, max: Option<usize>) -> Expr {
+        match (min, max) {
+            (0, None) => Expr::Repeat(Box::new(expr)),
+            (0, Some(1)) => Expr::Optional(Box::new(expr)),
+            (1, Some(1)) => expr,
+            _ => Expr::Bounded {
+                expr: Box::new(expr),
+                min,
+                max,
+            },
+        }
+    }
+
+    fn class(ranges: &[(char, char)]) -> Expr {
+        Expr::Class {
+            negated: false,
+            ranges: ranges.to_vec(),
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Rule {
     pub name: String,
     pub expr: Expr,
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Grammar {
     pub rules: Vec<Rule>,
+    /// Schema keywords that could not be expressed, prefixed with the JSON
+    /// pointer of the schema they appear in. The grammar is more permissive
+    /// than the schema where they apply.
+    pub warnings: Vec<String>,
+}
+
+fn write_escaped(f: &mut fmt::Formatter<'_>, ch: char, specials: &[char]) -> fmt::Result {
+    match ch {
+        '\n' => f.write_str("\\n"),
+        '\r' => f.write_str("\\r"),
+        '\t' => f.write_str("\\t"),
+        c if c.is_control() && (c as u32) <= 0xFF => write!(f, "\\x{:02X}", c as u32),
+        c if c.is_control() => write!(f, "\\u{:04X}", c as u32),
+        c if specials.contains(&c) => write!(f, "\\x{:02X}", c as u32),
+        c => f.write_char(c),
+    }
 }
 
 impl fmt::Display for Expr {
@@ -33,18 +99,48 @@ impl fmt::Display for Expr {
                 for ch in s.chars() {
                     if ch == '"' || ch == '\\' {
                         f.write_char('\\')?;
+                        f.write_char(ch)?;
+                    } else {
+                        write_escaped(f, ch, &[])?;
                     }
-                    f.write_char(ch)?;
                 }
                 f.write_char('"')
             }
             Expr::Ref(s) => f.write_str(s),
+            Expr::Class { negated, ranges } => {
+                f.write_char('[')?;
+                if *negated {
+                    f.write_char('^')?;
+                }
+                for &(start, end) in ranges {
+                    for (i, ch) in [start, end].into_iter().enumerate() {
+                        if i == 1 {
+                            if start == end {
+                                break;
+                            }
+                            f.write_char('-')?;
+                        }
+                        match ch {
+                            '\\' | ']' | '[' => {
+                                f.write_char('\\')?;
+                                f.write_char(ch)?;
+                            }
+                            c => write_escaped(f, c, &['-', '^'])?,
+                        }
+                    }
+                }
+                f.write_char(']')
+            }
             Expr::Seq(v) => {
                 for (i, e) in v.iter().enumerate() {
                     if i > 0 {
                         f.write_char(' ')?;
                     }
-                    write!(f, "{}", e)?;
+                    if matches!(e, Expr::Choice(_)) {
+                        write!(f, "({})", e)?;
+                    } else {
+                        write!(f, "{}", e)?;
+                    }
                 }
                 Ok(())
             }
@@ -59,6 +155,12 @@ impl fmt::Display for Expr {
             }
             Expr::Repeat(e) => write!(f, "({})*", e),
             Expr::Optional(e) => write!(f, "({})?", e),
+            Expr::Bounded { expr, min, max } => match (min, max) {
+                (1, None) => write!(f, "({})+", expr),
+                (min, None) => write!(f, "({}){{{},}}", expr, min),
+                (min, Some(max)) if min == max => write!(f, "({}){{{}}}", expr, min),
+                (min, Some(max)) => write!(f, "({}){{{},{}}}", expr, min, max),
+            },
         }
     }
 }
@@ -81,10 +183,127 @@ impl fmt::Display for Grammar {
     }
 }
 
+/// Keywords that only describe a schema and never constrain it.
+const ANNOTATIONS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$comment",
+    "$defs",
+    "definitions",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "deprecated",
+    "readOnly",
+    "writeOnly",
+    "format",
+];
+
+/// Keywords the generator interprets, some of them only in part.
+const SUPPORTED: &[&str] = &[
+    "$ref",
+    "type",
+    "nullable",
+    "enum",
+    "const",
+    "anyOf",
+    "oneOf",
+    "allOf",
+    "properties",
+    "required",
+    "additionalProperties",
+    "items",
+    "minItems",
+    "maxItems",
+    "minLength",
+    "maxLength",
+    "pattern",
+    "minimum",
+];
+
+/// State of a single [`Generator::generate`] call.
+struct Context<'a> {
+    /// The rule for the whole schema, referred to by `"$ref": "#"`.
+    root: String,
+    defs: &'a BTreeMap<String, Value>,
+    /// Rules generated for `$defs` entries, by name.
+    cache: HashMap<String, String>,
+    /// Rules shared by the generated expressions, by purpose.
+    helpers: HashMap<&'static str, String>,
+    grammar: Grammar,
+}
+
+impl Context<'_> {
+    fn warn(&mut self, path: &str, message: impl fmt::Display) {
+        self.grammar.warnings.push(format!("{path}: {message}"));
+    }
+}
+
+/// Appends a JSON pointer segment to `path`.
+fn child(path: &str, segment: impl fmt::Display) -> String {
+    let segment = segment.to_string().replace('~', "~0").replace('/', "~1");
+    format!("{path}/{segment}")
+}
+
+/// A literal matching the compact JSON text of `value`.
+fn json_literal(value: &Value) -> Expr {
+    Expr::Literal(value.to_string())
+}
+
+/// Matches a JSON escape sequence, e.g. `\n` or `é`.
+fn escape_expr() -> Expr {
+    Expr::Seq(vec![
+        Expr::Literal("\\".into()),
+        Expr::Choice(vec![
+            Expr::class(&[
+                ('"', '"'),
+                ('\\', '\\'),
+                ('/', '/'),
+                ('b', 'b'),
+                ('f', 'f'),
+                ('n', 'n'),
+                ('r', 'r'),
+                ('t', 't'),
+            ]),
+            Expr::Seq(vec![
+                Expr::Literal("u".into()),
+                Expr::repeat(
+                    Expr::class(&[('0', '9'), ('a', 'f'), ('A', 'F')]),
+                    4,
+                    Some(4),
+                ),
+            ]),
+        ]),
+    ])
+}
+
+fn digits_expr(negative: bool) -> Expr {
+    let mut seq = Vec::new();
+    if negative {
+        seq.push(Expr::Optional(Box::new(Expr::Literal("-".into()))));
+    }
+    seq.push(Expr::Choice(vec![
+        Expr::Literal("0".into()),
+        Expr::Seq(vec![
+            Expr::class(&[('1', '9')]),
+            Expr::repeat(Expr::class(&[('0', '9')]), 0, Some(15)),
+        ]),
+    ]));
+    seq.push(Expr::Ref("ws".into()));
+    Expr::Seq(seq)
+}
+
 pub struct Generator {
     counter: AtomicUsize,
 }
 
+impl Default for Generator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Generator {
     pub fn new() -> Self {
         Self {
@@ -111,21 +330,30 @@ impl Generator {
         out
     }
 
+    /// Generates the rules for values matching `schema`, the first of which
+    /// is named after `name`.
+    ///
+    /// The rules refer to `ws`, `string`, `number` and `value` from
+    /// [`JSON_GRAMMAR`], which must be part of the final grammar. Object
+    /// properties are expected in a fixed order: the required ones, then the
+    /// optional ones, each sorted by name.
     pub fn generate(&self, name: &str, schema: &Schema) -> Grammar {
-        let mut grammar = Grammar::default();
-        let defs: BTreeMap<String, Schema> = schema
+        let defs: BTreeMap<String, Value> = schema
             .as_object()
             .and_then(|o| o.get("$defs").or_else(|| o.get("definitions")))
             .and_then(Value::as_object)
-            .map(|m| {
-                m.iter()
-                    .filter_map(|(k, v)| v.clone().try_into().ok().map(|s| (k.clone(), s)))
-                    .collect()
-            })
+            .map(|m| m.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
             .unwrap_or_default();
-        let mut cache = HashMap::new();
         let rule_name = format!("{}-{}", self.sanitize(name), self.unique());
-        let expr = self.expr_from_schema(schema, &defs, &mut cache, &mut grammar);
+        let mut cx = Context {
+            root: rule_name.clone(),
+            defs: &defs,
+            cache: HashMap::new(),
+            helpers: HashMap::new(),
+            grammar: Grammar::default(),
+        };
+        let expr = self.expr_from_schema(schema.as_value(), "#", &mut cx);
+        let mut grammar = cx.grammar;
         grammar.rules.insert(
             0,
             Rule {
@@ -150,72 +378,249 @@ impl Generator {
         }
     }
 
-    fn expr_from_schema(
-        &self,
-        schema: &Schema,
-        defs: &BTreeMap<String, Schema>,
-        cache: &mut HashMap<String, String>,
-        grammar: &mut Grammar,
-    ) -> Expr {
-        if let Some(obj) = schema.as_object() {
-            if let Some(reference) = obj.get("$ref").and_then(Value::as_str) {
-                if let Some(name) = reference
-                    .strip_prefix("#/$defs/")
-                    .or_else(|| reference.strip_prefix("#/definitions/"))
-                {
-                    if let Some(existing) = cache.get(name) {
-                        return Expr::Ref(existing.clone());
-                    }
-                    if let Some(resolved) = defs.get(name) {
-                        let expr = self.expr_from_schema(resolved, defs, cache, grammar);
-                        let expr = self.maybe_rule(expr, grammar);
-                        if let Expr::Ref(rule) = &expr {
-                            cache.insert(name.to_string(), rule.clone());
-                        }
-                        return expr;
-                    }
-                }
+    /// A reference to the shared rule `key`, built on first use.
+    fn helper(&self, key: &'static str, cx: &mut Context, build: fn() -> Expr) -> Expr {
+        if let Some(name) = cx.helpers.get(key) {
+            return Expr::Ref(name.clone());
+        }
+        let name = self.unique();
+        cx.helpers.insert(key, name.clone());
+        cx.grammar.rules.push(Rule {
+            name: name.clone(),
+            expr: build(),
+        });
+        Expr::Ref(name)
+    }
+
+    /// A single character of the text of a JSON string.
+    fn char_expr(&self, cx: &mut Context) -> Expr {
+        self.helper("char", cx, || {
+            Expr::Choice(vec![
+                Expr::Class {
+                    negated: true,
+                    ranges: vec![('"', '"'), ('\\', '\\'), ('\x7F', '\x7F'), ('\0', '\x1F')],
+                },
+                escape_expr(),
+            ])
+        })
+    }
+
+    fn expr_from_schema(&self, schema: &Value, path: &str, cx: &mut Context) -> Expr {
+        let obj = match schema {
+            Value::Object(obj) => obj,
+            Value::Bool(true) => return Expr::Ref("value".into()),
+            _ => {
+                cx.warn(path, "only object and `true` schemas are supported");
+                return Expr::Ref("value".into());
+            }
+        };
+        for key in obj.keys() {
+            if !SUPPORTED.contains(&key.as_str()) && !ANNOTATIONS.contains(&key.as_str()) {
+                cx.warn(path, format_args!("`{key}` is not supported"));
             }
         }
 
-        let ty = schema
-            .as_object()
-            .and_then(|o| o.get("type"))
-            .and_then(|t| match t {
-                Value::String(s) => Some(s.clone()),
-                Value::Array(arr) => arr
+        if let Some(reference) = obj.get("$ref").and_then(Value::as_str) {
+            return self.ref_expr(reference, path, cx);
+        }
+        if let Some(value) = obj.get("const") {
+            return json_literal(value);
+        }
+        if let Some(values) = obj.get("enum").and_then(Value::as_array) {
+            return match values.as_slice() {
+                [] => {
+                    cx.warn(path, "empty `enum` is not supported");
+                    Expr::Ref("value".into())
+                }
+                [value] => json_literal(value),
+                values => Expr::Choice(values.iter().map(json_literal).collect()),
+            };
+        }
+        for keyword in ["anyOf", "oneOf"] {
+            if let Some(options) = obj.get(keyword).and_then(Value::as_array) {
+                // Exclusivity cannot be expressed, so oneOf is treated as anyOf.
+                let mut choices: Vec<Expr> = options
                     .iter()
-                    .filter_map(Value::as_str)
-                    .find(|s| *s != "null")
-                    .map(|s| s.to_string()),
-                _ => None,
-            });
+                    .enumerate()
+                    .map(|(i, option)| {
+                        let path = child(&child(path, keyword), i);
+                        let expr = self.expr_from_schema(option, &path, cx);
+                        self.maybe_rule(expr, &mut cx.grammar)
+                    })
+                    .collect();
+                if obj.get("nullable") == Some(&Value::Bool(true)) {
+                    choices.push(Expr::Literal("null".into()));
+                }
+                return match choices.len() {
+                    0 => {
+                        cx.warn(path, format_args!("empty `{keyword}` is not supported"));
+                        Expr::Ref("value".into())
+                    }
+                    1 => choices.remove(0),
+                    _ => Expr::Choice(choices),
+                };
+            }
+        }
+        if let Some(all) = obj.get("allOf").and_then(Value::as_array) {
+            if let [single] = all.as_slice() {
+                return self.expr_from_schema(single, &child(&child(path, "allOf"), 0), cx);
+            }
+            cx.warn(path, "`allOf` with several schemas is not supported");
+            return Expr::Ref("value".into());
+        }
 
-        match ty.as_deref() {
-            Some("string") => Expr::Ref("string".into()),
-            Some("number") | Some("integer") => Expr::Ref("number".into()),
-            Some("boolean") => Expr::Choice(vec![
+        let mut types: Vec<&str> = match obj.get("type") {
+            Some(Value::String(s)) => vec![s.as_str()],
+            Some(Value::Array(arr)) => arr.iter().filter_map(Value::as_str).collect(),
+            _ if obj.contains_key("properties") || obj.contains_key("additionalProperties") => {
+                vec!["object"]
+            }
+            _ if obj.contains_key("items") => vec!["array"],
+            _ => return Expr::Ref("value".into()),
+        };
+        if obj.get("nullable") == Some(&Value::Bool(true)) && !types.contains(&"null") {
+            types.push("null");
+        }
+        let mut choices: Vec<Expr> = types
+            .into_iter()
+            .map(|ty| self.type_expr(ty, obj, path, cx))
+            .collect();
+        match choices.len() {
+            0 => Expr::Ref("value".into()),
+            1 => choices.remove(0),
+            _ => Expr::Choice(choices),
+        }
+    }
+
+    fn ref_expr(&self, reference: &str, path: &str, cx: &mut Context) -> Expr {
+        if reference == "#" {
+            return Expr::Ref(cx.root.clone());
+        }
+        let name = reference
+            .strip_prefix("#/$defs/")
+            .or_else(|| reference.strip_prefix("#/definitions/"));
+        let Some((name, schema)) = name.and_then(|n| cx.defs.get_key_value(n)) else {
+            cx.warn(
+                path,
+                format_args!("`$ref` to {reference} cannot be resolved"),
+            );
+            return Expr::Ref("value".into());
+        };
+        if let Some(existing) = cx.cache.get(name) {
+            return Expr::Ref(existing.clone());
+        }
+        // Name the rule before generating it so that recursive definitions
+        // refer to it.
+        let rule = self.unique();
+        cx.cache.insert(name.clone(), rule.clone());
+        let expr = self.expr_from_schema(schema, reference, cx);
+        cx.grammar.rules.push(Rule {
+            name: rule.clone(),
+            expr,
+        });
+        Expr::Ref(rule)
+    }
+
+    fn type_expr(&self, ty: &str, obj: &Map<String, Value>, path: &str, cx: &mut Context) -> Expr {
+        match ty {
+            "string" => self.string_expr(obj, path, cx),
+            "integer" => self.integer_expr(obj, path, cx),
+            "number" => {
+                if obj.contains_key("minimum") {
+                    cx.warn(path, "`minimum` is not supported for numbers");
+                }
+                Expr::Ref("number".into())
+            }
+            "boolean" => Expr::Choice(vec![
                 Expr::Literal("true".into()),
                 Expr::Literal("false".into()),
             ]),
-            Some("object") => self.object_expr(schema, defs, cache, grammar),
-            Some("array") => self.array_expr(schema, defs, cache, grammar),
-            _ => Expr::Ref("json".into()),
+            "null" => Expr::Literal("null".into()),
+            "object" => self.object_expr(obj, path, cx),
+            "array" => self.array_expr(obj, path, cx),
+            other => {
+                cx.warn(path, format_args!("unknown type `{other}`"));
+                Expr::Ref("value".into())
+            }
         }
     }
 
-    fn object_expr(
-        &self,
-        schema: &Schema,
-        defs: &BTreeMap<String, Schema>,
-        cache: &mut HashMap<String, String>,
-        grammar: &mut Grammar,
-    ) -> Expr {
-        let obj = match schema.as_object() {
-            Some(o) => o,
-            None => return Expr::Ref("json".into()),
+    fn integer_expr(&self, obj: &Map<String, Value>, path: &str, cx: &mut Context) -> Expr {
+        let minimum = obj.get("minimum").and_then(Value::as_f64);
+        match minimum {
+            Some(min) if min > 0.0 => {
+                cx.warn(path, format_args!("`minimum` {min} is only enforced as 0"));
+            }
+            Some(min) if min < 0.0 => {
+                cx.warn(path, "negative `minimum` is not supported");
+            }
+            _ => {}
+        }
+        if minimum.is_some_and(|min| min >= 0.0) {
+            self.helper("unsigned", cx, || digits_expr(false))
+        } else {
+            self.helper("integer", cx, || digits_expr(true))
+        }
+    }
+
+    fn string_expr(&self, obj: &Map<String, Value>, path: &str, cx: &mut Context) -> Expr {
+        let min = obj.get("minLength").and_then(Value::as_u64);
+        let max = obj.get("maxLength").and_then(Value::as_u64);
+        let content = if let Some(regex) = obj.get("pattern").and_then(Value::as_str) {
+            if min.is_some() || max.is_some() {
+                cx.warn(
+                    path,
+                    "`minLength` and `maxLength` are not enforced together with `pattern`",
+                );
+            }
+            let any = self.char_expr(cx);
+            match pattern::pattern_expr(regex, &any) {
+                Ok(expr) => expr,
+                Err(err) => {
+                    cx.warn(path, format_args!("`pattern` is not supported: {err}"));
+                    return Expr::Ref("string".into());
+                }
+            }
+        } else if min.is_some() || max.is_some() {
+            let any = self.char_expr(cx);
+            Expr::repeat(any, min.unwrap_or(0) as usize, max.map(|max| max as usize))
+        } else {
+            return Expr::Ref("string".into());
+        };
+        Expr::Seq(vec![
+            Expr::Literal("\"".into()),
+            content,
+            Expr::Literal("\"".into()),
+            Expr::Ref("ws".into()),
+        ])
+    }
+
+    fn object_expr(&self, obj: &Map<String, Value>, path: &str, cx: &mut Context) -> Expr {
+        let additional = obj.get("additionalProperties");
+        let Some(map) = obj.get("properties").and_then(Value::as_object) else {
+            return match additional {
+                Some(Value::Bool(false)) => Expr::Seq(vec![
+                    Expr::Literal("{".into()),
+                    Expr::Ref("ws".into()),
+                    Expr::Literal("}".into()),
+                ]),
+                Some(schema) => {
+                    let expr =
+                        self.expr_from_schema(schema, &child(path, "additionalProperties"), cx);
+                    let expr = self.maybe_rule(expr, &mut cx.grammar);
+                    self.map_expr(expr)
+                }
+                None => self.map_expr(Expr::Ref("value".into())),
+            };
         };
-        let props = obj.get("properties").and_then(Value::as_object);
+        if let Some(additional) = additional
+            && additional != &Value::Bool(false)
+        {
+            cx.warn(
+                path,
+                "`additionalProperties` is not supported together with `properties`, only the declared properties are allowed",
+            );
+        }
         let required: Vec<String> = obj
             .get("required")
             .and_then(Value::as_array)
@@ -226,117 +631,116 @@ impl Generator {
                     .collect()
             })
             .unwrap_or_default();
+        let properties = child(path, "properties");
         let mut seq = Vec::new();
         seq.push(Expr::Literal("{".into()));
         seq.push(Expr::Ref("ws".into()));
         let mut first = true;
-        if let Some(map) = props {
-            let mut required_props: Vec<_> = map
-                .iter()
-                .filter(|(name, _)| required.contains(*name))
-                .collect();
-            required_props.sort_by(|a, b| a.0.cmp(b.0));
-            for (name, subschema) in required_props {
-                if !first {
-                    seq.push(Expr::Literal(",".into()));
-                    seq.push(Expr::Ref("ws".into()));
-                }
-                first = false;
-                seq.push(Expr::Literal(format!("\"{}\"", name)));
+        let mut required_props: Vec<_> = map
+            .iter()
+            .filter(|(name, _)| required.contains(*name))
+            .collect();
+        required_props.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, subschema) in required_props {
+            if !first {
+                seq.push(Expr::Literal(",".into()));
                 seq.push(Expr::Ref("ws".into()));
-                seq.push(Expr::Literal(":".into()));
-                seq.push(Expr::Ref("ws".into()));
-                let subschema: Schema = subschema.clone().try_into().unwrap_or_default();
-                let expr = self.expr_from_schema(&subschema, defs, cache, grammar);
-                seq.push(self.maybe_rule(expr, grammar));
             }
+            first = false;
+            seq.push(json_literal(&Value::String(name.clone())));
+            seq.push(Expr::Ref("ws".into()));
+            seq.push(Expr::Literal(":".into()));
+            seq.push(Expr::Ref("ws".into()));
+            let expr = self.expr_from_schema(subschema, &child(&properties, name), cx);
+            seq.push(self.maybe_rule(expr, &mut cx.grammar));
+        }
 
-            let mut optional_props: Vec<_> = map
+        let mut optional_props: Vec<_> = map
+            .iter()
+            .filter(|(name, _)| !required.contains(*name))
+            .collect();
+        optional_props.sort_by(|a, b| a.0.cmp(b.0));
+
+        if !optional_props.is_empty() {
+            // Build optional property expressions in fixed order.
+            // When there are required fields already emitted (first == false),
+            // each optional field, if present, must be preceded by a comma.
+            // When no required fields exist (first == true), we construct a
+            // nested optional chain like: opt1 ("," opt2 ("," opt3)?)?
+            // and wrap the whole chain in Optional(...), allowing an empty object.
+
+            // Precompute value expressions for each optional prop so we reuse
+            // the same rule/reference across multiple chains and avoid duplicates.
+            let prepared: Vec<(String, Expr)> = optional_props
                 .iter()
-                .filter(|(name, _)| !required.contains(*name))
+                .map(|(name, subschema)| {
+                    let expr = self.expr_from_schema(subschema, &child(&properties, name), cx);
+                    let expr = self.maybe_rule(expr, &mut cx.grammar);
+                    ((*name).clone(), expr)
+                })
                 .collect();
-            optional_props.sort_by(|a, b| a.0.cmp(b.0));
-
-            if !optional_props.is_empty() {
-                // Build optional property expressions in fixed order.
-                // When there are required fields already emitted (first == false),
-                // each optional field, if present, must be preceded by a comma.
-                // When no required fields exist (first == true), we construct a
-                // nested optional chain like: opt1 ("," opt2 ("," opt3)?)?
-                // and wrap the whole chain in Optional(...), allowing an empty object.
-
-                // Precompute value expressions for each optional prop so we reuse
-                // the same rule/reference across multiple chains and avoid duplicates.
-                let prepared: Vec<(String, Expr)> = optional_props
-                    .iter()
-                    .map(|(name, subschema)| {
-                        let subschema: Schema = (*subschema).clone().try_into().unwrap_or_default();
-                        let expr = self.expr_from_schema(&subschema, defs, cache, grammar);
-                        let expr = self.maybe_rule(expr, grammar);
-                        ((*name).clone(), expr)
-                    })
-                    .collect();
 
-                // Helper to build a single property expr (without any leading comma)
-                let mk_prop_idx = |idx: usize| {
-                    let (ref name, ref expr) = prepared[idx];
-                    Expr::Seq(vec![
-                        Expr::Literal(format!("\"{}\"", name)),
-                        Expr::Ref("ws".into()),
-                        Expr::Literal(":".into()),
-                        Expr::Ref("ws".into()),
-                        expr.clone(),
-                    ])
-                };
+            // Helper to build a single property expr (without any leading comma)
+            let mk_prop_idx = |idx: usize| {
+                let (ref name, ref expr) = prepared[idx];
+                Expr::Seq(vec![
+                    json_literal(&Value::String(name.clone())),
+                    Expr::Ref("ws".into()),
+                    Expr::Literal(":".into()),
+                    Expr::Ref("ws".into()),
+                    expr.clone(),
+                ])
+            };
 
-                if first {
-                    // No requireds: allow starting at any optional and skipping
-                    // middles while preserving order and uniqueness.
-                    // Build chain_i for each i: prop(i) followed by optional
-                    // comma + choice of any chain_j where j > i.
-                    let n = prepared.len();
-                    if n > 0 {
-                        let mut chains: Vec<Expr> = vec![Expr::Literal(String::new()); n];
-                        // Build from the end to the front so later chains exist.
-                        for i in (0..n).rev() {
-                            let prop = mk_prop_idx(i);
-                            let mut choices: Vec<Expr> = Vec::new();
-                            for j in (i + 1)..n {
-                                choices.push(chains[j].clone());
-                            }
-                            let chain_i = if choices.is_empty() {
-                                prop
+            if first {
+                // No requireds: allow starting at any optional and skipping
+                // middles while preserving order and uniqueness.
+                // Build chain_i for each i: prop(i) followed by optional
+                // comma + choice of any chain_j where j > i.
+                let n = prepared.len();
+                if n > 0 {
+                    let mut chains: Vec<Expr> = vec![Expr::Literal(String::new()); n];
+                    // Build from the end to the front so later chains exist.
+                    for i in (0..n).rev() {
+                        let prop = mk_prop_idx(i);
+                        let mut choices: Vec<Expr> = chains[i + 1..].to_vec();
+                        let chain_i = if choices.is_empty() {
+                            prop
+                        } else {
+                            let next = if choices.len() == 1 {
+                                choices.remove(0)
                             } else {
-                                Expr::Seq(vec![
-                                    prop,
-                                    Expr::Optional(Box::new(Expr::Seq(vec![
-                                        Expr::Ref("ws".into()),
-                                        Expr::Literal(",".into()),
-                                        Expr::Ref("ws".into()),
-                                        Expr::Choice(choices),
-                                    ]))),
-                                ])
+                                Expr::Choice(choices)
                             };
-                            chains[i] = chain_i;
-                        }
-                        let top_choice = if n == 1 {
-                            chains.remove(0)
-                        } else {
-                            Expr::Choice(chains)
+                            Expr::Seq(vec![
+                                prop,
+                                Expr::Optional(Box::new(Expr::Seq(vec![
+                                    Expr::Ref("ws".into()),
+                                    Expr::Literal(",".into()),
+                                    Expr::Ref("ws".into()),
+                                    next,
+                                ]))),
+                            ])
                         };
-                        seq.push(Expr::Optional(Box::new(top_choice)));
-                    }
-                } else {
-                    // Requireds present: add each optional as an independent ("," prop)?
-                    for (idx, _) in prepared.iter().enumerate() {
-                        let prop = mk_prop_idx(idx);
-                        seq.push(Expr::Optional(Box::new(Expr::Seq(vec![
-                            Expr::Ref("ws".into()),
-                            Expr::Literal(",".into()),
-                            Expr::Ref("ws".into()),
-                            prop,
-                        ]))));
+                        chains[i] = chain_i;
                     }
+                    let top_choice = if n == 1 {
+                        chains.remove(0)
+                    } else {
+                        Expr::Choice(chains)
+                    };
+                    seq.push(Expr::Optional(Box::new(top_choice)));
+                }
+            } else {
+                // Requireds present: add each optional as an independent ("," prop)?
+                for (idx, _) in prepared.iter().enumerate() {
+                    let prop = mk_prop_idx(idx);
+                    seq.push(Expr::Optional(Box::new(Expr::Seq(vec![
+                        Expr::Ref("ws".into()),
+                        Expr::Literal(",".into()),
+                        Expr::Ref("ws".into()),
+                        prop,
+                    ]))));
                 }
             }
         }
@@ -345,35 +749,80 @@ impl Generator {
         Expr::Seq(seq)
     }
 
-    fn array_expr(
-        &self,
-        schema: &Schema,
-        defs: &BTreeMap<String, Schema>,
-        cache: &mut HashMap<String, String>,
-        grammar: &mut Grammar,
-    ) -> Expr {
-        let items_schema = schema.as_object().and_then(|o| o.get("items")).cloned();
-        let items_schema: Schema = match items_schema {
-            Some(s) => s.try_into().unwrap_or_default(),
-            None => return Expr::Ref("json".into()),
-        };
-        let item_expr = self.expr_from_schema(&items_schema, defs, cache, grammar);
-        let item_expr = self.maybe_rule(item_expr, grammar);
+    /// An object with any keys and values matching `value`.
+    fn map_expr(&self, value: Expr) -> Expr {
+        let entry = Expr::Seq(vec![
+            Expr::Ref("string".into()),
+            Expr::Literal(":".into()),
+            Expr::Ref("ws".into()),
+            value,
+        ]);
         Expr::Seq(vec![
-            Expr::Literal("[".into()),
+            Expr::Literal("{".into()),
             Expr::Ref("ws".into()),
             Expr::Optional(Box::new(Expr::Seq(vec![
-                item_expr.clone(),
+                entry.clone(),
                 Expr::Repeat(Box::new(Expr::Seq(vec![
+                    Expr::Ref("ws".into()),
                     Expr::Literal(",".into()),
                     Expr::Ref("ws".into()),
-                    item_expr,
+                    entry,
                 ]))),
             ]))),
             Expr::Ref("ws".into()),
-            Expr::Literal("]".into()),
+            Expr::Literal("}".into()),
         ])
     }
+
+    fn array_expr(&self, obj: &Map<String, Value>, path: &str, cx: &mut Context) -> Expr {
+        let min = obj.get("minItems").and_then(Value::as_u64).unwrap_or(0) as usize;
+        let mut max = obj
+            .get("maxItems")
+            .and_then(Value::as_u64)
+            .map(|max| max as usize);
+        let item_expr = match obj.get("items") {
+            Some(Value::Bool(false)) => {
+                max = Some(0);
+                Expr::Ref("value".into())
+            }
+            Some(schema) => {
+                let expr = self.expr_from_schema(schema, &child(path, "items"), cx);
+                self.maybe_rule(expr, &mut cx.grammar)
+            }
+            None => Expr::Ref("value".into()),
+        };
+        if max.is_some_and(|max| max < min) {
+            cx.warn(path, "`maxItems` is less than `minItems`");
+            max = Some(min);
+        }
+        let mut seq = vec![Expr::Literal("[".into()), Expr::Ref("ws".into())];
+        if max != Some(0) {
+            let items = if max == Some(1) {
+                item_expr
+            } else {
+                Expr::Seq(vec![
+                    item_expr.clone(),
+                    Expr::repeat(
+                        Expr::Seq(vec![
+                            Expr::Literal(",".into()),
+                            Expr::Ref("ws".into()),
+                            item_expr,
+                        ]),
+                        min.saturating_sub(1),
+                        max.map(|max| max - 1),
+                    ),
+                ])
+            };
+            seq.push(if min == 0 {
+                Expr::Optional(Box::new(items))
+            } else {
+                items
+            });
+        }
+        seq.push(Expr::Ref("ws".into()));
+        seq.push(Expr::Literal("]".into()));
+        Expr::Seq(seq)
+    }
 }
 
 #[cfg(test)]
@@ -396,7 +845,9 @@ mod tests {
         let generator = Generator::new();
         let grammar = generator.generate("params", &schema);
         insta::assert_snapshot!(grammar.to_string(), @r###"
-params-r0 ::= "{" ws "\"file_path\"" ws ":" ws string "," ws "\"new_string\"" ws ":" ws string "," ws "\"old_string\"" ws ":" ws string (ws "," ws "\"expected_replacements\"" ws ":" ws number)? ws "}"
+params-r0 ::= "{" ws "\"file_path\"" ws ":" ws string "," ws "\"new_string\"" ws ":" ws string "," ws "\"old_string\"" ws ":" ws string (ws "," ws "\"expected_replacements\"" ws ":" ws r2)? ws "}"
+r1 ::= ("0" | [1-9] ([0-9]){0,15}) ws
+r2 ::= r1 | "null"
 "###);
     }
 
@@ -415,9 +866,9 @@ params-r0 ::= "{" ws "\"file_path\"" ws ":" ws string "," ws "\"new_string\"" ws
         let grammar = generator.generate("params", &schema);
         insta::assert_snapshot!(grammar.to_string(), @r###"
 params-r0 ::= "{" ws "\"path\"" ws ":" ws string (ws "," ws "\"ignore\"" ws ":" ws r1)? (ws "," ws "\"include\"" ws ":" ws r2)? (ws "," ws "\"include_hidden\"" ws ":" ws r3)? ws "}"
-r1 ::= "[" ws (string ("," ws string)*)? ws "]"
-r2 ::= "[" ws (string ("," ws string)*)? ws "]"
-r3 ::= "true" | "false"
+r1 ::= "[" ws (string ("," ws string)*)? ws "]" | "null"
+r2 ::= "[" ws (string ("," ws string)*)? ws "]" | "null"
+r3 ::= "true" | "false" | "null"
 "###);
     }
 
@@ -434,9 +885,10 @@ r3 ::= "true" | "false"
         let generator = Generator::new();
         let grammar = generator.generate("params", &schema);
         insta::assert_snapshot!(grammar.to_string(), @r###"
-params-r0 ::= "{" ws ("\"a\"" ws ":" ws string (ws "," ws "\"b\"" ws ":" ws r1 (ws "," ws "\"c\"" ws ":" ws r2)? | "\"c\"" ws ":" ws r2)? | "\"b\"" ws ":" ws r1 (ws "," ws "\"c\"" ws ":" ws r2)? | "\"c\"" ws ":" ws r2)? ws "}"
-r1 ::= "true" | "false"
-r2 ::= "[" ws (string ("," ws string)*)? ws "]"
+params-r0 ::= "{" ws ("\"a\"" ws ":" ws r1 (ws "," ws ("\"b\"" ws ":" ws r2 (ws "," ws "\"c\"" ws ":" ws r3)? | "\"c\"" ws ":" ws r3))? | "\"b\"" ws ":" ws r2 (ws "," ws "\"c\"" ws ":" ws r3)? | "\"c\"" ws ":" ws r3)? ws "}"
+r1 ::= string | "null"
+r2 ::= "true" | "false" | "null"
+r3 ::= "[" ws (string ("," ws string)*)? ws "]" | "null"
 "###);
     }
 
@@ -460,6 +912,261 @@ r2 ::= "[" ws (string ("," ws string)*)? ws "]"
 params-r0 ::= "{" ws "\"item\"" ws ":" ws r1 "," ws "\"items\"" ws ":" ws r2 ws "}"
 r1 ::= "{" ws "\"value\"" ws ":" ws string ws "}"
 r2 ::= "[" ws (r1 ("," ws r1)*)? ws "]"
+"###);
+    }
+
+    fn generate(schema: Value) -> Grammar {
+        let schema: Schema = schema.try_into().unwrap();
+        Generator::new().generate("params", &schema)
+    }
+
+    #[test]
+    fn enum_and_const_grammar() {
+        let grammar = generate(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "mode": { "type": "string", "enum": ["fast", "slow \"quoted\""] },
+                "level": { "enum": [1, null, true] },
+                "kind": { "const": "file" }
+            },
+            "required": ["mode", "level", "kind"]
+        }));
+        insta::assert_snapshot!(grammar.to_string(), @r###"
+params-r0 ::= "{" ws "\"kind\"" ws ":" ws "\"file\"" "," ws "\"level\"" ws ":" ws r1 "," ws "\"mode\"" ws ":" ws r2 ws "}"
+r1 ::= "1" | "null" | "true"
+r2 ::= "\"fast\"" | "\"slow \\\"quoted\\\"\""
+"###);
+        assert!(grammar.warnings.is_empty());
+    }
+
+    #[derive(JsonSchema)]
+    #[serde(untagged)]
+    enum Target {
+        Path(String),
+        Line(u32),
+    }
+
+    #[derive(JsonSchema)]
+    struct Choices {
+        target: Target,
+        item: Option<Item>,
+    }
+
+    #[test]
+    fn any_of_and_one_of_grammar() {
+        let schema = schemars::schema_for!(Choices);
+        let grammar = Generator::new().generate("params", &schema);
+        insta::assert_snapshot!(grammar.to_string(), @r###"
+params-r0 ::= "{" ws "\"target\"" ws ":" ws r1 (ws "," ws "\"item\"" ws ":" ws r4)? ws "}"
+r2 ::= ("0" | [1-9] ([0-9]){0,15}) ws
+r1 ::= string | r2
+r3 ::= "{" ws "\"value\"" ws ":" ws string ws "}"
+r4 ::= r3 | "null"
+"###);
+
+        let grammar = generate(serde_json::json!({
+            "oneOf": [{ "type": "boolean" }, { "type": "string", "maxLength": 1 }]
+        }));
+        insta::assert_snapshot!(grammar.to_string(), @r###"
+params-r0 ::= r1 | r3
+r1 ::= "true" | "false"
+r2 ::= [^"\\\x7F\x00-\x1F] | "\\" (["\\/bfnrt] | "u" ([0-9a-fA-F]){4})
+r3 ::= "\"" (r2)? "\"" ws
+"###);
+    }
+
+    #[test]
+    fn nullable_types_grammar() {
+        let grammar = generate(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "a": { "type": ["string", "null"] },
+                "b": { "type": "number", "nullable": true },
+                "c": { "type": ["boolean", "integer"] }
+            },
+            "required": ["a", "b", "c"]
+        }));
+        insta::assert_snapshot!(grammar.to_string(), @r###"
+params-r0 ::= "{" ws "\"a\"" ws ":" ws r1 "," ws "\"b\"" ws ":" ws r2 "," ws "\"c\"" ws ":" ws r4 ws "}"
+r1 ::= string | "null"
+r2 ::= number | "null"
+r3 ::= ("-")? ("0" | [1-9] ([0-9]){0,15}) ws
+r4 ::= "true" | "false" | r3
+"###);
+    }
+
+    #[test]
+    fn integer_and_number_grammar() {
+        let grammar = generate(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "count": { "type": "integer", "format": "uint", "minimum": 0 },
+                "offset": { "type": "integer" },
+                "line": { "type": "integer", "minimum": 1 },
+                "ratio": { "type": "number" }
+            },
+            "required": ["count", "offset", "line", "ratio"]
+        }));
+        insta::assert_snapshot!(grammar.to_string(), @r###"
+params-r0 ::= "{" ws "\"count\"" ws ":" ws r1 "," ws "\"line\"" ws ":" ws r1 "," ws "\"offset\"" ws ":" ws r2 "," ws "\"ratio\"" ws ":" ws number ws "}"
+r1 ::= ("0" | [1-9] ([0-9]){0,15}) ws
+r2 ::= ("-")? ("0" | [1-9] ([0-9]){0,15}) ws
+"###);
+        insta::assert_snapshot!(grammar.warnings.join("\n"), @r###"
+#/properties/line: `minimum` 1 is only enforced as 0
+"###);
+    }
+
+    #[test]
+    fn empty_choices_grammar() {
+        let grammar = generate(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "kind": { "enum": [] },
+                "either": { "anyOf": [] },
+                "one": { "oneOf": [] },
+                "maybe": { "anyOf": [], "nullable": true }
+            },
+            "required": ["kind", "either", "one", "maybe"]
+        }));
+        insta::assert_snapshot!(grammar.to_string(), @r###"
+params-r0 ::= "{" ws "\"either\"" ws ":" ws value "," ws "\"kind\"" ws ":" ws value "," ws "\"maybe\"" ws ":" ws "null" "," ws "\"one\"" ws ":" ws value ws "}"
+"###);
+        insta::assert_snapshot!(grammar.warnings.join("\n"), @r###"
+#/properties/either: empty `anyOf` is not supported
+#/properties/kind: empty `enum` is not supported
+#/properties/one: empty `oneOf` is not supported
+"###);
+    }
+
+    #[test]
+    fn string_length_grammar() {
+        let grammar = generate(serde_json::json!({
+            "type": "array",
+            "items": { "type": "string", "minLength": 1, "maxLength": 8 }
+        }));
+        insta::assert_snapshot!(grammar.to_string(), @r###"
+params-r0 ::= "[" ws (r2 ("," ws r2)*)? ws "]"
+r1 ::= [^"\\\x7F\x00-\x1F] | "\\" (["\\/bfnrt] | "u" ([0-9a-fA-F]){4})
+r2 ::= "\"" (r1){1,8} "\"" ws
+"###);
+    }
+
+    #[test]
+    fn string_pattern_grammar() {
+        let grammar = generate(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "string", "pattern": "^[a-z]+-\\d{2,4}$" },
+                "free": { "type": "string", "pattern": "(?<=a)b" }
+            },
+            "required": ["id", "free"]
+        }));
+        insta::assert_snapshot!(grammar.to_string(), @r###"
+params-r0 ::= "{" ws "\"free\"" ws ":" ws string "," ws "\"id\"" ws ":" ws r2 ws "}"
+r1 ::= [^"\\\x7F\x00-\x1F] | "\\" (["\\/bfnrt] | "u" ([0-9a-fA-F]){4})
+r2 ::= "\"" ([a-z])+ "-" ([0-9]){2,4} "\"" ws
+"###);
+        insta::assert_snapshot!(grammar.warnings.join("\n"), @r###"
+#/properties/free: `pattern` is not supported: lookaround and named groups are not supported
+"###);
+    }
+
+    #[test]
+    fn array_bounds_grammar() {
+        let grammar = generate(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "one": { "type": "array", "items": { "type": "boolean" }, "maxItems": 1 },
+                "pair": { "type": "array", "items": { "type": "boolean" }, "minItems": 2, "maxItems": 2 },
+                "some": { "type": "array", "items": { "type": "boolean" }, "minItems": 1 },
+                "none": { "type": "array", "items": false },
+                "any": { "type": "array" }
+            },
+            "required": ["one", "pair", "some", "none", "any"]
+        }));
+        insta::assert_snapshot!(grammar.to_string(), @r###"
+params-r0 ::= "{" ws "\"any\"" ws ":" ws r1 "," ws "\"none\"" ws ":" ws r2 "," ws "\"one\"" ws ":" ws r4 "," ws "\"pair\"" ws ":" ws r6 "," ws "\"some\"" ws ":" ws r8 ws "}"
+r1 ::= "[" ws (value ("," ws value)*)? ws "]"
+r2 ::= "[" ws ws "]"
+r3 ::= "true" | "false"
+r4 ::= "[" ws (r3)? ws "]"
+r5 ::= "true" | "false"
+r6 ::= "[" ws r5 "," ws r5 ws "]"
+r7 ::= "true" | "false"
+r8 ::= "[" ws r7 ("," ws r7)* ws "]"
+"###);
+    }
+
+    #[test]
+    fn additional_properties_grammar() {
+        let grammar = generate(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "env": { "type": "object", "additionalProperties": { "type": "string" } },
+                "meta": { "type": "object" },
+                "empty": { "type": "object", "additionalProperties": false }
+            },
+            "required": ["env", "meta", "empty"]
+        }));
+        insta::assert_snapshot!(grammar.to_string(), @r###"
+params-r0 ::= "{" ws "\"empty\"" ws ":" ws r1 "," ws "\"env\"" ws ":" ws r2 "," ws "\"meta\"" ws ":" ws r3 ws "}"
+r1 ::= "{" ws "}"
+r2 ::= "{" ws (string ":" ws string (ws "," ws string ":" ws string)*)? ws "}"
+r3 ::= "{" ws (string ":" ws value (ws "," ws string ":" ws value)*)? ws "}"
+"###);
+    }
+
+    #[derive(JsonSchema)]
+    struct Tree {
+        name: String,
+        children: Vec<Tree>,
+    }
+
+    #[test]
+    fn recursive_ref_grammar() {
+        let schema = schemars::schema_for!(Tree);
+        let grammar = Generator::new().generate("params", &schema);
+        insta::assert_snapshot!(grammar.to_string(), @r###"
+params-r0 ::= "{" ws "\"children\"" ws ":" ws r1 "," ws "\"name\"" ws ":" ws string ws "}"
+r1 ::= "[" ws (params-r0 ("," ws params-r0)*)? ws "]"
+"###);
+        assert!(grammar.warnings.is_empty());
+    }
+
+    #[test]
+    fn unsupported_keywords_are_reported() {
+        let grammar = generate(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "size": { "type": "number", "minimum": 1, "maximum": 10, "multipleOf": 2 },
+                "tags": { "type": "array", "uniqueItems": true, "items": { "type": "string" } },
+                "both": { "allOf": [{ "type": "string" }, { "minLength": 2 }] },
+                "ref": { "$ref": "#/$defs/Missing" },
+                "extra": {
+                    "type": "object",
+                    "properties": { "a~/b": { "not": { "type": "null" } } },
+                    "additionalProperties": { "type": "string" },
+                    "minProperties": 1
+                }
+            },
+            "required": ["size", "tags", "both", "ref", "extra"]
+        }));
+        insta::assert_snapshot!(grammar.to_string(), @r###"
+params-r0 ::= "{" ws "\"both\"" ws ":" ws value "," ws "\"extra\"" ws ":" ws r1 "," ws "\"ref\"" ws ":" ws value "," ws "\"size\"" ws ":" ws number "," ws "\"tags\"" ws ":" ws r2 ws "}"
+r1 ::= "{" ws ("\"a~/b\"" ws ":" ws value)? ws "}"
+r2 ::= "[" ws (string ("," ws string)*)? ws "]"
+"###);
+        insta::assert_snapshot!(grammar.warnings.join("\n"), @r###"
+#/properties/both: `allOf` with several schemas is not supported
+#/properties/extra: `minProperties` is not supported
+#/properties/extra: `additionalProperties` is not supported together with `properties`, only the declared properties are allowed
+#/properties/extra/properties/a~0~1b: `not` is not supported
+#/properties/ref: `$ref` to #/$defs/Missing cannot be resolved
+#/properties/size: `maximum` is not supported
+#/properties/size: `multipleOf` is not supported
+#/properties/size: `minimum` is not supported for numbers
+#/properties/tags: `uniqueItems` is not supported
 "###);
     }
 }
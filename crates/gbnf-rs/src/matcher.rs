@@ -0,0 +1,409 @@
+//! Matching of strings against a grammar, to test grammars without a model.
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::rc::Rc;
+
+use crate::{Expr, Grammar, GrammarError};
+
+/// Checks whether strings are accepted by a grammar.
+///
+/// Matching follows every alternative, so it is meant for tests rather than
+/// long inputs.
+pub struct Matcher<'a> {
+    rules: HashMap<&'a str, &'a Expr>,
+}
+
+/// The positions where the matches of an expression end.
+type Ends = BTreeSet<usize>;
+
+/// State of a single [`Matcher::accepts`] call.
+struct Run<'a> {
+    input: Vec<char>,
+    /// The end positions of the matches of a rule at a start position.
+    memo: RefCell<HashMap<(&'a str, usize), Rc<Ends>>>,
+}
+
+impl<'a> Matcher<'a> {
+    /// Creates a matcher for `grammar`, which must pass
+    /// [`Grammar::validate`].
+    pub fn new(grammar: &'a Grammar) -> Result<Self, Vec<GrammarError>> {
+        grammar.validate()?;
+        Ok(Self {
+            rules: grammar
+                .rules
+                .iter()
+                .map(|rule| (rule.name.as_str(), &rule.expr))
+                .collect(),
+        })
+    }
+
+    /// Whether the rule `root` matches all of `input`. Returns `false` when
+    /// there is no such rule.
+    pub fn accepts(&self, root: &str, input: &str) -> bool {
+        let Some((root, _)) = self.rules.get_key_value(root) else {
+            return false;
+        };
+        let run = Run {
+            input: input.chars().collect(),
+            memo: RefCell::new(HashMap::new()),
+        };
+        self.rule(root, 0, &run).contains(&run.input.len())
+    }
+
+    fn rule(&self, name: &'a str, pos: usize, run: &Run<'a>) -> Rc<Ends> {
+        if let Some(ends) = run.memo.borrow().get(&(name, pos)) {
+            return ends.clone();
+        }
+        // Validation rules out left recursion, so a rule never needs its own
+        // result at the same position.
+        let ends = Rc::new(self.expr(self.rules[name], pos, run));
+        run.memo.borrow_mut().insert((name, pos), ends.clone());
+        ends
+    }
+
+    fn expr(&self, expr: &'a Expr, pos: usize, run: &Run<'a>) -> BTreeSet<usize> {
+        match expr {
+            Expr::Literal(text) => {
+                let mut end = pos;
+                for c in text.chars() {
+                    if run.input.get(end) != Some(&c) {
+                        return BTreeSet::new();
+                    }
+                    end += 1;
+                }
+                BTreeSet::from([end])
+            }
+            Expr::Ref(name) => {
+                let (name, _) = self.rules.get_key_value(name.as_str()).unwrap();
+                (*self.rule(name, pos, run)).clone()
+            }
+            Expr::Class { negated, ranges } => match run.input.get(pos) {
+                Some(c) if ranges.iter().any(|(s, e)| s <= c && c <= e) != *negated => {
+                    BTreeSet::from([pos + 1])
+                }
+                _ => BTreeSet::new(),
+            },
+            Expr::Seq(items) => {
+                let mut ends = BTreeSet::from([pos]);
+                for item in items {
+                    ends = ends
+                        .into_iter()
+                        .flat_map(|start| self.expr(item, start, run))
+                        .collect();
+                    if ends.is_empty() {
+                        break;
+                    }
+                }
+                ends
+            }
+            Expr::Choice(options) => options
+                .iter()
+                .flat_map(|option| self.expr(option, pos, run))
+                .collect(),
+            Expr::Repeat(expr) => self.repeat(expr, 0, None, pos, run),
+            Expr::Optional(expr) => self.repeat(expr, 0, Some(1), pos, run),
+            Expr::Bounded { expr, min, max } => self.repeat(expr, *min, *max, pos, run),
+        }
+    }
+
+    fn repeat(
+        &self,
+        expr: &'a Expr,
+        min: usize,
+        max: Option<usize>,
+        pos: usize,
+        run: &Run<'a>,
+    ) -> BTreeSet<usize> {
+        let mut ends = BTreeSet::new();
+        let mut current = BTreeSet::from([pos]);
+        // Once `min` is reached without a `max`, continuing from a position
+        // gives the same ends whatever the count, so each is expanded once.
+        let mut expanded = BTreeSet::new();
+        let mut count = 0;
+        loop {
+            if count >= min {
+                ends.extend(current.iter().copied());
+            }
+            if max == Some(count) {
+                break;
+            }
+            let starts: Vec<usize> = if count >= min && max.is_none() {
+                current
+                    .into_iter()
+                    .filter(|start| expanded.insert(*start))
+                    .collect()
+            } else {
+                current.into_iter().collect()
+            };
+            if starts.is_empty() {
+                break;
+            }
+            current = starts
+                .into_iter()
+                .flat_map(|start| self.expr(expr, start, run))
+                .collect();
+            count += 1;
+        }
+        ends
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Generator, JSON_GRAMMAR, parse};
+    use schemars::{JsonSchema, Schema};
+    use serde_json::{Map, Value, json};
+
+    #[test]
+    fn matches_strings() {
+        let grammar = parse(
+            r#"
+root ::= word ("," " "? word){1,2} "!"?
+word ::= [a-z]+ | "\"" [^"]* "\""
+"#,
+        )
+        .unwrap();
+        let matcher = Matcher::new(&grammar).unwrap();
+        assert!(matcher.accepts("root", "a,b"));
+        assert!(matcher.accepts("root", "ab, \"c d\",e!"));
+        assert!(!matcher.accepts("root", "a"));
+        assert!(!matcher.accepts("root", "a,b,c,d"));
+        assert!(!matcher.accepts("root", "a,B"));
+        assert!(!matcher.accepts("missing", "a,b"));
+        assert!(Matcher::new(&parse("root ::= root \"a\"").unwrap()).is_err());
+    }
+
+    #[test]
+    fn matches_pattern_strings() {
+        let schema = json!({ "type": "string", "pattern": "^[^x]+-\\d{2}$" });
+        let generated = Generator::new().generate("root", &Schema::try_from(schema).unwrap());
+        let grammar = parse(&format!("{}\n{}", generated, JSON_GRAMMAR)).unwrap();
+        let matcher = Matcher::new(&grammar).unwrap();
+        assert!(matcher.accepts("root-r0", r#""ab-12""#));
+        assert!(matcher.accepts("root-r0", r#""a\"\n\u0041-12""#));
+        assert!(!matcher.accepts("root-r0", r#""ab-1""#));
+        assert!(!matcher.accepts("root-r0", r#""xb-12""#));
+        assert!(!matcher.accepts("root-r0", r#""a"b-12""#));
+    }
+
+    /// A deterministic xorshift generator, so failures can be reproduced.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    /// Text of a random JSON instance of `schema`, with object properties in
+    /// the order the generator expects: required, then optional, each sorted.
+    fn instance(schema: &Value, root: &Value, rng: &mut Rng, out: &mut String) {
+        let Some(obj) = schema.as_object() else {
+            out.push_str("null");
+            return;
+        };
+        if let Some(reference) = obj.get("$ref").and_then(Value::as_str) {
+            let target = match reference.strip_prefix("#/$defs/") {
+                Some(name) => &root["$defs"][name],
+                None => root,
+            };
+            return instance(target, root, rng, out);
+        }
+        if let Some(value) = obj.get("const") {
+            out.push_str(&value.to_string());
+            return;
+        }
+        if let Some(values) = obj.get("enum").and_then(Value::as_array) {
+            out.push_str(&values[rng.below(values.len())].to_string());
+            return;
+        }
+        if let Some(options) = obj
+            .get("anyOf")
+            .or_else(|| obj.get("oneOf"))
+            .and_then(Value::as_array)
+        {
+            return instance(&options[rng.below(options.len())], root, rng, out);
+        }
+        let types: Vec<&str> = match obj.get("type") {
+            Some(Value::String(ty)) => vec![ty],
+            Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+            _ => vec!["object"],
+        };
+        match types[rng.below(types.len())] {
+            "null" => out.push_str("null"),
+            "boolean" => out.push_str(["true", "false"][rng.below(2)]),
+            "integer" => {
+                let n = rng.below(100_000) as i64;
+                let n = if obj.contains_key("minimum") {
+                    n
+                } else {
+                    n - 50_000
+                };
+                out.push_str(&n.to_string());
+            }
+            "number" => {
+                let n = (rng.below(1_000_000) as f64 - 500_000.0) / 7.0;
+                out.push_str(&json!(n).to_string());
+            }
+            "string" => {
+                let min = obj.get("minLength").and_then(Value::as_u64).unwrap_or(0) as usize;
+                let max = obj
+                    .get("maxLength")
+                    .and_then(Value::as_u64)
+                    .map(|m| m as usize);
+                let len = min + rng.below(max.unwrap_or(min + 8) - min + 1);
+                const CHARS: [char; 8] = ['a', 'Z', '0', ' ', '"', '\\', '\n', 'é'];
+                let text: String = (0..len).map(|_| CHARS[rng.below(CHARS.len())]).collect();
+                out.push_str(&Value::String(text).to_string());
+            }
+            "array" => {
+                let min = obj.get("minItems").and_then(Value::as_u64).unwrap_or(0) as usize;
+                let max = obj
+                    .get("maxItems")
+                    .and_then(Value::as_u64)
+                    .map(|m| m as usize);
+                let len = min + rng.below(max.unwrap_or(min + 3) - min + 1);
+                out.push('[');
+                for i in 0..len {
+                    if i > 0 {
+                        out.push_str([",", ", "][rng.below(2)]);
+                    }
+                    instance(&obj["items"], root, rng, out);
+                }
+                out.push(']');
+            }
+            _ => {
+                let empty = Map::new();
+                let properties = obj
+                    .get("properties")
+                    .and_then(Value::as_object)
+                    .unwrap_or(&empty);
+                let required: Vec<&str> = obj
+                    .get("required")
+                    .and_then(Value::as_array)
+                    .map(|r| r.iter().filter_map(Value::as_str).collect())
+                    .unwrap_or_default();
+                let mut keys: Vec<(&str, &Value)> = properties
+                    .iter()
+                    .filter(|(name, _)| required.contains(&name.as_str()))
+                    .map(|(name, schema)| (name.as_str(), schema))
+                    .collect();
+                keys.sort_by_key(|(name, _)| *name);
+                let mut optional: Vec<(&str, &Value)> = properties
+                    .iter()
+                    .filter(|(name, _)| !required.contains(&name.as_str()) && rng.below(2) == 0)
+                    .map(|(name, schema)| (name.as_str(), schema))
+                    .collect();
+                optional.sort_by_key(|(name, _)| *name);
+                keys.extend(optional);
+                let names = ["k", "other key"];
+                if let Some(values) = obj.get("additionalProperties").filter(|v| v.is_object()) {
+                    for name in names.iter().take(rng.below(3)) {
+                        keys.push((*name, values));
+                    }
+                }
+                out.push('{');
+                for (i, (name, schema)) in keys.into_iter().enumerate() {
+                    if i > 0 {
+                        out.push_str([",", ", "][rng.below(2)]);
+                    }
+                    out.push_str(&Value::String(name.into()).to_string());
+                    out.push_str([":", ": "][rng.below(2)]);
+                    instance(schema, root, rng, out);
+                }
+                out.push('}');
+            }
+        }
+    }
+
+    /// Checks that random instances of `schema` are accepted, and that
+    /// `invalid` instances are rejected.
+    fn check_schema(schema: Value, invalid: &[&str]) {
+        let generator = Generator::new();
+        let generated = generator.generate("root", &Schema::try_from(schema.clone()).unwrap());
+        let text = format!("{}\n{}", generated, JSON_GRAMMAR);
+        let grammar = parse(&text).unwrap();
+        let matcher = Matcher::new(&grammar).unwrap();
+        let root = &generated.rules[0].name;
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..200 {
+            let mut text = String::new();
+            instance(&schema, &schema, &mut rng, &mut text);
+            serde_json::from_str::<Value>(&text).unwrap();
+            assert!(matcher.accepts(root, &text), "rejected {text}\n{generated}");
+        }
+        for text in invalid {
+            assert!(!matcher.accepts(root, text), "accepted {text}\n{generated}");
+        }
+    }
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    struct ListDirectoryParams {
+        path: String,
+        ignore: Option<Vec<String>>,
+        include_hidden: Option<bool>,
+        depth: Option<u8>,
+    }
+
+    #[test]
+    fn accepts_instances_of_derived_schemas() {
+        check_schema(
+            schemars::schema_for!(ListDirectoryParams).to_value(),
+            &[
+                r#"{}"#,
+                r#"{"ignore":null}"#,
+                r#"{"path":"a","depth":-1}"#,
+                r#"{"path":"a","extra":1}"#,
+                r#"{"path":"a","ignore":["x",]}"#,
+            ],
+        );
+    }
+
+    #[test]
+    fn accepts_instances_of_keyword_schemas() {
+        check_schema(
+            json!({
+                "type": "object",
+                "properties": {
+                    "mode": { "enum": ["fast", "slow"] },
+                    "kind": { "const": "file" },
+                    "count": { "type": "integer", "minimum": 0 },
+                    "ratio": { "type": ["number", "null"] },
+                    "name": { "type": "string", "minLength": 1, "maxLength": 3 },
+                    "tags": { "type": "array", "items": { "type": "string" }, "minItems": 1, "maxItems": 2 },
+                    "env": { "type": "object", "additionalProperties": { "type": "boolean" } },
+                    "target": { "anyOf": [{ "type": "integer" }, { "$ref": "#/$defs/Node" }] }
+                },
+                "required": ["mode", "kind", "count", "name"],
+                "$defs": {
+                    "Node": {
+                        "type": "object",
+                        "properties": {
+                            "value": { "type": "boolean" },
+                            "next": { "anyOf": [{ "$ref": "#/$defs/Node" }, { "type": "null" }] }
+                        },
+                        "required": ["value"]
+                    }
+                }
+            }),
+            &[
+                r#"{"count":1,"kind":"file","mode":"medium","name":"a"}"#,
+                r#"{"count":1,"kind":"dir","mode":"fast","name":"a"}"#,
+                r#"{"count":-1,"kind":"file","mode":"fast","name":"a"}"#,
+                r#"{"count":1.5,"kind":"file","mode":"fast","name":"a"}"#,
+                r#"{"count":1,"kind":"file","mode":"fast","name":""}"#,
+                r#"{"count":1,"kind":"file","mode":"fast","name":"abcd"}"#,
+                r#"{"count":1,"kind":"file","mode":"fast","name":"a","tags":[]}"#,
+                r#"{"count":1,"kind":"file","mode":"fast","name":"a","tags":["a","b","c"]}"#,
+                r#"{"count":1,"kind":"file","mode":"fast","name":"a","env":{"k":1}}"#,
+                r#"{"count":1,"kind":"file","mode":"fast","name":"a","target":{"next":null}}"#,
+            ],
+        );
+    }
+}
@@ -0,0 +1,369 @@
+//! Parsing of GBNF text, as accepted by llama.cpp.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Expr, Grammar, Rule};
+
+/// A syntax error in GBNF text, with 1-based line and column.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl FromStr for Grammar {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        parse(text)
+    }
+}
+
+/// Parses GBNF text into its rules.
+///
+/// Comments and the grouping of the text are not kept, so the rules print
+/// back in the normalized form of [`Grammar`]'s `Display`, which in turn
+/// parses to the same rules.
+pub fn parse(text: &str) -> Result<Grammar, ParseError> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+    };
+    let mut grammar = Grammar::default();
+    loop {
+        parser.skip_space(true);
+        if parser.peek().is_none() {
+            break;
+        }
+        let name = parser.name()?;
+        parser.skip_space(false);
+        if !parser.eat_str("::=") {
+            return Err(parser.error("expected `::=`"));
+        }
+        parser.skip_space(true);
+        let expr = parser.alternation(false)?;
+        grammar.rules.push(Rule { name, expr });
+        parser.skip_space(false);
+        if parser.peek().is_some() && !parser.eat('\n') {
+            return Err(parser.error("expected the end of the rule"));
+        }
+    }
+    Ok(grammar)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        let before = &self.chars[..self.pos.min(self.chars.len())];
+        let line = before.iter().filter(|c| **c == '\n').count() + 1;
+        let column = before.iter().rev().take_while(|c| **c != '\n').count() + 1;
+        ParseError {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<char, ParseError> {
+        let c = self
+            .peek()
+            .ok_or_else(|| self.error("unexpected end of input"))?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_str(&mut self, s: &str) -> bool {
+        let len = s.chars().count();
+        if self.chars.len() >= self.pos + len
+            && self.chars[self.pos..self.pos + len]
+                .iter()
+                .copied()
+                .eq(s.chars())
+        {
+            self.pos += len;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Skips spaces and comments, and line breaks if `newlines` is set. Rules
+    /// end at a line break unless it follows `::=` or `|`, or is nested in
+    /// parentheses.
+    fn skip_space(&mut self, newlines: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' => self.pos += 1,
+                '\n' if newlines => self.pos += 1,
+                '#' => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        while self.peek().is_some_and(is_name_char) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(self.error("expected a rule name"));
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn alternation(&mut self, nested: bool) -> Result<Expr, ParseError> {
+        let mut options = Vec::new();
+        loop {
+            match self.sequence(nested)? {
+                Expr::Choice(inner) => options.extend(inner),
+                option => options.push(option),
+            }
+            if !self.eat('|') {
+                break;
+            }
+            self.skip_space(true);
+        }
+        Ok(if options.len() == 1 {
+            options.remove(0)
+        } else {
+            Expr::Choice(options)
+        })
+    }
+
+    fn sequence(&mut self, nested: bool) -> Result<Expr, ParseError> {
+        let mut items = Vec::new();
+        loop {
+            let item = match self.peek() {
+                Some('"') => {
+                    self.pos += 1;
+                    self.literal()?
+                }
+                Some('[') => {
+                    self.pos += 1;
+                    self.class()?
+                }
+                Some('(') => {
+                    self.pos += 1;
+                    self.skip_space(true);
+                    let expr = self.alternation(true)?;
+                    self.skip_space(true);
+                    if !self.eat(')') {
+                        return Err(self.error("expected `)`"));
+                    }
+                    expr
+                }
+                Some(c) if is_name_char(c) => Expr::Ref(self.name()?),
+                _ => break,
+            };
+            self.skip_space(nested);
+            let item = self.quantified(item)?;
+            self.skip_space(nested);
+            // Nested sequences and choices print the same as flat ones.
+            match item {
+                Expr::Seq(inner) => items.extend(inner),
+                item => items.push(item),
+            }
+        }
+        Ok(if items.len() == 1 {
+            items.remove(0)
+        } else {
+            Expr::Seq(items)
+        })
+    }
+
+    fn quantified(&mut self, item: Expr) -> Result<Expr, ParseError> {
+        let item = match self.peek() {
+            Some('*') => Expr::Repeat(Box::new(item)),
+            Some('+') => Expr::Bounded {
+                expr: Box::new(item),
+                min: 1,
+                max: None,
+            },
+            Some('?') => Expr::Optional(Box::new(item)),
+            Some('{') => {
+                self.pos += 1;
+                let min = self.number()?;
+                let max = if self.eat(',') {
+                    if self.peek() == Some('}') {
+                        None
+                    } else {
+                        Some(self.number()?)
+                    }
+                } else {
+                    Some(min)
+                };
+                if max.is_some_and(|max| max < min) {
+                    return Err(self.error("the maximum repetitions are less than the minimum"));
+                }
+                if self.peek() != Some('}') {
+                    return Err(self.error("expected `}`"));
+                }
+                match (min, max) {
+                    (0, None) => Expr::Repeat(Box::new(item)),
+                    (0, Some(1)) => Expr::Optional(Box::new(item)),
+                    _ => Expr::Bounded {
+                        expr: Box::new(item),
+                        min,
+                        max,
+                    },
+                }
+            }
+            _ => return Ok(item),
+        };
+        self.pos += 1;
+        Ok(item)
+    }
+
+    fn number(&mut self) -> Result<usize, ParseError> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits
+            .parse()
+            .map_err(|_| self.error("expected a number of repetitions"))
+    }
+
+    fn literal(&mut self) -> Result<Expr, ParseError> {
+        let mut text = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Ok(Expr::Literal(text)),
+                '\\' => text.push(self.escape()?),
+                '\n' => return Err(self.error("unterminated string")),
+                c => text.push(c),
+            }
+        }
+    }
+
+    fn class(&mut self) -> Result<Expr, ParseError> {
+        let negated = self.eat('^');
+        let mut ranges = Vec::new();
+        loop {
+            let start = match self.next()? {
+                ']' => break,
+                '\\' => self.escape()?,
+                c => c,
+            };
+            let end = if self.peek() == Some('-')
+                && !matches!(self.chars.get(self.pos + 1), Some(']') | None)
+            {
+                self.pos += 1;
+                match self.next()? {
+                    '\\' => self.escape()?,
+                    c => c,
+                }
+            } else {
+                start
+            };
+            if end < start {
+                return Err(self.error(format!("invalid range {start:?}-{end:?}")));
+            }
+            ranges.push((start, end));
+        }
+        Ok(Expr::Class { negated, ranges })
+    }
+
+    fn escape(&mut self) -> Result<char, ParseError> {
+        match self.next()? {
+            'x' => self.hex(2),
+            'u' => self.hex(4),
+            'U' => self.hex(8),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            'n' => Ok('\n'),
+            c @ ('\\' | '"' | '[' | ']') => Ok(c),
+            c => Err(self.error(format!("unknown escape `\\{c}`"))),
+        }
+    }
+
+    fn hex(&mut self, len: usize) -> Result<char, ParseError> {
+        let digits: String = self.chars.iter().skip(self.pos).take(len).collect();
+        let c = (digits.len() == len)
+            .then(|| u32::from_str_radix(&digits, 16).ok())
+            .flatten()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error(format!("invalid escape `{digits}`")))?;
+        self.pos += len;
+        Ok(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JSON_GRAMMAR;
+
+    #[test]
+    fn parses_and_prints_rules() {
+        let grammar = parse(
+            r#"
+# A comment
+root ::= "a\"\x41" [^\]a-c\x2D] (x | y)+ z{2,} # trailing
+x ::=
+    "x" |
+    ( "y"
+      "z" )?
+y ::= | [0-9]{1,3}
+z ::= x*"#,
+        )
+        .unwrap();
+        insta::assert_snapshot!(grammar.to_string(), @r###"
+root ::= "a\"A" [^\]a-c\x2D] (x | y)+ (z){2,}
+x ::= "x" | ("y" "z")?
+y ::=  | ([0-9]){1,3}
+z ::= (x)*
+"###);
+        assert_eq!(parse(&grammar.to_string()).unwrap(), grammar);
+
+        let json = parse(JSON_GRAMMAR).unwrap();
+        assert_eq!(parse(&json.to_string()).unwrap(), json);
+    }
+
+    #[test]
+    fn reports_errors_with_positions() {
+        let err = parse("root ::= \"a\"\nnext ::= (\"b\"").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 14));
+        assert_eq!(parse("root ::= x ::= y").unwrap_err().column, 12);
+        assert!(parse("root ::= [a").is_err());
+        assert!(parse("root = x").is_err());
+        assert!(parse("root ::= \"\\q\"").is_err());
+    }
+}
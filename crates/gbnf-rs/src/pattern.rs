@@ -0,0 +1,413 @@
+//! Translation of the regular expressions in JSON Schema `pattern`s.
+
+use crate::{Expr, escape_expr};
+
+/// A set of characters, before it is split into the characters that appear
+/// as themselves in JSON text and those that must be escaped.
+struct CharSet {
+    negated: bool,
+    ranges: Vec<(char, char)>,
+}
+
+enum Escape {
+    Char(char),
+    Set(CharSet),
+}
+
+/// Characters that JSON strings only contain as escape sequences.
+const JSON_ESCAPED: [(char, char); 3] = [('"', '"'), ('\\', '\\'), ('\0', '\x1F')];
+
+/// Translates `pattern` to an expression matching the text between the
+/// quotes of the JSON strings it matches. `any` matches a single character
+/// of JSON string text.
+///
+/// Patterns are unanchored unless they start with `^` or end with `$`.
+/// Lookaround, backreferences and word boundaries are not supported.
+pub(crate) fn pattern_expr(pattern: &str, any: &Expr) -> Result<Expr, String> {
+    let mut chars: Vec<char> = pattern.chars().collect();
+    let anchored_start = chars.first() == Some(&'^');
+    if anchored_start {
+        chars.remove(0);
+    }
+    let trailing_backslashes = chars
+        .iter()
+        .rev()
+        .skip(1)
+        .take_while(|c| **c == '\\')
+        .count();
+    let anchored_end = chars.last() == Some(&'$') && trailing_backslashes % 2 == 0;
+    if anchored_end {
+        chars.pop();
+    }
+    let mut parser = Parser { chars, pos: 0, any };
+    let expr = parser.alternation()?;
+    if let Some(c) = parser.peek() {
+        return Err(format!("unexpected `{c}` at {}", parser.pos));
+    }
+    let mut seq = Vec::new();
+    if !anchored_start {
+        seq.push(Expr::Repeat(Box::new(any.clone())));
+    }
+    seq.push(expr);
+    if !anchored_end {
+        seq.push(Expr::Repeat(Box::new(any.clone())));
+    }
+    Ok(if seq.len() == 1 {
+        seq.remove(0)
+    } else {
+        Expr::Seq(seq)
+    })
+}
+
+/// The JSON text of `c` inside a string.
+fn json_text(c: char) -> String {
+    match c {
+        '"' => "\\\"".into(),
+        '\\' => "\\\\".into(),
+        '\n' => "\\n".into(),
+        '\r' => "\\r".into(),
+        '\t' => "\\t".into(),
+        '\x08' => "\\b".into(),
+        '\x0C' => "\\f".into(),
+        c if (c as u32) < 0x20 => format!("\\u{:04x}", c as u32),
+        c => c.to_string(),
+    }
+}
+
+fn is_json_escaped(c: char) -> bool {
+    JSON_ESCAPED
+        .iter()
+        .any(|&(start, end)| start <= c && c <= end)
+}
+
+/// Removes the characters in `cut` from `ranges`.
+fn subtract(ranges: &[(char, char)], cut: &[(char, char)]) -> Vec<(char, char)> {
+    let mut out: Vec<(u32, u32)> = ranges.iter().map(|&(s, e)| (s as u32, e as u32)).collect();
+    for &(cs, ce) in cut {
+        let (cs, ce) = (cs as u32, ce as u32);
+        out = out
+            .into_iter()
+            .flat_map(|(s, e)| {
+                let mut parts = Vec::new();
+                if s < cs {
+                    parts.push((s, e.min(cs - 1)));
+                }
+                if e > ce {
+                    parts.push((s.max(ce + 1), e));
+                }
+                parts
+            })
+            .collect();
+    }
+    out.into_iter()
+        .filter_map(|(s, e)| Some((char::from_u32(s)?, char::from_u32(e)?)))
+        .collect()
+}
+
+/// Sorts `ranges` and merges those that overlap or touch.
+fn normalize(mut ranges: Vec<(char, char)>) -> Vec<(char, char)> {
+    ranges.sort();
+    let mut out: Vec<(char, char)> = Vec::new();
+    for (start, end) in ranges {
+        match out.last_mut() {
+            Some(last) if start as u32 <= last.1 as u32 + 1 => last.1 = last.1.max(end),
+            _ => out.push((start, end)),
+        }
+    }
+    out
+}
+
+impl CharSet {
+    fn to_expr(&self) -> Expr {
+        if self.negated {
+            // The excluded characters are kept out of the class, and every
+            // escape sequence is allowed in their place.
+            let mut ranges = self.ranges.clone();
+            ranges.extend(JSON_ESCAPED);
+            let ranges = normalize(ranges);
+            return Expr::Choice(vec![
+                Expr::Class {
+                    negated: true,
+                    ranges,
+                },
+                escape_expr(),
+            ]);
+        }
+        let mut choices = Vec::new();
+        let plain = subtract(&self.ranges, &JSON_ESCAPED);
+        if !plain.is_empty() {
+            choices.push(Expr::Class {
+                negated: false,
+                ranges: plain,
+            });
+        }
+        for &(start, end) in &self.ranges {
+            for c in start..=end.min('\x7F') {
+                if is_json_escaped(c) {
+                    choices.push(Expr::Literal(json_text(c)));
+                }
+            }
+        }
+        match choices.len() {
+            1 => choices.remove(0),
+            _ => Expr::Choice(choices),
+        }
+    }
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    any: &'a Expr,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn alternation(&mut self) -> Result<Expr, String> {
+        let mut options = vec![self.sequence()?];
+        while self.eat('|') {
+            options.push(self.sequence()?);
+        }
+        Ok(if options.len() == 1 {
+            options.remove(0)
+        } else {
+            Expr::Choice(options)
+        })
+    }
+
+    fn sequence(&mut self) -> Result<Expr, String> {
+        let mut items: Vec<Expr> = Vec::new();
+        while let Some(c) = self.peek()
+            && c != '|'
+            && c != ')'
+        {
+            let atom = self.atom()?;
+            let item = self.quantified(atom)?;
+            // Merge runs of literal characters for readability.
+            if let (Some(Expr::Literal(last)), Expr::Literal(text)) = (items.last_mut(), &item) {
+                last.push_str(text);
+            } else {
+                items.push(item);
+            }
+        }
+        Ok(match items.len() {
+            0 => Expr::Literal(String::new()),
+            1 => items.remove(0),
+            _ => Expr::Seq(items),
+        })
+    }
+
+    fn quantified(&mut self, atom: Expr) -> Result<Expr, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => match self.bounds() {
+                Some(bounds) => bounds,
+                None => return Ok(atom),
+            },
+            _ => return Ok(atom),
+        };
+        // Consume the quantifier, or the closing brace of the bounds.
+        self.pos += 1;
+        // Lazy quantifiers match the same strings.
+        self.eat('?');
+        if self.peek() == Some('+') {
+            return Err("possessive quantifiers are not supported".into());
+        }
+        if max.is_some_and(|max| max < min) {
+            return Err(format!("invalid repetition {{{min},{}}}", max.unwrap()));
+        }
+        Ok(Expr::repeat(atom, min, max))
+    }
+
+    /// Parses `{n}`, `{n,}` or `{n,m}` at the current position, leaving the
+    /// position on the closing brace. Returns `None`, without consuming
+    /// anything, when the brace is a literal.
+    fn bounds(&mut self) -> Option<(usize, Option<usize>)> {
+        let rest: String = self.chars[self.pos + 1..].iter().collect();
+        let close = rest.find('}')?;
+        let inner = &rest[..close];
+        let parse = |s: &str| {
+            (!s.is_empty() && s.chars().all(|c| c.is_ascii_digit()))
+                .then(|| s.parse::<usize>().ok())
+                .flatten()
+        };
+        let bounds = match inner.split_once(',') {
+            None => {
+                let n = parse(inner)?;
+                (n, Some(n))
+            }
+            Some((min, "")) => (parse(min)?, None),
+            Some((min, max)) => (parse(min)?, Some(parse(max)?)),
+        };
+        self.pos += 1 + inner.chars().count();
+        Some(bounds)
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        let c = self.next().ok_or("unexpected end of pattern")?;
+        match c {
+            '(' => {
+                if self.eat('?') && !self.eat(':') {
+                    return Err("lookaround and named groups are not supported".into());
+                }
+                let expr = self.alternation()?;
+                if !self.eat(')') {
+                    return Err("unclosed group".into());
+                }
+                Ok(expr)
+            }
+            '[' => Ok(self.class()?.to_expr()),
+            '.' => Ok(self.any.clone()),
+            '\\' => match self.escape()? {
+                Escape::Char(c) => Ok(Expr::Literal(json_text(c))),
+                Escape::Set(set) => Ok(set.to_expr()),
+            },
+            '^' | '$' => Err("anchors are only supported at the start and end".into()),
+            '*' | '+' | '?' => Err(format!("nothing to repeat before `{c}`")),
+            c => Ok(Expr::Literal(json_text(c))),
+        }
+    }
+
+    fn class(&mut self) -> Result<CharSet, String> {
+        let negated = self.eat('^');
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.next().ok_or("unclosed character class")?;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let start = if c == '\\' {
+                match self.escape()? {
+                    Escape::Char(c) => c,
+                    Escape::Set(set) if !set.negated => {
+                        ranges.extend(set.ranges);
+                        continue;
+                    }
+                    Escape::Set(_) => {
+                        return Err("negated escapes inside a class are not supported".into());
+                    }
+                }
+            } else {
+                c
+            };
+            if self.peek() == Some('-') && !matches!(self.chars.get(self.pos + 1), Some(']') | None)
+            {
+                self.pos += 1;
+                let end = match self.next() {
+                    Some('\\') => match self.escape()? {
+                        Escape::Char(c) => c,
+                        Escape::Set(_) => return Err("invalid range in character class".into()),
+                    },
+                    Some(c) => c,
+                    None => return Err("unclosed character class".into()),
+                };
+                if end < start {
+                    return Err(format!("invalid range {start}-{end}"));
+                }
+                ranges.push((start, end));
+            } else {
+                ranges.push((start, start));
+            }
+        }
+        Ok(CharSet { negated, ranges })
+    }
+
+    fn escape(&mut self) -> Result<Escape, String> {
+        let c = self.next().ok_or("pattern ends with `\\`")?;
+        let set = |negated, ranges: &[(char, char)]| {
+            Ok(Escape::Set(CharSet {
+                negated,
+                ranges: ranges.to_vec(),
+            }))
+        };
+        const DIGIT: &[(char, char)] = &[('0', '9')];
+        const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+        const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
+        match c {
+            'd' => set(false, DIGIT),
+            'D' => set(true, DIGIT),
+            'w' => set(false, WORD),
+            'W' => set(true, WORD),
+            's' => set(false, SPACE),
+            'S' => set(true, SPACE),
+            'n' => Ok(Escape::Char('\n')),
+            'r' => Ok(Escape::Char('\r')),
+            't' => Ok(Escape::Char('\t')),
+            'f' => Ok(Escape::Char('\x0C')),
+            'v' => Ok(Escape::Char('\x0B')),
+            'x' => self.hex(2),
+            'u' => self.hex(4),
+            'b' | 'B' => Err("word boundaries are not supported".into()),
+            c if c.is_ascii_digit() => Err("backreferences are not supported".into()),
+            c if c.is_ascii_alphanumeric() => Err(format!("unknown escape `\\{c}`")),
+            c => Ok(Escape::Char(c)),
+        }
+    }
+
+    fn hex(&mut self, len: usize) -> Result<Escape, String> {
+        let digits: String = self.chars.iter().skip(self.pos).take(len).collect();
+        let code = (digits.len() == len)
+            .then(|| u32::from_str_radix(&digits, 16).ok())
+            .flatten()
+            .and_then(char::from_u32)
+            .ok_or_else(|| format!("invalid hex escape `{digits}`"))?;
+        self.pos += len;
+        Ok(Escape::Char(code))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translate(pattern: &str) -> Result<String, String> {
+        pattern_expr(pattern, &Expr::Ref("char".into())).map(|e| e.to_string())
+    }
+
+    #[test]
+    fn translates_patterns() {
+        assert_eq!(
+            translate(r"^[a-z][a-z0-9_-]{0,15}$").unwrap(),
+            r#"[a-z] ([a-z0-9_\x2D]){0,15}"#
+        );
+        assert_eq!(
+            translate(r"^(v\d+|latest)\.x?$").unwrap(),
+            r#"("v" ([0-9])+ | "latest") "." ("x")?"#
+        );
+        assert_eq!(
+            translate(r#"^[^"]+$"#).unwrap(),
+            r#"([^\x00-\x1F"\\] | "\\" (["\\/bfnrt] | "u" ([0-9a-fA-F]){4}))+"#
+        );
+        assert_eq!(translate(r#"a"b"#).unwrap(), r#"(char)* "a\\\"b" (char)*"#);
+        assert_eq!(translate("^a{2,}b{3}$").unwrap(), r#"("a"){2,} ("b"){3}"#);
+        assert_eq!(translate("^x{y}$").unwrap(), r#""x{y}""#);
+
+        assert!(translate(r"(?=a)").is_err());
+        assert!(translate(r"(a)\1").is_err());
+        assert!(translate(r"\bword").is_err());
+        assert!(translate("a$b").is_err());
+        assert!(translate("(a").is_err());
+    }
+}
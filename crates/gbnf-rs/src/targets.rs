@@ -0,0 +1,422 @@
+//! Conversions of a [`Grammar`] to the formats of other constrained decoding
+//! backends: Lark grammars, as taken by vLLM's `guided_grammar`, and regular
+//! expressions, as taken by vLLM's `guided_regex` and SGLang's `regex`.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
+
+use crate::{Expr, Grammar};
+
+/// Why a grammar has no equivalent regular expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RegexError {
+    /// The rules refer to themselves, as they do for free-form JSON values.
+    /// The cycle starts and ends with the same rule.
+    Recursive(Vec<String>),
+    /// A rule is not defined.
+    UndefinedRule(String),
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegexError::Recursive(cycle) => {
+                write!(f, "recursive rules have no regex: {}", cycle.join(" -> "))
+            }
+            RegexError::UndefinedRule(name) => write!(f, "rule {name} is not defined"),
+        }
+    }
+}
+
+impl std::error::Error for RegexError {}
+
+/// How tightly a piece of pattern binds, to know when it needs parentheses.
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Prec {
+    Choice,
+    Seq,
+    Atom,
+}
+
+fn escape_char(out: &mut String, ch: char, specials: &str) {
+    match ch {
+        '\n' => out.push_str("\\n"),
+        '\r' => out.push_str("\\r"),
+        '\t' => out.push_str("\\t"),
+        c if c.is_control() && (c as u32) <= 0xFF => write!(out, "\\x{:02x}", c as u32).unwrap(),
+        c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+        c if specials.contains(c) => {
+            out.push('\\');
+            out.push(c);
+        }
+        c => out.push(c),
+    }
+}
+
+/// A character class in the syntax shared by Python's `re` and Rust's
+/// `regex`.
+fn write_class(out: &mut String, negated: bool, ranges: &[(char, char)]) {
+    if negated && ranges.is_empty() {
+        out.push_str("[\\x00-\\U0010ffff]");
+        return;
+    }
+    out.push('[');
+    if negated {
+        out.push('^');
+    }
+    for &(start, end) in ranges {
+        escape_char(out, start, "\\[]^-/");
+        if end != start {
+            out.push('-');
+            escape_char(out, end, "\\[]^-/");
+        }
+    }
+    out.push(']');
+}
+
+/// The rules reachable from `root`, in the order they are defined.
+fn reachable<'a>(grammar: &'a Grammar, root: &str) -> Vec<&'a crate::Rule> {
+    fn visit<'a>(expr: &'a Expr, seen: &mut HashSet<&'a str>, rules: &HashMap<&str, &'a Expr>) {
+        match expr {
+            Expr::Ref(name) => {
+                if let Some(expr) = rules.get(name.as_str())
+                    && seen.insert(name)
+                {
+                    visit(expr, seen, rules);
+                }
+            }
+            Expr::Seq(items) | Expr::Choice(items) => {
+                for item in items {
+                    visit(item, seen, rules);
+                }
+            }
+            Expr::Repeat(expr) | Expr::Optional(expr) | Expr::Bounded { expr, .. } => {
+                visit(expr, seen, rules)
+            }
+            Expr::Literal(_) | Expr::Class { .. } => {}
+        }
+    }
+    let rules: HashMap<&str, &Expr> = grammar
+        .rules
+        .iter()
+        .map(|r| (r.name.as_str(), &r.expr))
+        .collect();
+    let mut seen = HashSet::new();
+    if let Some((name, expr)) = rules.get_key_value(root) {
+        seen.insert(*name);
+        visit(expr, &mut seen, &rules);
+    }
+    grammar
+        .rules
+        .iter()
+        .filter(|r| seen.remove(r.name.as_str()))
+        .collect()
+}
+
+/// Lark rule names are lowercase and use `_` instead of `-`.
+fn lark_name(name: &str, taken: &mut HashSet<String>) -> String {
+    let mut base: String = name
+        .chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            c @ ('a'..='z' | '0'..='9') => c,
+            _ => '_',
+        })
+        .collect();
+    if !base.starts_with(|c: char| c.is_ascii_lowercase()) || base == "start" {
+        base.insert_str(0, "r_");
+    }
+    let mut unique = base.clone();
+    let mut n = 1;
+    while !taken.insert(unique.clone()) {
+        n += 1;
+        unique = format!("{base}_{n}");
+    }
+    unique
+}
+
+fn is_empty(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(s) => s.is_empty(),
+        Expr::Seq(items) => items.iter().all(is_empty),
+        _ => false,
+    }
+}
+
+fn lark(expr: &Expr, names: &HashMap<&str, String>) -> (String, Prec) {
+    let group = |expr: &Expr| {
+        let (text, prec) = lark(expr, names);
+        if prec == Prec::Atom {
+            text
+        } else {
+            format!("({text})")
+        }
+    };
+    match expr {
+        // Lark rejects empty terminals, so empty strings are left out of
+        // sequences and make choices optional.
+        expr if is_empty(expr) => ("()".into(), Prec::Atom),
+        Expr::Literal(s) => {
+            let mut out = String::from("\"");
+            for ch in s.chars() {
+                escape_char(&mut out, ch, "\\\"");
+            }
+            out.push('"');
+            (out, Prec::Atom)
+        }
+        Expr::Ref(name) => (
+            names
+                .get(name.as_str())
+                .cloned()
+                .unwrap_or_else(|| lark_name(name, &mut HashSet::new())),
+            Prec::Atom,
+        ),
+        Expr::Class { negated, ranges } => {
+            let mut out = String::from("/");
+            write_class(&mut out, *negated, ranges);
+            out.push('/');
+            (out, Prec::Atom)
+        }
+        Expr::Seq(items) => {
+            let parts: Vec<String> = items
+                .iter()
+                .filter(|e| !is_empty(e))
+                .map(|e| match lark(e, names) {
+                    (text, Prec::Choice) => format!("({text})"),
+                    (text, _) => text,
+                })
+                .collect();
+            (parts.join(" "), Prec::Seq)
+        }
+        Expr::Choice(options) => {
+            let parts: Vec<String> = options
+                .iter()
+                .filter(|e| !is_empty(e))
+                .map(|e| lark(e, names).0)
+                .collect();
+            if parts.len() < options.len() {
+                (format!("[{}]", parts.join(" | ")), Prec::Atom)
+            } else {
+                (parts.join(" | "), Prec::Choice)
+            }
+        }
+        Expr::Repeat(e) => (format!("{}*", group(e)), Prec::Atom),
+        Expr::Optional(e) => (format!("{}?", group(e)), Prec::Atom),
+        Expr::Bounded { expr, min, max } => {
+            let inner = group(expr);
+            match (min, max) {
+                (1, None) => (format!("{inner}+"), Prec::Atom),
+                (min, None) => (format!("{inner} ~ {min} {inner}*"), Prec::Seq),
+                (min, Some(max)) if min == max => (format!("{inner} ~ {min}"), Prec::Atom),
+                (min, Some(max)) => (format!("{inner} ~ {min}..{max}"), Prec::Atom),
+            }
+        }
+    }
+}
+
+struct RegexContext<'a> {
+    rules: HashMap<&'a str, &'a Expr>,
+    done: HashMap<&'a str, (String, Prec)>,
+    stack: Vec<&'a str>,
+}
+
+impl<'a> RegexContext<'a> {
+    fn rule(&mut self, name: &'a str) -> Result<(String, Prec), RegexError> {
+        if let Some(done) = self.done.get(name) {
+            return Ok(done.clone());
+        }
+        if let Some(pos) = self.stack.iter().position(|r| *r == name) {
+            let mut cycle: Vec<String> = self.stack[pos..].iter().map(|r| r.to_string()).collect();
+            cycle.push(name.to_string());
+            return Err(RegexError::Recursive(cycle));
+        }
+        let expr = *self
+            .rules
+            .get(name)
+            .ok_or_else(|| RegexError::UndefinedRule(name.to_string()))?;
+        self.stack.push(name);
+        let result = self.expr(expr)?;
+        self.stack.pop();
+        self.done.insert(name, result.clone());
+        Ok(result)
+    }
+
+    fn group(&mut self, expr: &'a Expr) -> Result<String, RegexError> {
+        Ok(match self.expr(expr)? {
+            (text, Prec::Atom) => text,
+            (text, _) => format!("({text})"),
+        })
+    }
+
+    fn expr(&mut self, expr: &'a Expr) -> Result<(String, Prec), RegexError> {
+        Ok(match expr {
+            Expr::Literal(s) => {
+                let mut out = String::new();
+                for ch in s.chars() {
+                    escape_char(&mut out, ch, "\\.+*?()|[]{}^$");
+                }
+                let prec = if s.chars().count() == 1 {
+                    Prec::Atom
+                } else {
+                    Prec::Seq
+                };
+                (out, prec)
+            }
+            Expr::Ref(name) => self.rule(name)?,
+            Expr::Class { negated, ranges } => {
+                let mut out = String::new();
+                write_class(&mut out, *negated, ranges);
+                (out, Prec::Atom)
+            }
+            Expr::Seq(items) => {
+                let mut out = String::new();
+                for item in items {
+                    match self.expr(item)? {
+                        (text, Prec::Choice) => write!(out, "({text})").unwrap(),
+                        (text, _) => out.push_str(&text),
+                    }
+                }
+                (out, Prec::Seq)
+            }
+            Expr::Choice(options) => {
+                let parts = options
+                    .iter()
+                    .map(|e| Ok(self.expr(e)?.0))
+                    .collect::<Result<Vec<_>, RegexError>>()?;
+                (parts.join("|"), Prec::Choice)
+            }
+            Expr::Repeat(e) => (format!("{}*", self.group(e)?), Prec::Atom),
+            Expr::Optional(e) => (format!("{}?", self.group(e)?), Prec::Atom),
+            Expr::Bounded { expr, min, max } => {
+                let inner = self.group(expr)?;
+                let text = match (min, max) {
+                    (1, None) => format!("{inner}+"),
+                    (min, None) => format!("{inner}{{{min},}}"),
+                    (min, Some(max)) if min == max => format!("{inner}{{{min}}}"),
+                    (min, Some(max)) => format!("{inner}{{{min},{max}}}"),
+                };
+                (text, Prec::Atom)
+            }
+        })
+    }
+}
+
+impl Grammar {
+    /// The rules reachable from `root` as a Lark grammar, whose `start` rule
+    /// refers to `root`. Rule names are lowercased, with `-` replaced by `_`.
+    pub fn to_lark(&self, root: &str) -> String {
+        let rules = reachable(self, root);
+        let mut taken = HashSet::from(["start".to_string()]);
+        let names: HashMap<&str, String> = rules
+            .iter()
+            .map(|r| (r.name.as_str(), lark_name(&r.name, &mut taken)))
+            .collect();
+        let mut out = format!("start: {}\n", lark(&Expr::Ref(root.to_string()), &names).0);
+        for rule in rules {
+            writeln!(
+                out,
+                "{}: {}",
+                names[rule.name.as_str()],
+                lark(&rule.expr, &names).0
+            )
+            .unwrap();
+        }
+        out
+    }
+
+    /// A regular expression matching the same text as `root`, for grammars
+    /// without recursion, such as those generated for schemas without
+    /// free-form objects or arrays. The expression is not anchored, as the
+    /// backends match it against the whole output.
+    pub fn to_regex(&self, root: &str) -> Result<String, RegexError> {
+        let mut context = RegexContext {
+            rules: self
+                .rules
+                .iter()
+                .map(|r| (r.name.as_str(), &r.expr))
+                .collect(),
+            done: HashMap::new(),
+            stack: Vec::new(),
+        };
+        Ok(context.rule(root)?.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Generator, JSON_GRAMMAR, Matcher, parse};
+    use schemars::Schema;
+    use serde_json::json;
+
+    /// The grammar for `schema` with the JSON rules, and its root rule.
+    fn schema_grammar(schema: serde_json::Value) -> (Grammar, String) {
+        let generated = Generator::new().generate("root", &Schema::try_from(schema).unwrap());
+        let root = generated.rules[0].name.clone();
+        let grammar = parse(&format!("{}\n{}", generated, JSON_GRAMMAR)).unwrap();
+        (grammar, root)
+    }
+
+    #[test]
+    fn converts_to_lark() {
+        let grammar = parse(
+            r#"
+root ::= "a\"\n" item-list? ws
+item-list ::= [^\]a-c\x2D] (x | "y")+ x{2} x{1,} ("z"){0,3}
+x ::= "x" | ""
+ws ::= | " "
+unused ::= "u"
+"#,
+        )
+        .unwrap();
+        insta::assert_snapshot!(grammar.to_lark("root"), @r###"
+start: root
+root: "a\"\n" item_list? ws
+item_list: /[^\]a-c\-]/ (x | "y")+ x ~ 2 x+ "z" ~ 0..3
+x: ["x"]
+ws: [" "]
+"###);
+    }
+
+    #[test]
+    fn converts_to_regex() {
+        let (grammar, root) = schema_grammar(json!({
+            "type": "object",
+            "properties": {
+                "city": { "type": "string", "maxLength": 3 },
+                "unit": { "enum": ["c", "f"] },
+            },
+            "required": ["city"],
+        }));
+        let regex = grammar.to_regex(&root).unwrap();
+        insta::assert_snapshot!(regex, @r###"
+\{(| |\n[ \t]{0,20})"city"(| |\n[ \t]{0,20}):(| |\n[ \t]{0,20})"([^"\\\x7f\x00-\x1f]|\\(["\\\/bfnrt]|u[0-9a-fA-F]{4})){0,3}"(| |\n[ \t]{0,20})((| |\n[ \t]{0,20}),(| |\n[ \t]{0,20})"unit"(| |\n[ \t]{0,20}):(| |\n[ \t]{0,20})("c"|"f"))?(| |\n[ \t]{0,20})\}
+"###);
+
+        let regex = regex::Regex::new(&format!("^(?:{regex})$")).unwrap();
+        let matcher = Matcher::new(&grammar).unwrap();
+        for text in [
+            r#"{"city": "abc"}"#,
+            r#"{"city":"a\"", "unit":"f"}"#,
+            "{\n  \"city\": \"\\u00e9\" }",
+            r#"{"city": "abcd"}"#,
+            r#"{"unit": "c"}"#,
+            r#"{"city": "a", "unit": "k"}"#,
+        ] {
+            assert_eq!(regex.is_match(text), matcher.accepts(&root, text), "{text}");
+        }
+
+        let (grammar, root) = schema_grammar(json!({ "type": "object" }));
+        assert_eq!(
+            grammar.to_regex(&root),
+            Err(RegexError::Recursive(vec![
+                "value".into(),
+                "object".into(),
+                "value".into(),
+            ]))
+        );
+        assert_eq!(
+            grammar.to_regex("missing"),
+            Err(RegexError::UndefinedRule("missing".into()))
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! Checks that llama.cpp would otherwise only report when it loads a grammar.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+use crate::{Expr, Grammar};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GrammarError {
+    /// A rule is defined more than once.
+    DuplicateRule(String),
+    /// `rule` refers to `reference`, which is not defined.
+    UndefinedRule { rule: String, reference: String },
+    /// The rules can reach themselves without consuming any input. The cycle
+    /// starts and ends with the same rule.
+    LeftRecursion(Vec<String>),
+}
+
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GrammarError::DuplicateRule(name) => write!(f, "rule {name} is defined more than once"),
+            GrammarError::UndefinedRule { rule, reference } => {
+                write!(f, "rule {rule} refers to undefined rule {reference}")
+            }
+            GrammarError::LeftRecursion(cycle) => {
+                write!(f, "left recursion: {}", cycle.join(" -> "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for GrammarError {}
+
+/// Whether `expr` can match the empty string, given the rules that can.
+pub(crate) fn nullable(expr: &Expr, rules: &HashSet<&str>) -> bool {
+    match expr {
+        Expr::Literal(s) => s.is_empty(),
+        Expr::Ref(name) => rules.contains(name.as_str()),
+        Expr::Class { .. } => false,
+        Expr::Seq(items) => items.iter().all(|e| nullable(e, rules)),
+        Expr::Choice(options) => options.iter().any(|e| nullable(e, rules)),
+        Expr::Repeat(_) | Expr::Optional(_) => true,
+        Expr::Bounded { expr, min, .. } => *min == 0 || nullable(expr, rules),
+    }
+}
+
+/// The rules `expr` can refer to before it consumes any input.
+fn left_refs<'a>(expr: &'a Expr, nullable_rules: &HashSet<&str>, out: &mut BTreeSet<&'a str>) {
+    match expr {
+        Expr::Ref(name) => {
+            out.insert(name);
+        }
+        Expr::Seq(items) => {
+            for item in items {
+                left_refs(item, nullable_rules, out);
+                if !nullable(item, nullable_rules) {
+                    break;
+                }
+            }
+        }
+        Expr::Choice(options) => {
+            for option in options {
+                left_refs(option, nullable_rules, out);
+            }
+        }
+        Expr::Repeat(expr) | Expr::Optional(expr) | Expr::Bounded { expr, .. } => {
+            left_refs(expr, nullable_rules, out)
+        }
+        Expr::Literal(_) | Expr::Class { .. } => {}
+    }
+}
+
+fn refs<'a>(expr: &'a Expr, out: &mut Vec<&'a str>) {
+    match expr {
+        Expr::Ref(name) => out.push(name),
+        Expr::Seq(items) | Expr::Choice(items) => {
+            for item in items {
+                refs(item, out);
+            }
+        }
+        Expr::Repeat(expr) | Expr::Optional(expr) | Expr::Bounded { expr, .. } => refs(expr, out),
+        Expr::Literal(_) | Expr::Class { .. } => {}
+    }
+}
+
+impl Grammar {
+    /// The rules that can match the empty string.
+    pub(crate) fn nullable_rules(&self) -> HashSet<&str> {
+        let mut nullable_rules = HashSet::new();
+        loop {
+            let before = nullable_rules.len();
+            for rule in &self.rules {
+                if nullable(&rule.expr, &nullable_rules) {
+                    nullable_rules.insert(rule.name.as_str());
+                }
+            }
+            if nullable_rules.len() == before {
+                return nullable_rules;
+            }
+        }
+    }
+
+    /// Checks for duplicate and undefined rules, and for left recursion,
+    /// which llama.cpp rejects.
+    pub fn validate(&self) -> Result<(), Vec<GrammarError>> {
+        let mut errors = Vec::new();
+        let mut defined = HashSet::new();
+        for rule in &self.rules {
+            if !defined.insert(rule.name.as_str()) {
+                errors.push(GrammarError::DuplicateRule(rule.name.clone()));
+            }
+        }
+        for rule in &self.rules {
+            let mut referenced = Vec::new();
+            refs(&rule.expr, &mut referenced);
+            let mut reported = HashSet::new();
+            for reference in referenced {
+                if !defined.contains(reference) && reported.insert(reference) {
+                    errors.push(GrammarError::UndefinedRule {
+                        rule: rule.name.clone(),
+                        reference: reference.to_string(),
+                    });
+                }
+            }
+        }
+
+        let nullable_rules = self.nullable_rules();
+        let mut graph: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+        for rule in &self.rules {
+            left_refs(
+                &rule.expr,
+                &nullable_rules,
+                graph.entry(rule.name.as_str()).or_default(),
+            );
+        }
+        let mut done = HashSet::new();
+        let mut cycles = HashSet::new();
+        for rule in &self.rules {
+            let mut stack = Vec::new();
+            find_cycles(&rule.name, &graph, &mut stack, &mut done, &mut |cycle| {
+                // Report each cycle once, whichever rule it was found from.
+                let start = (0..cycle.len() - 1).min_by_key(|&i| cycle[i]).unwrap();
+                let mut rotated: Vec<String> = cycle[start..cycle.len() - 1]
+                    .iter()
+                    .chain(&cycle[..start])
+                    .map(|s| s.to_string())
+                    .collect();
+                rotated.push(rotated[0].clone());
+                if cycles.insert(rotated.clone()) {
+                    errors.push(GrammarError::LeftRecursion(rotated));
+                }
+            });
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn find_cycles<'a>(
+    rule: &'a str,
+    graph: &HashMap<&'a str, BTreeSet<&'a str>>,
+    stack: &mut Vec<&'a str>,
+    done: &mut HashSet<&'a str>,
+    report: &mut impl FnMut(&[&str]),
+) {
+    if let Some(pos) = stack.iter().position(|r| *r == rule) {
+        let mut cycle = stack[pos..].to_vec();
+        cycle.push(rule);
+        report(&cycle);
+        return;
+    }
+    if done.contains(rule) {
+        return;
+    }
+    stack.push(rule);
+    for next in graph.get(rule).into_iter().flatten() {
+        find_cycles(next, graph, stack, done, report);
+    }
+    stack.pop();
+    done.insert(rule);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{GrammarError, JSON_GRAMMAR, parse};
+
+    #[test]
+    fn reports_undefined_duplicate_and_left_recursive_rules() {
+        let grammar = parse(JSON_GRAMMAR).unwrap();
+        assert_eq!(grammar.validate(), Ok(()));
+
+        let grammar = parse(
+            r#"
+root ::= expr | missing
+expr ::= ws term "+" expr | term
+term ::= ws? expr "*" | [0-9]
+ws ::= " "*
+ws ::= ""
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            grammar.validate(),
+            Err(vec![
+                GrammarError::DuplicateRule("ws".into()),
+                GrammarError::UndefinedRule {
+                    rule: "root".into(),
+                    reference: "missing".into(),
+                },
+                GrammarError::LeftRecursion(vec!["expr".into(), "term".into(), "expr".into()]),
+            ])
+        );
+    }
+}
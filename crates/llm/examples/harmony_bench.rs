@@ -0,0 +1,130 @@
+//! Times a gpt-oss request with 50 tools under each grammar constraint.
+//!
+//! Needs a llama-server running gpt-oss:
+//!
+//! ```sh
+//! cargo run --release -p llm --example harmony_bench -- --host http://localhost:8080
+//! ```
+
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use clap::{Parser, ValueEnum};
+use futures_util::StreamExt;
+use llm::{
+    AssistantPart, ChatMessage, ChatMessageRequest, GrammarConstraint, LlmClient, ResponseChunk,
+    ToolInfo, harmony::HarmonyClient,
+};
+use serde_json::json;
+
+#[derive(Parser)]
+struct Args {
+    /// llama-server URL
+    #[arg(long, default_value = "http://localhost:8080")]
+    host: String,
+    /// Requests per constraint
+    #[arg(long, default_value_t = 3)]
+    runs: u32,
+    /// Maximum tokens generated per request
+    #[arg(long, default_value_t = 256)]
+    max_tokens: u32,
+}
+
+/// 50 tools with schemas of the usual shapes: strings, numbers, enums,
+/// arrays and nested objects.
+fn tools() -> Vec<ToolInfo> {
+    (0..50)
+        .map(|i| {
+            let parameters = json!({
+                "type": "object",
+                "properties": {
+                    "id": { "type": "string", "description": "Record id" },
+                    "limit": { "type": "integer", "minimum": 1, "maximum": 100 },
+                    "order": { "enum": ["asc", "desc"] },
+                    "tags": { "type": "array", "items": { "type": "string" }, "maxItems": 5 },
+                    "filter": {
+                        "type": "object",
+                        "properties": {
+                            "field": { "type": "string" },
+                            "value": { "anyOf": [{ "type": "string" }, { "type": "number" }] }
+                        },
+                        "required": ["field", "value"]
+                    }
+                },
+                "required": ["id"]
+            });
+            ToolInfo {
+                name: format!("lookup_{i}"),
+                description: format!("Looks up records in table {i}"),
+                parameters: parameters.try_into().unwrap(),
+            }
+        })
+        .collect()
+}
+
+struct Run {
+    first_chunk: Duration,
+    total: Duration,
+    output_tokens: u32,
+    tool_call: bool,
+}
+
+async fn run(
+    client: &HarmonyClient,
+    request: ChatMessageRequest,
+) -> Result<Run, Box<dyn Error + Send + Sync>> {
+    let start = Instant::now();
+    let mut stream = client.send_chat_messages_stream(request).await?;
+    let mut first_chunk = None;
+    let mut output_tokens = 0;
+    let mut tool_call = false;
+    while let Some(chunk) = stream.next().await {
+        first_chunk.get_or_insert_with(|| start.elapsed());
+        match chunk? {
+            ResponseChunk::Usage {
+                output_tokens: n, ..
+            } => output_tokens += n,
+            ResponseChunk::Part(AssistantPart::ToolCall { .. }) => tool_call = true,
+            ResponseChunk::Part(_) | ResponseChunk::Tokens(_) => {}
+            ResponseChunk::Done => break,
+        }
+    }
+    Ok(Run {
+        first_chunk: first_chunk.unwrap_or_default(),
+        total: start.elapsed(),
+        output_tokens,
+        tool_call,
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let args = Args::parse();
+    let client = HarmonyClient::new(Some(&args.host));
+    let tools = tools();
+    println!("constraint      run  first chunk      total  tokens  tool call");
+    for constraint in GrammarConstraint::value_variants() {
+        for i in 0..args.runs {
+            let request = ChatMessageRequest::new(
+                "gpt-oss".into(),
+                vec![ChatMessage::user(
+                    "Find record 1234 in table 17, newest first.".into(),
+                )],
+            )
+            .tools(tools.clone())
+            .max_tokens(args.max_tokens)
+            .grammar(*constraint);
+            let r = run(&client, request).await?;
+            println!(
+                "{:<14} {:>4} {:>12.2?} {:>10.2?} {:>7} {:>10}",
+                format!("{constraint:?}"),
+                i,
+                r.first_chunk,
+                r.total,
+                r.output_tokens,
+                r.tool_call
+            );
+        }
+    }
+    Ok(())
+}
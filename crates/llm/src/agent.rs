@@ -0,0 +1,476 @@
+use std::{
+    error::Error,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    AssistantMessage, ChatMessage, ChatMessageRequest, GrammarConstraint, LlmClient,
+    ResponseFormat, ToolCall, ToolOutput,
+    tool_output_limit::ToolOutputLimit,
+    tools::{ToolEvent, ToolExecutor, run_tool_loop_with},
+};
+
+/// Callbacks invoked by the tool loop. All methods default to doing nothing.
+pub trait AgentHooks: Send + Sync {
+    /// Called before each request is sent; the request may be modified.
+    fn before_request(&self, _request: &mut ChatMessageRequest) {}
+
+    /// Called with each complete response, before it is added to the history.
+    fn after_request(&self, _response: &AssistantMessage) {}
+
+    /// Called before a tool is executed. Returning an error skips the call
+    /// and reports the message to the model as the tool's failure.
+    fn before_tool_call(&self, _call: &ToolCall) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called with each tool result, before it is added to the history.
+    fn after_tool_call(&self, _name: &str, _output: &mut ToolOutput) {}
+}
+
+pub struct NoHooks;
+
+impl AgentHooks for NoHooks {}
+
+/// Limits on a single run of the tool loop.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Budget {
+    pub max_requests: Option<u32>,
+    /// Input and output tokens, as reported by the provider.
+    pub max_tokens: Option<u32>,
+}
+
+#[derive(Debug)]
+pub enum BudgetExceeded {
+    Requests(u32),
+    Tokens(u32),
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BudgetExceeded::Requests(max) => write!(f, "request budget of {max} exceeded"),
+            BudgetExceeded::Tokens(max) => write!(f, "token budget of {max} exceeded"),
+        }
+    }
+}
+
+impl Error for BudgetExceeded {}
+
+/// Runs the tool loop against a shared chat history.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use llm::{agent::Agent, mcp::McpContext, LlmClient};
+/// # async fn example(client: Arc<dyn LlmClient>, tools: McpContext) {
+/// let agent = Agent::new(client, "gpt-oss:20b")
+///     .tools(Arc::new(tools))
+///     .system_prompt("You are a helpful assistant.")
+///     .max_requests(10);
+/// let (events, handle) = agent.run("What's in this directory?");
+/// # }
+/// ```
+pub struct Agent {
+    client: Arc<dyn LlmClient>,
+    model_name: String,
+    tools: Option<Arc<dyn ToolExecutor>>,
+    system_prompt: Option<String>,
+    history: Arc<Mutex<Vec<ChatMessage>>>,
+    hooks: Arc<dyn AgentHooks>,
+    budget: Budget,
+    output_limit: Option<ToolOutputLimit>,
+    think: Option<bool>,
+    repair_tool_calls: bool,
+    response_format: Option<ResponseFormat>,
+    grammar: GrammarConstraint,
+    inspect_tokens: bool,
+    cancel: CancellationToken,
+    /// Child of `cancel` for the current run, replaced when a run starts.
+    run_cancel: Mutex<CancellationToken>,
+}
+
+impl Agent {
+    pub fn new(client: Arc<dyn LlmClient>, model_name: impl Into<String>) -> Self {
+        Self {
+            client,
+            model_name: model_name.into(),
+            tools: None,
+            system_prompt: None,
+            history: Arc::new(Mutex::new(Vec::new())),
+            hooks: Arc::new(NoHooks),
+            budget: Budget::default(),
+            output_limit: None,
+            think: None,
+            repair_tool_calls: false,
+            response_format: None,
+            grammar: GrammarConstraint::default(),
+            inspect_tokens: false,
+            cancel: CancellationToken::new(),
+            run_cancel: Mutex::new(CancellationToken::new()),
+        }
+    }
+
+    pub fn tools(mut self, tools: Arc<dyn ToolExecutor>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    /// Replaces any leading system messages in the history when a run starts.
+    pub fn system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.system_prompt = Some(prompt.into());
+        self
+    }
+
+    /// Shares the chat history with the caller, which may inspect or edit it
+    /// between runs.
+    pub fn history(mut self, history: Arc<Mutex<Vec<ChatMessage>>>) -> Self {
+        self.history = history;
+        self
+    }
+
+    pub fn hooks(mut self, hooks: Arc<dyn AgentHooks>) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    pub fn max_requests(mut self, max: u32) -> Self {
+        self.budget.max_requests = Some(max);
+        self
+    }
+
+    pub fn max_tokens(mut self, max: u32) -> Self {
+        self.budget.max_tokens = Some(max);
+        self
+    }
+
+    /// Truncates tool results longer than the limit before they are added
+    /// to the history.
+    pub fn max_tool_output(mut self, limit: ToolOutputLimit) -> Self {
+        self.output_limit = Some(limit);
+        self
+    }
+
+    pub fn think(mut self, think: bool) -> Self {
+        self.think = Some(think);
+        self
+    }
+
+    pub fn repair_tool_calls(mut self, repair: bool) -> Self {
+        self.repair_tool_calls = repair;
+        self
+    }
+
+    /// Constrains the final answer of each run to a JSON schema.
+    pub fn response_format(mut self, format: ResponseFormat) -> Self {
+        self.response_format = Some(format);
+        self
+    }
+
+    /// How much of each response is constrained by a grammar.
+    pub fn grammar(mut self, grammar: GrammarConstraint) -> Self {
+        self.grammar = grammar;
+        self
+    }
+
+    /// Asks for the raw tokens of each request, see
+    /// [`ChatMessageRequest::inspect_tokens`].
+    pub fn inspect_tokens(mut self, inspect: bool) -> Self {
+        self.inspect_tokens = inspect;
+        self
+    }
+
+    /// Cancels every run, current and future, when `token` is cancelled.
+    pub fn cancel_token(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Stops the current run, keeping the partial response and marking
+    /// in-flight tool calls as cancelled. Later runs are not affected.
+    pub fn cancel(&self) {
+        self.run_cancel.lock().unwrap().cancel();
+    }
+
+    pub fn chat_history(&self) -> Arc<Mutex<Vec<ChatMessage>>> {
+        self.history.clone()
+    }
+
+    /// Adds `prompt` as a user message and runs until the model stops calling tools.
+    pub fn run(
+        &self,
+        prompt: impl Into<String>,
+    ) -> (
+        UnboundedReceiverStream<ToolEvent>,
+        JoinHandle<Result<(), Box<dyn Error + Send + Sync>>>,
+    ) {
+        self.history
+            .lock()
+            .unwrap()
+            .push(ChatMessage::user(prompt.into()));
+        self.resume()
+    }
+
+    /// Runs on the existing history without adding a user message.
+    pub fn resume(
+        &self,
+    ) -> (
+        UnboundedReceiverStream<ToolEvent>,
+        JoinHandle<Result<(), Box<dyn Error + Send + Sync>>>,
+    ) {
+        let messages = {
+            let mut history = self.history.lock().unwrap();
+            if let Some(prompt) = &self.system_prompt {
+                while matches!(history.first(), Some(ChatMessage::System(_))) {
+                    history.remove(0);
+                }
+                history.insert(0, ChatMessage::system(prompt.clone()));
+            }
+            history.clone()
+        };
+        let tools = self
+            .tools
+            .as_ref()
+            .map(|t| t.tool_infos())
+            .unwrap_or_default();
+        let mut request = ChatMessageRequest::new(self.model_name.clone(), messages)
+            .tools(tools)
+            .repair_tool_calls(self.repair_tool_calls)
+            .grammar(self.grammar)
+            .inspect_tokens(self.inspect_tokens);
+        request.think = self.think;
+        request.response_format = self.response_format.clone();
+        let executor = self
+            .tools
+            .clone()
+            .unwrap_or_else(|| Arc::new(NoTools) as Arc<dyn ToolExecutor>);
+        let cancel = self.cancel.child_token();
+        *self.run_cancel.lock().unwrap() = cancel.clone();
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let handle = tokio::spawn(run_tool_loop_with(
+            self.client.clone(),
+            request,
+            executor,
+            self.history.clone(),
+            tx,
+            self.hooks.clone(),
+            self.budget,
+            self.output_limit.clone(),
+            cancel,
+        ));
+        (UnboundedReceiverStream::new(rx), handle)
+    }
+}
+
+struct NoTools;
+
+#[async_trait]
+impl ToolExecutor for NoTools {
+    async fn call(
+        &self,
+        name: &str,
+        _args: Value,
+    ) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+        Err(format!("{name} is not a valid tool name").into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AssistantPart, JsonResult, ResponseChunk, TestProvider};
+    use serde_json::json;
+    use tokio_stream::StreamExt;
+
+    fn tool_call_response() -> Vec<ResponseChunk> {
+        vec![
+            ResponseChunk::Part(AssistantPart::ToolCall {
+                call: ToolCall {
+                    id: "1".into(),
+                    name: "rm".into(),
+                    arguments: JsonResult::Content { content: json!({}) },
+                    repaired_from: None,
+                },
+                encrypted_content: None,
+            }),
+            ResponseChunk::Usage {
+                input_tokens: 10,
+                output_tokens: 5,
+            },
+            ResponseChunk::Done,
+        ]
+    }
+
+    struct Deny;
+
+    impl AgentHooks for Deny {
+        fn before_request(&self, request: &mut ChatMessageRequest) {
+            request.model_name = "hooked".into();
+        }
+
+        fn before_tool_call(&self, call: &ToolCall) -> Result<(), String> {
+            Err(format!("{} is not allowed", call.name))
+        }
+    }
+
+    #[tokio::test]
+    async fn run_applies_system_prompt_and_hooks() {
+        let provider = Arc::new(TestProvider::new());
+        provider.enqueue(tool_call_response());
+        provider.enqueue(vec![
+            ResponseChunk::Part(AssistantPart::Text {
+                text: "done".into(),
+                encrypted_content: None,
+            }),
+            ResponseChunk::Done,
+        ]);
+        let agent = Agent::new(provider.clone(), "m")
+            .system_prompt("be careful")
+            .hooks(Arc::new(Deny));
+        agent
+            .chat_history()
+            .lock()
+            .unwrap()
+            .push(ChatMessage::system("old".into()));
+        let (events, handle) = agent.run("delete everything");
+        let events: Vec<ToolEvent> = events.collect().await;
+        handle.await.unwrap().unwrap();
+
+        assert!(events.iter().any(|e| matches!(
+            e,
+            ToolEvent::ToolResult { result: Err(err), .. } if err.to_string() == "rm is not allowed"
+        )));
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].model_name, "hooked");
+        let history = agent.chat_history().lock().unwrap().clone();
+        assert!(matches!(&history[0], ChatMessage::System(s) if s.content == "be careful"));
+        assert!(matches!(&history[1], ChatMessage::User(u) if u.content == "delete everything"));
+        assert!(matches!(&history[3], ChatMessage::Tool(t) if t.output.is_error));
+    }
+
+    struct SlowTool;
+
+    #[async_trait]
+    impl ToolExecutor for SlowTool {
+        async fn call(
+            &self,
+            _name: &str,
+            _args: Value,
+        ) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn cancel_commits_partial_output() {
+        let provider = Arc::new(TestProvider::new());
+        let mut response = vec![ResponseChunk::Part(AssistantPart::Text {
+            text: "working on it".into(),
+            encrypted_content: None,
+        })];
+        response.extend(tool_call_response());
+        provider.enqueue(response);
+        let agent = Agent::new(provider.clone(), "m").tools(Arc::new(SlowTool));
+        let (mut events, handle) = agent.run("go");
+        while let Some(event) = events.next().await {
+            if matches!(event, ToolEvent::ToolStarted { .. }) {
+                agent.cancel();
+            }
+        }
+        handle.await.unwrap().unwrap();
+
+        assert_eq!(provider.requests.lock().unwrap().len(), 1);
+        let history = agent.chat_history().lock().unwrap().clone();
+        assert_eq!(history.len(), 3);
+        assert!(matches!(&history[1], ChatMessage::Assistant(a) if a.content.len() == 2));
+        match &history[2] {
+            ChatMessage::Tool(t) => {
+                assert_eq!(t.id, "1");
+                assert!(t.output.is_error);
+                assert_eq!(t.output.to_text(), "cancelled by user");
+            }
+            _ => panic!("expected cancelled tool result"),
+        }
+
+        provider.enqueue(vec![
+            ResponseChunk::Part(AssistantPart::Text {
+                text: "again".into(),
+                encrypted_content: None,
+            }),
+            ResponseChunk::Done,
+        ]);
+        let (events, handle) = agent.run("go on");
+        let _: Vec<ToolEvent> = events.collect().await;
+        handle.await.unwrap().unwrap();
+        assert_eq!(provider.requests.lock().unwrap().len(), 2);
+        let history = agent.chat_history().lock().unwrap().clone();
+        assert!(matches!(&history[4], ChatMessage::Assistant(a) if a.text() == "again"));
+    }
+
+    struct BigTool;
+
+    #[async_trait]
+    impl ToolExecutor for BigTool {
+        async fn call(
+            &self,
+            _name: &str,
+            _args: Value,
+        ) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+            Ok(ToolOutput::text("x".repeat(1000)))
+        }
+    }
+
+    #[tokio::test]
+    async fn truncates_large_tool_results() {
+        let provider = Arc::new(TestProvider::new());
+        provider.enqueue(tool_call_response());
+        provider.enqueue(vec![ResponseChunk::Done]);
+        let dir = tempfile::tempdir().unwrap();
+        let limit = ToolOutputLimit::new(100, dir.path());
+        let agent = Agent::new(provider.clone(), "m")
+            .tools(Arc::new(BigTool))
+            .max_tool_output(limit.clone());
+        let (events, handle) = agent.run("read it");
+        let events: Vec<ToolEvent> = events.collect().await;
+        handle.await.unwrap().unwrap();
+
+        let truncated = events
+            .iter()
+            .find_map(|e| match e {
+                ToolEvent::ToolResult {
+                    result: Ok(output), ..
+                } => output.truncated.clone(),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(truncated.total_chars, 1000);
+        let history = agent.chat_history().lock().unwrap().clone();
+        let ChatMessage::Tool(t) = &history[2] else {
+            panic!("expected tool result");
+        };
+        assert_eq!(t.output.truncated, Some(truncated.clone()));
+        assert!(t.output.to_text().len() < 300);
+        let page = limit.read(&truncated.id, 0, 2000).unwrap();
+        assert_eq!(page.text, "x".repeat(1000));
+    }
+
+    #[tokio::test]
+    async fn stops_when_budget_is_exceeded() {
+        let provider = Arc::new(TestProvider::new());
+        provider.enqueue(tool_call_response());
+        provider.enqueue(tool_call_response());
+        let agent = Agent::new(provider.clone(), "m").max_tokens(20);
+        let (events, handle) = agent.run("loop");
+        let _: Vec<ToolEvent> = events.collect().await;
+        let err = handle.await.unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "token budget of 20 exceeded");
+        assert_eq!(provider.requests.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,778 @@
+//! A text browser with the `search`, `open` and `find` tools that gpt-oss
+//! was trained on, see [`browser_tools`].
+//!
+//! Pages are shown in the format the model cites from: a `[cursor] title
+//! (url)` header, then numbered lines, with links written as
+//! `【id†text†domain】` so they can be opened by id.
+
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use reqwest::Url;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    ToolOutput,
+    function_tool::{FunctionTool, FunctionToolSet},
+    tools::ToolExecutor,
+};
+
+/// Prefix of the browser tools. The Harmony provider declares tools named
+/// `browser_<method>` as its built-in browser.
+pub const BROWSER_PREFIX: &str = "browser";
+
+/// Methods of the browser tool.
+pub const BROWSER_METHODS: [&str; 3] = ["search", "open", "find"];
+
+/// The browser method a tool named `browser_<method>` calls, if any.
+pub fn browser_method(tool_name: &str) -> Option<&str> {
+    tool_name
+        .strip_prefix(BROWSER_PREFIX)
+        .and_then(|n| n.strip_prefix('_'))
+        .filter(|m| BROWSER_METHODS.contains(m))
+}
+
+/// Lines shown when `num_lines` is not given.
+const DEFAULT_LINES: usize = 50;
+/// Most lines shown at once.
+const MAX_LINES: usize = 400;
+/// Lines longer than this are wrapped.
+const LINE_WIDTH: usize = 120;
+
+/// A link on a page, opened at line `loc`.
+#[derive(Clone, Debug, PartialEq)]
+struct Link {
+    url: String,
+    loc: usize,
+    /// The page in the history that the link leads to, for the matches of
+    /// `find`, whose page may have no URL.
+    cursor: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+struct Page {
+    url: String,
+    title: String,
+    lines: Vec<String>,
+    links: Vec<Link>,
+}
+
+impl Page {
+    /// Builds a page from text in which Markdown links and bare URLs are
+    /// turned into numbered links.
+    fn from_text(url: &str, title: &str, text: &str) -> Page {
+        let base = Url::parse(url).ok();
+        let mut links = Vec::new();
+        let mut body = String::new();
+        let mut rest = text;
+        while !rest.is_empty() {
+            if let Some((label, target, len)) =
+                markdown_link(rest).or_else(|| bare_url(rest).map(|(url, len)| (url, url, len)))
+            {
+                let resolved = match &base {
+                    Some(base) => base.join(target).map(String::from).ok(),
+                    None => Url::parse(target).map(String::from).ok(),
+                };
+                match resolved {
+                    Some(resolved) => {
+                        let label = label.split_whitespace().collect::<Vec<_>>().join(" ");
+                        body.push_str(&format!(
+                            "【{}†{}†{}】",
+                            links.len(),
+                            label,
+                            domain(&resolved)
+                        ));
+                        links.push(Link {
+                            url: resolved,
+                            loc: 0,
+                            cursor: None,
+                        });
+                    }
+                    None => body.push_str(label),
+                }
+                rest = &rest[len..];
+            } else {
+                let ch = rest.chars().next().unwrap();
+                body.push(ch);
+                rest = &rest[ch.len_utf8()..];
+            }
+        }
+        let mut lines = Vec::new();
+        for line in body.lines() {
+            let line = line.trim_end();
+            if line.is_empty() && lines.last().is_some_and(|l: &String| l.is_empty()) {
+                continue;
+            }
+            wrap(line, &mut lines);
+        }
+        Page {
+            url: url.into(),
+            title: title.into(),
+            lines,
+            links,
+        }
+    }
+
+    /// Shows `num_lines` lines from `loc`, for the page at `cursor`.
+    fn render(&self, cursor: usize, loc: usize, num_lines: usize) -> String {
+        let last = self.lines.len().saturating_sub(1);
+        let start = loc.min(last);
+        let end = (start + num_lines.clamp(1, MAX_LINES)).min(self.lines.len());
+        let mut out = format!("[{cursor}] {}", self.title);
+        if !self.url.is_empty() {
+            out.push_str(&format!(" ({})", self.url));
+        }
+        out.push_str(&format!(
+            "\n**viewing lines [{start} - {}] of {last}**\n\n",
+            end.saturating_sub(1).max(start)
+        ));
+        for (i, line) in self.lines[start..end].iter().enumerate() {
+            out.push_str(&format!("L{}: {line}\n", start + i));
+        }
+        out
+    }
+}
+
+/// `[label](target)` at the start of `text`, and its length.
+fn markdown_link(text: &str) -> Option<(&str, &str, usize)> {
+    let label_end = text.strip_prefix('[')?.find("](")? + 1;
+    let label = &text[1..label_end];
+    if label.contains('[') || label.contains('\n') {
+        return None;
+    }
+    let target_start = label_end + 2;
+    let target_len = text[target_start..].find(')')?;
+    let target = &text[target_start..target_start + target_len];
+    if target.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((label, target, target_start + target_len + 1))
+}
+
+/// An `http` or `https` URL at the start of `text`, and its length.
+fn bare_url(text: &str) -> Option<(&str, usize)> {
+    if !text.starts_with("http://") && !text.starts_with("https://") {
+        return None;
+    }
+    let len = text
+        .find(|c: char| c.is_whitespace() || matches!(c, '"' | '<' | '>' | ')' | ']'))
+        .unwrap_or(text.len());
+    let url = text[..len].trim_end_matches(['.', ',', ';', ':']);
+    Some((url, url.len()))
+}
+
+fn domain(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|u| {
+            u.host_str()
+                .map(|h| h.trim_start_matches("www.").to_string())
+        })
+        .unwrap_or_default()
+}
+
+/// Splits `line` at spaces into lines of at most [`LINE_WIDTH`] characters.
+fn wrap(line: &str, out: &mut Vec<String>) {
+    let mut current = String::new();
+    for word in line.split(' ') {
+        if !current.is_empty() && current.chars().count() + word.chars().count() >= LINE_WIDTH {
+            out.push(std::mem::take(&mut current));
+        } else if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    out.push(current);
+}
+
+/// Converts HTML to text, keeping links in Markdown form. Returns the title
+/// and the text.
+fn html_to_text(html: &str) -> (String, String) {
+    const BLOCKS: &[&str] = &[
+        "p",
+        "div",
+        "br",
+        "li",
+        "tr",
+        "h1",
+        "h2",
+        "h3",
+        "h4",
+        "h5",
+        "h6",
+        "ul",
+        "ol",
+        "table",
+        "section",
+        "article",
+        "header",
+        "footer",
+        "nav",
+        "pre",
+        "blockquote",
+        "hr",
+    ];
+    const SKIPPED: &[&str] = &["script", "style", "noscript", "template", "svg"];
+    let mut title = String::new();
+    let mut text = String::new();
+    let mut href: Option<String> = None;
+    let mut link_text = String::new();
+    let mut skip_until: Option<String> = None;
+    let mut in_title = false;
+    let mut rest = html;
+    while let Some(open) = rest.find('<') {
+        let chunk = decode_entities(&rest[..open]);
+        if skip_until.is_none() {
+            if in_title {
+                title.push_str(&chunk);
+            } else if href.is_some() {
+                link_text.push_str(&chunk);
+            } else {
+                text.push_str(&chunk);
+            }
+        }
+        let Some(close) = rest[open..].find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = &rest[open + 1..open + close];
+        rest = &rest[open + close + 1..];
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if let Some(skipped) = &skip_until {
+            if closing && name == *skipped {
+                skip_until = None;
+            }
+            continue;
+        }
+        match name.as_str() {
+            name if SKIPPED.contains(&name) && !closing => skip_until = Some(name.to_string()),
+            "title" => in_title = !closing,
+            "a" if !closing => {
+                href = attribute(tag, "href");
+                link_text.clear();
+            }
+            "a" => {
+                if let Some(target) = href.take() {
+                    let label = link_text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    text.push_str(&format!("[{label}]({target})"));
+                }
+            }
+            name if BLOCKS.contains(&name) => text.push('\n'),
+            _ => {}
+        }
+    }
+    text.push_str(&decode_entities(rest));
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !(line.is_empty() && lines.last().is_some_and(|l| l.is_empty())) {
+            lines.push(line);
+        }
+    }
+    let text = lines.join("\n");
+    (title.trim().to_string(), text.trim().to_string())
+}
+
+/// The value of attribute `name` in the text of a tag.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let start = lower.find(&format!(" {name}="))? + name.len() + 2;
+    let value = &tag[start..];
+    let value = match value.chars().next()? {
+        quote @ ('"' | '\'') => value[1..].split(quote).next()?,
+        _ => value
+            .split(|c: char| c.is_whitespace() || c == '>')
+            .next()?,
+    };
+    Some(decode_entities(value))
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest[1..].find(';').map(|end| &rest[1..end + 1]);
+        let decoded = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let code = entity.strip_prefix('#')?;
+                let code = match code.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => code.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (entity, decoded) {
+            (Some(entity), Some(ch)) => {
+                out.push(ch);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Where search results come from.
+enum Search {
+    /// Fetches a results page, with `{query}` in the URL replaced.
+    Url(String),
+    /// Calls a tool with `{"query": ...}`.
+    Tool(Arc<dyn ToolExecutor>, String),
+}
+
+/// Where pages come from.
+enum Fetch {
+    Http,
+    /// Calls a tool with `{"url": ...}`.
+    Tool(Arc<dyn ToolExecutor>, String),
+}
+
+/// The pages opened so far, by cursor.
+#[derive(Default)]
+struct History {
+    pages: Vec<Page>,
+}
+
+/// The state shared by the browser tools.
+pub struct Browser {
+    http: reqwest::Client,
+    search: Search,
+    fetch: Fetch,
+    history: Mutex<History>,
+}
+
+impl Default for Browser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Browser {
+    /// Fetches pages over HTTP and searches DuckDuckGo's HTML results.
+    pub fn new() -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            search: Search::Url("https://html.duckduckgo.com/html/?q={query}".into()),
+            fetch: Fetch::Http,
+            history: Mutex::default(),
+        }
+    }
+
+    /// Searches by fetching `url`, in which `{query}` is replaced by the
+    /// encoded query.
+    pub fn search_url(mut self, url: impl Into<String>) -> Self {
+        self.search = Search::Url(url.into());
+        self
+    }
+
+    /// Searches by calling `tool`, e.g. of an MCP server, with `{"query": ...}`.
+    pub fn search_tool(mut self, executor: Arc<dyn ToolExecutor>, tool: impl Into<String>) -> Self {
+        self.search = Search::Tool(executor, tool.into());
+        self
+    }
+
+    /// Fetches pages by calling `tool`, e.g. of an MCP server, with
+    /// `{"url": ...}`. The tool is expected to return text or Markdown.
+    pub fn fetch_tool(mut self, executor: Arc<dyn ToolExecutor>, tool: impl Into<String>) -> Self {
+        self.fetch = Fetch::Tool(executor, tool.into());
+        self
+    }
+
+    /// Adds `page` to the history and shows it.
+    fn show_new(&self, page: Page, loc: usize, num_lines: usize) -> String {
+        let mut history = self.history.lock().unwrap();
+        history.pages.push(page);
+        let cursor = history.pages.len() - 1;
+        history.pages[cursor].render(cursor, loc, num_lines)
+    }
+
+    /// The page at `cursor`, or the last page if negative.
+    fn page(&self, cursor: i64) -> Result<(usize, Page), Box<dyn Error + Send + Sync>> {
+        let history = self.history.lock().unwrap();
+        let index = if cursor < 0 {
+            history.pages.len().checked_sub(1)
+        } else {
+            Some(cursor as usize).filter(|c| *c < history.pages.len())
+        };
+        let index = index.ok_or_else(|| format!("no page at cursor {cursor}"))?;
+        Ok((index, history.pages[index].clone()))
+    }
+
+    async fn get(&self, url: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let response = self.http.get(url).send().await?.error_for_status()?;
+        Ok(response.text().await?)
+    }
+
+    async fn call_tool(
+        executor: &Arc<dyn ToolExecutor>,
+        tool: &str,
+        args: serde_json::Value,
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let output = executor.call(tool, args).await?;
+        if output.is_error {
+            return Err(output.to_text().into());
+        }
+        Ok(output.to_text())
+    }
+
+    async fn search(&self, query: &str) -> Result<Page, Box<dyn Error + Send + Sync>> {
+        let title = format!("Search results for `{query}`");
+        match &self.search {
+            Search::Url(template) => {
+                let encoded: String = Url::parse_with_params("http://q", [("q", query)])?
+                    .query()
+                    .unwrap_or_default()
+                    .trim_start_matches("q=")
+                    .to_string();
+                let url = template.replace("{query}", &encoded);
+                let (_, text) = html_to_text(&self.get(&url).await?);
+                Ok(Page::from_text(&url, &title, &text))
+            }
+            Search::Tool(executor, tool) => {
+                let text = Self::call_tool(executor, tool, json!({ "query": query })).await?;
+                Ok(Page::from_text("", &title, &text))
+            }
+        }
+    }
+
+    async fn fetch(&self, url: &str) -> Result<Page, Box<dyn Error + Send + Sync>> {
+        match &self.fetch {
+            Fetch::Http => {
+                let (title, text) = html_to_text(&self.get(url).await?);
+                let title = if title.is_empty() { url } else { &title };
+                Ok(Page::from_text(url, title, &text))
+            }
+            Fetch::Tool(executor, tool) => {
+                let text = Self::call_tool(executor, tool, json!({ "url": url })).await?;
+                let title = text
+                    .lines()
+                    .find_map(|l| l.strip_prefix("# "))
+                    .unwrap_or(url)
+                    .to_string();
+                Ok(Page::from_text(url, &title, &text))
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct SearchParams {
+    pub query: String,
+    /// Number of results to show.
+    #[serde(default = "default_topn")]
+    pub topn: usize,
+}
+
+fn default_topn() -> usize {
+    10
+}
+
+/// A link id on the page at `cursor`, or a URL.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum OpenTarget {
+    Link(i64),
+    Url(String),
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct OpenParams {
+    /// Link id or URL to open. Omit to scroll the page at `cursor`.
+    #[serde(default)]
+    pub id: Option<OpenTarget>,
+    /// Page the link is on, the last page if omitted.
+    #[serde(default)]
+    pub cursor: Option<i64>,
+    /// First line to show.
+    #[serde(default)]
+    pub loc: Option<i64>,
+    /// Number of lines to show.
+    #[serde(default)]
+    pub num_lines: Option<i64>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct FindParams {
+    pub pattern: String,
+    /// Page to search, the last page if omitted.
+    #[serde(default)]
+    pub cursor: Option<i64>,
+}
+
+fn non_negative(value: Option<i64>) -> Option<usize> {
+    value.filter(|v| *v >= 0).map(|v| v as usize)
+}
+
+struct SearchTool(Arc<Browser>);
+
+#[async_trait]
+impl FunctionTool for SearchTool {
+    const NAME: &'static str = "search";
+    const DESCRIPTION: &'static str =
+        "Searches the web for `query` and shows the results, whose links can be opened by id";
+    type Params = SearchParams;
+
+    async fn call(&self, params: SearchParams) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+        let page = self.0.search(&params.query).await?;
+        // Each result takes a few lines, so show as many as are asked for.
+        let lines = params.topn.saturating_mul(4).max(DEFAULT_LINES);
+        Ok(ToolOutput::text(self.0.show_new(page, 0, lines)))
+    }
+}
+
+struct OpenTool(Arc<Browser>);
+
+#[async_trait]
+impl FunctionTool for OpenTool {
+    const NAME: &'static str = "open";
+    const DESCRIPTION: &'static str =
+        "Opens a link id or URL, or scrolls the page at `cursor` to line `loc`";
+    type Params = OpenParams;
+
+    async fn call(&self, params: OpenParams) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+        let browser = &self.0;
+        let num_lines = non_negative(params.num_lines).unwrap_or(DEFAULT_LINES);
+        let loc = non_negative(params.loc);
+        let cursor = params.cursor.unwrap_or(-1);
+        let link = match params.id {
+            Some(OpenTarget::Url(url)) => Link {
+                url,
+                loc: 0,
+                cursor: None,
+            },
+            Some(OpenTarget::Link(id)) if id >= 0 => {
+                let (_, page) = browser.page(cursor)?;
+                page.links
+                    .get(id as usize)
+                    .cloned()
+                    .ok_or_else(|| format!("no link {id} on {}", page.title))?
+            }
+            _ => {
+                let (index, page) = browser.page(cursor)?;
+                return Ok(ToolOutput::text(page.render(
+                    index,
+                    loc.unwrap_or(0),
+                    num_lines,
+                )));
+            }
+        };
+        let loc = loc.unwrap_or(link.loc);
+        let cached = match link.cursor {
+            Some(cursor) => Some(cursor),
+            // Pages without a URL, such as search results from a tool, can
+            // only be told apart by their cursor.
+            None if link.url.is_empty() => None,
+            None => {
+                let history = browser.history.lock().unwrap();
+                history.pages.iter().rposition(|p| p.url == link.url)
+            }
+        };
+        if let Some(index) = cached {
+            let page = browser.history.lock().unwrap().pages[index].clone();
+            return Ok(ToolOutput::text(page.render(index, loc, num_lines)));
+        }
+        let page = browser.fetch(&link.url).await?;
+        Ok(ToolOutput::text(browser.show_new(page, loc, num_lines)))
+    }
+}
+
+struct FindTool(Arc<Browser>);
+
+#[async_trait]
+impl FunctionTool for FindTool {
+    const NAME: &'static str = "find";
+    const DESCRIPTION: &'static str = "Finds `pattern` in the page at `cursor`, ignoring case";
+    type Params = FindParams;
+
+    async fn call(&self, params: FindParams) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+        let (cursor, page) = self.0.page(params.cursor.unwrap_or(-1))?;
+        let pattern = params.pattern.to_lowercase();
+        let mut text = String::new();
+        let mut links = Vec::new();
+        for (i, line) in page.lines.iter().enumerate() {
+            if line.to_lowercase().contains(&pattern) {
+                text.push_str(&format!("【{}†match at L{i}】\n{line}\n\n", links.len()));
+                links.push(Link {
+                    url: page.url.clone(),
+                    loc: i,
+                    cursor: Some(cursor),
+                });
+            }
+        }
+        if links.is_empty() {
+            text = format!("No `{}` found", params.pattern);
+        }
+        let mut results = Page::from_text(
+            "",
+            &format!(
+                "Find results for text: `{}` in `{}`",
+                params.pattern, page.title
+            ),
+            &text,
+        );
+        results.links = links;
+        Ok(ToolOutput::text(self.0.show_new(results, 0, DEFAULT_LINES)))
+    }
+}
+
+/// The `search`, `open` and `find` tools of `browser`, under
+/// [`BROWSER_PREFIX`].
+pub fn browser_tools(browser: Arc<Browser>) -> FunctionToolSet {
+    FunctionToolSet::new(BROWSER_PREFIX)
+        .with(SearchTool(browser.clone()))
+        .with(OpenTool(browser.clone()))
+        .with(FindTool(browser))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::serve;
+    use axum::{Router, extract::Query, response::Html, routing::get};
+    use std::collections::HashMap;
+
+    #[test]
+    fn converts_html_to_text_with_links() {
+        let (title, text) = html_to_text(
+            "<html><head><title>Rust &amp; you</title><style>p {}</style></head>\
+             <body><p>Read <a href=\"/book\">the\n book</a>.</p><script>x()</script>\
+             <ul><li>one&nbsp;&#x32;</li></ul></body></html>",
+        );
+        assert_eq!(title, "Rust & you");
+        assert_eq!(text, "Read [the book](/book).\n\none 2");
+
+        let page = Page::from_text(
+            "https://example.com/a/",
+            &title,
+            &format!("{text}\nSee https://www.rust-lang.org/learn."),
+        );
+        assert_eq!(
+            page.lines,
+            vec![
+                "Read 【0†the book†example.com】.",
+                "",
+                "one 2",
+                "See 【1†https://www.rust-lang.org/learn†rust-lang.org】.",
+            ]
+        );
+        assert_eq!(page.links[0].url, "https://example.com/book");
+        assert_eq!(
+            page.render(3, 1, 2),
+            "[3] Rust & you (https://example.com/a/)\n\
+             **viewing lines [1 - 2] of 3**\n\n\
+             L1: \nL2: one 2\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn searches_opens_and_finds() {
+        let base = serve(
+            Router::new()
+                .route(
+                    "/search",
+                    get(|Query(q): Query<HashMap<String, String>>| async move {
+                        Html(format!(
+                            "<p>Results for {}</p><a href=\"/page\">Fixture page</a>",
+                            q["q"]
+                        ))
+                    }),
+                )
+                .route(
+                    "/page",
+                    get(|| async {
+                        Html("<title>Fixture</title><p>alpha</p><p>beta gamma</p><p>Gamma</p>")
+                    }),
+                ),
+        )
+        .await;
+        let browser = Arc::new(Browser::new().search_url(format!("{base}/search?q={{query}}")));
+        let tools = browser_tools(browser);
+
+        let call = |name: &'static str, args: serde_json::Value| {
+            let tools = &tools;
+            async move { tools.call(name, args).await.unwrap().to_text() }
+        };
+        let results = call("search", json!({"query": "a b"})).await;
+        assert!(results.starts_with("[0] Search results for `a b`"));
+        assert!(results.contains("L0: Results for a b"));
+        assert!(results.contains("【0†Fixture page†127.0.0.1】"));
+
+        let page = call("open", json!({"id": 0})).await;
+        assert_eq!(
+            page,
+            format!(
+                "[1] Fixture ({base}/page)\n**viewing lines [0 - 4] of 4**\n\n\
+                 L0: alpha\nL1: \nL2: beta gamma\nL3: \nL4: Gamma\n"
+            )
+        );
+
+        let found = call("find", json!({"pattern": "GAMMA"})).await;
+        assert!(found.starts_with("[2] Find results for text: `GAMMA` in `Fixture`"));
+        assert!(found.contains("L0: 【0†match at L2】"));
+        let opened = call("open", json!({"id": 1})).await;
+        assert!(opened.starts_with("[1] Fixture"));
+        assert!(opened.contains("**viewing lines [4 - 4] of 4**"));
+
+        let scrolled = call("open", json!({"cursor": 1, "loc": 2, "num_lines": 1})).await;
+        assert!(scrolled.ends_with("L2: beta gamma\n"));
+        assert!(tools.call("open", json!({"id": 9})).await.is_err());
+    }
+
+    /// Returns a result per line for `{"query": ...}`.
+    struct SearchResults;
+
+    #[async_trait]
+    impl ToolExecutor for SearchResults {
+        async fn call(
+            &self,
+            _name: &str,
+            args: serde_json::Value,
+        ) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+            let query = args["query"].as_str().unwrap();
+            let text: String = (0..600).map(|i| format!("{query} {i}\n")).collect();
+            Ok(ToolOutput::text(text))
+        }
+    }
+
+    #[tokio::test]
+    async fn finds_on_search_results_from_a_tool() {
+        let browser = Browser::new().search_tool(Arc::new(SearchResults), "web_search");
+        let tools = browser_tools(Arc::new(browser));
+        let call = |name: &'static str, args: serde_json::Value| {
+            let tools = &tools;
+            async move { tools.call(name, args).await.unwrap().to_text() }
+        };
+
+        let results = call("search", json!({"query": "rust", "topn": usize::MAX})).await;
+        assert!(results.contains("**viewing lines [0 - 399] of 599**"));
+        let found = call("find", json!({"pattern": "rust 42"})).await;
+        assert!(found.starts_with("[1] Find results for text: `rust 42`"));
+        let opened = call("open", json!({"id": 0, "num_lines": 1})).await;
+        assert_eq!(
+            opened,
+            "[0] Search results for `rust`\n**viewing lines [42 - 42] of 599**\n\nL42: rust 42\n"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use std::error::Error;
+
+use async_trait::async_trait;
+use schemars::{JsonSchema, schema_for};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{ToolInfo, ToolOutput, tools::ToolExecutor};
+
+/// A tool implemented in Rust and called in-process.
+///
+/// The parameter schema advertised to the model is derived from `Params`.
+#[async_trait]
+pub trait FunctionTool: Send + Sync + 'static {
+    const NAME: &'static str;
+    const DESCRIPTION: &'static str;
+    type Params: DeserializeOwned + JsonSchema + Send;
+
+    async fn call(&self, params: Self::Params) -> Result<ToolOutput, Box<dyn Error + Send + Sync>>;
+}
+
+#[async_trait]
+trait ErasedFunctionTool: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn info(&self) -> ToolInfo;
+    async fn call_value(&self, args: Value) -> Result<ToolOutput, Box<dyn Error + Send + Sync>>;
+}
+
+#[async_trait]
+impl<T: FunctionTool> ErasedFunctionTool for T {
+    fn name(&self) -> &'static str {
+        T::NAME
+    }
+
+    fn info(&self) -> ToolInfo {
+        ToolInfo {
+            name: T::NAME.into(),
+            description: T::DESCRIPTION.into(),
+            parameters: schema_for!(T::Params),
+        }
+    }
+
+    async fn call_value(&self, args: Value) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+        let params = serde_json::from_value(args)
+            .map_err(|err| format!("Invalid arguments for {}: {err}", T::NAME))?;
+        self.call(params).await
+    }
+}
+
+/// A group of [`FunctionTool`]s exposed under a common prefix, see
+/// [`crate::mcp::McpContext::insert_functions`].
+pub struct FunctionToolSet {
+    prefix: String,
+    tools: Vec<Box<dyn ErasedFunctionTool>>,
+}
+
+impl FunctionToolSet {
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            tools: Vec::new(),
+        }
+    }
+
+    pub fn with<T: FunctionTool>(mut self, tool: T) -> Self {
+        self.tools.push(Box::new(tool));
+        self
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Returns the tools without the prefix applied.
+    pub fn tool_infos(&self) -> Vec<ToolInfo> {
+        self.tools.iter().map(|t| t.info()).collect()
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for FunctionToolSet {
+    async fn call(
+        &self,
+        name: &str,
+        args: Value,
+    ) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+        let tool = self
+            .tools
+            .iter()
+            .find(|t| t.name() == name)
+            .ok_or_else(|| format!("{name} is not a valid tool name"))?;
+        tool.call_value(args).await
+    }
+
+    fn tool_infos(&self) -> Vec<ToolInfo> {
+        FunctionToolSet::tool_infos(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::McpContext;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Deserialize, JsonSchema)]
+    struct AddParams {
+        a: i64,
+        b: i64,
+    }
+
+    struct Add;
+
+    #[async_trait]
+    impl FunctionTool for Add {
+        const NAME: &'static str = "add";
+        const DESCRIPTION: &'static str = "Adds two numbers";
+        type Params = AddParams;
+
+        async fn call(
+            &self,
+            params: AddParams,
+        ) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+            Ok(ToolOutput::text((params.a + params.b).to_string()))
+        }
+    }
+
+    #[test]
+    fn derives_tool_info_from_params() {
+        let infos = FunctionToolSet::new("math").with(Add).tool_infos();
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].name, "add");
+        assert_eq!(infos[0].description, "Adds two numbers");
+        assert_eq!(
+            infos[0].parameters.get("required"),
+            Some(&json!(["a", "b"]))
+        );
+    }
+
+    #[tokio::test]
+    async fn calls_tool_with_deserialized_params() {
+        let set = FunctionToolSet::new("math").with(Add);
+        let output = set.call("add", json!({"a": 2, "b": 3})).await.unwrap();
+        assert_eq!(output.to_text(), "5");
+
+        let err = set.call("add", json!({"a": 2})).await.unwrap_err();
+        assert!(err.to_string().starts_with("Invalid arguments for add"));
+        assert!(set.call("sub", json!({})).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn composes_with_mcp_context() {
+        let ctx = McpContext::default();
+        ctx.insert_functions(FunctionToolSet::new("math").with(Add));
+        assert_eq!(ctx.tool_names(), vec!["math_add".to_string()]);
+        assert_eq!(ctx.tool_infos()[0].name, "math_add");
+        let output = ctx.call("math_add", json!({"a": 1, "b": 1})).await.unwrap();
+        assert_eq!(output.to_text(), "2");
+
+        ctx.remove("math");
+        assert!(ctx.tool_names().is_empty());
+    }
+}
@@ -108,24 +108,18 @@ impl LlmClient for GeminiRustClient {
                         system_instruction = Some(s.content);
                     }
                 }
-                ChatMessage::Tool(t) => match t.content {
-                    JsonResult::Content { content } => {
-                        builder = builder
-                            .with_function_response(
-                                t.tool_name,
-                                serde_json::json!({ "output": content }),
-                            )
-                            .unwrap();
-                    }
-                    JsonResult::Error { error } => {
-                        builder = builder
-                            .with_function_response(
-                                t.tool_name,
-                                serde_json::json!({ "error": error }),
-                            )
-                            .unwrap();
-                    }
-                },
+                ChatMessage::Tool(t) => {
+                    let response = if t.output.is_error {
+                        serde_json::json!({ "error": t.output.to_text() })
+                    } else if let Some(structured) = t.output.structured_content.clone() {
+                        serde_json::json!({ "output": structured })
+                    } else {
+                        serde_json::json!({ "output": t.output.to_text() })
+                    };
+                    builder = builder
+                        .with_function_response(t.tool_name, response)
+                        .unwrap();
+                }
             }
         }
         if let Some(si) = system_instruction {
@@ -146,6 +140,16 @@ impl LlmClient for GeminiRustClient {
             builder = builder.with_function_calling_mode(FunctionCallingMode::Auto);
         }
 
+        if let Some(max_tokens) = request.max_tokens {
+            builder = builder.with_max_output_tokens(max_tokens as i32);
+        }
+
+        if let Some(format) = &request.response_format {
+            builder = builder
+                .with_response_mime_type("application/json")
+                .with_response_schema(to_openapi_schema(&format.schema));
+        }
+
         if request.think.unwrap_or(true) {
             builder = builder.with_thinking_config(gemini_rust::ThinkingConfig {
                 thinking_level: Some(gemini_rust::ThinkingLevel::ThinkingLevelUnspecified),
@@ -210,6 +214,7 @@ impl LlmClient for GeminiRustClient {
                                             arguments: JsonResult::Content {
                                                 content: function_call.args.clone(),
                                             },
+                                            repaired_from: None,
                                         },
                                         encrypted_content: thought_signature.clone(),
                                     })));
@@ -1,13 +1,20 @@
 use std::error::Error;
 
 use super::{
-    AssistantPart, ChatMessage, ChatMessageRequest, ChatStream, JsonResult, LlmClient,
-    ResponseChunk, ToolCall, ToolInfo, to_openapi_schema,
+    AssistantPart, ChatMessage, ChatMessageRequest, ChatStream, EmbeddingRequest,
+    GrammarConstraint, JsonResult, LlmClient, ResponseChunk, ResponseFormat, TokenDebug, TokenInfo,
+    ToolCall, ToolInfo, json_repair::parse_tool_arguments, to_openapi_schema,
+};
+use crate::{
+    browser::{BROWSER_PREFIX, browser_method},
+    llama_server::{
+        CompletionRequest, TokenProbs, llama_server_completion, llama_server_embedding,
+    },
+    python::{PYTHON_PREFIX, PYTHON_TOOL},
 };
-use crate::llama_server::{CompletionRequest, llama_server_completion};
 use async_trait::async_trait;
 use futures_util::StreamExt;
-use gbnf_rs::Generator;
+use gbnf_rs::{Generator, JSON_GRAMMAR};
 use openai_harmony::{
     HarmonyEncoding, HarmonyEncodingName, StreamableParser,
     chat::{
@@ -17,7 +24,9 @@ use openai_harmony::{
     load_harmony_encoding,
 };
 use reqwest::Client;
-use serde_json::Value;
+use schemars::Schema;
+use serde_json::json;
+use tokio::sync::OnceCell;
 use uuid::Uuid;
 
 pub struct HarmonyClient {
@@ -38,6 +47,55 @@ impl HarmonyClient {
     }
 }
 
+/// The gpt-oss encoding, loaded on first use and shared by all clients.
+static ENCODING: OnceCell<HarmonyEncoding> = OnceCell::const_new();
+
+async fn encoding() -> Result<&'static HarmonyEncoding, Box<dyn Error + Send + Sync>> {
+    ENCODING
+        .get_or_try_init(|| async {
+            tokio::task::spawn_blocking(|| {
+                load_harmony_encoding(HarmonyEncodingName::HarmonyGptOss)
+            })
+            .await
+            .map_err(|e| Box::<dyn Error + Send + Sync>::from(e))?
+            .map_err(|e| Box::<dyn Error + Send + Sync>::from(e))
+        })
+        .await
+}
+
+/// Alternatives reported for each response token when inspecting tokens.
+const TOP_PROBS: u32 = 5;
+
+/// Decodes `tokens`, along with the probabilities llama-server reported for
+/// them, if any.
+fn token_infos(encoding: &HarmonyEncoding, tokens: &[u32], probs: &[TokenProbs]) -> Vec<TokenInfo> {
+    tokens
+        .iter()
+        .enumerate()
+        .map(|(i, &id)| {
+            let text = encoding
+                .tokenizer()
+                .decode_utf8(&[id])
+                .map(|t| t.to_string())
+                .unwrap_or_else(|_| format!("<{id}>"));
+            let probs = probs.get(i);
+            TokenInfo {
+                id,
+                text,
+                prob: probs.map(|p| p.prob),
+                top: probs
+                    .map(|p| {
+                        p.top_probs
+                            .iter()
+                            .map(|t| (t.token.clone(), t.prob))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
 #[derive(Copy, Clone)]
 enum GrammarRoot {
     Harmony,
@@ -76,20 +134,34 @@ fn build_prompt(
 ) -> Result<(Vec<u32>, Option<Vec<u32>>, GrammarRoot), Box<dyn Error + Send + Sync>> {
     let mut system_msgs = Vec::new();
     let mut other_msgs = Vec::new();
-    let mut developer = DeveloperContent::new();
+    let mut instructions = String::new();
     for msg in &request.messages {
         match msg {
             ChatMessage::System(s) => {
                 if !s.content.is_empty() {
-                    developer = developer.with_instructions(s.content.clone());
+                    instructions = s.content.clone();
                 }
             }
             other => other_msgs.push(other.clone()),
         }
     }
-    if !request.tools.is_empty() {
-        let tools: Vec<ToolDescription> = request
-            .tools
+    if let Some(format) = &request.response_format {
+        if !instructions.is_empty() {
+            instructions.push_str("\n\n");
+        }
+        instructions.push_str(&response_format_instructions(format));
+    }
+    let mut developer = DeveloperContent::new();
+    if !instructions.is_empty() {
+        developer = developer.with_instructions(instructions);
+    }
+    let functions: Vec<&ToolInfo> = request
+        .tools
+        .iter()
+        .filter(|t| builtin_recipient(&t.name).is_none())
+        .collect();
+    if !functions.is_empty() {
+        let tools: Vec<ToolDescription> = functions
             .iter()
             .map(|t| {
                 ToolDescription::new(
@@ -101,10 +173,18 @@ fn build_prompt(
             .collect();
         developer = developer.with_function_tools(tools);
     }
-    system_msgs.push(Message::from_role_and_content(
-        Role::System,
-        SystemContent::new(),
-    ));
+    let mut system = SystemContent::new();
+    if request
+        .tools
+        .iter()
+        .any(|t| browser_method(&t.name).is_some())
+    {
+        system = system.with_browser_tool();
+    }
+    if request.tools.iter().any(|t| t.name == PYTHON_TOOL) {
+        system = system.with_python_tool();
+    }
+    system_msgs.push(Message::from_role_and_content(Role::System, system));
     if developer.instructions.is_some() || developer.tools.is_some() {
         system_msgs.push(Message::from_role_and_content(Role::Developer, developer));
     }
@@ -134,36 +214,40 @@ fn build_prompt(
                         }
                         AssistantPart::ToolCall { call, .. } => {
                             let args = match &call.arguments {
+                                // The python tool is sent the script itself.
+                                JsonResult::Content { content } if call.name == PYTHON_TOOL => {
+                                    content["code"].as_str().unwrap_or_default().to_string()
+                                }
                                 JsonResult::Content { .. } => {
                                     call.arguments_content_with_id().to_string()
                                 }
                                 JsonResult::Error { error } => error.clone(),
                             };
-                            convo_msgs.push(
-                                Message::from_role_and_content(Role::Assistant, args)
+                            let msg = Message::from_role_and_content(Role::Assistant, args);
+                            convo_msgs.push(match builtin_recipient(&call.name) {
+                                Some(recipient) => msg
+                                    .with_channel("analysis")
+                                    .with_recipient(recipient)
+                                    .with_content_type("code"),
+                                None => msg
                                     .with_channel("commentary")
                                     .with_recipient(format!("functions.{}", call.name))
                                     .with_content_type("<|constrain|>json"),
-                            );
+                            });
                         }
                     }
                 }
             }
             ChatMessage::Tool(t) => {
-                let content_str = match &t.content {
-                    JsonResult::Content { content } => match content {
-                        Value::String(s) => s.clone(),
-                        v => v.to_string(),
-                    },
-                    JsonResult::Error { error } => error.clone(),
+                let content_str = t.output.to_model_text();
+                let (author, channel) = match builtin_recipient(&t.tool_name) {
+                    Some(recipient) => (recipient, "analysis"),
+                    None => (format!("functions.{}", t.tool_name), "commentary"),
                 };
                 convo_msgs.push(
-                    Message::from_author_and_content(
-                        Author::new(Role::Tool, format!("functions.{}", t.tool_name)),
-                        content_str,
-                    )
-                    .with_channel("commentary")
-                    .with_recipient("assistant"),
+                    Message::from_author_and_content(Author::new(Role::Tool, author), content_str)
+                        .with_channel(channel)
+                        .with_recipient("assistant"),
                 );
             }
             ChatMessage::System(_) => {}
@@ -215,37 +299,102 @@ fn build_prompt(
     conversation_to_prompt(encoding, &conversation, prefill).map(|(t, p)| (t, p, root))
 }
 
+/// The recipient of a tool that gpt-oss has built in, for the tools named
+/// after one. Through an [`crate::mcp::McpContext`] only the browser and
+/// python tool sets get these names, other tools are renamed.
+fn builtin_recipient(tool_name: &str) -> Option<String> {
+    if tool_name == PYTHON_TOOL {
+        return Some(PYTHON_PREFIX.to_string());
+    }
+    browser_method(tool_name).map(|method| format!("{BROWSER_PREFIX}.{method}"))
+}
+
+/// Describes `format` the way gpt-oss was trained to expect in the
+/// developer message.
+fn response_format_instructions(format: &ResponseFormat) -> String {
+    format!(
+        "# Response Formats\n\n## {}\n\n{}",
+        format.name,
+        to_openapi_schema(&format.schema)
+    )
+}
+
 const HARMONY_GRAMMAR: &str = include_str!("harmony.gbnf");
 
+/// The grammar of a response, whose final answer conforms to `response` if
+/// set. Tool arguments follow their schemas unless `constraint` is
+/// [`GrammarConstraint::RecipientOnly`].
 fn build_grammar(
     tools: &[ToolInfo],
+    response: Option<&Schema>,
+    constraint: GrammarConstraint,
     root: GrammarRoot,
 ) -> Result<String, Box<dyn Error + Send + Sync>> {
     let mut grammar = String::from(HARMONY_GRAMMAR);
-    if tools.is_empty() {
-        grammar.push_str("\ntool-call ::= preamble? \"<|channel|>commentary to=functions.\"");
-    } else {
-        let mut tool_alts = Vec::new();
-        let mut extra_rules = String::new();
-        let generator = Generator::new();
-        for tool in tools {
+    grammar.push('\n');
+    grammar.push_str(JSON_GRAMMAR);
+    let generator = Generator::new();
+    match response {
+        Some(schema) => {
+            let g = generator.generate("response", schema);
+            grammar.push_str(&format!("\nfinal ::= {}\n", g.rules[0].name));
+            grammar.push_str(&g.to_string());
+        }
+        None => grammar.push_str("\nfinal ::= not-return*"),
+    }
+    let mut call_alts = Vec::new();
+    let mut extra_rules = String::new();
+    let mut function_alts = Vec::new();
+    let mut browser_alts = Vec::new();
+    let mut python = false;
+    for tool in tools {
+        if let Some(method) = browser_method(&tool.name) {
+            browser_alts.push(format!("\"{method}\""));
+            continue;
+        }
+        if tool.name == PYTHON_TOOL {
+            python = true;
+            continue;
+        }
+        let rule_name = if constraint == GrammarConstraint::RecipientOnly {
+            "object".to_string()
+        } else {
             let g = generator.generate(&format!("{}_json", tool.name), &tool.parameters);
-            let rule_name = g.rules[0].name.clone();
             extra_rules.push('\n');
             extra_rules.push_str(&g.to_string());
-            tool_alts.push(format!(
-                "\"{name}\" ws \"<|constrain|>json<|message|>\" ws {rule} ws",
-                name = tool.name,
-                rule = rule_name
-            ));
-        }
-
-        let tool_call_rule = format!(
-            "tool-call ::= \"<|channel|>commentary to=functions.\" ({})",
-            tool_alts.join(" | ")
-        );
-        grammar.push('\n');
-        grammar.push_str(&tool_call_rule);
+            g.rules[0].name.clone()
+        };
+        function_alts.push(format!(
+            "\"{name}\" ws \"<|constrain|>json<|message|>\" ws {rule} ws",
+            name = tool.name,
+            rule = rule_name
+        ));
+    }
+    if !function_alts.is_empty() {
+        call_alts.push(format!(
+            "\"<|channel|>commentary to=functions.\" ({})",
+            function_alts.join(" | ")
+        ));
+    }
+    // The browser is called from either channel, with the arguments in the
+    // model's own key order.
+    if !browser_alts.is_empty() {
+        call_alts.push(format!(
+            "\"<|channel|>\" (\"analysis\" | \"commentary\") \" to={BROWSER_PREFIX}.\" ({}) \
+             (\" \" \"<|constrain|>\"? [a-z]+)? \"<|message|>\" ws object ws",
+            browser_alts.join(" | ")
+        ));
+    }
+    if python {
+        call_alts.push(format!(
+            "\"<|channel|>\" (\"analysis\" | \"commentary\") \" to={PYTHON_PREFIX}\" \
+             (\" \" \"<|constrain|>\"? [a-z]+)? \"<|message|>\" not-call*"
+        ));
+    }
+    if call_alts.is_empty() {
+        grammar.push_str("\ntool-call ::= preamble? \"<|channel|>commentary to=functions.\"");
+    } else {
+        grammar.push_str(&format!("\ntool-call ::= {}", call_alts.join(" | ")));
         grammar.push_str(&extra_rules);
     }
 
@@ -269,19 +418,26 @@ impl LlmClient for HarmonyClient {
         &self,
         request: ChatMessageRequest,
     ) -> Result<ChatStream, Box<dyn Error + Send + Sync>> {
-        let encoding = tokio::task::spawn_blocking(|| {
-            load_harmony_encoding(HarmonyEncodingName::HarmonyGptOss)
-        })
-        .await
-        .map_err(|e| Box::<dyn Error + Send + Sync>::from(e))?
-        .map_err(|e| Box::<dyn Error + Send + Sync>::from(e))?;
-        let (prompt_tokens, prefill_tokens, root) = build_prompt(&encoding, &request)?;
+        let encoding = encoding().await?;
+        let (prompt_tokens, prefill_tokens, root) = build_prompt(encoding, &request)?;
         let mut input_tokens = prompt_tokens.len() as u32;
-        let grammar = build_grammar(&request.tools, root)?;
+        let response = request.response_format.as_ref().map(|f| &f.schema);
+        let grammar = match request.grammar {
+            GrammarConstraint::Off => None,
+            constraint => Some(build_grammar(&request.tools, response, constraint, root)?),
+        };
+        let inspect = request.inspect_tokens;
+        let prompt_chunk = inspect.then(|| {
+            let prompt = token_infos(encoding, &prompt_tokens, &[]);
+            Ok(ResponseChunk::Tokens(TokenDebug::Prompt(prompt)))
+        });
         let req = CompletionRequest {
             prompt: prompt_tokens,
             stream: true,
-            grammar: Some(grammar),
+            grammar,
+            n_predict: request.max_tokens,
+            n_probs: inspect.then_some(TOP_PROBS),
+            post_sampling_probs: inspect,
         };
         let event_stream = llama_server_completion(&self.http, &self.host, req).await?;
         let mut parser = StreamableParser::new(encoding.clone(), Some(Role::Assistant))?;
@@ -291,11 +447,17 @@ impl LlmClient for HarmonyClient {
             }
         }
         let mut seen = parser.messages().len();
+        let repair_tool_calls = request.repair_tool_calls;
         let mut output_tokens: u32 = 0;
         let mapped = event_stream.flat_map(move |res| match res {
             Ok(chunk) => {
                 output_tokens += chunk.tokens.len() as u32;
                 let mut out = vec![];
+                if inspect && !chunk.tokens.is_empty() {
+                    let tokens =
+                        token_infos(encoding, &chunk.tokens, &chunk.completion_probabilities);
+                    out.push(Ok(ResponseChunk::Tokens(TokenDebug::Response(tokens))));
+                }
                 if !chunk.tokens.is_empty() {
                     for t in chunk.tokens {
                         parser.process(t).ok();
@@ -328,19 +490,28 @@ impl LlmClient for HarmonyClient {
                     let msg = &messages[seen];
                     seen += 1;
                     if let Some(recipient) = &msg.recipient {
-                        if let Some(name) = recipient.strip_prefix("functions.") {
+                        let name = match recipient.split_once('.') {
+                            Some(("functions", name)) => Some(name.to_string()),
+                            Some((BROWSER_PREFIX, method)) => {
+                                Some(format!("{BROWSER_PREFIX}_{method}"))
+                            }
+                            None if recipient == PYTHON_PREFIX => Some(PYTHON_TOOL.to_string()),
+                            _ => None,
+                        };
+                        if let Some(name) = name {
                             if let Some(Content::Text(TextContent { text })) = msg.content.first() {
-                                let arguments = match serde_json::from_str(text) {
-                                    Ok(v) => JsonResult::Content { content: v },
-                                    Err(_) => JsonResult::Error {
-                                        error: text.clone(),
-                                    },
+                                let (arguments, repaired_from) = if name == PYTHON_TOOL {
+                                    let content = json!({ "code": text });
+                                    (JsonResult::Content { content }, None)
+                                } else {
+                                    parse_tool_arguments(text, repair_tool_calls)
                                 };
                                 out.push(Ok(ResponseChunk::Part(AssistantPart::ToolCall {
                                     call: ToolCall {
                                         id: Uuid::new_v4().to_string(),
-                                        name: name.to_string(),
+                                        name,
                                         arguments,
+                                        repaired_from,
                                     },
                                     encrypted_content: None,
                                 })));
@@ -361,18 +532,28 @@ impl LlmClient for HarmonyClient {
             }
             Err(e) => tokio_stream::iter(vec![Err::<ResponseChunk, _>(e.into())]),
         });
-        Ok(Box::pin(mapped))
+        Ok(Box::pin(tokio_stream::iter(prompt_chunk).chain(mapped)))
     }
 
     async fn list_models(&self) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
         Ok(vec!["gpt-oss".to_string()])
     }
+
+    /// Embeds with the model llama-server was started with, which needs
+    /// `--embeddings`.
+    async fn embed(
+        &self,
+        request: EmbeddingRequest,
+    ) -> Result<Vec<Vec<f32>>, Box<dyn Error + Send + Sync>> {
+        llama_server_embedding(&self.http, &self.host, request.input).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{AssistantMessage, AssistantPart, JsonResult, ToolCall};
+    use crate::{AssistantMessage, AssistantPart, JsonResult, ToolCall, ToolOutput};
+    use rmcp::model::{CallToolResult, Content};
     use serde_json::json;
 
     fn prompt_and_prefill(
@@ -482,15 +663,14 @@ mod tests {
                         arguments: JsonResult::Content {
                             content: json!({"a": 2, "b": 2}),
                         },
+                        repaired_from: None,
                     },
                     encrypted_content: None,
                 }],
             }),
             ChatMessage::tool(
                 "1".into(),
-                JsonResult::Content {
-                    content: json!({"sum": 4}),
-                },
+                ToolOutput::text(json!({"sum": 4}).to_string()),
                 "add".into(),
             ),
         ]);
@@ -508,6 +688,34 @@ mod tests {
         assert!(prompt.ends_with(&expected_tail));
     }
 
+    #[test]
+    fn repaired_tool_call_renders_canonical_arguments() {
+        let (_, prompt, _, _) = setup(vec![
+            ChatMessage::user("2+2?".into()),
+            ChatMessage::Assistant(AssistantMessage {
+                content: vec![AssistantPart::ToolCall {
+                    call: ToolCall {
+                        id: "1".into(),
+                        name: "add".into(),
+                        arguments: JsonResult::Content {
+                            content: json!({"a": 2, "b": 2}),
+                        },
+                        repaired_from: Some("{'a': 2, 'b': 2,".into()),
+                    },
+                    encrypted_content: None,
+                }],
+            }),
+            ChatMessage::tool(
+                "1".into(),
+                ToolOutput::text(json!({"sum": 4}).to_string()),
+                "add".into(),
+            ),
+        ]);
+        let args = json!({"a": 2, "b": 2, "_id": "1"}).to_string();
+        assert!(prompt.contains(&format!("<|message|>{args}<|call|>")));
+        assert!(!prompt.contains("'a'"));
+    }
+
     #[test]
     fn parser_continues_after_prefill() {
         let (encoding, _, prefill_tokens, _) = setup(vec![
@@ -527,16 +735,279 @@ mod tests {
         assert_eq!(delta, " world");
     }
 
+    #[test]
+    fn token_infos_pair_tokens_with_probabilities() {
+        let encoding = load_harmony_encoding(HarmonyEncodingName::HarmonyGptOss).unwrap();
+        let tokens = encoding
+            .tokenizer()
+            .encode_with_special_tokens("<|channel|>final<|message|>Hi");
+        let probs: Vec<TokenProbs> = serde_json::from_value(json!([
+            { "prob": 1.0 },
+            { "prob": 0.75, "top_probs": [
+                { "token": "final", "prob": 0.75 },
+                { "token": "analysis", "prob": 0.25 }
+            ] }
+        ]))
+        .unwrap();
+        let infos = token_infos(&encoding, &tokens, &probs);
+        let texts: Vec<_> = infos.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts, ["<|channel|>", "final", "<|message|>", "Hi"]);
+        assert_eq!(infos[0].prob, Some(1.0));
+        assert_eq!(
+            infos[1].top,
+            vec![("final".to_string(), 0.75), ("analysis".to_string(), 0.25)]
+        );
+        assert_eq!(infos[2].prob, None);
+    }
+
     #[test]
     fn grammar_root_selection() {
-        let g = build_grammar(&[], GrammarRoot::Harmony).unwrap();
+        let g = build_grammar(&[], None, GrammarConstraint::Full, GrammarRoot::Harmony).unwrap();
         assert_eq!(g.matches("root ::=").count(), 1);
         assert!(g.contains("root ::= harmony-default"));
-        let g = build_grammar(&[], GrammarRoot::PrefillThinking).unwrap();
+        let g = build_grammar(
+            &[],
+            None,
+            GrammarConstraint::Full,
+            GrammarRoot::PrefillThinking,
+        )
+        .unwrap();
         assert_eq!(g.matches("root ::=").count(), 1);
         assert!(g.contains("root ::= harmony-prefill-thinking"));
-        let g = build_grammar(&[], GrammarRoot::PrefillContent).unwrap();
+        let g = build_grammar(
+            &[],
+            None,
+            GrammarConstraint::Full,
+            GrammarRoot::PrefillContent,
+        )
+        .unwrap();
         assert_eq!(g.matches("root ::=").count(), 1);
         assert!(g.contains("root ::= harmony-prefill-content"));
     }
+
+    #[test]
+    fn grammar_accepts_tool_calls() {
+        let tool = ToolInfo {
+            name: "echo".into(),
+            description: String::new(),
+            parameters: json!({
+                "type": "object",
+                "properties": { "text": { "type": "string" } },
+                "required": ["text"]
+            })
+            .try_into()
+            .unwrap(),
+        };
+        let text = build_grammar(
+            &[tool.clone()],
+            None,
+            GrammarConstraint::Full,
+            GrammarRoot::Harmony,
+        )
+        .unwrap();
+        let grammar = gbnf_rs::parse(&text).unwrap();
+        let matcher = gbnf_rs::Matcher::new(&grammar).unwrap();
+        let call = |args: &str| {
+            format!(
+                "<|channel|>analysis<|message|>Echo it.<|end|><|start|>assistant<|channel|>commentary to=functions.echo <|constrain|>json<|message|>{args}"
+            )
+        };
+        assert!(matcher.accepts("root", &call(r#"{"text":"hi"}"#)));
+        assert!(!matcher.accepts("root", &call(r#"{"text":1}"#)));
+        assert!(!matcher.accepts("root", &call(r#"{"other":"hi"}"#)));
+        assert!(matcher.accepts(
+            "root",
+            "<|channel|>analysis<|message|>Hi.<|end|><|start|>assistant<|channel|>final<|message|>Hello"
+        ));
+
+        let text = build_grammar(
+            &[tool],
+            None,
+            GrammarConstraint::RecipientOnly,
+            GrammarRoot::Harmony,
+        )
+        .unwrap();
+        assert!(!text.contains("echo-json"));
+        let grammar = gbnf_rs::parse(&text).unwrap();
+        let matcher = gbnf_rs::Matcher::new(&grammar).unwrap();
+        assert!(matcher.accepts("root", &call(r#"{"other":"hi"}"#)));
+        assert!(!matcher.accepts("root", &call("[1]")));
+    }
+
+    #[test]
+    fn marks_failed_tool_results() {
+        let encoding = load_harmony_encoding(HarmonyEncodingName::HarmonyGptOss).unwrap();
+        let failed =
+            crate::mcp::tool_output(CallToolResult::error(vec![Content::text("no such file")]));
+        let request = ChatMessageRequest::new(
+            "gpt-oss".into(),
+            vec![
+                ChatMessage::user("Read it".into()),
+                ChatMessage::Assistant(AssistantMessage {
+                    content: vec![AssistantPart::ToolCall {
+                        call: ToolCall {
+                            id: "1".into(),
+                            name: "read".into(),
+                            arguments: JsonResult::Content { content: json!({}) },
+                            repaired_from: None,
+                        },
+                        encrypted_content: None,
+                    }],
+                }),
+                ChatMessage::tool("1".into(), failed, "read".into()),
+            ],
+        );
+        let (prompt, _, _) = prompt_and_prefill(&encoding, &request);
+        assert!(prompt.contains("<|message|>Tool Failed: no such file<|end|>"));
+    }
+
+    #[test]
+    fn browser_is_declared_and_called_natively() {
+        let search = ToolInfo {
+            name: "browser_search".into(),
+            description: String::new(),
+            parameters: json!({"type": "object"}).try_into().unwrap(),
+        };
+        let encoding = load_harmony_encoding(HarmonyEncodingName::HarmonyGptOss).unwrap();
+        let request = ChatMessageRequest::new(
+            "gpt-oss".into(),
+            vec![
+                ChatMessage::user("News?".into()),
+                ChatMessage::Assistant(AssistantMessage {
+                    content: vec![AssistantPart::ToolCall {
+                        call: ToolCall {
+                            id: "1".into(),
+                            name: "browser_search".into(),
+                            arguments: JsonResult::Content {
+                                content: json!({"query": "news"}),
+                            },
+                            repaired_from: None,
+                        },
+                        encrypted_content: None,
+                    }],
+                }),
+                ChatMessage::tool(
+                    "1".into(),
+                    ToolOutput::text("[0] results".into()),
+                    "browser_search".into(),
+                ),
+            ],
+        )
+        .tools(vec![search.clone()]);
+        let (prompt, _, _) = prompt_and_prefill(&encoding, &request);
+        assert!(prompt.contains("namespace browser {"));
+        assert!(!prompt.contains("namespace functions {"));
+        let args = json!({"query": "news", "_id": "1"}).to_string();
+        assert!(prompt.ends_with(&format!(
+            concat!(
+                "<|start|>assistant to=browser.search<|channel|>analysis code<|message|>{args}<|call|>",
+                "<|start|>browser.search to=assistant<|channel|>analysis<|message|>[0] results<|end|>",
+                "<|start|>assistant"
+            ),
+            args = args
+        )));
+
+        let text = build_grammar(
+            &[search],
+            None,
+            GrammarConstraint::Full,
+            GrammarRoot::Harmony,
+        )
+        .unwrap();
+        let grammar = gbnf_rs::parse(&text).unwrap();
+        let matcher = gbnf_rs::Matcher::new(&grammar).unwrap();
+        let call = |recipient: &str| {
+            format!(
+                "<|channel|>analysis<|message|>Look.<|end|><|start|>assistant<|channel|>analysis to={recipient} code<|message|>{{\"query\": \"rust\", \"topn\": 5}}"
+            )
+        };
+        assert!(matcher.accepts("root", &call("browser.search")));
+        assert!(!matcher.accepts("root", &call("browser.open")));
+        assert!(!matcher.accepts("root", &call("functions.browser_search")));
+    }
+
+    #[test]
+    fn python_is_declared_and_called_natively() {
+        let python = ToolInfo {
+            name: PYTHON_TOOL.into(),
+            description: String::new(),
+            parameters: json!({"type": "object"}).try_into().unwrap(),
+        };
+        let encoding = load_harmony_encoding(HarmonyEncodingName::HarmonyGptOss).unwrap();
+        let request = ChatMessageRequest::new(
+            "gpt-oss".into(),
+            vec![
+                ChatMessage::user("2**10?".into()),
+                ChatMessage::Assistant(AssistantMessage {
+                    content: vec![AssistantPart::ToolCall {
+                        call: ToolCall {
+                            id: "1".into(),
+                            name: PYTHON_TOOL.into(),
+                            arguments: JsonResult::Content {
+                                content: json!({"code": "print(2**10)"}),
+                            },
+                            repaired_from: None,
+                        },
+                        encrypted_content: None,
+                    }],
+                }),
+                ChatMessage::tool("1".into(), ToolOutput::text("1024\n"), PYTHON_TOOL.into()),
+            ],
+        )
+        .tools(vec![python.clone()]);
+        let (prompt, _, _) = prompt_and_prefill(&encoding, &request);
+        assert!(prompt.contains("## python\n"));
+        assert!(!prompt.contains("namespace functions {"));
+        assert!(prompt.ends_with(concat!(
+            "<|start|>assistant to=python<|channel|>analysis code<|message|>print(2**10)<|call|>",
+            "<|start|>python to=assistant<|channel|>analysis<|message|>1024\n<|end|>",
+            "<|start|>assistant"
+        )));
+
+        let text = build_grammar(
+            &[python],
+            None,
+            GrammarConstraint::Full,
+            GrammarRoot::Harmony,
+        )
+        .unwrap();
+        let grammar = gbnf_rs::parse(&text).unwrap();
+        let matcher = gbnf_rs::Matcher::new(&grammar).unwrap();
+        let call = |recipient: &str| {
+            format!(
+                "<|channel|>analysis<|message|>Compute.<|end|><|start|>assistant<|channel|>analysis to={recipient} code<|message|>import math\nprint(math.pi)"
+            )
+        };
+        assert!(matcher.accepts("root", &call("python")));
+        assert!(!matcher.accepts("root", &call("functions.python_exec")));
+        assert!(!matcher.accepts("root", &format!("{}<|call|>", call("python"))));
+    }
+
+    #[test]
+    fn grammar_constrains_final_answer() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "answer": { "type": "integer" } },
+            "required": ["answer"]
+        })
+        .try_into()
+        .unwrap();
+        let text = build_grammar(
+            &[],
+            Some(&schema),
+            GrammarConstraint::Full,
+            GrammarRoot::Harmony,
+        )
+        .unwrap();
+        let grammar = gbnf_rs::parse(&text).unwrap();
+        let matcher = gbnf_rs::Matcher::new(&grammar).unwrap();
+        let answer = |text: &str| {
+            format!(
+                "<|channel|>analysis<|message|>Think.<|end|><|start|>assistant<|channel|>final<|message|>{text}"
+            )
+        };
+        assert!(matcher.accepts("root", &answer(r#"{"answer": 42}"#)));
+        assert!(!matcher.accepts("root", &answer("42")));
+        assert!(!matcher.accepts("root", &answer(r#"{"answer": "42"}"#)));
+    }
 }
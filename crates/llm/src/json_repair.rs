@@ -0,0 +1,288 @@
+use serde_json::Value;
+
+use crate::JsonResult;
+
+enum Frame {
+    /// `key_start` is the output offset of a key that has not yet been followed by `:`.
+    Object {
+        key_start: Option<usize>,
+    },
+    Array,
+}
+
+/// Attempts to turn almost-JSON produced by a model into valid JSON.
+///
+/// Handles trailing commas, single-quoted strings, raw control characters
+/// inside strings and output that was truncated before strings, arrays or
+/// objects were closed. Returns `None` if the result still does not parse.
+pub fn repair_json(text: &str) -> Option<Value> {
+    let mut out = String::with_capacity(text.len() + 8);
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut in_string: Option<char> = None;
+    let mut chars = text.trim().chars();
+    while let Some(ch) = chars.next() {
+        if let Some(quote) = in_string {
+            match ch {
+                '\\' => match chars.next() {
+                    Some('\'') => out.push('\''),
+                    Some(c @ ('"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't' | 'u')) => {
+                        out.push('\\');
+                        out.push(c);
+                    }
+                    Some(c) => {
+                        out.push_str("\\\\");
+                        push_string_char(&mut out, c);
+                    }
+                    None => {}
+                },
+                c if c == quote => {
+                    out.push('"');
+                    in_string = None;
+                }
+                '"' => out.push_str("\\\""),
+                c => push_string_char(&mut out, c),
+            }
+            continue;
+        }
+        match ch {
+            '"' | '\'' => {
+                if let Some(Frame::Object { key_start }) = stack.last_mut()
+                    && matches!(out.trim_end().chars().last(), Some('{' | ','))
+                {
+                    *key_start = Some(out.len());
+                }
+                out.push('"');
+                in_string = Some(ch);
+            }
+            '{' => {
+                stack.push(Frame::Object { key_start: None });
+                out.push('{');
+            }
+            '[' => {
+                stack.push(Frame::Array);
+                out.push('[');
+            }
+            '}' | ']' => {
+                strip_trailing_comma(&mut out);
+                stack.pop();
+                out.push(ch);
+            }
+            ':' => {
+                if let Some(Frame::Object { key_start }) = stack.last_mut() {
+                    *key_start = None;
+                }
+                out.push(':');
+            }
+            c => out.push(c),
+        }
+    }
+
+    if in_string.is_some() {
+        strip_partial_escape(&mut out);
+        out.push('"');
+    }
+    if !stack.is_empty() {
+        if let Some(Frame::Object {
+            key_start: Some(start),
+        }) = stack.last()
+        {
+            // A key without a value can't be completed meaningfully, drop it.
+            out.truncate(*start);
+        }
+        complete_trailing_token(&mut out);
+        strip_trailing_comma(&mut out);
+        if out.ends_with(':') {
+            out.push_str("null");
+        }
+        for frame in stack.iter().rev() {
+            out.push(match frame {
+                Frame::Object { .. } => '}',
+                Frame::Array => ']',
+            });
+        }
+    }
+    serde_json::from_str(&out).ok()
+}
+
+/// Parses tool-call arguments, falling back to [`repair_json`] when `repair`
+/// is set. Returns the arguments and, if a repair was needed, the original
+/// text so that callers can record it.
+pub fn parse_tool_arguments(text: &str, repair: bool) -> (JsonResult, Option<String>) {
+    if let Ok(content) = serde_json::from_str::<Value>(text) {
+        return (JsonResult::Content { content }, None);
+    }
+    if repair && let Some(content @ Value::Object(_)) = repair_json(text) {
+        return (JsonResult::Content { content }, Some(text.to_string()));
+    }
+    (
+        JsonResult::Error {
+            error: text.to_string(),
+        },
+        None,
+    )
+}
+
+fn push_string_char(out: &mut String, ch: char) {
+    match ch {
+        '\n' => out.push_str("\\n"),
+        '\r' => out.push_str("\\r"),
+        '\t' => out.push_str("\\t"),
+        c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+        c => out.push(c),
+    }
+}
+
+fn strip_trailing_comma(out: &mut String) {
+    let trimmed = out.trim_end();
+    if trimmed.ends_with(',') {
+        out.truncate(trimmed.len() - 1);
+    } else {
+        out.truncate(trimmed.len());
+    }
+}
+
+/// Removes a `\uXX` escape that was cut off before its four hex digits.
+fn strip_partial_escape(out: &mut String) {
+    if let Some(pos) = out.rfind("\\u") {
+        let tail = &out[pos + 2..];
+        if tail.len() < 4 && tail.chars().all(|c| c.is_ascii_hexdigit()) {
+            out.truncate(pos);
+        }
+    }
+}
+
+/// Completes a literal or number that was truncated, e.g. `tru` or `1.`.
+fn complete_trailing_token(out: &mut String) {
+    let trimmed_len = out.trim_end().len();
+    out.truncate(trimmed_len);
+    let start = out
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+')))
+        .map(|(i, c)| i + c.len_utf8())
+        .unwrap_or(0);
+    let token = &out[start..];
+    if token.is_empty() {
+        return;
+    }
+    for literal in ["true", "false", "null"] {
+        if literal.starts_with(token) {
+            out.truncate(start);
+            out.push_str(literal);
+            return;
+        }
+    }
+    while out.len() > start && out.ends_with(['.', '-', '+', 'e', 'E']) {
+        out.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn repairs_corpus() {
+        // Broken tool-call arguments as emitted by models, with the expected repair.
+        let corpus: Vec<(&str, Value)> = vec![
+            (
+                r#"{"path": "src/main.rs",}"#,
+                json!({"path": "src/main.rs"}),
+            ),
+            (
+                r#"{"paths": ["a.rs", "b.rs",], "recursive": true}"#,
+                json!({"paths": ["a.rs", "b.rs"], "recursive": true}),
+            ),
+            (
+                "{'command': 'ls -la', 'workdir': '/tmp'}",
+                json!({"command": "ls -la", "workdir": "/tmp"}),
+            ),
+            (
+                r#"{"message": 'it\'s done'}"#,
+                json!({"message": "it's done"}),
+            ),
+            (r#"{'text': 'say "hi"'}"#, json!({"text": "say \"hi\""})),
+            (
+                "{\"file_path\": \"a.py\", \"content\": \"def f():\n\treturn 1\n\"}",
+                json!({"file_path": "a.py", "content": "def f():\n\treturn 1\n"}),
+            ),
+            (r#"{"command": "echo hi""#, json!({"command": "echo hi"})),
+            (
+                r##"{"file_path": "notes.md", "content": "# Title"##,
+                json!({"file_path": "notes.md", "content": "# Title"}),
+            ),
+            (
+                r#"{"edits": [{"old": "a", "new": "b"}, {"old": "c""#,
+                json!({"edits": [{"old": "a", "new": "b"}, {"old": "c"}]}),
+            ),
+            (r#"{"a": {"b": [1, 2"#, json!({"a": {"b": [1, 2]}})),
+            (r#"{"a": 1, "b""#, json!({"a": 1})),
+            (r#"{"a": 1, "b": "#, json!({"a": 1, "b": null})),
+            (r#"{"a": 1,"#, json!({"a": 1})),
+            (r#"{"recursive": tru"#, json!({"recursive": true})),
+            (r#"{"limit": 10."#, json!({"limit": 10})),
+            (r#"{"text": "caf\u00e"#, json!({"text": "caf"})),
+            (r#"{"pattern": "\d+"}"#, json!({"pattern": "\\d+"})),
+            (
+                r#"{"text": "naïve — ok", "done": fals"#,
+                json!({"text": "naïve — ok", "done": false}),
+            ),
+            (r#"{"名前": "値", "n": 1."#, json!({"名前": "値", "n": 1})),
+        ];
+        for (broken, expected) in corpus {
+            assert!(
+                serde_json::from_str::<Value>(broken).is_err(),
+                "corpus entry is already valid: {broken}"
+            );
+            assert_eq!(repair_json(broken), Some(expected), "repairing {broken}");
+        }
+    }
+
+    #[test]
+    fn leaves_valid_json_unchanged() {
+        let valid = r#"{"a": [1, {"b": "c, d"}], "e": "it's"}"#;
+        assert_eq!(
+            repair_json(valid),
+            Some(serde_json::from_str::<Value>(valid).unwrap())
+        );
+    }
+
+    #[test]
+    fn gives_up_on_non_json() {
+        assert_eq!(repair_json("not json at all"), None);
+        assert_eq!(repair_json("{\"a\" 1}"), None);
+        // Non-ASCII right before a truncated value.
+        assert_eq!(repair_json("{\"text\": “hi”"), None);
+        assert_eq!(repair_json("{\"a\": 1é"), None);
+        assert_eq!(repair_json("{\"a\": é1"), None);
+    }
+
+    #[test]
+    fn parse_tool_arguments_is_opt_in() {
+        let broken = "{'a': 1,}";
+        match parse_tool_arguments(broken, false) {
+            (JsonResult::Error { error }, None) => assert_eq!(error, broken),
+            _ => panic!("expected error without repair"),
+        }
+        match parse_tool_arguments(broken, true) {
+            (JsonResult::Content { content }, Some(original)) => {
+                assert_eq!(content, json!({"a": 1}));
+                assert_eq!(original, broken);
+            }
+            _ => panic!("expected repaired arguments"),
+        }
+        match parse_tool_arguments(r#"{"a": 1}"#, true) {
+            (JsonResult::Content { .. }, None) => {}
+            _ => panic!("valid arguments should not be marked as repaired"),
+        }
+    }
+
+    #[test]
+    fn parse_tool_arguments_requires_object() {
+        assert!(matches!(
+            parse_tool_arguments("[1, 2,", true),
+            (JsonResult::Error { .. }, None)
+        ));
+    }
+}
@@ -4,8 +4,9 @@ use std::sync::Arc;
 
 use async_trait::async_trait;
 use clap::ValueEnum;
-use schemars::Schema;
-use serde::{Deserialize, Serialize};
+use futures_util::{StreamExt, stream};
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json::{Value, to_value};
 use tokio_stream::Stream;
 
@@ -36,11 +37,11 @@ impl ChatMessage {
         Self::System(SystemMessage { content })
     }
 
-    pub fn tool(id: String, content: JsonResult, tool_name: String) -> Self {
+    pub fn tool(id: String, output: ToolOutput, tool_name: String) -> Self {
         Self::Tool(ToolMessage {
             id,
             tool_name,
-            content,
+            output,
         })
     }
 }
@@ -51,12 +52,66 @@ pub struct UserMessage {
     pub content: String,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct AssistantMessage {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub content: Vec<AssistantPart>,
 }
 
+impl AssistantMessage {
+    /// Adds a streamed part, appending text or thinking to the previous part
+    /// of the same kind unless either has encrypted content.
+    pub fn push(&mut self, part: AssistantPart) {
+        match (self.content.last_mut(), part) {
+            (
+                Some(AssistantPart::Text {
+                    text,
+                    encrypted_content: None,
+                }),
+                AssistantPart::Text {
+                    text: delta,
+                    encrypted_content: None,
+                },
+            )
+            | (
+                Some(AssistantPart::Thinking {
+                    text,
+                    encrypted_content: None,
+                }),
+                AssistantPart::Thinking {
+                    text: delta,
+                    encrypted_content: None,
+                },
+            ) => text.push_str(&delta),
+            (_, part) => self.content.push(part),
+        }
+    }
+
+    /// The text parts, joined.
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|part| match part {
+                AssistantPart::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Deserializes the text of a response requested with a
+    /// [`ResponseFormat`]. A surrounding Markdown code fence is ignored.
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T, Box<dyn Error + Send + Sync>> {
+        let text = self.text();
+        let mut json = text.trim();
+        if let Some(fenced) = json.strip_prefix("```")
+            && let Some(fenced) = fenced.strip_suffix("```")
+        {
+            json = fenced.trim_start_matches("json").trim();
+        }
+        serde_json::from_str(json).map_err(|e| format!("invalid response: {e}").into())
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SystemMessage {
     #[serde(default, skip_serializing_if = "String::is_empty")]
@@ -64,11 +119,170 @@ pub struct SystemMessage {
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "ToolMessageRepr")]
 pub struct ToolMessage {
     pub id: String,
     pub tool_name: String,
     #[serde(flatten)]
-    pub content: JsonResult,
+    pub output: ToolOutput,
+}
+
+/// Deserialization form of [`ToolMessage`] that also accepts the
+/// `content`/`error` fields written by earlier versions.
+#[derive(Deserialize)]
+struct ToolMessageRepr {
+    id: String,
+    tool_name: String,
+    #[serde(flatten)]
+    output: ToolOutput,
+    #[serde(default)]
+    content: Option<Value>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+impl From<ToolMessageRepr> for ToolMessage {
+    fn from(repr: ToolMessageRepr) -> Self {
+        let output = match (repr.error, repr.content) {
+            (Some(error), _) => ToolOutput::error(error),
+            (None, Some(Value::String(text))) => ToolOutput::text(text),
+            (None, Some(value)) => ToolOutput::structured(value),
+            (None, None) => repr.output,
+        };
+        ToolMessage {
+            id: repr.id,
+            tool_name: repr.tool_name,
+            output,
+        }
+    }
+}
+
+/// Starts the text of a failed tool call as the model sees it.
+const TOOL_FAILED: &str = "Tool Failed: ";
+
+/// The result of a tool call, as returned by a [`tools::ToolExecutor`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolOutput {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<ToolOutputPart>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<Value>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_error: bool,
+    /// Set when the output was cut down, see
+    /// [`tool_output_limit::ToolOutputLimit`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncated: Option<Truncation>,
+}
+
+/// Where the full text of a truncated [`ToolOutput`] was stored.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Truncation {
+    /// Identifies the stored output, see
+    /// [`tool_output_limit::ToolOutputLimit::read`].
+    pub id: String,
+    /// The length of the full text, in characters.
+    pub total_chars: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolOutputPart {
+    Text {
+        text: String,
+    },
+    Image {
+        /// Base64 encoded image data.
+        data: String,
+        mime_type: String,
+    },
+    ResourceLink {
+        uri: String,
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mime_type: Option<String>,
+    },
+}
+
+impl ToolOutput {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            parts: vec![ToolOutputPart::Text { text: text.into() }],
+            ..Self::default()
+        }
+    }
+
+    pub fn error(text: impl Into<String>) -> Self {
+        Self {
+            is_error: true,
+            ..Self::text(text)
+        }
+    }
+
+    pub fn structured(value: Value) -> Self {
+        Self {
+            structured_content: Some(value),
+            ..Self::default()
+        }
+    }
+
+    /// Renders the output for providers that only accept text.
+    ///
+    /// Text parts are joined by newlines and other parts are described in
+    /// brackets. Structured content is only used when there is no text, as
+    /// tools conventionally also return it serialized as text.
+    pub fn to_text(&self) -> String {
+        let mut lines = Vec::new();
+        let mut has_text = false;
+        for part in &self.parts {
+            match part {
+                ToolOutputPart::Text { text } => {
+                    has_text = true;
+                    lines.push(text.clone());
+                }
+                ToolOutputPart::Image { mime_type, .. } => {
+                    lines.push(format!("[image: {mime_type}]"));
+                }
+                ToolOutputPart::ResourceLink {
+                    uri,
+                    name,
+                    description,
+                    ..
+                } => match description {
+                    Some(description) => {
+                        lines.push(format!("[resource: {name}]({uri}) {description}"))
+                    }
+                    None => lines.push(format!("[resource: {name}]({uri})")),
+                },
+            }
+        }
+        if !has_text && let Some(value) = &self.structured_content {
+            lines.push(value.to_string());
+        }
+        lines.join("\n")
+    }
+
+    /// Renders the output for the model, like [`ToolOutput::to_text`], with
+    /// failed calls marked as such for providers that have no other way to
+    /// tell.
+    pub fn to_model_text(&self) -> String {
+        let text = self.to_text();
+        if self.is_error && !text.starts_with(TOOL_FAILED) {
+            format!("{TOOL_FAILED}{text}")
+        } else {
+            text
+        }
+    }
+
+    /// Returns the `(mime_type, data)` of each image part.
+    pub fn images(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.parts.iter().filter_map(|part| match part {
+            ToolOutputPart::Image { data, mime_type } => Some((mime_type.as_str(), data.as_str())),
+            _ => None,
+        })
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -93,6 +307,10 @@ pub struct ToolCall {
     pub name: String,
     #[serde(flatten)]
     pub arguments: JsonResult,
+    /// The original argument text when `arguments` was recovered by
+    /// [`json_repair::repair_json`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repaired_from: Option<String>,
 }
 
 impl ToolCall {
@@ -134,6 +352,46 @@ pub struct ToolInfo {
     pub parameters: Schema,
 }
 
+/// A JSON schema the final answer must conform to, see
+/// [`AssistantMessage::parse`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResponseFormat {
+    /// Names the format for providers that require it, using ASCII letters,
+    /// digits, `_` and `-`.
+    pub name: String,
+    pub schema: Schema,
+}
+
+impl ResponseFormat {
+    pub fn new(name: impl Into<String>, schema: Schema) -> Self {
+        Self {
+            name: name.into(),
+            schema,
+        }
+    }
+
+    /// The schema of `T`, named after the type. Subschemas are inlined, as
+    /// not all providers resolve references.
+    pub fn of<T: JsonSchema>() -> Self {
+        let name = T::schema_name()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .take(64)
+            .collect::<String>();
+        let schema = schemars::generate::SchemaSettings::default()
+            .with(|s| s.inline_subschemas = true)
+            .into_generator()
+            .into_root_schema_for::<T>();
+        Self::new(name, schema)
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ChatMessageRequest {
     pub model_name: String,
@@ -142,6 +400,23 @@ pub struct ChatMessageRequest {
     pub tools: Vec<ToolInfo>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub think: Option<bool>,
+    /// Attempt to repair malformed tool-call arguments instead of reporting them as errors.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub repair_tool_calls: bool,
+    /// Upper bound on the number of tokens to generate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    /// Constrains the final answer to a JSON schema.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    /// How much of the response is constrained, for providers that decode
+    /// with a grammar.
+    #[serde(default)]
+    pub grammar: GrammarConstraint,
+    /// Ask for the raw prompt and response tokens, which providers that
+    /// support it send as [`ResponseChunk::Tokens`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub inspect_tokens: bool,
 }
 
 impl ChatMessageRequest {
@@ -151,6 +426,11 @@ impl ChatMessageRequest {
             messages,
             tools: Vec::new(),
             think: None,
+            repair_tool_calls: false,
+            max_tokens: None,
+            response_format: None,
+            grammar: GrammarConstraint::default(),
+            inspect_tokens: false,
         }
     }
 
@@ -163,16 +443,81 @@ impl ChatMessageRequest {
         self.think = Some(think);
         self
     }
+
+    pub fn repair_tool_calls(mut self, repair: bool) -> Self {
+        self.repair_tool_calls = repair;
+        self
+    }
+
+    pub fn max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn response_format(mut self, format: ResponseFormat) -> Self {
+        self.response_format = Some(format);
+        self
+    }
+
+    pub fn grammar(mut self, grammar: GrammarConstraint) -> Self {
+        self.grammar = grammar;
+        self
+    }
+
+    pub fn inspect_tokens(mut self, inspect: bool) -> Self {
+        self.inspect_tokens = inspect;
+        self
+    }
+}
+
+/// How much of a response is held to a grammar, see
+/// [`ChatMessageRequest::grammar`]. Large grammars slow down some servers.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum GrammarConstraint {
+    /// Tool arguments follow their schemas.
+    #[default]
+    Full,
+    /// Tool names are constrained, their arguments can be any JSON object.
+    RecipientOnly,
+    /// No grammar is sent.
+    Off,
+}
+
+/// Texts to embed with a model, see [`LlmClient::embed`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EmbeddingRequest {
+    pub model_name: String,
+    pub input: Vec<String>,
 }
 
+impl EmbeddingRequest {
+    pub fn new(model_name: String, input: Vec<String>) -> Self {
+        Self { model_name, input }
+    }
+}
+
+pub mod agent;
+pub mod browser;
+pub mod function_tool;
 pub mod gemini_rust;
 pub mod harmony;
+pub mod json_repair;
 mod llama_server;
 pub mod mcp;
+mod mcp_events;
+mod mcp_sampling;
+mod mcp_sse;
 pub mod ollama;
 pub mod openai_chat;
+pub mod python;
 pub mod test_provider;
+#[cfg(test)]
+mod test_server;
+mod tool_names;
+pub mod tool_output_limit;
 pub mod tools;
+pub mod vector_index;
 
 pub use test_provider::TestProvider;
 
@@ -184,6 +529,8 @@ pub enum Provider {
     OpenAiChat,
     Harmony,
     GeminiRust,
+    Vllm,
+    Sglang,
 }
 
 #[derive(Clone)]
@@ -219,6 +566,13 @@ impl LlmClient for Client {
     async fn list_models(&self) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
         self.inner.list_models().await
     }
+
+    async fn embed(
+        &self,
+        request: EmbeddingRequest,
+    ) -> Result<Vec<Vec<f32>>, Box<dyn Error + Send + Sync>> {
+        self.inner.embed(request).await
+    }
 }
 
 pub fn client_from(
@@ -231,6 +585,12 @@ pub fn client_from(
         Provider::OpenAiChat => Arc::new(openai_chat::OpenAiChatClient::new(host)),
         Provider::Harmony => Arc::new(harmony::HarmonyClient::new(host)),
         Provider::GeminiRust => Arc::new(gemini_rust::GeminiRustClient::new(host)?),
+        Provider::Vllm => Arc::new(openai_chat::OpenAiChatClient::new(host).guided(
+            openai_chat::GuidedDecoding::Vllm(openai_chat::GuidedFormat::Grammar),
+        )),
+        Provider::Sglang => Arc::new(openai_chat::OpenAiChatClient::new(host).guided(
+            openai_chat::GuidedDecoding::Sglang(openai_chat::GuidedFormat::Grammar),
+        )),
     };
     Ok(Client {
         inner,
@@ -246,9 +606,31 @@ pub enum ResponseChunk {
         input_tokens: u32,
         output_tokens: u32,
     },
+    /// Raw tokens, see [`ChatMessageRequest::inspect_tokens`].
+    Tokens(TokenDebug),
     Done,
 }
 
+/// Raw tokens of a request, as the model sees them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenDebug {
+    /// The rendered prompt, sent before the response.
+    Prompt(Vec<TokenInfo>),
+    /// The next tokens of the response.
+    Response(Vec<TokenInfo>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenInfo {
+    pub id: u32,
+    /// The decoded text, including special tokens such as `<|end|>`.
+    pub text: String,
+    /// The probability the token was sampled with, if known.
+    pub prob: Option<f32>,
+    /// The most likely tokens at this position and their probabilities.
+    pub top: Vec<(String, f32)>,
+}
+
 pub type ChatStream =
     Pin<Box<dyn Stream<Item = Result<ResponseChunk, Box<dyn Error + Send + Sync>>> + Send>>;
 
@@ -286,6 +668,20 @@ pub fn to_openapi_schema(schema: &Schema) -> Value {
     value
 }
 
+/// Tokens used by one or more requests.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+}
+
+/// A whole response, see [`LlmClient::complete`].
+#[derive(Clone, Debug, Default)]
+pub struct Completion {
+    pub message: AssistantMessage,
+    pub usage: Usage,
+}
+
 #[async_trait]
 pub trait LlmClient: Send + Sync {
     async fn send_chat_messages_stream(
@@ -294,6 +690,53 @@ pub trait LlmClient: Send + Sync {
     ) -> Result<ChatStream, Box<dyn Error + Send + Sync>>;
 
     async fn list_models(&self) -> Result<Vec<String>, Box<dyn Error + Send + Sync>>;
+
+    /// Sends `request` and waits for the whole response. Tool calls are
+    /// returned, not run.
+    async fn complete(
+        &self,
+        request: ChatMessageRequest,
+    ) -> Result<Completion, Box<dyn Error + Send + Sync>> {
+        let mut stream = self.send_chat_messages_stream(request).await?;
+        let mut completion = Completion::default();
+        while let Some(chunk) = stream.next().await {
+            match chunk? {
+                ResponseChunk::Part(part) => completion.message.push(part),
+                ResponseChunk::Usage {
+                    input_tokens,
+                    output_tokens,
+                } => {
+                    completion.usage.input_tokens += input_tokens;
+                    completion.usage.output_tokens += output_tokens;
+                }
+                ResponseChunk::Tokens(_) => {}
+                ResponseChunk::Done => break,
+            }
+        }
+        Ok(completion)
+    }
+
+    /// Embeds each input text, returning one vector per text in order.
+    async fn embed(
+        &self,
+        _request: EmbeddingRequest,
+    ) -> Result<Vec<Vec<f32>>, Box<dyn Error + Send + Sync>> {
+        Err("embeddings are not supported by this provider".into())
+    }
+}
+
+/// Completes independent `requests` on `client`, running at most
+/// `concurrency` at a time. The results are in the order of the requests.
+pub async fn complete_batch<C: LlmClient + ?Sized>(
+    client: &C,
+    requests: impl IntoIterator<Item = ChatMessageRequest>,
+    concurrency: usize,
+) -> Vec<Result<Completion, Box<dyn Error + Send + Sync>>> {
+    stream::iter(requests)
+        .map(|request| client.complete(request))
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
 }
 
 #[cfg(test)]
@@ -307,6 +750,176 @@ mod tests {
         value: u32,
     }
 
+    #[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+    struct Answer {
+        value: u32,
+    }
+
+    #[tokio::test]
+    async fn completes_requests_alone_and_in_batches() {
+        let provider = TestProvider::new();
+        let text = |text: &str| {
+            ResponseChunk::Part(AssistantPart::Text {
+                text: text.into(),
+                encrypted_content: None,
+            })
+        };
+        let usage = ResponseChunk::Usage {
+            input_tokens: 3,
+            output_tokens: 1,
+        };
+        provider.enqueue(vec![
+            ResponseChunk::Part(AssistantPart::Thinking {
+                text: "Hm".into(),
+                encrypted_content: None,
+            }),
+            text("Hel"),
+            usage.clone(),
+            text("lo"),
+            usage.clone(),
+            ResponseChunk::Done,
+        ]);
+        let request = || ChatMessageRequest::new("m".into(), vec![ChatMessage::user("hi".into())]);
+        let completion = provider.complete(request()).await.unwrap();
+        assert_eq!(completion.message.content.len(), 2);
+        assert_eq!(completion.message.text(), "Hello");
+        assert_eq!(
+            completion.usage,
+            Usage {
+                input_tokens: 6,
+                output_tokens: 2
+            }
+        );
+
+        struct Slow {
+            running: std::sync::atomic::AtomicUsize,
+            most: std::sync::atomic::AtomicUsize,
+        }
+
+        #[async_trait]
+        impl LlmClient for Slow {
+            async fn send_chat_messages_stream(
+                &self,
+                request: ChatMessageRequest,
+            ) -> Result<ChatStream, Box<dyn Error + Send + Sync>> {
+                use std::sync::atomic::Ordering::SeqCst;
+                let running = self.running.fetch_add(1, SeqCst) + 1;
+                self.most.fetch_max(running, SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                self.running.fetch_sub(1, SeqCst);
+                let ChatMessage::User(prompt) = &request.messages[0] else {
+                    unreachable!()
+                };
+                let chunk = ResponseChunk::Part(AssistantPart::Text {
+                    text: prompt.content.clone(),
+                    encrypted_content: None,
+                });
+                Ok(Box::pin(tokio_stream::iter(vec![Ok(chunk)])))
+            }
+
+            async fn list_models(&self) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+                Ok(vec![])
+            }
+        }
+
+        let client = Slow {
+            running: Default::default(),
+            most: Default::default(),
+        };
+        let requests = (0..10)
+            .map(|i| ChatMessageRequest::new("m".into(), vec![ChatMessage::user(i.to_string())]));
+        let results = complete_batch(&client, requests, 3).await;
+        let texts: Vec<String> = results
+            .into_iter()
+            .map(|r| r.unwrap().message.text())
+            .collect();
+        assert_eq!(texts, (0..10).map(|i| i.to_string()).collect::<Vec<_>>());
+        assert_eq!(client.most.load(std::sync::atomic::Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn parses_responses_of_a_format() {
+        let format = ResponseFormat::of::<Answer>();
+        assert_eq!(format.name, "Answer");
+        assert_eq!(
+            format.schema.get("required"),
+            Some(&serde_json::json!(["value"]))
+        );
+
+        let message = |text: &str| AssistantMessage {
+            content: vec![
+                AssistantPart::Thinking {
+                    text: "{}".into(),
+                    encrypted_content: None,
+                },
+                AssistantPart::Text {
+                    text: text.into(),
+                    encrypted_content: None,
+                },
+            ],
+        };
+        assert_eq!(
+            message(r#"{"value": 1}"#).parse::<Answer>().unwrap(),
+            Answer { value: 1 }
+        );
+        assert_eq!(
+            message("```json\n{\"value\": 2}\n```")
+                .parse::<Answer>()
+                .unwrap(),
+            Answer { value: 2 }
+        );
+        assert!(message("two").parse::<Answer>().is_err());
+    }
+
+    #[test]
+    fn tool_message_reads_legacy_format() {
+        let msg: ChatMessage = serde_json::from_str(
+            r#"{"role": "tool", "id": "1", "tool_name": "t", "content": {"a": 1}}"#,
+        )
+        .unwrap();
+        let ChatMessage::Tool(t) = msg else {
+            panic!("expected tool message");
+        };
+        assert_eq!(
+            t.output,
+            ToolOutput::structured(serde_json::json!({"a": 1}))
+        );
+
+        let msg: ChatMessage = serde_json::from_str(
+            r#"{"role": "tool", "id": "1", "tool_name": "t", "error": "Tool Failed: x"}"#,
+        )
+        .unwrap();
+        let ChatMessage::Tool(t) = msg else {
+            panic!("expected tool message");
+        };
+        assert_eq!(t.output, ToolOutput::error("Tool Failed: x"));
+    }
+
+    #[test]
+    fn tool_message_round_trips() {
+        let output = ToolOutput {
+            parts: vec![
+                ToolOutputPart::Text {
+                    text: "see image".into(),
+                },
+                ToolOutputPart::Image {
+                    data: "aGk=".into(),
+                    mime_type: "image/png".into(),
+                },
+            ],
+            structured_content: Some(serde_json::json!({"ok": true})),
+            is_error: false,
+            truncated: None,
+        };
+        let msg = ChatMessage::tool("1".into(), output.clone(), "t".into());
+        let json = serde_json::to_string(&msg).unwrap();
+        let ChatMessage::Tool(t) = serde_json::from_str(&json).unwrap() else {
+            panic!("expected tool message");
+        };
+        assert_eq!(t.output, output);
+        assert_eq!(t.output.to_text(), "see image\n[image: image/png]");
+    }
+
     #[test]
     fn unsigned_integers_use_signed_format() {
         let schema = schemars::schema_for!(Params);
@@ -11,6 +11,14 @@ pub struct CompletionRequest {
     pub stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub grammar: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n_predict: Option<u32>,
+    /// Number of most likely tokens reported for each generated token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n_probs: Option<u32>,
+    /// Report probabilities after sampling rather than log-probabilities.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub post_sampling_probs: bool,
 }
 
 /// Streamed response chunk from the llama-server `/completion` endpoint.
@@ -20,6 +28,24 @@ pub struct CompletionResponse {
     pub tokens: Vec<u32>,
     #[serde(default)]
     pub stop: bool,
+    /// Set when `n_probs` was requested, one entry per token.
+    #[serde(default)]
+    pub completion_probabilities: Vec<TokenProbs>,
+}
+
+/// The probability of a generated token and the most likely alternatives.
+#[derive(Default, Deserialize)]
+pub struct TokenProbs {
+    #[serde(default)]
+    pub prob: f32,
+    #[serde(default)]
+    pub top_probs: Vec<TopProb>,
+}
+
+#[derive(Deserialize)]
+pub struct TopProb {
+    pub token: String,
+    pub prob: f32,
 }
 
 pub type CompletionStream =
@@ -50,3 +76,93 @@ pub async fn llama_server_completion(
     });
     Ok(Box::pin(stream))
 }
+
+/// Request payload for the llama-server `/embedding` endpoint.
+#[derive(Serialize)]
+struct EmbeddingRequest {
+    content: Vec<String>,
+}
+
+/// One result of the llama-server `/embedding` endpoint.
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    #[serde(default)]
+    index: usize,
+    embedding: Embedding,
+}
+
+/// A pooled embedding, or one embedding per token when the server runs
+/// with `--pooling none`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Embedding {
+    Pooled(Vec<f32>),
+    Tokens(Vec<Vec<f32>>),
+}
+
+impl Embedding {
+    /// Averages per-token embeddings into one vector.
+    fn pooled(self) -> Vec<f32> {
+        match self {
+            Embedding::Pooled(v) => v,
+            Embedding::Tokens(tokens) => {
+                let n = tokens.len().max(1) as f32;
+                let mut sum = vec![0.0; tokens.first().map_or(0, Vec::len)];
+                for token in &tokens {
+                    for (s, x) in sum.iter_mut().zip(token) {
+                        *s += x;
+                    }
+                }
+                sum.into_iter().map(|s| s / n).collect()
+            }
+        }
+    }
+}
+
+/// Embeds `content` with the llama-server `/embedding` endpoint.
+pub async fn llama_server_embedding(
+    client: &reqwest::Client,
+    host: &str,
+    content: Vec<String>,
+) -> Result<Vec<Vec<f32>>, Box<dyn Error + Send + Sync>> {
+    let url = format!("{}/embedding", host.trim_end_matches('/'));
+    let mut results: Vec<EmbeddingResponse> = client
+        .post(url)
+        .json(&EmbeddingRequest { content })
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    results.sort_by_key(|r| r.index);
+    Ok(results.into_iter().map(|r| r.embedding.pooled()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::serve;
+    use axum::{Json, Router, routing::post};
+    use serde_json::{Value, json};
+
+    #[tokio::test]
+    async fn embeds_in_order_and_pools_tokens() {
+        let router = Router::new().route(
+            "/embedding",
+            post(|Json(body): Json<Value>| async move {
+                assert_eq!(body, json!({ "content": ["a", "b"] }));
+                Json(json!([
+                    { "index": 1, "embedding": [[1.0, 2.0], [3.0, 4.0]] },
+                    { "index": 0, "embedding": [0.5, 0.5] },
+                ]))
+            }),
+        );
+        let host = format!("{}/", serve(router).await);
+
+        let embeddings =
+            llama_server_embedding(&reqwest::Client::new(), &host, vec!["a".into(), "b".into()])
+                .await
+                .unwrap();
+        assert_eq!(embeddings, vec![vec![0.5, 0.5], vec![2.0, 3.0]]);
+    }
+}
@@ -1,61 +1,263 @@
-use arc_swap::ArcSwap;
+use arc_swap::{ArcSwap, ArcSwapOption};
 use async_trait::async_trait;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderName, HeaderValue};
 use rmcp::{
-    ClientHandler,
-    model::{CallToolRequestParams, RawContent},
-    service::{NotificationContext, RoleClient, RunningService, ServiceExt},
-    transport::TokioChildProcess,
+    ClientHandler, ErrorData as McpError,
+    model::{
+        CallToolRequestParams, CallToolResult, ClientCapabilities, ClientInfo, ClientRequest,
+        CreateElicitationRequestParams, CreateElicitationResult, CreateMessageRequestParams,
+        CreateMessageResult, GetPromptRequestParams, LoggingMessageNotificationParam,
+        NumberOrString, ProgressNotificationParam, ProgressToken, Prompt, PromptMessageContent,
+        PromptMessageRole, RawContent, ReadResourceRequestParams, Request, RequestId,
+        RequestParamsMeta, Resource, ResourceContents, ResourceTemplate,
+        ResourceUpdatedNotificationParam, ServerResult, SubscribeRequestParams,
+        UnsubscribeRequestParams,
+    },
+    service::{
+        NotificationContext, Peer, PeerRequestOptions, RequestContext, RoleClient, RunningService,
+        ServiceExt,
+    },
+    transport::{
+        StreamableHttpClientTransport, TokioChildProcess,
+        streamable_http_client::StreamableHttpClientTransportConfig,
+    },
 };
+use schemars::JsonSchema;
 use serde::Deserialize;
-use serde_json::Value;
+use serde_json::{Value, json};
 use std::{
     collections::HashMap,
+    error::Error,
     fmt,
-    sync::{Arc, Mutex},
+    path::PathBuf,
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
 };
-use tokio::process::Command;
+use tokio::{
+    process::Command,
+    sync::{broadcast, mpsc::UnboundedSender},
+    task::AbortHandle,
+};
+
+use crate::{
+    ChatMessage, Schema, ToolInfo, ToolOutput, ToolOutputPart,
+    function_tool::{FunctionTool, FunctionToolSet},
+    mcp_sse::connect_sse,
+    tool_names::{ToolKey, ToolNames, ToolSource},
+    tools::{ToolExecutor, ToolProgress},
+};
+
+pub use crate::mcp_events::{Elicitation, ElicitationRequest, ElicitationResponse, McpLogMessage};
+pub use crate::mcp_sampling::{AllowSampling, Sampling, SamplingPolicy, SamplingRequest};
+pub use rmcp::model::LoggingLevel;
 
-use crate::{Schema, ToolInfo, tools::ToolExecutor};
+/// Prefix of the tools of [`McpContext::resource_tools`].
+pub const RESOURCE_TOOLS_PREFIX: &str = "mcp";
+
+/// Handlers shared by the services of a context, for the requests and
+/// notifications that servers send to the client.
+struct ClientHandlers {
+    sampling: ArcSwapOption<Sampling>,
+    elicitation: ArcSwapOption<Box<dyn Elicitation>>,
+    logs: broadcast::Sender<McpLogMessage>,
+    /// Progress of running tool calls, keyed by progress token.
+    progress: Mutex<HashMap<String, UnboundedSender<ToolProgress>>>,
+}
+
+impl Default for ClientHandlers {
+    fn default() -> Self {
+        Self {
+            sampling: ArcSwapOption::empty(),
+            elicitation: ArcSwapOption::empty(),
+            logs: broadcast::channel(256).0,
+            progress: Mutex::new(HashMap::new()),
+        }
+    }
+}
 
 pub struct McpService {
+    /// The name of the server in the configuration.
+    pub name: String,
+    /// Prepended to the names of the server's tools.
     pub prefix: String,
     pub tools: ArcSwap<Vec<ToolInfo>>,
+    pub resources: ArcSwap<Vec<Resource>>,
+    pub resource_templates: ArcSwap<Vec<ResourceTemplate>>,
+    pub prompts: ArcSwap<Vec<Prompt>>,
+    resource_updates: broadcast::Sender<String>,
+    handlers: Arc<ClientHandlers>,
+    filter: ToolFilter,
+    tool_timeout: Option<Duration>,
 }
 
-#[derive(Debug)]
-pub struct InvalidPrefixError {
-    prefix: String,
-}
-
-impl InvalidPrefixError {
-    pub fn new(prefix: impl Into<String>) -> Self {
+impl McpService {
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
         Self {
-            prefix: prefix.into(),
+            prefix: name.clone(),
+            name,
+            tools: ArcSwap::new(Arc::new(Vec::new())),
+            resources: ArcSwap::new(Arc::new(Vec::new())),
+            resource_templates: ArcSwap::new(Arc::new(Vec::new())),
+            prompts: ArcSwap::new(Arc::new(Vec::new())),
+            resource_updates: broadcast::channel(16).0,
+            handlers: Arc::default(),
+            filter: ToolFilter::default(),
+            tool_timeout: None,
+        }
+    }
+
+    async fn refresh_resources(&self, peer: &Peer<RoleClient>) {
+        let supported = peer
+            .peer_info()
+            .is_some_and(|info| info.capabilities.resources.is_some());
+        if !supported {
+            return;
+        }
+        if let Ok(resources) = peer.list_all_resources().await {
+            self.resources.store(Arc::new(resources));
+        }
+        if let Ok(templates) = peer.list_all_resource_templates().await {
+            self.resource_templates.store(Arc::new(templates));
         }
     }
 
-    pub fn prefix(&self) -> &str {
-        &self.prefix
+    async fn refresh_prompts(&self, peer: &Peer<RoleClient>) {
+        let supported = peer
+            .peer_info()
+            .is_some_and(|info| info.capabilities.prompts.is_some());
+        if supported && let Ok(prompts) = peer.list_all_prompts().await {
+            self.prompts.store(Arc::new(prompts));
+        }
     }
 }
 
-impl fmt::Display for InvalidPrefixError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "MCP prefix '{}' must not contain '_'", self.prefix)
-    }
+/// A resource listed by an MCP server.
+#[derive(Clone, Debug, PartialEq)]
+pub struct McpResource {
+    pub server: String,
+    pub uri: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub mime_type: Option<String>,
 }
 
-impl std::error::Error for InvalidPrefixError {}
+/// A prompt template published by an MCP server.
+#[derive(Clone, Debug, PartialEq)]
+pub struct McpPrompt {
+    pub server: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub arguments: Vec<McpPromptArgument>,
+}
 
-fn validate_prefix(prefix: &str) -> Result<(), InvalidPrefixError> {
-    if prefix.contains('_') {
-        Err(InvalidPrefixError::new(prefix))
-    } else {
-        Ok(())
-    }
+#[derive(Clone, Debug, PartialEq)]
+pub struct McpPromptArgument {
+    pub name: String,
+    pub description: Option<String>,
+    pub required: bool,
+}
+
+/// A parameterized resource URI, e.g. `file:///{path}`, listed by an MCP server.
+#[derive(Clone, Debug, PartialEq)]
+pub struct McpResourceTemplate {
+    pub server: String,
+    pub uri_template: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub mime_type: Option<String>,
 }
 
 impl ClientHandler for McpService {
+    fn get_info(&self) -> ClientInfo {
+        let mut info = ClientInfo::default();
+        info.capabilities = ClientCapabilities::builder()
+            .enable_sampling()
+            .enable_elicitation()
+            .build();
+        info
+    }
+
+    fn create_message(
+        &self,
+        params: CreateMessageRequestParams,
+        _context: RequestContext<RoleClient>,
+    ) -> impl std::future::Future<Output = Result<CreateMessageResult, McpError>> + Send + '_ {
+        async move {
+            let Some(sampling) = self.handlers.sampling.load_full() else {
+                return Err(McpError::invalid_request("sampling is not enabled", None));
+            };
+            sampling.create_message(&self.name, params).await
+        }
+    }
+
+    fn create_elicitation(
+        &self,
+        params: CreateElicitationRequestParams,
+        _context: RequestContext<RoleClient>,
+    ) -> impl std::future::Future<Output = Result<CreateElicitationResult, McpError>> + Send + '_
+    {
+        async move {
+            let params = serde_json::to_value(&params)
+                .map_err(|err| McpError::internal_error(err.to_string(), None))?;
+            let elicitation = self.handlers.elicitation.load_full();
+            // Only form mode is supported, URL mode requests are declined.
+            let response = match elicitation {
+                Some(elicitation) if params["mode"].as_str().unwrap_or("form") == "form" => {
+                    elicitation
+                        .elicit(ElicitationRequest {
+                            server: self.name.clone(),
+                            message: params["message"].as_str().unwrap_or_default().to_string(),
+                            schema: params["requestedSchema"].clone(),
+                        })
+                        .await
+                }
+                _ => ElicitationResponse::Decline,
+            };
+            let result = match response {
+                ElicitationResponse::Accept(content) => {
+                    json!({ "action": "accept", "content": content })
+                }
+                ElicitationResponse::Decline => json!({ "action": "decline" }),
+                ElicitationResponse::Cancel => json!({ "action": "cancel" }),
+            };
+            serde_json::from_value(result)
+                .map_err(|err| McpError::internal_error(err.to_string(), None))
+        }
+    }
+
+    fn on_progress(
+        &self,
+        params: ProgressNotificationParam,
+        _context: NotificationContext<RoleClient>,
+    ) -> impl std::future::Future<Output = ()> + Send + '_ {
+        if let NumberOrString::String(token) = &params.progress_token.0
+            && let Some(progress) = self.handlers.progress.lock().unwrap().get(&**token)
+        {
+            let _ = progress.send(ToolProgress {
+                progress: params.progress,
+                total: params.total,
+                message: params.message,
+            });
+        }
+        std::future::ready(())
+    }
+
+    fn on_logging_message(
+        &self,
+        params: LoggingMessageNotificationParam,
+        _context: NotificationContext<RoleClient>,
+    ) -> impl std::future::Future<Output = ()> + Send + '_ {
+        let _ = self.handlers.logs.send(McpLogMessage {
+            server: self.name.clone(),
+            level: params.level,
+            logger: params.logger,
+            data: params.data,
+        });
+        std::future::ready(())
+    }
+
     fn on_tool_list_changed(
         &self,
         context: NotificationContext<RoleClient>,
@@ -63,7 +265,7 @@ impl ClientHandler for McpService {
         async move {
             if let Ok(tools) = context.peer.list_all_tools().await {
                 let mut infos = Vec::new();
-                for tool in tools {
+                for tool in tools.into_iter().filter(|t| self.filter.allows(&t.name)) {
                     if let Ok(schema) =
                         serde_json::from_value::<Schema>(tool.schema_as_json_value())
                     {
@@ -79,56 +281,567 @@ impl ClientHandler for McpService {
             }
         }
     }
+
+    fn on_resource_list_changed(
+        &self,
+        context: NotificationContext<RoleClient>,
+    ) -> impl std::future::Future<Output = ()> + Send + '_ {
+        async move { self.refresh_resources(&context.peer).await }
+    }
+
+    fn on_prompt_list_changed(
+        &self,
+        context: NotificationContext<RoleClient>,
+    ) -> impl std::future::Future<Output = ()> + Send + '_ {
+        async move { self.refresh_prompts(&context.peer).await }
+    }
+
+    fn on_resource_updated(
+        &self,
+        params: ResourceUpdatedNotificationParam,
+        _context: NotificationContext<RoleClient>,
+    ) -> impl std::future::Future<Output = ()> + Send + '_ {
+        let _ = self.resource_updates.send(params.uri);
+        std::future::ready(())
+    }
+}
+
+/// How often running servers are checked for having exited.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+/// Number of consecutive restarts before a crashing server is given up on.
+const MAX_RESTARTS: u32 = 5;
+/// A server that stays up this long is restarted without backoff again.
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+/// The delay before restart `attempt` (starting at 1): 1s, 2s, 4s, … up to 30s.
+fn restart_backoff(attempt: u32) -> Duration {
+    Duration::from_secs(1 << attempt.saturating_sub(1).min(5)).min(Duration::from_secs(30))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum McpServerStatus {
+    Starting,
+    Running,
+    /// The server exited and is restarted after a backoff.
+    Restarting {
+        attempt: u32,
+    },
+    /// The server could not be started, or kept exiting.
+    Failed(String),
+    Stopped,
+}
+
+impl fmt::Display for McpServerStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            McpServerStatus::Starting => write!(f, "starting"),
+            McpServerStatus::Running => write!(f, "running"),
+            McpServerStatus::Restarting { attempt } => {
+                write!(f, "restarting (attempt {attempt}/{MAX_RESTARTS})")
+            }
+            McpServerStatus::Failed(err) => write!(f, "failed: {err}"),
+            McpServerStatus::Stopped => write!(f, "stopped"),
+        }
+    }
+}
+
+/// A configured server, see [`McpContext::servers`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct McpServerInfo {
+    pub name: String,
+    pub status: McpServerStatus,
+    pub tools: usize,
+}
+
+/// A configured server and the task that restarts it when it exits.
+struct ServerSlot {
+    config: McpServer,
+    status: McpServerStatus,
+    supervisor: Option<AbortHandle>,
 }
 
 #[derive(Default, Clone)]
 pub struct McpContext {
     services: Arc<Mutex<HashMap<String, RunningService<RoleClient, McpService>>>>,
+    slots: Arc<Mutex<HashMap<String, ServerSlot>>>,
+    functions: Arc<Mutex<HashMap<String, Arc<FunctionToolSet>>>>,
+    handlers: Arc<ClientHandlers>,
+    names: Arc<Mutex<ToolNames>>,
+}
+
+/// A [`McpContext`] that doesn't keep it alive, held by the tools that the
+/// context owns.
+#[derive(Clone)]
+struct WeakMcpContext {
+    services: Weak<Mutex<HashMap<String, RunningService<RoleClient, McpService>>>>,
+    slots: Weak<Mutex<HashMap<String, ServerSlot>>>,
+    functions: Weak<Mutex<HashMap<String, Arc<FunctionToolSet>>>>,
+    handlers: Weak<ClientHandlers>,
+    names: Weak<Mutex<ToolNames>>,
+}
+
+impl WeakMcpContext {
+    fn upgrade(&self) -> Result<McpContext, Box<dyn Error + Send + Sync>> {
+        let closed = || "the MCP context was closed";
+        Ok(McpContext {
+            services: self.services.upgrade().ok_or_else(closed)?,
+            slots: self.slots.upgrade().ok_or_else(closed)?,
+            functions: self.functions.upgrade().ok_or_else(closed)?,
+            handlers: self.handlers.upgrade().ok_or_else(closed)?,
+            names: self.names.upgrade().ok_or_else(closed)?,
+        })
+    }
 }
 
 impl McpContext {
-    pub fn insert(
-        &self,
-        service: RunningService<RoleClient, McpService>,
-    ) -> Result<(), InvalidPrefixError> {
-        let prefix = service.service().prefix.clone();
-        validate_prefix(&prefix)?;
-        self.services.lock().unwrap().insert(prefix, service);
-        Ok(())
+    fn downgrade(&self) -> WeakMcpContext {
+        WeakMcpContext {
+            services: Arc::downgrade(&self.services),
+            slots: Arc::downgrade(&self.slots),
+            functions: Arc::downgrade(&self.functions),
+            handlers: Arc::downgrade(&self.handlers),
+            names: Arc::downgrade(&self.names),
+        }
+    }
+
+    /// Serves sampling requests from the servers of this context, or rejects
+    /// them when `None`. Takes effect for servers that are already connected.
+    pub fn set_sampling(&self, sampling: Option<Sampling>) {
+        self.handlers.sampling.store(sampling.map(Arc::new));
+    }
+
+    /// Asks `elicitation` for the input requested by servers, or declines
+    /// their requests when `None`.
+    pub fn set_elicitation(&self, elicitation: Option<Box<dyn Elicitation>>) {
+        self.handlers.elicitation.store(elicitation.map(Arc::new));
+    }
+
+    /// Receives the log messages sent by all servers of this context.
+    pub fn subscribe_logs(&self) -> broadcast::Receiver<McpLogMessage> {
+        self.handlers.logs.subscribe()
+    }
+
+    pub fn insert(&self, service: RunningService<RoleClient, McpService>) {
+        let name = service.service().name.clone();
+        self.services.lock().unwrap().insert(name, service);
+        self.named_tools();
+    }
+
+    /// Registers in-process tools alongside the MCP servers.
+    pub fn insert_functions(&self, tools: FunctionToolSet) {
+        let prefix = tools.prefix().to_string();
+        self.functions
+            .lock()
+            .unwrap()
+            .insert(prefix, Arc::new(tools));
+        self.named_tools();
     }
 
     pub fn remove(&self, prefix: &str) {
+        if let Some(slot) = self.slots.lock().unwrap().remove(prefix)
+            && let Some(supervisor) = slot.supervisor
+        {
+            supervisor.abort();
+        }
         self.services.lock().unwrap().remove(prefix);
+        self.functions.lock().unwrap().remove(prefix);
     }
 
+    /// The tools of the servers and in-process tool sets, under unique names
+    /// that providers accept: `<prefix>_<tool>` with unsupported characters
+    /// replaced, shortened with a hash when too long or colliding.
     pub fn tool_infos(&self) -> Vec<ToolInfo> {
-        let mut infos = Vec::new();
+        self.named_tools()
+    }
+
+    pub fn tool_names(&self) -> Vec<String> {
+        self.named_tools()
+            .into_iter()
+            .map(|tool| tool.name)
+            .collect()
+    }
+
+    /// The tools of every source, under their own names.
+    fn tools(&self) -> Vec<(ToolKey, ToolInfo)> {
+        let mut tools = Vec::new();
+        for (name, svc) in self.services.lock().unwrap().iter() {
+            for tool in svc.service().tools.load().iter() {
+                let key = ToolKey {
+                    source: ToolSource::Server(name.clone()),
+                    prefix: svc.service().prefix.clone(),
+                    tool: tool.name.clone(),
+                };
+                tools.push((key, tool.clone()));
+            }
+        }
+        for (prefix, set) in self.functions.lock().unwrap().iter() {
+            for tool in set.tool_infos() {
+                let key = ToolKey {
+                    source: ToolSource::Functions(prefix.clone()),
+                    prefix: prefix.clone(),
+                    tool: tool.name.clone(),
+                };
+                tools.push((key, tool));
+            }
+        }
+        tools
+    }
+
+    /// Names the current tools, see [`ToolNames`], and warns in the log of
+    /// their server about tools renamed because their names collide.
+    fn named_tools(&self) -> Vec<ToolInfo> {
+        let tools = self.tools();
+        let mut names = self.names.lock().unwrap();
+        let collisions = names.update(tools.iter().map(|(key, _)| key.clone()).collect());
+        for collision in collisions {
+            let _ = self.handlers.logs.send(McpLogMessage {
+                server: collision.key.source.name().to_string(),
+                level: LoggingLevel::Warning,
+                logger: Some("llment".into()),
+                data: Value::String(format!(
+                    "tool {} is named {} because {} is already taken",
+                    collision.key.tool, collision.name, collision.preferred
+                )),
+            });
+        }
+        let mut infos: Vec<ToolInfo> = tools
+            .into_iter()
+            .filter_map(|(key, tool)| {
+                Some(ToolInfo {
+                    name: names.name(&key)?.to_string(),
+                    ..tool
+                })
+            })
+            .collect();
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        infos
+    }
+
+    fn resolve_tool(&self, name: &str) -> Option<ToolKey> {
+        self.names.lock().unwrap().resolve(name).cloned()
+    }
+
+    pub fn resources(&self) -> Vec<McpResource> {
         let services = self.services.lock().unwrap();
+        let mut resources = Vec::new();
         for svc in services.values() {
-            let prefix = svc.service().prefix.clone();
-            let tools = svc.service().tools.load();
-            for tool in tools.iter() {
-                infos.push(ToolInfo {
-                    name: format!("{}_{}", prefix, tool.name),
-                    description: tool.description.clone(),
-                    parameters: tool.parameters.clone(),
+            let server = &svc.service().name;
+            for resource in svc.service().resources.load().iter() {
+                resources.push(McpResource {
+                    server: server.clone(),
+                    uri: resource.raw.uri.clone(),
+                    name: resource.raw.name.clone(),
+                    description: resource.raw.description.clone(),
+                    mime_type: resource.raw.mime_type.clone(),
                 });
             }
         }
-        infos
+        resources.sort_by(|a, b| (&a.server, &a.uri).cmp(&(&b.server, &b.uri)));
+        resources
     }
 
-    pub fn tool_names(&self) -> Vec<String> {
-        let mut names = Vec::new();
+    pub fn resource_templates(&self) -> Vec<McpResourceTemplate> {
+        let services = self.services.lock().unwrap();
+        let mut templates = Vec::new();
+        for svc in services.values() {
+            let server = &svc.service().name;
+            for template in svc.service().resource_templates.load().iter() {
+                templates.push(McpResourceTemplate {
+                    server: server.clone(),
+                    uri_template: template.raw.uri_template.clone(),
+                    name: template.raw.name.clone(),
+                    description: template.raw.description.clone(),
+                    mime_type: template.raw.mime_type.clone(),
+                });
+            }
+        }
+        templates.sort_by(|a, b| (&a.server, &a.uri_template).cmp(&(&b.server, &b.uri_template)));
+        templates
+    }
+
+    pub fn prompts(&self) -> Vec<McpPrompt> {
         let services = self.services.lock().unwrap();
+        let mut prompts = Vec::new();
         for svc in services.values() {
-            let prefix = svc.service().prefix.clone();
-            let tools = svc.service().tools.load();
-            for tool in tools.iter() {
-                names.push(format!("{}_{}", prefix, tool.name));
+            let server = &svc.service().name;
+            for prompt in svc.service().prompts.load().iter() {
+                let arguments = prompt
+                    .arguments
+                    .iter()
+                    .flatten()
+                    .map(|arg| McpPromptArgument {
+                        name: arg.name.clone(),
+                        description: arg.description.clone(),
+                        required: arg.required.unwrap_or(false),
+                    })
+                    .collect();
+                prompts.push(McpPrompt {
+                    server: server.clone(),
+                    name: prompt.name.clone(),
+                    description: prompt.description.clone(),
+                    arguments,
+                });
+            }
+        }
+        prompts.sort_by(|a, b| (&a.server, &a.name).cmp(&(&b.server, &b.name)));
+        prompts
+    }
+
+    /// Expands a prompt into chat messages. Non-text content is rendered as
+    /// a placeholder, see [`ToolOutput::to_text`].
+    pub async fn get_prompt(
+        &self,
+        server: &str,
+        name: &str,
+        arguments: &HashMap<String, String>,
+    ) -> Result<Vec<ChatMessage>, Box<dyn Error + Send + Sync>> {
+        let mut params = GetPromptRequestParams::new(name);
+        params.arguments = Some(
+            arguments
+                .iter()
+                .map(|(k, v)| (k.clone(), Value::String(v.clone())))
+                .collect(),
+        );
+        let result = self.peer(server)?.get_prompt(params).await?;
+        Ok(result
+            .messages
+            .into_iter()
+            .map(|message| {
+                let part = match message.content {
+                    PromptMessageContent::Text { text } => ToolOutputPart::Text { text },
+                    PromptMessageContent::Image { image } => ToolOutputPart::Image {
+                        data: image.raw.data,
+                        mime_type: image.raw.mime_type,
+                    },
+                    PromptMessageContent::Resource { resource } => {
+                        resource_part(resource.raw.resource)
+                    }
+                    PromptMessageContent::ResourceLink { link } => ToolOutputPart::ResourceLink {
+                        uri: link.raw.uri,
+                        name: link.raw.name,
+                        description: link.raw.description,
+                        mime_type: link.raw.mime_type,
+                    },
+                };
+                let text = ToolOutput {
+                    parts: vec![part],
+                    ..ToolOutput::default()
+                }
+                .to_text();
+                match message.role {
+                    PromptMessageRole::User => ChatMessage::user(text),
+                    PromptMessageRole::Assistant => ChatMessage::assistant(text),
+                }
+            })
+            .collect())
+    }
+
+    /// Asks the server to complete the value of a prompt argument. `context`
+    /// holds the arguments that were already provided.
+    pub async fn complete_prompt_argument(
+        &self,
+        server: &str,
+        prompt: &str,
+        argument: &str,
+        value: &str,
+        context: &HashMap<String, String>,
+    ) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        let context = serde_json::from_value(json!({ "arguments": context }))?;
+        let completion = self
+            .peer(server)?
+            .complete_prompt_argument(prompt, argument, value, Some(context))
+            .await?;
+        Ok(completion.values)
+    }
+
+    fn peer(&self, server: &str) -> Result<Peer<RoleClient>, Box<dyn Error + Send + Sync>> {
+        let services = self.services.lock().unwrap();
+        let svc = services
+            .get(server)
+            .ok_or_else(|| format!("{server} is not a connected MCP server"))?;
+        Ok(svc.peer().clone())
+    }
+
+    pub async fn read_resource(
+        &self,
+        server: &str,
+        uri: &str,
+    ) -> Result<Vec<ToolOutputPart>, Box<dyn Error + Send + Sync>> {
+        let result = self
+            .peer(server)?
+            .read_resource(ReadResourceRequestParams::new(uri))
+            .await?;
+        Ok(result.contents.into_iter().map(resource_part).collect())
+    }
+
+    /// Subscribes to changes of `uri`. The receiver yields the URI of every
+    /// updated resource the server has been subscribed to.
+    pub async fn subscribe_resource(
+        &self,
+        server: &str,
+        uri: &str,
+    ) -> Result<broadcast::Receiver<String>, Box<dyn Error + Send + Sync>> {
+        let updates = {
+            let services = self.services.lock().unwrap();
+            let svc = services
+                .get(server)
+                .ok_or_else(|| format!("{server} is not a connected MCP server"))?;
+            svc.service().resource_updates.subscribe()
+        };
+        self.peer(server)?
+            .subscribe(SubscribeRequestParams::new(uri))
+            .await?;
+        Ok(updates)
+    }
+
+    pub async fn unsubscribe_resource(
+        &self,
+        server: &str,
+        uri: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.peer(server)?
+            .unsubscribe(UnsubscribeRequestParams::new(uri))
+            .await?;
+        Ok(())
+    }
+
+    /// Returns `list_resources` and `read_resource` tools that let the model
+    /// browse the resources of every connected server.
+    ///
+    /// They are named like the tools of a server named `mcp`, and renamed
+    /// like any other colliding tool if such a server was connected first.
+    pub fn resource_tools(&self) -> FunctionToolSet {
+        FunctionToolSet::new(RESOURCE_TOOLS_PREFIX)
+            .with(ListResources {
+                ctx: self.downgrade(),
+            })
+            .with(ReadResource {
+                ctx: self.downgrade(),
+            })
+    }
+}
+
+/// The output of an MCP tool call. A result that the server marks as an
+/// error stays an output, flagged with `is_error`.
+pub(crate) fn tool_output(result: CallToolResult) -> ToolOutput {
+    let parts = result
+        .content
+        .into_iter()
+        .filter_map(|c| match c.raw {
+            RawContent::Text(t) => Some(ToolOutputPart::Text { text: t.text }),
+            RawContent::Image(i) => Some(ToolOutputPart::Image {
+                data: i.data,
+                mime_type: i.mime_type,
+            }),
+            RawContent::ResourceLink(r) => Some(ToolOutputPart::ResourceLink {
+                uri: r.uri,
+                name: r.name,
+                description: r.description,
+                mime_type: r.mime_type,
+            }),
+            RawContent::Resource(r) => Some(resource_part(r.resource)),
+            _ => None,
+        })
+        .collect();
+    ToolOutput {
+        parts,
+        structured_content: result.structured_content,
+        is_error: result.is_error.unwrap_or(false),
+        truncated: None,
+    }
+}
+
+fn resource_part(contents: ResourceContents) -> ToolOutputPart {
+    match contents {
+        ResourceContents::TextResourceContents { text, .. } => ToolOutputPart::Text { text },
+        ResourceContents::BlobResourceContents {
+            mime_type: Some(mime_type),
+            blob,
+            ..
+        } if mime_type.starts_with("image/") => ToolOutputPart::Image {
+            data: blob,
+            mime_type,
+        },
+        ResourceContents::BlobResourceContents { uri, mime_type, .. } => ToolOutputPart::Text {
+            text: format!(
+                "[binary resource {uri} ({})]",
+                mime_type.as_deref().unwrap_or("unknown type")
+            ),
+        },
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ListResourcesParams {}
+
+struct ListResources {
+    ctx: WeakMcpContext,
+}
+
+#[async_trait]
+impl FunctionTool for ListResources {
+    const NAME: &'static str = "list_resources";
+    const DESCRIPTION: &'static str =
+        "Lists the resources and resource templates exposed by the connected MCP servers";
+    type Params = ListResourcesParams;
+
+    async fn call(
+        &self,
+        _params: ListResourcesParams,
+    ) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+        let ctx = self.ctx.upgrade()?;
+        let mut lines = Vec::new();
+        for r in ctx.resources() {
+            let mut line = format!("{} {} ({})", r.server, r.uri, r.name);
+            if let Some(description) = r.description {
+                line.push_str(&format!(": {description}"));
+            }
+            lines.push(line);
+        }
+        for t in ctx.resource_templates() {
+            let mut line = format!("{} {} ({}, template)", t.server, t.uri_template, t.name);
+            if let Some(description) = t.description {
+                line.push_str(&format!(": {description}"));
             }
+            lines.push(line);
+        }
+        if lines.is_empty() {
+            lines.push("No resources available".into());
         }
-        names
+        Ok(ToolOutput::text(lines.join("\n")))
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ReadResourceParams {
+    /// The name of the MCP server that exposes the resource
+    pub server: String,
+    /// The resource URI, or a resource template with its parameters filled in
+    pub uri: String,
+}
+
+struct ReadResource {
+    ctx: WeakMcpContext,
+}
+
+#[async_trait]
+impl FunctionTool for ReadResource {
+    const NAME: &'static str = "read_resource";
+    const DESCRIPTION: &'static str = "Reads a resource from an MCP server";
+    type Params = ReadResourceParams;
+
+    async fn call(
+        &self,
+        params: ReadResourceParams,
+    ) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+        let ctx = self.ctx.upgrade()?;
+        Ok(match ctx.read_resource(&params.server, &params.uri).await {
+            Ok(parts) => ToolOutput {
+                parts,
+                ..ToolOutput::default()
+            },
+            Err(err) => ToolOutput::error(err.to_string()),
+        })
     }
 }
 
@@ -138,43 +851,158 @@ impl ToolExecutor for McpContext {
         &self,
         name: &str,
         args: Value,
-    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        let (prefix, tool_name) = name
-            .split_once('_')
-            .ok_or_else(|| format!("{name} is not a valid tool name"))?;
-        let peer = {
+    ) -> Result<ToolOutput, Box<dyn std::error::Error + Send + Sync>> {
+        self.call_tool(name, args, None).await
+    }
+
+    async fn call_with_progress(
+        &self,
+        name: &str,
+        args: Value,
+        progress: UnboundedSender<ToolProgress>,
+    ) -> Result<ToolOutput, Box<dyn std::error::Error + Send + Sync>> {
+        self.call_tool(name, args, Some(progress)).await
+    }
+
+    fn tool_infos(&self) -> Vec<ToolInfo> {
+        McpContext::tool_infos(self)
+    }
+
+    fn server_tool_name(&self, server: &str, tool: &str) -> Option<String> {
+        let prefix = self
+            .services
+            .lock()
+            .unwrap()
+            .get(server)?
+            .service()
+            .prefix
+            .clone();
+        let key = ToolKey {
+            source: ToolSource::Server(server.to_string()),
+            prefix,
+            tool: tool.to_string(),
+        };
+        let name = self.names.lock().unwrap().name(&key).map(str::to_string);
+        // Tools that appeared since they were last listed are named first.
+        name.or_else(|| {
+            self.named_tools();
+            self.names.lock().unwrap().name(&key).map(str::to_string)
+        })
+    }
+}
+
+impl McpContext {
+    async fn call_tool(
+        &self,
+        name: &str,
+        args: Value,
+        progress: Option<UnboundedSender<ToolProgress>>,
+    ) -> Result<ToolOutput, Box<dyn std::error::Error + Send + Sync>> {
+        let invalid = || format!("{name} is not a valid tool name");
+        // Tools that appeared since they were last listed are named first.
+        let key = match self.resolve_tool(name) {
+            Some(key) => key,
+            None => {
+                self.named_tools();
+                self.resolve_tool(name).ok_or_else(invalid)?
+            }
+        };
+        let server = match &key.source {
+            ToolSource::Functions(prefix) => {
+                let functions = self.functions.lock().unwrap().get(prefix).cloned();
+                return functions.ok_or_else(invalid)?.call(&key.tool, args).await;
+            }
+            ToolSource::Server(server) => server,
+        };
+        let (peer, timeout) = {
             let services = self.services.lock().unwrap();
             let svc = services
-                .get(prefix)
-                .ok_or_else(|| format!("{name} is not a valid tool name"))?;
-            svc.peer().clone()
-        };
-        let result = peer
-            .call_tool(
-                CallToolRequestParams::new(tool_name.to_string())
-                    .with_arguments(args.as_object().cloned().unwrap_or_default()),
+                .get(server)
+                .filter(|s| s.service().tools.load().iter().any(|t| t.name == key.tool))
+                .ok_or_else(invalid)?;
+            (svc.peer().clone(), svc.service().tool_timeout)
+        };
+        let mut params = CallToolRequestParams::new(key.tool.clone())
+            .with_arguments(args.as_object().cloned().unwrap_or_default());
+        let _progress = progress.map(|progress| {
+            let token = uuid::Uuid::new_v4().to_string();
+            params.set_progress_token(ProgressToken(NumberOrString::String(token.as_str().into())));
+            ProgressGuard::new(self.handlers.clone(), token, progress)
+        });
+        let handle = peer
+            .send_cancellable_request(
+                ClientRequest::CallToolRequest(Request::new(params)),
+                PeerRequestOptions::no_options(),
             )
             .await?;
-        let text = if !result.content.is_empty() {
-            result
-                .content
-                .into_iter()
-                .filter_map(|c| match c.raw {
-                    RawContent::Text(t) => Some(t.text),
-                    _ => None,
-                })
-                .collect::<Vec<_>>()
-                .join("\n")
-        } else if let Some(value) = result.structured_content {
-            value.to_string()
-        } else {
-            String::new()
+        let mut guard = CancelOnDrop {
+            peer: Some(peer),
+            request_id: handle.id.clone(),
+        };
+        let response = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, handle.await_response())
+                .await
+                .map_err(|_| format!("{name} timed out after {}ms", timeout.as_millis()))?,
+            None => handle.await_response().await,
+        };
+        guard.peer = None;
+        let result = match response? {
+            ServerResult::CallToolResult(result) => result,
+            _ => return Err(format!("unexpected response to {name}").into()),
         };
+        Ok(tool_output(result))
+    }
+}
 
-        if result.is_error.unwrap_or(false) {
-            Err(text.into())
-        } else {
-            Ok(text)
+/// Forwards progress notifications for a tool call until it completes.
+struct ProgressGuard {
+    handlers: Arc<ClientHandlers>,
+    token: String,
+}
+
+impl ProgressGuard {
+    fn new(
+        handlers: Arc<ClientHandlers>,
+        token: String,
+        progress: UnboundedSender<ToolProgress>,
+    ) -> Self {
+        handlers
+            .progress
+            .lock()
+            .unwrap()
+            .insert(token.clone(), progress);
+        Self { handlers, token }
+    }
+}
+
+impl Drop for ProgressGuard {
+    fn drop(&mut self) {
+        self.handlers.progress.lock().unwrap().remove(&self.token);
+    }
+}
+
+/// Sends `notifications/cancelled` if a tool call is dropped before the
+/// server responds, e.g. because the tool loop was cancelled.
+struct CancelOnDrop {
+    peer: Option<Peer<RoleClient>>,
+    request_id: RequestId,
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        let Some(peer) = self.peer.take() else {
+            return;
+        };
+        let Ok(params) = serde_json::from_value(json!({
+            "requestId": self.request_id,
+            "reason": "cancelled by user",
+        })) else {
+            return;
+        };
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                let _ = peer.notify_cancelled(params).await;
+            });
         }
     }
 }
@@ -185,64 +1013,1515 @@ struct McpConfig {
     mcp_servers: HashMap<String, McpServer>,
 }
 
-#[derive(Deserialize)]
-struct McpServer {
-    command: String,
-    #[serde(default)]
-    args: Vec<String>,
-    #[serde(default)]
-    env: HashMap<String, String>,
+/// Reads the servers configured in `paths`. A server configured in several
+/// files takes its configuration from the last one. Files that can't be read
+/// and servers whose variables can't be expanded are skipped, and their
+/// errors returned alongside.
+async fn read_mcp_config(paths: &[PathBuf]) -> (HashMap<String, McpServer>, Vec<String>) {
+    let mut servers = HashMap::new();
+    let mut errors = Vec::new();
+    for path in paths {
+        let config = match tokio::fs::read_to_string(path).await {
+            Ok(data) => serde_json::from_str::<McpConfig>(&data).map_err(|err| err.to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+        let config = match config {
+            Ok(config) => config,
+            Err(err) => {
+                errors.push(format!("{}: {err}", path.display()));
+                continue;
+            }
+        };
+        for (name, server) in config.mcp_servers {
+            match server.expand_env() {
+                Ok(server) => {
+                    servers.insert(name, server);
+                }
+                Err(err) => {
+                    servers.remove(&name);
+                    errors.push(format!("MCP server '{name}': {err}"));
+                }
+            }
+        }
+    }
+    (servers, errors)
 }
 
-pub async fn load_mcp_servers(
-    path: &str,
-) -> Result<McpContext, Box<dyn std::error::Error + Send + Sync>> {
-    let data = tokio::fs::read_to_string(path).await?;
-    let config: McpConfig = serde_json::from_str(&data)?;
-    let ctx = McpContext::default();
-    for (server_name, server) in config.mcp_servers.iter() {
-        validate_prefix(server_name)?;
-        let mut cmd = Command::new(&server.command);
-        cmd.args(&server.args);
-        for (k, v) in &server.env {
-            cmd.env(k, v);
-        }
-        let process = TokioChildProcess::new(cmd)?;
-        let handler = McpService {
-            prefix: server_name.clone(),
-            tools: ArcSwap::new(Arc::new(Vec::new())),
+/// Expands `${VAR}` and `${VAR:-default}` in `value`. `$` that does not
+/// start such a reference is kept as is.
+fn expand_env(value: &str) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let reference = &rest[start + 2..];
+        let end = reference
+            .find('}')
+            .ok_or_else(|| format!("unterminated variable reference in '{value}'"))?;
+        let (var, default) = match reference[..end].split_once(":-") {
+            Some((var, default)) => (var, Some(default)),
+            None => (&reference[..end], None),
         };
-        let service = handler.serve(process).await?;
-        let tools = service.peer().list_all_tools().await?;
-        let mut infos = Vec::new();
-        for tool in tools {
-            let schema: Schema = serde_json::from_value(tool.schema_as_json_value())?;
-            let description = tool.description.clone().unwrap_or_default().to_string();
-            infos.push(ToolInfo {
-                name: tool.name.to_string(),
-                description,
-                parameters: schema,
-            });
+        match (std::env::var(var), default) {
+            (Ok(v), _) if !v.is_empty() || default.is_none() => expanded.push_str(&v),
+            (_, Some(default)) => expanded.push_str(default),
+            (Err(_), None) => return Err(format!("environment variable {var} is not set")),
         }
-        service.service().tools.store(Arc::new(infos));
-        ctx.insert(service)?;
+        rest = &reference[end + 1..];
     }
-    Ok(ctx)
+    expanded.push_str(rest);
+    Ok(expanded)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::validate_prefix;
+/// Selects the tools of a server by name, see `includeTools` and
+/// `excludeTools`.
+#[derive(Clone, Debug, Default)]
+struct ToolFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl ToolFilter {
+    fn new(include: &[String], exclude: &[String]) -> Result<Self, globset::Error> {
+        let build = |globs: &[String]| {
+            let mut set = GlobSetBuilder::new();
+            for glob in globs {
+                set.add(Glob::new(glob)?);
+            }
+            set.build()
+        };
+        Ok(Self {
+            include: if include.is_empty() {
+                None
+            } else {
+                Some(build(include)?)
+            },
+            exclude: build(exclude)?,
+        })
+    }
+
+    fn allows(&self, tool: &str) -> bool {
+        self.include.as_ref().is_none_or(|set| set.is_match(tool)) && !self.exclude.is_match(tool)
+    }
+}
+
+/// The configuration of a server, as in the `mcpServers` of an mcp.json file.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct McpServer {
+    #[serde(default)]
+    command: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    #[serde(default, rename = "type")]
+    transport: Option<McpTransport>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    bearer_token: Option<String>,
+    /// The working directory of a STDIO server.
+    #[serde(default)]
+    cwd: Option<String>,
+    /// Keeps the server configured but does not start it.
+    #[serde(default)]
+    disabled: bool,
+    /// Globs selecting the tools to expose, all of them when empty.
+    #[serde(default)]
+    include_tools: Vec<String>,
+    /// Globs selecting tools to hide, applied after `include_tools`.
+    #[serde(default)]
+    exclude_tools: Vec<String>,
+    /// Time allowed to connect and list the tools, 30 seconds by default.
+    #[serde(default)]
+    startup_timeout_ms: Option<u64>,
+    /// Time allowed for a single tool call, unlimited by default.
+    #[serde(default)]
+    tool_timeout_ms: Option<u64>,
+    /// Prepended to the tool names instead of the server name.
+    #[serde(default)]
+    prefix: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum McpTransport {
+    Stdio,
+    Http,
+    Sse,
+}
+
+impl McpServer {
+    /// A server launched as a child process that talks over STDIO.
+    pub fn command(command: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            command: Some(command.into()),
+            args,
+            ..Self::default()
+        }
+    }
+
+    /// A remote server that uses the streamable HTTP transport.
+    pub fn url(url: impl Into<String>) -> Self {
+        Self {
+            url: Some(url.into()),
+            ..Self::default()
+        }
+    }
+
+    /// The prefix of the server's tool names.
+    fn prefix<'a>(&'a self, name: &'a str) -> &'a str {
+        self.prefix.as_deref().unwrap_or(name)
+    }
+
+    /// Replaces `${VAR}` and `${VAR:-default}` with environment variables in
+    /// every string of the configuration.
+    fn expand_env(mut self) -> Result<Self, String> {
+        let values = self
+            .command
+            .iter_mut()
+            .chain(self.args.iter_mut())
+            .chain(self.env.values_mut())
+            .chain(self.url.iter_mut())
+            .chain(self.headers.values_mut())
+            .chain(self.bearer_token.iter_mut())
+            .chain(self.cwd.iter_mut());
+        for value in values {
+            *value = expand_env(value)?;
+        }
+        Ok(self)
+    }
+
+    fn http_client(&self) -> Result<reqwest::Client, Box<dyn std::error::Error + Send + Sync>> {
+        let mut headers = HeaderMap::new();
+        for (k, v) in &self.headers {
+            headers.insert(HeaderName::try_from(k.as_str())?, HeaderValue::try_from(v)?);
+        }
+        if let Some(token) = &self.bearer_token {
+            headers.insert(
+                AUTHORIZATION,
+                HeaderValue::try_from(format!("Bearer {token}"))?,
+            );
+        }
+        Ok(reqwest::Client::builder()
+            .default_headers(headers)
+            .build()?)
+    }
+}
+
+impl McpContext {
+    /// The configured servers, sorted by name.
+    pub fn servers(&self) -> Vec<McpServerInfo> {
+        let services = self.services.lock().unwrap();
+        let mut servers: Vec<_> = self
+            .slots
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, slot)| McpServerInfo {
+                name: name.clone(),
+                status: slot.status.clone(),
+                tools: services
+                    .get(name)
+                    .map_or(0, |s| s.service().tools.load().len()),
+            })
+            .collect();
+        servers.sort_by(|a, b| a.name.cmp(&b.name));
+        servers
+    }
+
+    /// Configures a new server and starts it, unless it is disabled.
+    pub async fn add_server(
+        &self,
+        name: &str,
+        config: McpServer,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let disabled = config.disabled;
+        {
+            let mut slots = self.slots.lock().unwrap();
+            if slots.contains_key(name) {
+                return Err(format!("MCP server '{name}' already exists").into());
+            }
+            slots.insert(
+                name.to_string(),
+                ServerSlot {
+                    config,
+                    status: McpServerStatus::Stopped,
+                    supervisor: None,
+                },
+            );
+        }
+        if disabled {
+            return Ok(());
+        }
+        self.start_server(name).await
+    }
+
+    /// Stops a server and removes its configuration.
+    pub fn remove_server(&self, name: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.stop_server(name)?;
+        self.slots.lock().unwrap().remove(name);
+        Ok(())
+    }
+
+    /// Connects to a configured server that is not running, and restarts it
+    /// with backoff whenever it exits.
+    pub async fn start_server(&self, name: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let config = {
+            let mut slots = self.slots.lock().unwrap();
+            let slot = slots
+                .get_mut(name)
+                .ok_or_else(|| format!("unknown MCP server '{name}'"))?;
+            if slot.supervisor.is_some() || slot.status == McpServerStatus::Starting {
+                return Err(format!("MCP server '{name}' is already running").into());
+            }
+            slot.status = McpServerStatus::Starting;
+            slot.config.clone()
+        };
+        match connect_server(name, &config, &self.handlers).await {
+            Ok(service) => {
+                self.services
+                    .lock()
+                    .unwrap()
+                    .insert(name.to_string(), service);
+                self.named_tools();
+                let supervisor = tokio::spawn(self.clone().supervise(name.to_string(), config));
+                self.set_slot(
+                    name,
+                    McpServerStatus::Running,
+                    Some(supervisor.abort_handle()),
+                );
+                Ok(())
+            }
+            Err(err) => {
+                self.set_slot(name, McpServerStatus::Failed(err.to_string()), None);
+                Err(err)
+            }
+        }
+    }
+
+    /// Disconnects from a server until it is started again.
+    pub fn stop_server(&self, name: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        {
+            let mut slots = self.slots.lock().unwrap();
+            let slot = slots
+                .get_mut(name)
+                .ok_or_else(|| format!("unknown MCP server '{name}'"))?;
+            if let Some(supervisor) = slot.supervisor.take() {
+                supervisor.abort();
+            }
+            slot.status = McpServerStatus::Stopped;
+        }
+        // Dropping the service cancels it, which also ends a child process.
+        self.services.lock().unwrap().remove(name);
+        Ok(())
+    }
+
+    pub async fn restart_server(&self, name: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.stop_server(name)?;
+        self.start_server(name).await
+    }
+
+    /// Applies the servers configured in `paths`: new servers are added,
+    /// changed ones restarted and missing ones removed.
+    pub async fn reload_servers(
+        &self,
+        paths: &[PathBuf],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // A file that can't be read would otherwise remove its servers.
+        let (config, errors) = read_mcp_config(paths).await;
+        if !errors.is_empty() {
+            return Err(errors.join("\n").into());
+        }
+        let current: HashMap<String, McpServer> = self
+            .slots
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, slot)| (name.clone(), slot.config.clone()))
+            .collect();
+        let mut errors = Vec::new();
+        for name in current.keys() {
+            if !config.contains_key(name) {
+                self.remove_server(name)?;
+            }
+        }
+        for (name, server) in config {
+            let result = match current.get(&name) {
+                Some(existing) if *existing == server => continue,
+                Some(_) => {
+                    self.remove_server(&name)?;
+                    self.add_server(&name, server).await
+                }
+                None => self.add_server(&name, server).await,
+            };
+            if let Err(err) = result {
+                errors.push(format!("{name}: {err}"));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n").into())
+        }
+    }
+
+    fn set_slot(&self, name: &str, status: McpServerStatus, supervisor: Option<AbortHandle>) {
+        if let Some(slot) = self.slots.lock().unwrap().get_mut(name) {
+            slot.status = status;
+            slot.supervisor = supervisor;
+        }
+    }
+
+    fn set_status(&self, name: &str, status: McpServerStatus) {
+        if let Some(slot) = self.slots.lock().unwrap().get_mut(name) {
+            slot.status = status;
+        }
+    }
+
+    fn is_connected(&self, name: &str) -> bool {
+        self.services
+            .lock()
+            .unwrap()
+            .get(name)
+            .is_some_and(|s| !s.peer().is_transport_closed())
+    }
+
+    /// Waits for the server to exit and reconnects, giving up after
+    /// [`MAX_RESTARTS`] consecutive failures.
+    async fn supervise(self, name: String, config: McpServer) {
+        let mut attempt = 0;
+        let mut up_since = Instant::now();
+        loop {
+            while self.is_connected(&name) {
+                tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+            }
+            self.services.lock().unwrap().remove(&name);
+            if up_since.elapsed() >= STABLE_AFTER {
+                attempt = 0;
+            }
+            loop {
+                attempt += 1;
+                if attempt > MAX_RESTARTS {
+                    self.set_slot(
+                        &name,
+                        McpServerStatus::Failed(format!("exited {MAX_RESTARTS} times")),
+                        None,
+                    );
+                    return;
+                }
+                self.set_status(&name, McpServerStatus::Restarting { attempt });
+                tokio::time::sleep(restart_backoff(attempt)).await;
+                if let Ok(service) = connect_server(&name, &config, &self.handlers).await {
+                    self.services.lock().unwrap().insert(name.clone(), service);
+                    self.named_tools();
+                    self.set_status(&name, McpServerStatus::Running);
+                    up_since = Instant::now();
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Time allowed for a server to start when it does not configure one.
+const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+async fn connect_server(
+    name: &str,
+    server: &McpServer,
+    handlers: &Arc<ClientHandlers>,
+) -> Result<RunningService<RoleClient, McpService>, Box<dyn std::error::Error + Send + Sync>> {
+    let timeout = server
+        .startup_timeout_ms
+        .map_or(DEFAULT_STARTUP_TIMEOUT, Duration::from_millis);
+    tokio::time::timeout(timeout, start_service(name, server, handlers))
+        .await
+        .map_err(|_| {
+            format!(
+                "MCP server '{name}' did not start within {}ms",
+                timeout.as_millis()
+            )
+        })?
+}
+
+async fn start_service(
+    name: &str,
+    server: &McpServer,
+    handlers: &Arc<ClientHandlers>,
+) -> Result<RunningService<RoleClient, McpService>, Box<dyn std::error::Error + Send + Sync>> {
+    let filter = ToolFilter::new(&server.include_tools, &server.exclude_tools)?;
+    let mut handler = McpService::new(name);
+    handler.prefix = server.prefix(name).to_string();
+    handler.handlers = handlers.clone();
+    handler.filter = filter.clone();
+    handler.tool_timeout = server.tool_timeout_ms.map(Duration::from_millis);
+    let service = match (&server.command, &server.url, server.transport) {
+        (Some(command), None, None | Some(McpTransport::Stdio)) => {
+            let mut cmd = Command::new(command);
+            cmd.args(&server.args);
+            for (k, v) in &server.env {
+                cmd.env(k, v);
+            }
+            if let Some(cwd) = &server.cwd {
+                cmd.current_dir(cwd);
+            }
+            handler.serve(TokioChildProcess::new(cmd)?).await?
+        }
+        (None, Some(url), Some(McpTransport::Sse)) => {
+            let transport = connect_sse(server.http_client()?, url).await?;
+            handler.serve(transport).await?
+        }
+        (None, Some(url), None | Some(McpTransport::Http)) => {
+            let transport = StreamableHttpClientTransport::with_client(
+                server.http_client()?,
+                StreamableHttpClientTransportConfig::with_uri(url.as_str()),
+            );
+            handler.serve(transport).await?
+        }
+        _ => {
+            return Err(format!(
+                "MCP server '{name}' needs either a command or a url matching its type"
+            )
+            .into());
+        }
+    };
+    let tools = service.peer().list_all_tools().await?;
+    let mut infos = Vec::new();
+    for tool in tools.into_iter().filter(|t| filter.allows(&t.name)) {
+        let schema: Schema = serde_json::from_value(tool.schema_as_json_value())?;
+        let description = tool.description.clone().unwrap_or_default().to_string();
+        infos.push(ToolInfo {
+            name: tool.name.to_string(),
+            description,
+            parameters: schema,
+        });
+    }
+    service.service().tools.store(Arc::new(infos));
+    service.service().refresh_resources(service.peer()).await;
+    service.service().refresh_prompts(service.peer()).await;
+    Ok(service)
+}
+
+/// Starts the servers configured in the mcp.json files at `paths`, merged
+/// in order.
+pub async fn load_mcp_servers(
+    paths: &[PathBuf],
+) -> Result<McpContext, Box<dyn std::error::Error + Send + Sync>> {
+    let (ctx, errors) = load_mcp_servers_partial(paths).await;
+    if errors.is_empty() {
+        Ok(ctx)
+    } else {
+        Err(errors.join("\n").into())
+    }
+}
+
+/// Like [`load_mcp_servers`], but keeps going past the files that can't be
+/// read and the servers that fail to start, returning their errors along
+/// with the context. Servers that fail to start stay configured.
+pub async fn load_mcp_servers_partial(paths: &[PathBuf]) -> (McpContext, Vec<String>) {
+    let ctx = McpContext::default();
+    let (servers, mut errors) = read_mcp_config(paths).await;
+    let mut servers: Vec<_> = servers.into_iter().collect();
+    servers.sort_by(|a, b| a.0.cmp(&b.0));
+    for (server_name, server) in servers {
+        if let Err(err) = ctx.add_server(&server_name, server).await {
+            errors.push(format!("{server_name}: {err}"));
+        }
+    }
+    (ctx, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AssistantPart, ResponseChunk, TestProvider, test_server::serve};
+    use axum::{
+        Json, Router,
+        extract::{Request as HttpRequest, State},
+        http::StatusCode,
+        middleware::{self, Next},
+        response::{
+            Response,
+            sse::{Event as SseEvent, Sse},
+        },
+        routing::{get, post},
+    };
+    use futures_util::{Stream, StreamExt, sink, stream};
+    use rmcp::{
+        ErrorData, RoleServer, ServerHandler,
+        handler::server::{router::tool::ToolRouter, wrapper::Parameters},
+        model::AnnotateAble,
+        model::{
+            ClientJsonRpcMessage, CompleteRequestParams, CompleteResult, GetPromptResult,
+            ListPromptsResult, ListResourceTemplatesResult, ListResourcesResult, Meta,
+            PaginatedRequestParams, RawResource, RawResourceTemplate, ReadResourceResult,
+            ServerCapabilities, ServerInfo, ServerJsonRpcMessage,
+        },
+        service::RequestContext,
+        tool, tool_handler, tool_router,
+        transport::streamable_http_server::{
+            StreamableHttpService, session::local::LocalSessionManager,
+        },
+    };
+    use std::{convert::Infallible, io::Write};
+    use tokio::sync::mpsc;
+    use tokio_stream::wrappers::{ReceiverStream, UnboundedReceiverStream};
+
+    #[derive(Deserialize, schemars::JsonSchema)]
+    struct EchoParams {
+        text: String,
+    }
+
+    #[derive(Clone)]
+    struct Echo {
+        tool_router: ToolRouter<Self>,
+    }
+
+    #[tool_router]
+    impl Echo {
+        fn new() -> Self {
+            Self {
+                tool_router: Self::tool_router(),
+            }
+        }
+
+        #[tool(description = "Returns the text unchanged")]
+        fn echo(&self, Parameters(params): Parameters<EchoParams>) -> String {
+            params.text
+        }
+    }
+
+    #[tool_handler(router = self.tool_router)]
+    impl ServerHandler for Echo {
+        fn get_info(&self) -> ServerInfo {
+            ServerInfo::new(ServerCapabilities::builder().enable_tools().build())
+        }
+    }
+
+    /// Has a tool named like one of [`McpContext::resource_tools`] when the
+    /// server is named `mcp`.
+    #[derive(Clone)]
+    struct Shadow {
+        tool_router: ToolRouter<Self>,
+    }
+
+    #[tool_router]
+    impl Shadow {
+        fn new() -> Self {
+            Self {
+                tool_router: Self::tool_router(),
+            }
+        }
+
+        #[tool(description = "Lists something else")]
+        fn list_resources(&self) -> String {
+            "shadow".into()
+        }
+    }
+
+    #[tool_handler(router = self.tool_router)]
+    impl ServerHandler for Shadow {
+        fn get_info(&self) -> ServerInfo {
+            ServerInfo::new(ServerCapabilities::builder().enable_tools().build())
+        }
+    }
+
+    /// Serves a single `mem://readme` resource and announces an update as
+    /// soon as it is subscribed to.
+    #[derive(Clone)]
+    struct Docs;
+
+    impl ServerHandler for Docs {
+        fn get_info(&self) -> ServerInfo {
+            ServerInfo::new(
+                ServerCapabilities::builder()
+                    .enable_resources()
+                    .enable_resources_subscribe()
+                    .build(),
+            )
+        }
+
+        async fn list_resources(
+            &self,
+            _request: Option<PaginatedRequestParams>,
+            _context: RequestContext<RoleServer>,
+        ) -> Result<ListResourcesResult, ErrorData> {
+            let mut readme = RawResource::new("mem://readme", "readme");
+            readme.mime_type = Some("text/markdown".into());
+            Ok(ListResourcesResult::with_all_items(vec![
+                readme.no_annotation(),
+            ]))
+        }
+
+        async fn list_resource_templates(
+            &self,
+            _request: Option<PaginatedRequestParams>,
+            _context: RequestContext<RoleServer>,
+        ) -> Result<ListResourceTemplatesResult, ErrorData> {
+            Ok(ListResourceTemplatesResult::with_all_items(vec![
+                RawResourceTemplate::new("mem://{name}", "page").no_annotation(),
+            ]))
+        }
+
+        async fn read_resource(
+            &self,
+            request: ReadResourceRequestParams,
+            _context: RequestContext<RoleServer>,
+        ) -> Result<ReadResourceResult, ErrorData> {
+            Ok(ReadResourceResult::new(vec![ResourceContents::text(
+                format!("contents of {}", request.uri),
+                request.uri,
+            )]))
+        }
+
+        async fn subscribe(
+            &self,
+            request: SubscribeRequestParams,
+            context: RequestContext<RoleServer>,
+        ) -> Result<(), ErrorData> {
+            tokio::spawn(async move {
+                let _ = context
+                    .peer
+                    .notify_resource_updated(ResourceUpdatedNotificationParam::new(request.uri))
+                    .await;
+            });
+            Ok(())
+        }
+    }
+
+    /// Publishes a `review` prompt whose `path` argument can be completed.
+    #[derive(Clone)]
+    struct Reviewer;
+
+    impl ServerHandler for Reviewer {
+        fn get_info(&self) -> ServerInfo {
+            ServerInfo::new(
+                ServerCapabilities::builder()
+                    .enable_prompts()
+                    .enable_completions()
+                    .build(),
+            )
+        }
+
+        async fn list_prompts(
+            &self,
+            _request: Option<PaginatedRequestParams>,
+            _context: RequestContext<RoleServer>,
+        ) -> Result<ListPromptsResult, ErrorData> {
+            let prompt = serde_json::from_value(json!({
+                "name": "review",
+                "description": "Review a file",
+                "arguments": [
+                    { "name": "path", "required": true },
+                    { "name": "focus", "description": "What to look for" }
+                ]
+            }))
+            .unwrap();
+            Ok(ListPromptsResult::with_all_items(vec![prompt]))
+        }
+
+        async fn get_prompt(
+            &self,
+            request: GetPromptRequestParams,
+            _context: RequestContext<RoleServer>,
+        ) -> Result<GetPromptResult, ErrorData> {
+            let path = request
+                .arguments
+                .and_then(|args| args.get("path").cloned())
+                .unwrap_or_default();
+            Ok(serde_json::from_value(json!({
+                "messages": [
+                    { "role": "user", "content": { "type": "text", "text": format!("Review {}", path.as_str().unwrap_or_default()) } },
+                    { "role": "assistant", "content": { "type": "text", "text": "Sure." } }
+                ]
+            }))
+            .unwrap())
+        }
+
+        async fn complete(
+            &self,
+            request: CompleteRequestParams,
+            _context: RequestContext<RoleServer>,
+        ) -> Result<CompleteResult, ErrorData> {
+            let values: Vec<&str> = ["src/lib.rs", "src/main.rs", "README.md"]
+                .into_iter()
+                .filter(|v| v.starts_with(&request.argument.value))
+                .collect();
+            Ok(serde_json::from_value(json!({ "completion": { "values": values } })).unwrap())
+        }
+    }
+
+    /// Asks the client's model to summarize the given text.
+    #[derive(Clone)]
+    struct Summarizer {
+        tool_router: ToolRouter<Self>,
+    }
+
+    #[tool_router]
+    impl Summarizer {
+        fn new() -> Self {
+            Self {
+                tool_router: Self::tool_router(),
+            }
+        }
+
+        #[tool(description = "Summarizes the text")]
+        async fn summarize(
+            &self,
+            Parameters(params): Parameters<EchoParams>,
+            peer: Peer<RoleServer>,
+        ) -> Result<String, ErrorData> {
+            let request = serde_json::from_value(json!({
+                "systemPrompt": "Be brief.",
+                "maxTokens": 4096,
+                "messages": [{
+                    "role": "user",
+                    "content": { "type": "text", "text": params.text }
+                }]
+            }))
+            .unwrap();
+            let result = peer
+                .create_message(request)
+                .await
+                .map_err(|err| ErrorData::internal_error(err.to_string(), None))?;
+            let result = serde_json::to_value(result).unwrap();
+            let content = match &result["content"] {
+                Value::Array(items) => items[0].clone(),
+                content => content.clone(),
+            };
+            Ok(format!(
+                "{}: {}",
+                result["stopReason"].as_str().unwrap_or_default(),
+                content["text"].as_str().unwrap_or_default()
+            ))
+        }
+    }
+
+    #[tool_handler(router = self.tool_router)]
+    impl ServerHandler for Summarizer {
+        fn get_info(&self) -> ServerInfo {
+            ServerInfo::new(ServerCapabilities::builder().enable_tools().build())
+        }
+    }
+
+    /// Reports progress and a log message, then asks which environment to
+    /// deploy to.
+    #[derive(Clone)]
+    struct Deployer {
+        tool_router: ToolRouter<Self>,
+    }
+
+    #[tool_router]
+    impl Deployer {
+        fn new() -> Self {
+            Self {
+                tool_router: Self::tool_router(),
+            }
+        }
+
+        #[tool(description = "Deploys the project")]
+        async fn deploy(&self, meta: Meta, peer: Peer<RoleServer>) -> Result<String, ErrorData> {
+            let internal =
+                |err: rmcp::service::ServiceError| ErrorData::internal_error(err.to_string(), None);
+            if let Some(token) = meta.get_progress_token() {
+                peer.notify_progress(
+                    serde_json::from_value(json!({
+                        "progressToken": token,
+                        "progress": 1,
+                        "total": 2,
+                        "message": "building"
+                    }))
+                    .unwrap(),
+                )
+                .await
+                .map_err(internal)?;
+            }
+            peer.notify_logging_message(
+                serde_json::from_value(json!({
+                    "level": "warning",
+                    "logger": "deploy",
+                    "data": "cache is cold"
+                }))
+                .unwrap(),
+            )
+            .await
+            .map_err(internal)?;
+            let result = peer
+                .create_elicitation(
+                    serde_json::from_value(json!({
+                        "mode": "form",
+                        "message": "Which environment?",
+                        "requestedSchema": {
+                            "type": "object",
+                            "properties": { "env": { "type": "string" } },
+                            "required": ["env"]
+                        }
+                    }))
+                    .unwrap(),
+                )
+                .await
+                .map_err(internal)?;
+            let result = serde_json::to_value(result).unwrap();
+            Ok(format!("{} {}", result["action"], result["content"]))
+        }
+    }
+
+    #[tool_handler(router = self.tool_router)]
+    impl ServerHandler for Deployer {
+        fn get_info(&self) -> ServerInfo {
+            ServerInfo::new(
+                ServerCapabilities::builder()
+                    .enable_tools()
+                    .enable_logging()
+                    .build(),
+            )
+        }
+    }
+
+    /// Answers elicitations once the server's log message was received.
+    struct AnswerAfterLog {
+        logs: tokio::sync::Mutex<broadcast::Receiver<McpLogMessage>>,
+        seen: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Elicitation for AnswerAfterLog {
+        async fn elicit(&self, request: ElicitationRequest) -> ElicitationResponse {
+            let log = self.logs.lock().await.recv().await.unwrap();
+            let mut seen = self.seen.lock().unwrap();
+            seen.push(format!("{} {:?} {}", log.server, log.level, log.text()));
+            seen.push(format!("{} {}", request.server, request.message));
+            assert_eq!(request.schema["required"], json!(["env"]));
+            ElicitationResponse::Accept(json!({ "env": "staging" }))
+        }
+    }
+
+    struct DenySampling;
+
+    #[async_trait]
+    impl SamplingPolicy for DenySampling {
+        async fn approve(&self, request: &SamplingRequest) -> Result<(), String> {
+            Err(format!("{} may not sample", request.server))
+        }
+    }
+
+    async fn load(config: Value) -> McpContext {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(config.to_string().as_bytes()).unwrap();
+        load_mcp_servers(&[file.path().to_path_buf()])
+            .await
+            .unwrap()
+    }
+
+    async fn require_auth(request: HttpRequest, next: Next) -> Result<Response, StatusCode> {
+        let header = |name| request.headers().get(name).and_then(|v| v.to_str().ok());
+        if header("authorization") == Some("Bearer secret") && header("x-team") == Some("llment") {
+            Ok(next.run(request).await)
+        } else {
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+
+    #[tokio::test]
+    async fn loads_streamable_http_server() {
+        let service = StreamableHttpService::new(
+            || Ok(Echo::new()),
+            LocalSessionManager::default().into(),
+            Default::default(),
+        );
+        let router = Router::new()
+            .nest_service("/mcp", service)
+            .layer(middleware::from_fn(require_auth));
+        let base = serve(router).await;
+        let ctx = load(json!({
+            "mcpServers": {
+                "remote": {
+                    "url": format!("{base}/mcp"),
+                    "headers": { "X-Team": "llment" },
+                    "bearerToken": "secret"
+                }
+            }
+        }))
+        .await;
+        assert_eq!(ctx.tool_names(), vec!["remote_echo".to_string()]);
+        let output = ctx
+            .call("remote_echo", json!({"text": "hi"}))
+            .await
+            .unwrap();
+        assert_eq!(output.to_text(), "hi");
+    }
+
+    #[tokio::test]
+    async fn lists_reads_and_subscribes_to_resources() {
+        let service = StreamableHttpService::new(
+            || Ok(Docs),
+            LocalSessionManager::default().into(),
+            Default::default(),
+        );
+        let base = serve(Router::new().nest_service("/mcp", service)).await;
+        let ctx = load(json!({
+            "mcpServers": { "docs": { "url": format!("{base}/mcp") } }
+        }))
+        .await;
+
+        let resources = ctx.resources();
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0].server, "docs");
+        assert_eq!(resources[0].uri, "mem://readme");
+        assert_eq!(resources[0].mime_type.as_deref(), Some("text/markdown"));
+        assert_eq!(ctx.resource_templates()[0].uri_template, "mem://{name}");
+
+        let parts = ctx.read_resource("docs", "mem://readme").await.unwrap();
+        assert!(
+            matches!(&parts[..], [ToolOutputPart::Text { text }] if text == "contents of mem://readme")
+        );
+
+        let mut updates = ctx
+            .subscribe_resource("docs", "mem://readme")
+            .await
+            .unwrap();
+        assert_eq!(updates.recv().await.unwrap(), "mem://readme");
+
+        ctx.insert_functions(ctx.resource_tools());
+        let output = ctx
+            .call(
+                "mcp_read_resource",
+                json!({"server": "docs", "uri": "mem://intro"}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(output.to_text(), "contents of mem://intro");
+        let output = ctx
+            .call(
+                "mcp_read_resource",
+                json!({"server": "nope", "uri": "mem://x"}),
+            )
+            .await
+            .unwrap();
+        assert!(output.is_error);
+    }
+
+    #[tokio::test]
+    async fn resource_tools_collide_like_other_tools() {
+        let service = StreamableHttpService::new(
+            || Ok(Shadow::new()),
+            LocalSessionManager::default().into(),
+            Default::default(),
+        );
+        let base = serve(Router::new().nest_service("/mcp", service)).await;
+        let ctx = load(json!({
+            "mcpServers": { "mcp": { "url": format!("{base}/mcp") } }
+        }))
+        .await;
+        ctx.insert_functions(ctx.resource_tools());
+        let names = ctx.tool_names();
+        assert_eq!(names.len(), 3);
+        let renamed = names
+            .iter()
+            .find(|n| n.starts_with("mcp_list_resources_"))
+            .unwrap();
+        assert!(names.contains(&"mcp_read_resource".to_string()));
+
+        let output = ctx.call("mcp_list_resources", json!({})).await.unwrap();
+        assert_eq!(output.to_text(), "shadow");
+        let output = ctx.call(renamed, json!({})).await.unwrap();
+        assert_eq!(output.to_text(), "No resources available");
+    }
 
     #[test]
-    fn accepts_prefix_without_underscore() {
-        assert!(validate_prefix("files").is_ok());
-        assert!(validate_prefix("agent").is_ok());
+    fn resource_tools_dont_keep_the_context_alive() {
+        let ctx = McpContext::default();
+        ctx.insert_functions(ctx.resource_tools());
+        let services = Arc::downgrade(&ctx.services);
+        drop(ctx);
+        assert!(services.upgrade().is_none());
+    }
+
+    #[tokio::test]
+    async fn lists_completes_and_expands_prompts() {
+        let service = StreamableHttpService::new(
+            || Ok(Reviewer),
+            LocalSessionManager::default().into(),
+            Default::default(),
+        );
+        let base = serve(Router::new().nest_service("/mcp", service)).await;
+        let ctx = load(json!({
+            "mcpServers": { "files": { "url": format!("{base}/mcp") } }
+        }))
+        .await;
+
+        assert_eq!(
+            ctx.prompts(),
+            vec![McpPrompt {
+                server: "files".into(),
+                name: "review".into(),
+                description: Some("Review a file".into()),
+                arguments: vec![
+                    McpPromptArgument {
+                        name: "path".into(),
+                        description: None,
+                        required: true,
+                    },
+                    McpPromptArgument {
+                        name: "focus".into(),
+                        description: Some("What to look for".into()),
+                        required: false,
+                    },
+                ],
+            }]
+        );
+
+        let values = ctx
+            .complete_prompt_argument("files", "review", "path", "src/", &HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(values, vec!["src/lib.rs", "src/main.rs"]);
+
+        let args = HashMap::from([("path".to_string(), "src/lib.rs".to_string())]);
+        let messages = ctx.get_prompt("files", "review", &args).await.unwrap();
+        assert!(matches!(&messages[..], [
+            ChatMessage::User(u),
+            ChatMessage::Assistant(_),
+        ] if u.content == "Review src/lib.rs"));
+    }
+
+    #[tokio::test]
+    async fn serves_sampling_requests() {
+        let service = StreamableHttpService::new(
+            || Ok(Summarizer::new()),
+            LocalSessionManager::default().into(),
+            Default::default(),
+        );
+        let base = serve(Router::new().nest_service("/mcp", service)).await;
+        let ctx = load(json!({
+            "mcpServers": { "notes": { "url": format!("{base}/mcp") } }
+        }))
+        .await;
+        let args = json!({"text": "a long story"});
+
+        let err = ctx.call("notes_summarize", args.clone()).await.unwrap_err();
+        assert!(err.to_string().contains("sampling is not enabled"));
+
+        let client = Arc::new(TestProvider::new());
+        client.enqueue(vec![
+            ResponseChunk::Part(AssistantPart::Text {
+                text: "short".into(),
+                encrypted_content: None,
+            }),
+            ResponseChunk::Usage {
+                input_tokens: 10,
+                output_tokens: 1,
+            },
+            ResponseChunk::Done,
+        ]);
+        let sampling = Sampling {
+            client: client.clone(),
+            model: "test".into(),
+            policy: Arc::new(AllowSampling),
+            max_tokens: 256,
+        };
+        ctx.set_sampling(Some(sampling.clone()));
+        let output = ctx.call("notes_summarize", args.clone()).await.unwrap();
+        assert_eq!(output.to_text(), "endTurn: short");
+        let requests = client.requests.lock().unwrap().clone();
+        assert_eq!(requests[0].max_tokens, Some(256));
+        assert!(matches!(&requests[0].messages[..], [
+            ChatMessage::System(s),
+            ChatMessage::User(u),
+        ] if s.content == "Be brief." && u.content == "a long story"));
+
+        ctx.set_sampling(Some(Sampling {
+            policy: Arc::new(DenySampling),
+            ..sampling
+        }));
+        let err = ctx.call("notes_summarize", args).await.unwrap_err();
+        assert!(err.to_string().contains("notes may not sample"));
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn forwards_progress_logs_and_elicitation() {
+        let service = StreamableHttpService::new(
+            || Ok(Deployer::new()),
+            LocalSessionManager::default().into(),
+            Default::default(),
+        );
+        let base = serve(Router::new().nest_service("/mcp", service)).await;
+        let ctx = load(json!({
+            "mcpServers": { "ci": { "url": format!("{base}/mcp") } }
+        }))
+        .await;
+
+        let output = ctx.call("ci_deploy", json!({})).await.unwrap();
+        assert_eq!(output.to_text(), "\"decline\" null");
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        ctx.set_elicitation(Some(Box::new(AnswerAfterLog {
+            logs: tokio::sync::Mutex::new(ctx.subscribe_logs()),
+            seen: seen.clone(),
+        })));
+        let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+        let output = ctx
+            .call_with_progress("ci_deploy", json!({}), progress_tx)
+            .await
+            .unwrap();
+        assert_eq!(output.to_text(), "\"accept\" {\"env\":\"staging\"}");
+        assert_eq!(
+            progress_rx.try_recv().unwrap(),
+            ToolProgress {
+                progress: 1.0,
+                total: Some(2.0),
+                message: Some("building".into()),
+            }
+        );
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec!["ci Warning cache is cold", "ci Which environment?"]
+        );
+    }
+
+    #[tokio::test]
+    async fn merges_config_files_and_applies_server_options() {
+        let service = StreamableHttpService::new(
+            || Ok(Echo::new()),
+            LocalSessionManager::default().into(),
+            Default::default(),
+        );
+        let base = serve(Router::new().nest_service("/mcp", service)).await;
+        let url = format!("${{LLMENT_UNSET_TEST_URL:-{base}/mcp}}");
+        let write = |config: Value| {
+            let mut file = tempfile::NamedTempFile::new().unwrap();
+            file.write_all(config.to_string().as_bytes()).unwrap();
+            file
+        };
+        let user = write(json!({
+            "mcpServers": {
+                "remote": { "url": "http://unused.invalid/mcp" },
+                "quiet": { "url": url, "excludeTools": ["ec*"] }
+            }
+        }));
+        let project = write(json!({
+            "mcpServers": {
+                "remote": { "url": url, "prefix": "say", "includeTools": ["echo"] },
+                "off": { "command": "/nonexistent/mcp-server", "disabled": true }
+            }
+        }));
+        let ctx = load_mcp_servers(&[user.path().to_path_buf(), project.path().to_path_buf()])
+            .await
+            .unwrap();
+        let status: Vec<_> = ctx
+            .servers()
+            .into_iter()
+            .map(|s| (s.name, s.status, s.tools))
+            .collect();
+        assert_eq!(
+            status,
+            vec![
+                ("off".into(), McpServerStatus::Stopped, 0),
+                ("quiet".into(), McpServerStatus::Running, 0),
+                ("remote".into(), McpServerStatus::Running, 1),
+            ]
+        );
+        assert_eq!(ctx.tool_names(), vec!["say_echo".to_string()]);
+        assert_eq!(
+            ctx.server_tool_name("remote", "echo").as_deref(),
+            Some("say_echo")
+        );
+        assert_eq!(ctx.server_tool_name("quiet", "echo"), None);
+        assert_eq!(ctx.server_tool_name("off", "echo"), None);
+        let output = ctx.call("say_echo", json!({"text": "hi"})).await.unwrap();
+        assert_eq!(output.to_text(), "hi");
+        assert!(ctx.call("quiet_echo", json!({"text": "hi"})).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn names_tools_of_any_server_uniquely() {
+        let service = StreamableHttpService::new(
+            || Ok(Echo::new()),
+            LocalSessionManager::default().into(),
+            Default::default(),
+        );
+        let url = format!(
+            "{}/mcp",
+            serve(Router::new().nest_service("/mcp", service)).await
+        );
+        let ctx = McpContext::default();
+        let mut logs = ctx.subscribe_logs();
+        ctx.add_server("my_server", McpServer::url(&url))
+            .await
+            .unwrap();
+        let clash = McpServer {
+            prefix: Some("my server".into()),
+            ..McpServer::url(&url)
+        };
+        ctx.add_server("my.server", clash).await.unwrap();
+
+        let names = ctx.tool_names();
+        assert_eq!(names.len(), 2);
+        assert_eq!(names[0], "my_server_echo");
+        assert!(names[1].starts_with("my_server_echo_"));
+        for name in &names {
+            let output = ctx.call(name, json!({"text": name})).await.unwrap();
+            assert_eq!(output.to_text(), *name);
+        }
+        let warning = logs.try_recv().unwrap();
+        assert_eq!(warning.server, "my.server");
+        assert_eq!(warning.level, LoggingLevel::Warning);
+        assert_eq!(
+            warning.text(),
+            format!(
+                "tool echo is named {} because my_server_echo is already taken",
+                names[1]
+            )
+        );
+        assert!(ctx.call("my_echo", json!({"text": "hi"})).await.is_err());
+    }
+
+    #[test]
+    fn expands_environment_variables() {
+        assert_eq!(
+            expand_env("${CARGO_PKG_NAME}-${LLMENT_UNSET_TEST_VAR:-x}/$HOME"),
+            Ok("llm-x/$HOME".to_string())
+        );
+        assert_eq!(
+            expand_env("${LLMENT_UNSET_TEST_VAR}"),
+            Err("environment variable LLMENT_UNSET_TEST_VAR is not set".to_string())
+        );
+        assert!(expand_env("${CARGO_PKG_NAME").is_err());
+    }
+
+    /// Never answers an elicitation.
+    struct NeverAnswer;
+
+    #[async_trait]
+    impl Elicitation for NeverAnswer {
+        async fn elicit(&self, _request: ElicitationRequest) -> ElicitationResponse {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn times_out_slow_tool_calls() {
+        let service = StreamableHttpService::new(
+            || Ok(Deployer::new()),
+            LocalSessionManager::default().into(),
+            Default::default(),
+        );
+        let base = serve(Router::new().nest_service("/mcp", service)).await;
+        let ctx = load(json!({
+            "mcpServers": { "ci": { "url": format!("{base}/mcp"), "toolTimeoutMs": 200 } }
+        }))
+        .await;
+        ctx.set_elicitation(Some(Box::new(NeverAnswer)));
+        let err = ctx.call("ci_deploy", json!({})).await.unwrap_err();
+        assert_eq!(err.to_string(), "ci_deploy timed out after 200ms");
+    }
+
+    #[tokio::test]
+    async fn starts_stops_and_reloads_servers() {
+        let service = StreamableHttpService::new(
+            || Ok(Echo::new()),
+            LocalSessionManager::default().into(),
+            Default::default(),
+        );
+        let url = format!(
+            "{}/mcp",
+            serve(Router::new().nest_service("/mcp", service)).await
+        );
+        let ctx = McpContext::default();
+        ctx.add_server("remote", McpServer::url(&url))
+            .await
+            .unwrap();
+        assert!(
+            ctx.add_server("remote", McpServer::url(&url))
+                .await
+                .is_err()
+        );
+        assert_eq!(
+            ctx.servers(),
+            vec![McpServerInfo {
+                name: "remote".into(),
+                status: McpServerStatus::Running,
+                tools: 1,
+            }]
+        );
+        assert!(ctx.start_server("remote").await.is_err());
+
+        ctx.stop_server("remote").unwrap();
+        assert_eq!(ctx.servers()[0].status, McpServerStatus::Stopped);
+        assert!(ctx.tool_names().is_empty());
+        assert!(
+            ctx.call("remote_echo", json!({"text": "hi"}))
+                .await
+                .is_err()
+        );
+
+        ctx.restart_server("remote").await.unwrap();
+        let output = ctx
+            .call("remote_echo", json!({"text": "hi"}))
+            .await
+            .unwrap();
+        assert_eq!(output.to_text(), "hi");
+
+        let missing = McpServer::command("/nonexistent/mcp-server", Vec::new());
+        assert!(ctx.add_server("broken", missing).await.is_err());
+        assert!(matches!(
+            ctx.servers()[0].status,
+            McpServerStatus::Failed(_)
+        ));
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(
+            json!({ "mcpServers": { "remote": { "url": url } } })
+                .to_string()
+                .as_bytes(),
+        )
+        .unwrap();
+        ctx.reload_servers(&[file.path().to_path_buf()])
+            .await
+            .unwrap();
+        assert_eq!(
+            ctx.servers(),
+            vec![McpServerInfo {
+                name: "remote".into(),
+                status: McpServerStatus::Running,
+                tools: 1,
+            }]
+        );
+
+        ctx.remove_server("remote").unwrap();
+        assert!(ctx.servers().is_empty());
+        assert!(ctx.tool_names().is_empty());
     }
 
     #[test]
-    fn rejects_prefix_with_underscore() {
-        let err = validate_prefix("with_underscore").unwrap_err();
-        assert_eq!(err.prefix(), "with_underscore");
+    fn restarts_with_exponential_backoff() {
+        let delays: Vec<u64> = (1..=7).map(|n| restart_backoff(n).as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 30, 30]);
+    }
+
+    /// A minimal server for the legacy HTTP+SSE transport, which rmcp no longer provides.
+    #[derive(Clone, Default)]
+    struct SseServer {
+        to_server: Arc<Mutex<Option<mpsc::UnboundedSender<ClientJsonRpcMessage>>>>,
+    }
+
+    async fn sse_connect(
+        State(state): State<SseServer>,
+    ) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+        let (to_server, from_client) = mpsc::unbounded_channel();
+        let (to_client, from_server) = mpsc::channel::<ServerJsonRpcMessage>(16);
+        *state.to_server.lock().unwrap() = Some(to_server);
+        let outgoing = Box::pin(sink::unfold(
+            to_client,
+            |tx, message: ServerJsonRpcMessage| async move {
+                tx.send(message)
+                    .await
+                    .map_err(|err| std::io::Error::other(err.to_string()))?;
+                Ok::<_, std::io::Error>(tx)
+            },
+        ));
+        tokio::spawn(async move {
+            let service = Echo::new()
+                .serve((outgoing, UnboundedReceiverStream::new(from_client)))
+                .await
+                .unwrap();
+            let _ = service.waiting().await;
+        });
+        let endpoint =
+            stream::once(async { Ok(SseEvent::default().event("endpoint").data("/message")) });
+        let messages = ReceiverStream::new(from_server).map(|message| {
+            Ok(SseEvent::default()
+                .event("message")
+                .data(serde_json::to_string(&message).unwrap()))
+        });
+        Sse::new(endpoint.chain(messages))
+    }
+
+    async fn sse_message(
+        State(state): State<SseServer>,
+        Json(message): Json<ClientJsonRpcMessage>,
+    ) -> StatusCode {
+        match state.to_server.lock().unwrap().as_ref() {
+            Some(tx) if tx.send(message).is_ok() => StatusCode::ACCEPTED,
+            _ => StatusCode::NOT_FOUND,
+        }
+    }
+
+    #[tokio::test]
+    async fn loads_legacy_sse_server() {
+        let router = Router::new()
+            .route("/sse", get(sse_connect))
+            .route("/message", post(sse_message))
+            .layer(middleware::from_fn(require_auth))
+            .with_state(SseServer::default());
+        let base = serve(router).await;
+        let ctx = load(json!({
+            "mcpServers": {
+                "legacy": {
+                    "type": "sse",
+                    "url": format!("{base}/sse"),
+                    "headers": {
+                        "Authorization": "Bearer secret",
+                        "X-Team": "llment"
+                    }
+                }
+            }
+        }))
+        .await;
+        assert_eq!(ctx.tool_names(), vec!["legacy_echo".to_string()]);
+        let output = ctx
+            .call("legacy_echo", json!({"text": "hello"}))
+            .await
+            .unwrap();
+        assert_eq!(output.to_text(), "hello");
+    }
+
+    #[tokio::test]
+    async fn partial_load_skips_broken_files_and_servers() {
+        let write = |data: &[u8]| {
+            let mut file = tempfile::NamedTempFile::new().unwrap();
+            file.write_all(data).unwrap();
+            file
+        };
+        let malformed = write(b"{\"mcpServers\": ");
+        let config = write(
+            br#"{"mcpServers": {
+                "broken": {"type": "sse"},
+                "off": {"command": "/nonexistent/mcp-server", "disabled": true}
+            }}"#,
+        );
+        let (ctx, errors) = load_mcp_servers_partial(&[
+            malformed.path().to_path_buf(),
+            config.path().to_path_buf(),
+        ])
+        .await;
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with(&malformed.path().display().to_string()));
+        assert!(errors[1].starts_with("broken: "));
+        let names: Vec<_> = ctx.servers().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, ["broken", "off"]);
+        assert!(
+            ctx.reload_servers(&[malformed.path().to_path_buf()])
+                .await
+                .is_err()
+        );
+        assert_eq!(ctx.servers().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rejects_server_without_command_or_url() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(br#"{"mcpServers": {"broken": {"type": "sse"}}}"#)
+            .unwrap();
+        let err = load_mcp_servers(&[file.path().to_path_buf()])
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("'broken'"));
     }
 }
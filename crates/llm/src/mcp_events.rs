@@ -0,0 +1,46 @@
+use async_trait::async_trait;
+use rmcp::model::LoggingLevel;
+use serde_json::Value;
+
+/// A server's request for structured input from the user, see [`Elicitation`].
+#[derive(Clone, Debug)]
+pub struct ElicitationRequest {
+    pub server: String,
+    pub message: String,
+    /// A flat JSON Schema object whose properties are strings, numbers,
+    /// booleans or enums.
+    pub schema: Value,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ElicitationResponse {
+    /// The user's answers, conforming to [`ElicitationRequest::schema`].
+    Accept(Value),
+    Decline,
+    Cancel,
+}
+
+/// Asks the user for the input requested by an MCP server.
+#[async_trait]
+pub trait Elicitation: Send + Sync {
+    async fn elicit(&self, request: ElicitationRequest) -> ElicitationResponse;
+}
+
+/// A `notifications/message` log entry sent by an MCP server.
+#[derive(Clone, Debug)]
+pub struct McpLogMessage {
+    pub server: String,
+    pub level: LoggingLevel,
+    pub logger: Option<String>,
+    pub data: Value,
+}
+
+impl McpLogMessage {
+    /// The data as a single line, without quotes around plain strings.
+    pub fn text(&self) -> String {
+        match &self.data {
+            Value::String(text) => text.clone(),
+            data => data.to_string(),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::{error::Error, sync::Arc};
+
+use async_trait::async_trait;
+use rmcp::{
+    ErrorData as McpError,
+    model::{CreateMessageRequestParams, CreateMessageResult},
+};
+use serde_json::{Value, json};
+
+use crate::{ChatMessage, ChatMessageRequest, LlmClient};
+
+/// A server's request to sample the model, see [`SamplingPolicy`].
+#[derive(Clone, Debug)]
+pub struct SamplingRequest {
+    pub server: String,
+    pub system_prompt: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    /// The number of tokens requested by the server, capped by [`Sampling::max_tokens`].
+    pub max_tokens: u32,
+}
+
+/// Decides whether an MCP server may sample the model, e.g. by asking the user.
+#[async_trait]
+pub trait SamplingPolicy: Send + Sync {
+    /// Returning an error rejects the request with the given reason.
+    async fn approve(&self, request: &SamplingRequest) -> Result<(), String>;
+}
+
+/// Approves every request.
+pub struct AllowSampling;
+
+#[async_trait]
+impl SamplingPolicy for AllowSampling {
+    async fn approve(&self, _request: &SamplingRequest) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Serves `sampling/createMessage` requests from MCP servers, see
+/// [`crate::mcp::McpContext::set_sampling`].
+#[derive(Clone)]
+pub struct Sampling {
+    pub client: Arc<dyn LlmClient>,
+    pub model: String,
+    pub policy: Arc<dyn SamplingPolicy>,
+    /// Upper bound on the tokens generated for a single request.
+    pub max_tokens: u32,
+}
+
+impl Sampling {
+    pub(crate) async fn create_message(
+        &self,
+        server: &str,
+        params: CreateMessageRequestParams,
+    ) -> Result<CreateMessageResult, McpError> {
+        let params = serde_json::to_value(&params)
+            .map_err(|err| McpError::internal_error(err.to_string(), None))?;
+        let request = SamplingRequest {
+            server: server.to_string(),
+            system_prompt: params["systemPrompt"].as_str().map(str::to_string),
+            messages: params["messages"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(sampling_message)
+                .collect(),
+            max_tokens: params["maxTokens"]
+                .as_u64()
+                .map_or(self.max_tokens, |n| n.min(self.max_tokens as u64) as u32),
+        };
+        self.policy
+            .approve(&request)
+            .await
+            .map_err(|reason| McpError::invalid_request(reason, None))?;
+        let (text, output_tokens) = self
+            .sample(&request)
+            .await
+            .map_err(|err| McpError::internal_error(err.to_string(), None))?;
+        let stop_reason = if output_tokens >= request.max_tokens {
+            "maxTokens"
+        } else {
+            "endTurn"
+        };
+        serde_json::from_value(json!({
+            "model": self.model,
+            "stopReason": stop_reason,
+            "role": "assistant",
+            "content": { "type": "text", "text": text },
+        }))
+        .map_err(|err| McpError::internal_error(err.to_string(), None))
+    }
+
+    async fn sample(
+        &self,
+        request: &SamplingRequest,
+    ) -> Result<(String, u32), Box<dyn Error + Send + Sync>> {
+        let mut messages = Vec::new();
+        if let Some(system_prompt) = &request.system_prompt {
+            messages.push(ChatMessage::system(system_prompt.clone()));
+        }
+        messages.extend(request.messages.iter().cloned());
+        let chat_request = ChatMessageRequest::new(self.model.clone(), messages)
+            .think(false)
+            .max_tokens(request.max_tokens);
+        let completion = self.client.complete(chat_request).await?;
+        Ok((completion.message.text(), completion.usage.output_tokens))
+    }
+}
+
+/// Converts a sampling message to a chat message. Content other than text is
+/// replaced by a placeholder, as in [`crate::ToolOutput::to_text`].
+fn sampling_message(message: &Value) -> Option<ChatMessage> {
+    let contents = match &message["content"] {
+        Value::Array(items) => items.iter().collect(),
+        content => vec![content],
+    };
+    let text = contents
+        .into_iter()
+        .map(|content| match content["type"].as_str() {
+            Some("text") => content["text"].as_str().unwrap_or_default().to_string(),
+            Some(kind) => format!(
+                "[{kind}: {}]",
+                content["mimeType"].as_str().unwrap_or("unknown type")
+            ),
+            None => String::new(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    match message["role"].as_str()? {
+        "user" => Some(ChatMessage::user(text)),
+        "assistant" => Some(ChatMessage::assistant(text)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_sampling_messages() {
+        let user = sampling_message(&json!({
+            "role": "user",
+            "content": [
+                { "type": "text", "text": "describe" },
+                { "type": "image", "data": "", "mimeType": "image/png" }
+            ]
+        }));
+        assert!(
+            matches!(user, Some(ChatMessage::User(u)) if u.content == "describe\n[image: image/png]")
+        );
+        let assistant = sampling_message(&json!({
+            "role": "assistant",
+            "content": { "type": "text", "text": "ok" }
+        }));
+        assert!(matches!(assistant, Some(ChatMessage::Assistant(_))));
+    }
+}
@@ -0,0 +1,67 @@
+use std::error::Error;
+
+use futures_util::{Sink, Stream, StreamExt, sink, stream};
+use reqwest::{Client, Url};
+use reqwest_eventsource::{Event, EventSource};
+use rmcp::model::{ClientJsonRpcMessage, ServerJsonRpcMessage};
+
+/// Connects to an MCP server using the legacy HTTP+SSE transport.
+///
+/// The server announces the URL to POST requests to in an `endpoint` event,
+/// then sends its messages as `message` events on the same stream.
+pub async fn connect_sse(
+    client: Client,
+    url: &str,
+) -> Result<
+    (
+        impl Sink<ClientJsonRpcMessage, Error = reqwest::Error> + Send + Unpin + 'static,
+        impl Stream<Item = ServerJsonRpcMessage> + Send + Unpin + 'static,
+    ),
+    Box<dyn Error + Send + Sync>,
+> {
+    let base = Url::parse(url)?;
+    let mut events = EventSource::new(client.get(base.clone()))?;
+    let endpoint = loop {
+        match events.next().await {
+            Some(Ok(Event::Message(msg))) if msg.event == "endpoint" => {
+                break base.join(msg.data.trim())?;
+            }
+            Some(Ok(_)) => {}
+            Some(Err(err)) => {
+                events.close();
+                return Err(err.into());
+            }
+            None => return Err(format!("{url} closed before sending an endpoint").into()),
+        }
+    };
+    let messages = stream::unfold(events, |mut events| async move {
+        loop {
+            match events.next().await? {
+                Ok(Event::Message(msg)) if msg.event == "message" => {
+                    if let Ok(message) = serde_json::from_str(&msg.data) {
+                        return Some((message, events));
+                    }
+                }
+                Ok(_) => {}
+                Err(_) => {
+                    // Reconnecting would start a new session, so end the stream instead.
+                    events.close();
+                    return None;
+                }
+            }
+        }
+    });
+    let requests = sink::unfold(
+        (client, endpoint),
+        |(client, endpoint), message: ClientJsonRpcMessage| async move {
+            client
+                .post(endpoint.clone())
+                .json(&message)
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok::<_, reqwest::Error>((client, endpoint))
+        },
+    );
+    Ok((Box::pin(requests), Box::pin(messages)))
+}
@@ -4,29 +4,35 @@ use async_trait::async_trait;
 use futures_util::StreamExt;
 use ollama_rs::{
     Ollama,
+    generation::parameters::{FormatType, JsonStructure},
     generation::{
         chat::{
             ChatMessage as OllamaChatMessage, ChatMessageResponseStream,
             MessageRole as OllamaMessageRole,
             request::ChatMessageRequest as OllamaChatMessageRequest,
         },
+        images::Image,
         tools::{
             ToolCall as OllamaToolCall, ToolCallFunction as OllamaToolCallFunction,
             ToolFunctionInfo as OllamaToolFunctionInfo, ToolInfo as OllamaToolInfo,
             ToolType as OllamaToolType,
         },
     },
+    models::ModelOptions,
 };
-use serde_json::Value;
+use serde::Deserialize;
+use serde_json::{Value, json};
 use uuid::Uuid;
 
 use super::{
-    AssistantPart, ChatMessage, ChatMessageRequest, ChatStream, JsonResult, LlmClient,
-    ResponseChunk, ToolCall,
+    AssistantPart, ChatMessage, ChatMessageRequest, ChatStream, EmbeddingRequest, JsonResult,
+    LlmClient, ResponseChunk, ToolCall,
 };
 
 pub struct OllamaClient {
     inner: Ollama,
+    http: reqwest::Client,
+    host: String,
 }
 
 impl OllamaClient {
@@ -34,10 +40,17 @@ impl OllamaClient {
         let host = host.unwrap_or("http://127.0.0.1:11434");
         Ok(Self {
             inner: Ollama::try_new(host)?,
+            http: reqwest::Client::new(),
+            host: host.trim_end_matches('/').to_string(),
         })
     }
 }
 
+#[derive(Deserialize)]
+struct EmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
 #[async_trait]
 impl LlmClient for OllamaClient {
     async fn send_chat_messages_stream(
@@ -86,15 +99,19 @@ impl LlmClient for OllamaClient {
                         OllamaChatMessage::new(OllamaMessageRole::System, s.content)
                     }
                     ChatMessage::Tool(t) => {
-                        let content_str = match t.content {
-                            JsonResult::Content { content } => match content {
-                                Value::String(s) => s,
-                                v => v.to_string(),
-                            },
-                            JsonResult::Error { error } => error,
-                        };
-                        let mut msg = OllamaChatMessage::new(OllamaMessageRole::Tool, content_str);
+                        let images: Vec<Image> = t
+                            .output
+                            .images()
+                            .map(|(_, data)| Image::from_base64(data))
+                            .collect();
+                        let mut msg = OllamaChatMessage::new(
+                            OllamaMessageRole::Tool,
+                            t.output.to_model_text(),
+                        );
                         msg.tool_name = Some(t.tool_name);
+                        if !images.is_empty() {
+                            msg = msg.with_images(images);
+                        }
                         msg
                     }
                 })
@@ -117,6 +134,14 @@ impl LlmClient for OllamaClient {
             if let Some(t) = request.think {
                 req = req.think(t);
             }
+            if let Some(max_tokens) = request.max_tokens {
+                req = req.options(ModelOptions::default().num_predict(max_tokens as i32));
+            }
+            if let Some(format) = request.response_format {
+                req = req.format(FormatType::StructuredJson(Box::new(
+                    JsonStructure::new_for_schema(format.schema),
+                )));
+            }
             req
         };
         let stream: ChatMessageResponseStream =
@@ -142,6 +167,7 @@ impl LlmClient for OllamaClient {
                         arguments: JsonResult::Content {
                             content: tc.function.arguments,
                         },
+                        repaired_from: None,
                     })
                     .collect();
                 for tc in tool_calls {
@@ -176,4 +202,20 @@ impl LlmClient for OllamaClient {
         let models = self.inner.list_local_models().await?;
         Ok(models.into_iter().map(|m| m.name).collect())
     }
+
+    async fn embed(
+        &self,
+        request: EmbeddingRequest,
+    ) -> Result<Vec<Vec<f32>>, Box<dyn Error + Send + Sync>> {
+        let response: EmbedResponse = self
+            .http
+            .post(format!("{}/api/embed", self.host))
+            .json(&json!({ "model": request.model_name, "input": request.input }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(response.embeddings)
+    }
 }
@@ -1,14 +1,17 @@
 use std::error::Error;
 
 use super::{
-    AssistantPart, ChatMessage, ChatMessageRequest, ChatStream, JsonResult, LlmClient,
-    ResponseChunk, ToolCall, to_openapi_schema,
+    AssistantPart, ChatMessage, ChatMessageRequest, ChatStream, EmbeddingRequest,
+    GrammarConstraint, JsonResult, LlmClient, ResponseChunk, ToolCall, ToolInfo,
+    json_repair::parse_tool_arguments, to_openapi_schema,
 };
 use async_openai::{Client, config::OpenAIConfig, types::*};
 use async_trait::async_trait;
 use futures_util::StreamExt;
+use gbnf_rs::{Expr, Generator, Grammar, JSON_GRAMMAR, Rule};
+use schemars::Schema;
 use serde::Deserialize;
-use serde_json::Value;
+use serde_json::{Value, json};
 use uuid::Uuid;
 
 #[derive(Default)]
@@ -38,8 +41,126 @@ struct StreamingDelta {
     tool_calls: Option<Vec<ChatCompletionMessageToolCallChunk>>,
 }
 
+/// How the constraint of [`GuidedDecoding`] is written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GuidedFormat {
+    /// A Lark grammar for vLLM, GBNF for SGLang.
+    #[default]
+    Grammar,
+    /// A regular expression. Fails for tools whose arguments include
+    /// free-form objects or arrays.
+    Regex,
+}
+
+/// Constrained decoding of OpenAI-compatible servers, used to hold tool calls
+/// to the tools' schemas.
+///
+/// The output is constrained to either text that does not start with `{`,
+/// or `{"name": ..., "arguments": ...}` calling one of the tools, which is
+/// turned into a tool call. With a response format, the text is replaced by
+/// an answer in that format. Requests without tools use the server's own
+/// `response_format` support.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GuidedDecoding {
+    /// vLLM's `guided_grammar` or `guided_regex`.
+    Vllm(GuidedFormat),
+    /// SGLang's `ebnf` or `regex`.
+    Sglang(GuidedFormat),
+}
+
+impl GuidedDecoding {
+    /// The request fields constraining the output to `grammar`'s `root`.
+    fn fields(
+        self,
+        grammar: &Grammar,
+    ) -> Result<(&'static str, String), Box<dyn Error + Send + Sync>> {
+        Ok(match self {
+            GuidedDecoding::Vllm(GuidedFormat::Grammar) => {
+                ("guided_grammar", grammar.to_lark("root"))
+            }
+            GuidedDecoding::Vllm(GuidedFormat::Regex) => {
+                ("guided_regex", grammar.to_regex("root")?)
+            }
+            GuidedDecoding::Sglang(GuidedFormat::Grammar) => ("ebnf", grammar.to_string()),
+            GuidedDecoding::Sglang(GuidedFormat::Regex) => ("regex", grammar.to_regex("root")?),
+        })
+    }
+}
+
+/// Text that does not start with `{`, or an answer conforming to `response`
+/// if set, or a call of one of `tools`.
+fn tool_call_grammar(
+    tools: &[ToolInfo],
+    response: Option<&Schema>,
+    constraint: GrammarConstraint,
+) -> Result<Grammar, Box<dyn Error + Send + Sync>> {
+    let generator = Generator::new();
+    let mut text = String::new();
+    let mut calls = Vec::new();
+    for tool in tools {
+        let arguments = if constraint == GrammarConstraint::RecipientOnly {
+            Expr::Ref("object".into())
+        } else {
+            let arguments =
+                generator.generate(&format!("{}-arguments", tool.name), &tool.parameters);
+            text.push_str(&arguments.to_string());
+            text.push('\n');
+            Expr::Ref(arguments.rules[0].name.clone())
+        };
+        let ws = || Expr::Ref("ws".into());
+        calls.push(Expr::Seq(vec![
+            Expr::Literal(serde_json::to_string(&tool.name)?),
+            ws(),
+            Expr::Literal(",".into()),
+            ws(),
+            Expr::Literal("\"arguments\":".into()),
+            ws(),
+            arguments,
+        ]));
+    }
+    let answer = match response {
+        Some(schema) => {
+            let answer = generator.generate("response", schema);
+            text.push_str(&answer.to_string());
+            text.push('\n');
+            Expr::Ref(answer.rules[0].name.clone())
+        }
+        None => Expr::Optional(Box::new(Expr::Seq(vec![
+            Expr::Class {
+                negated: true,
+                ranges: vec![('{', '{')],
+            },
+            Expr::Repeat(Box::new(Expr::Class {
+                negated: true,
+                ranges: vec![('\0', '\0')],
+            })),
+        ]))),
+    };
+    text.push_str(JSON_GRAMMAR);
+    let mut grammar = gbnf_rs::parse(&text)?;
+    let call = Expr::Seq(vec![
+        Expr::Literal("{\"name\":".into()),
+        Expr::Ref("ws".into()),
+        Expr::Choice(calls),
+        Expr::Literal("}".into()),
+    ]);
+    grammar.rules.push(Rule {
+        name: "root".into(),
+        expr: Expr::Choice(vec![call, answer]),
+    });
+    Ok(grammar)
+}
+
+/// A tool call written as text under [`GuidedDecoding`].
+#[derive(Deserialize)]
+struct GuidedToolCall {
+    name: String,
+    arguments: Value,
+}
+
 pub struct OpenAiChatClient {
     inner: Client<OpenAIConfig>,
+    guided: Option<GuidedDecoding>,
 }
 
 impl OpenAiChatClient {
@@ -50,8 +171,127 @@ impl OpenAiChatClient {
         };
         Self {
             inner: Client::with_config(config),
+            guided: None,
         }
     }
+
+    /// Constrains tool calls with the server's guided decoding.
+    pub fn guided(mut self, guided: GuidedDecoding) -> Self {
+        self.guided = Some(guided);
+        self
+    }
+}
+
+/// Tool messages only carry text, so images returned by tools are passed to
+/// the model in a user message following the run of tool results.
+fn flush_tool_images(messages: &mut Vec<Value>, images: &mut Vec<Value>) {
+    if images.is_empty() {
+        return;
+    }
+    let mut content = vec![json!({
+        "type": "text",
+        "text": "Images returned by the preceding tool calls:",
+    })];
+    content.append(images);
+    messages.push(json!({ "role": "user", "content": content }));
+}
+
+/// Converts the history to Chat Completions messages.
+fn openai_messages(chat_messages: Vec<ChatMessage>) -> Result<Vec<Value>, serde_json::Error> {
+    let mut messages: Vec<Value> = Vec::new();
+    let mut tool_images: Vec<Value> = Vec::new();
+    for m in chat_messages {
+        if !matches!(m, ChatMessage::Tool(_)) {
+            flush_tool_images(&mut messages, &mut tool_images);
+        }
+        let message = match m {
+            ChatMessage::User(u) => serde_json::to_value(ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(ChatCompletionRequestUserMessageContent::Text(u.content))
+                    .build()
+                    .unwrap(),
+            )),
+            ChatMessage::Assistant(a) => {
+                let mut builder = ChatCompletionRequestAssistantMessageArgs::default();
+                let mut content_acc = String::new();
+                let mut thinking_acc = String::new();
+                let mut tool_calls_acc: Vec<ToolCall> = Vec::new();
+                for part in a.content {
+                    match part {
+                        AssistantPart::Text { text, .. } => content_acc.push_str(&text),
+                        AssistantPart::Thinking { text, .. } => thinking_acc.push_str(&text),
+                        AssistantPart::ToolCall { call, .. } => tool_calls_acc.push(call),
+                    }
+                }
+                if !content_acc.is_empty() {
+                    builder.content(ChatCompletionRequestAssistantMessageContent::Text(
+                        content_acc,
+                    ));
+                }
+                if !tool_calls_acc.is_empty() {
+                    let tool_calls: Vec<ChatCompletionMessageToolCall> = tool_calls_acc
+                        .into_iter()
+                        .map(|tc| {
+                            let args = match &tc.arguments {
+                                JsonResult::Content { .. } => {
+                                    tc.arguments_content_with_id().to_string()
+                                }
+                                JsonResult::Error { error } => error.clone(),
+                            };
+                            ChatCompletionMessageToolCall {
+                                id: tc.id,
+                                r#type: ChatCompletionToolType::Function,
+                                function: FunctionCall {
+                                    name: tc.name,
+                                    arguments: args,
+                                },
+                            }
+                        })
+                        .collect();
+                    builder.tool_calls(tool_calls);
+                }
+                let result = serde_json::to_value(ChatCompletionRequestMessage::Assistant(
+                    builder.build().unwrap(),
+                ));
+                if !thinking_acc.is_empty() {
+                    result.map(|mut inner| {
+                        inner
+                            .as_object_mut()
+                            .unwrap()
+                            .insert("reasoning_content".to_string(), Value::String(thinking_acc));
+                        inner
+                    })
+                } else {
+                    result
+                }
+            }
+            ChatMessage::System(s) => serde_json::to_value(ChatCompletionRequestMessage::System(
+                ChatCompletionRequestSystemMessageArgs::default()
+                    .content(ChatCompletionRequestSystemMessageContent::Text(s.content))
+                    .build()
+                    .unwrap(),
+            )),
+            ChatMessage::Tool(t) => {
+                let content_str = t.output.to_model_text();
+                tool_images.extend(t.output.images().map(|(mime_type, data)| {
+                    json!({
+                        "type": "image_url",
+                        "image_url": { "url": format!("data:{mime_type};base64,{data}") },
+                    })
+                }));
+                serde_json::to_value(ChatCompletionRequestMessage::Tool(
+                    ChatCompletionRequestToolMessageArgs::default()
+                        .content(ChatCompletionRequestToolMessageContent::Text(content_str))
+                        .tool_call_id(t.id)
+                        .build()
+                        .unwrap(),
+                ))
+            }
+        }?;
+        messages.push(message);
+    }
+    flush_tool_images(&mut messages, &mut tool_images);
+    Ok(messages)
 }
 
 #[async_trait]
@@ -60,97 +300,21 @@ impl LlmClient for OpenAiChatClient {
         &self,
         request: ChatMessageRequest,
     ) -> Result<ChatStream, Box<dyn Error + Send + Sync>> {
-        let messages: Vec<Value> = request
-            .messages
-            .into_iter()
-            .map(|m| match m {
-                ChatMessage::User(u) => serde_json::to_value(ChatCompletionRequestMessage::User(
-                    ChatCompletionRequestUserMessageArgs::default()
-                        .content(ChatCompletionRequestUserMessageContent::Text(u.content))
-                        .build()
-                        .unwrap(),
-                )),
-                ChatMessage::Assistant(a) => {
-                    let mut builder = ChatCompletionRequestAssistantMessageArgs::default();
-                    let mut content_acc = String::new();
-                    let mut thinking_acc = String::new();
-                    let mut tool_calls_acc: Vec<ToolCall> = Vec::new();
-                    for part in a.content {
-                        match part {
-                            AssistantPart::Text { text, .. } => content_acc.push_str(&text),
-                            AssistantPart::Thinking { text, .. } => thinking_acc.push_str(&text),
-                            AssistantPart::ToolCall { call, .. } => tool_calls_acc.push(call),
-                        }
-                    }
-                    if !content_acc.is_empty() {
-                        builder.content(ChatCompletionRequestAssistantMessageContent::Text(
-                            content_acc,
-                        ));
-                    }
-                    if !tool_calls_acc.is_empty() {
-                        let tool_calls: Vec<ChatCompletionMessageToolCall> = tool_calls_acc
-                            .into_iter()
-                            .map(|tc| {
-                                let args = match &tc.arguments {
-                                    JsonResult::Content { .. } => {
-                                        tc.arguments_content_with_id().to_string()
-                                    }
-                                    JsonResult::Error { error } => error.clone(),
-                                };
-                                ChatCompletionMessageToolCall {
-                                    id: tc.id,
-                                    r#type: ChatCompletionToolType::Function,
-                                    function: FunctionCall {
-                                        name: tc.name,
-                                        arguments: args,
-                                    },
-                                }
-                            })
-                            .collect();
-                        builder.tool_calls(tool_calls);
-                    }
-                    let result = serde_json::to_value(ChatCompletionRequestMessage::Assistant(
-                        builder.build().unwrap(),
-                    ));
-                    if !thinking_acc.is_empty() {
-                        result.map(|mut inner| {
-                            inner.as_object_mut().unwrap().insert(
-                                "reasoning_content".to_string(),
-                                Value::String(thinking_acc),
-                            );
-                            inner
-                        })
-                    } else {
-                        result
-                    }
-                }
-                ChatMessage::System(s) => {
-                    serde_json::to_value(ChatCompletionRequestMessage::System(
-                        ChatCompletionRequestSystemMessageArgs::default()
-                            .content(ChatCompletionRequestSystemMessageContent::Text(s.content))
-                            .build()
-                            .unwrap(),
-                    ))
-                }
-                ChatMessage::Tool(t) => {
-                    let content_str = match &t.content {
-                        JsonResult::Content { content } => match content {
-                            Value::String(s) => s.clone(),
-                            v => v.to_string(),
-                        },
-                        JsonResult::Error { error } => error.clone(),
-                    };
-                    serde_json::to_value(ChatCompletionRequestMessage::Tool(
-                        ChatCompletionRequestToolMessageArgs::default()
-                            .content(ChatCompletionRequestToolMessageContent::Text(content_str))
-                            .tool_call_id(t.id)
-                            .build()
-                            .unwrap(),
-                    ))
-                }
-            })
-            .collect::<Result<Vec<_>, _>>()?;
+        let messages = openai_messages(request.messages)?;
 
+        let guided = match self.guided {
+            Some(guided)
+                if !request.tools.is_empty() && request.grammar != GrammarConstraint::Off =>
+            {
+                let grammar = tool_call_grammar(
+                    &request.tools,
+                    request.response_format.as_ref().map(|f| &f.schema),
+                    request.grammar,
+                )?;
+                Some(guided.fields(&grammar)?)
+            }
+            _ => None,
+        };
         let tools: Option<Vec<ChatCompletionTool>> = if request.tools.is_empty() {
             None
         } else {
@@ -178,22 +342,46 @@ impl LlmClient for OpenAiChatClient {
         if let Some(t) = tools {
             req_builder.tools(t);
         }
+        if let Some(max_tokens) = request.max_tokens {
+            req_builder.max_completion_tokens(max_tokens);
+        }
+        if guided.is_none()
+            && let Some(format) = &request.response_format
+        {
+            req_builder.response_format(ResponseFormat::JsonSchema {
+                json_schema: ResponseFormatJsonSchema {
+                    description: None,
+                    name: format.name.clone(),
+                    schema: Some(to_openapi_schema(&format.schema)),
+                    strict: None,
+                },
+            });
+        }
         req_builder.stream(true);
         req_builder.stream_options(ChatCompletionStreamOptions {
             include_usage: true,
         });
         let req = req_builder.build()?;
         let mut req_value = serde_json::to_value(&req)?;
-        req_value
-            .as_object_mut()
-            .ok_or("req was not object")?
-            .insert("messages".to_string(), Value::Array(messages));
+        let req_object = req_value.as_object_mut().ok_or("req was not object")?;
+        req_object.insert("messages".to_string(), Value::Array(messages));
+        let guided = if let Some((field, constraint)) = guided {
+            req_object.insert(field.to_string(), Value::String(constraint));
+            true
+        } else {
+            false
+        };
         let stream = self
             .inner
             .chat()
             .create_stream_byot::<Value, StreamingChunk>(req_value)
             .await?;
+        let repair_tool_calls = request.repair_tool_calls;
         let mut pending_tool_calls: Vec<ToolCallBuilder> = Vec::new();
+        // Under guided decoding, text starting with `{` is held back until
+        // it is known to be a tool call.
+        let mut content_started = false;
+        let mut guided_call: Option<String> = None;
         let mapped = stream.flat_map(move |res| {
             let mut out: Vec<Result<ResponseChunk, Box<dyn Error + Send + Sync>>> = Vec::new();
             match res {
@@ -203,7 +391,14 @@ impl LlmClient for OpenAiChatClient {
                     let mut tool_calls = Vec::new();
                     for choice in &chunk.choices {
                         if let Some(c) = choice.delta.content.as_deref() {
-                            content_acc.push_str(c);
+                            if guided && !content_started && c.starts_with('{') {
+                                guided_call = Some(String::new());
+                            }
+                            content_started |= !c.is_empty();
+                            match &mut guided_call {
+                                Some(call) => call.push_str(c),
+                                None => content_acc.push_str(c),
+                            }
                         }
                         if let Some(r) = choice.delta.reasoning_content.as_deref() {
                             thinking_acc.push_str(r);
@@ -231,20 +426,33 @@ impl LlmClient for OpenAiChatClient {
                         if matches!(choice.finish_reason, Some(FinishReason::ToolCalls)) {
                             if !pending_tool_calls.is_empty() {
                                 for b in pending_tool_calls.drain(..) {
-                                    let arguments = match serde_json::from_str(&b.arguments) {
-                                        Ok(v) => JsonResult::Content { content: v },
-                                        Err(_) => JsonResult::Error {
-                                            error: b.arguments.clone(),
-                                        },
-                                    };
+                                    let (arguments, repaired_from) =
+                                        parse_tool_arguments(&b.arguments, repair_tool_calls);
                                     tool_calls.push(ToolCall {
                                         id: b.id.unwrap_or_else(|| Uuid::new_v4().to_string()),
                                         name: b.name.unwrap_or_default(),
                                         arguments,
+                                        repaired_from,
                                     });
                                 }
                             }
                         }
+                        if choice.finish_reason.is_some()
+                            && let Some(text) = guided_call.take()
+                        {
+                            match serde_json::from_str::<GuidedToolCall>(&text) {
+                                Ok(call) => tool_calls.push(ToolCall {
+                                    id: Uuid::new_v4().to_string(),
+                                    name: call.name,
+                                    arguments: JsonResult::Content {
+                                        content: call.arguments,
+                                    },
+                                    repaired_from: None,
+                                }),
+                                // Cut short, for instance by the token limit.
+                                Err(_) => content_acc.push_str(&text),
+                            }
+                        }
                     }
                     let done = chunk.choices.iter().any(|c| c.finish_reason.is_some());
                     let usage = if done {
@@ -293,4 +501,125 @@ impl LlmClient for OpenAiChatClient {
         let resp = self.inner.models().list().await?;
         Ok(resp.data.into_iter().map(|m| m.id).collect())
     }
+
+    async fn embed(
+        &self,
+        request: EmbeddingRequest,
+    ) -> Result<Vec<Vec<f32>>, Box<dyn Error + Send + Sync>> {
+        let mut resp: EmbeddingList = self
+            .inner
+            .embeddings()
+            .create_byot(json!({ "model": request.model_name, "input": request.input }))
+            .await?;
+        resp.data.sort_by_key(|e| e.index);
+        Ok(resp.data.into_iter().map(|e| e.embedding).collect())
+    }
+}
+
+/// Response of `/v1/embeddings`.
+#[derive(Deserialize)]
+struct EmbeddingList {
+    data: Vec<EmbeddingItem>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingItem {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ToolOutput, test_server::serve};
+    use rmcp::model::{CallToolResult, Content};
+
+    #[test]
+    fn marks_failed_tool_results() {
+        let failed =
+            crate::mcp::tool_output(CallToolResult::error(vec![Content::text("no such file")]));
+        let messages = openai_messages(vec![
+            ChatMessage::tool("1".into(), failed, "read".into()),
+            ChatMessage::tool(
+                "2".into(),
+                ToolOutput::error("Tool Failed: timed out"),
+                "read".into(),
+            ),
+            ChatMessage::tool("3".into(), ToolOutput::text("ok"), "read".into()),
+        ])
+        .unwrap();
+        let contents: Vec<&str> = messages
+            .iter()
+            .map(|m| m["content"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            contents,
+            ["Tool Failed: no such file", "Tool Failed: timed out", "ok"]
+        );
+    }
+
+    #[test]
+    fn guided_grammar_accepts_tool_calls_and_text() {
+        let tool = ToolInfo {
+            name: "echo".into(),
+            description: String::new(),
+            parameters: json!({
+                "type": "object",
+                "properties": { "text": { "type": "string", "maxLength": 8 } },
+                "required": ["text"]
+            })
+            .try_into()
+            .unwrap(),
+        };
+        let grammar = tool_call_grammar(&[tool.clone()], None, GrammarConstraint::Full).unwrap();
+        let matcher = gbnf_rs::Matcher::new(&grammar).unwrap();
+        assert!(matcher.accepts("root", r#"{"name": "echo", "arguments": {"text": "hi"}}"#));
+        assert!(matcher.accepts("root", "Hello {there}"));
+        assert!(matcher.accepts("root", ""));
+        assert!(!matcher.accepts("root", r#"{"name": "echo", "arguments": {"text": 1}}"#));
+        assert!(!matcher.accepts("root", r#"{"name": "other", "arguments": {}}"#));
+
+        let (field, regex) = GuidedDecoding::Sglang(GuidedFormat::Regex)
+            .fields(&grammar)
+            .unwrap();
+        assert_eq!(field, "regex");
+        assert!(regex.starts_with(r#"\{"name":"#));
+
+        let grammar = tool_call_grammar(&[tool], None, GrammarConstraint::RecipientOnly).unwrap();
+        let matcher = gbnf_rs::Matcher::new(&grammar).unwrap();
+        assert!(matcher.accepts("root", r#"{"name": "echo", "arguments": {"text": 1}}"#));
+        assert!(!matcher.accepts("root", r#"{"name": "other", "arguments": {}}"#));
+    }
+
+    #[tokio::test]
+    async fn embeds_with_the_embeddings_api() {
+        use axum::{Json, Router, routing::post};
+
+        let router = Router::new().route(
+            "/v1/embeddings",
+            post(|Json(body): Json<Value>| async move {
+                assert_eq!(body, json!({ "model": "embed", "input": ["a", "b"] }));
+                Json(json!({
+                    "object": "list",
+                    "model": "embed",
+                    "data": [
+                        { "object": "embedding", "index": 1, "embedding": [0.0, 1.0] },
+                        { "object": "embedding", "index": 0, "embedding": [1.0, 0.0] },
+                    ],
+                    "usage": { "prompt_tokens": 2, "total_tokens": 2 },
+                }))
+            }),
+        );
+        let host = format!("{}/v1", serve(router).await);
+
+        let client = OpenAiChatClient::new(Some(&host));
+        let embeddings = client
+            .embed(EmbeddingRequest::new(
+                "embed".into(),
+                vec!["a".into(), "b".into()],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(embeddings, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+    }
 }
@@ -0,0 +1,287 @@
+//! The stateless `python` tool that gpt-oss was trained on, see
+//! [`python_tools`].
+//!
+//! Each call runs a whole script in a fresh interpreter. The model gets back
+//! the plain output of the process, stdout followed by stderr.
+
+use std::error::Error;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::{
+    ToolOutput,
+    function_tool::{FunctionTool, FunctionToolSet},
+    tools::ToolExecutor,
+};
+
+/// Prefix of the python tool.
+pub const PYTHON_PREFIX: &str = "python";
+/// Name of the python tool. The Harmony provider declares a tool of this
+/// name as its built-in `python`.
+pub const PYTHON_TOOL: &str = "python_exec";
+
+const TIME_LIMIT: Duration = Duration::from_secs(30);
+
+/// Where scripts run.
+enum Exec {
+    /// Runs a local command with the script on stdin.
+    Command(String, Vec<String>),
+    /// Runs `python3 -` through the `run` tool of an mcp-shell server, by
+    /// server name.
+    Shell(Arc<dyn ToolExecutor>, String),
+}
+
+/// Runs the scripts of the python tool.
+pub struct Python {
+    exec: Exec,
+    time_limit: Duration,
+}
+
+impl Default for Python {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The result of mcp-shell's `run`.
+#[derive(Deserialize)]
+struct ShellResult {
+    #[serde(default)]
+    stdout: String,
+    #[serde(default)]
+    stderr: String,
+    status: String,
+}
+
+impl Python {
+    /// Runs scripts with a local `python3`.
+    pub fn new() -> Self {
+        Self {
+            exec: Exec::Command("python3".into(), vec!["-".into()]),
+            time_limit: TIME_LIMIT,
+        }
+    }
+
+    /// Runs scripts with `program`, which reads the script from stdin, e.g.
+    /// `podman` with `["exec", "-i", "box", "python3", "-"]`.
+    pub fn command(
+        mut self,
+        program: impl Into<String>,
+        args: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.exec = Exec::Command(program.into(), args.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Runs scripts through the mcp-shell server named `server`, e.g. in its
+    /// container. The server's time limit applies as well.
+    pub fn shell_tool(
+        mut self,
+        executor: Arc<dyn ToolExecutor>,
+        server: impl Into<String>,
+    ) -> Self {
+        self.exec = Exec::Shell(executor, server.into());
+        self
+    }
+
+    /// Stops scripts that run longer than `limit`, 30 seconds by default.
+    pub fn time_limit(mut self, limit: Duration) -> Self {
+        self.time_limit = limit;
+        self
+    }
+
+    /// Runs `code` and returns its output.
+    pub async fn run(&self, code: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let (stdout, stderr, timed_out) = match &self.exec {
+            Exec::Command(program, args) => {
+                let mut child = Command::new(program)
+                    .args(args)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .kill_on_drop(true)
+                    .spawn()
+                    .map_err(|e| format!("failed to start {program}: {e}"))?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(code.as_bytes()).await?;
+                }
+                match tokio::time::timeout(self.time_limit, child.wait_with_output()).await {
+                    Ok(output) => {
+                        let output = output?;
+                        (
+                            String::from_utf8_lossy(&output.stdout).into_owned(),
+                            String::from_utf8_lossy(&output.stderr).into_owned(),
+                            false,
+                        )
+                    }
+                    Err(_) => (String::new(), String::new(), true),
+                }
+            }
+            Exec::Shell(executor, server) => {
+                let tool_name = |tool: &str| {
+                    executor
+                        .server_tool_name(server, tool)
+                        .ok_or_else(|| format!("MCP server '{server}' has no {tool} tool"))
+                };
+                let output = executor
+                    .call(
+                        &tool_name("run")?,
+                        json!({ "command": "python3 -", "stdin": code }),
+                    )
+                    .await?;
+                if output.is_error {
+                    return Err(output.to_text().into());
+                }
+                let result: ShellResult = serde_json::from_str(&output.to_text())?;
+                let timed_out = result.status != "finished";
+                if timed_out {
+                    executor.call(&tool_name("terminate")?, json!({})).await?;
+                }
+                (result.stdout, result.stderr, timed_out)
+            }
+        };
+        let mut text = stdout;
+        text.push_str(&stderr);
+        if timed_out {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text.push_str("[ERROR] Timed out, the script was stopped");
+        }
+        Ok(text)
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct PythonParams {
+    /// The script to run.
+    pub code: String,
+}
+
+struct ExecTool(Arc<Python>);
+
+#[async_trait]
+impl FunctionTool for ExecTool {
+    const NAME: &'static str = "exec";
+    const DESCRIPTION: &'static str =
+        "Runs a Python script in a new interpreter and returns what it prints to stdout and stderr";
+    type Params = PythonParams;
+
+    async fn call(&self, params: PythonParams) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+        Ok(ToolOutput::text(self.0.run(&params.code).await?))
+    }
+}
+
+/// The python tool, named [`PYTHON_TOOL`].
+pub fn python_tools(python: Arc<Python>) -> FunctionToolSet {
+    FunctionToolSet::new(PYTHON_PREFIX).with(ExecTool(python))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn runs_scripts_with_a_command() {
+        let python = Python::new().command("sh", ["-s"]);
+        let output = python.run("echo out; echo err >&2").await.unwrap();
+        assert_eq!(output, "out\nerr\n");
+
+        let python = python.time_limit(Duration::from_millis(100));
+        let output = python.run("echo started; sleep 5").await.unwrap();
+        assert_eq!(output, "[ERROR] Timed out, the script was stopped");
+    }
+
+    /// An mcp-shell server named `shell`, whose tools are prefixed with
+    /// `prefix`.
+    struct Shell {
+        prefix: &'static str,
+        calls: Mutex<Vec<(String, serde_json::Value)>>,
+    }
+
+    impl Shell {
+        fn new(prefix: &'static str) -> Self {
+            Self {
+                prefix,
+                calls: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ToolExecutor for Shell {
+        fn server_tool_name(&self, server: &str, tool: &str) -> Option<String> {
+            (server == "shell").then(|| format!("{}_{tool}", self.prefix))
+        }
+
+        async fn call(
+            &self,
+            name: &str,
+            args: serde_json::Value,
+        ) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+            self.calls.lock().unwrap().push((name.into(), args.clone()));
+            let status = if args["stdin"] == "loop()" {
+                "still running, call wait or terminate"
+            } else {
+                "finished"
+            };
+            Ok(ToolOutput::text(
+                json!({"stdout": "4\n", "exit_code": 0, "status": status}).to_string(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_scripts_through_mcp_shell() {
+        let shell = Arc::new(Shell::new("shell"));
+        let python = Python::new().shell_tool(shell.clone(), "shell");
+        assert_eq!(python.run("print(2 + 2)").await.unwrap(), "4\n");
+        assert_eq!(
+            python.run("loop()").await.unwrap(),
+            "4\n[ERROR] Timed out, the script was stopped"
+        );
+        let calls = shell.calls.lock().unwrap();
+        assert_eq!(
+            *calls,
+            vec![
+                (
+                    "shell_run".into(),
+                    json!({"command": "python3 -", "stdin": "print(2 + 2)"})
+                ),
+                (
+                    "shell_run".into(),
+                    json!({"command": "python3 -", "stdin": "loop()"})
+                ),
+                ("shell_terminate".into(), json!({})),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn finds_the_shell_tools_by_server() {
+        let shell = Arc::new(Shell::new("box"));
+        let python = Python::new().shell_tool(shell.clone(), "shell");
+        python.run("loop()").await.unwrap();
+        let calls: Vec<String> = shell
+            .calls
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect();
+        assert_eq!(calls, ["box_run", "box_terminate"]);
+
+        let python = Python::new().shell_tool(shell, "other");
+        let err = python.run("print(1)").await.unwrap_err();
+        assert_eq!(err.to_string(), "MCP server 'other' has no run tool");
+    }
+}
@@ -52,7 +52,7 @@ impl LlmClient for TestProvider {
 mod tests {
     use super::*;
     use crate::tools::{ToolExecutor, run_tool_loop};
-    use crate::{AssistantPart, ChatMessage, JsonResult, ToolCall};
+    use crate::{AssistantPart, ChatMessage, JsonResult, ToolCall, ToolOutput};
     use serde_json::Value;
     use std::sync::{Arc, Mutex};
 
@@ -64,8 +64,8 @@ mod tests {
             &self,
             name: &str,
             _args: Value,
-        ) -> Result<String, Box<dyn Error + Send + Sync>> {
-            Ok(format!("called {name}"))
+        ) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+            Ok(ToolOutput::text(format!("called {name}")))
         }
     }
 
@@ -80,6 +80,7 @@ mod tests {
                     arguments: JsonResult::Content {
                         content: Value::Null,
                     },
+                    repaired_from: None,
                 },
                 encrypted_content: None,
             }),
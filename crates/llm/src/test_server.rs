@@ -0,0 +1,12 @@
+//! A stub HTTP server for tests.
+
+use axum::Router;
+
+/// Serves `router` on a free local port and returns its base URL, e.g.
+/// `http://127.0.0.1:4000`.
+pub(crate) async fn serve(router: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    format!("http://{addr}")
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+
+use crate::{
+    browser::{BROWSER_PREFIX, browser_method},
+    python::{PYTHON_PREFIX, PYTHON_TOOL},
+};
+
+/// Longest tool name accepted by the providers.
+const MAX_NAME_LEN: usize = 64;
+/// Length of the `_` and hash appended to shortened or colliding names.
+const HASH_SUFFIX_LEN: usize = 9;
+
+/// Where a tool is served from. In-process tools are named before the
+/// tools of servers, so they keep their names on collisions.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum ToolSource {
+    /// A set of in-process tools, by prefix.
+    Functions(String),
+    /// An MCP server, by name.
+    Server(String),
+}
+
+impl ToolSource {
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            ToolSource::Functions(name) | ToolSource::Server(name) => name,
+        }
+    }
+}
+
+/// A tool as exposed by its source, and the prefix its name is given.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct ToolKey {
+    pub source: ToolSource,
+    pub prefix: String,
+    pub tool: String,
+}
+
+/// A tool that could not be given its preferred name, see
+/// [`ToolNames::update`].
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ToolNameCollision {
+    pub key: ToolKey,
+    pub preferred: String,
+    pub name: String,
+}
+
+/// Maps tools to unique names that providers accept, and back.
+///
+/// A tool is named `<prefix>_<tool>`, with characters other than ASCII
+/// letters, digits, `_` and `-` replaced by `_`. Names longer than 64
+/// characters are shortened and end with a hash of the tool, as do the names
+/// of tools that would collide with a tool that was named first. The names
+/// that the Harmony provider declares as its built-in tools are kept for the
+/// in-process tool sets that implement them, see [`reserved_for`].
+#[derive(Default)]
+pub(crate) struct ToolNames {
+    names: HashMap<ToolKey, String>,
+    keys: HashMap<String, ToolKey>,
+}
+
+impl ToolNames {
+    /// Names the tools in `keys`, forgetting those that are missing. Tools
+    /// that were named before keep their names, so that a name stays valid
+    /// for as long as its tool exists. Returns the tools that had to be given
+    /// another name than their preferred one.
+    pub(crate) fn update(&mut self, mut keys: Vec<ToolKey>) -> Vec<ToolNameCollision> {
+        keys.sort();
+        keys.dedup();
+        let mut names = HashMap::new();
+        let mut taken = HashMap::new();
+        let mut unnamed = Vec::new();
+        for key in keys {
+            match self.names.get(&key) {
+                Some(name) => {
+                    taken.insert(name.clone(), key.clone());
+                    names.insert(key, name.clone());
+                }
+                None => unnamed.push(key),
+            }
+        }
+        let mut collisions = Vec::new();
+        for key in unnamed {
+            let preferred = preferred_name(&key);
+            let mut name = preferred.clone();
+            let mut attempt = 0;
+            while taken.contains_key(&name)
+                || reserved_for(&name)
+                    .is_some_and(|prefix| key.source != ToolSource::Functions(prefix.to_string()))
+            {
+                let seed = format!(
+                    "{}/{}/{}/{attempt}",
+                    key.source.name(),
+                    key.prefix,
+                    key.tool
+                );
+                name = with_hash(&preferred, &seed);
+                attempt += 1;
+            }
+            if name != preferred {
+                collisions.push(ToolNameCollision {
+                    key: key.clone(),
+                    preferred,
+                    name: name.clone(),
+                });
+            }
+            taken.insert(name.clone(), key.clone());
+            names.insert(key, name);
+        }
+        self.names = names;
+        self.keys = taken;
+        collisions
+    }
+
+    pub(crate) fn name(&self, key: &ToolKey) -> Option<&str> {
+        self.names.get(key).map(String::as_str)
+    }
+
+    pub(crate) fn resolve(&self, name: &str) -> Option<&ToolKey> {
+        self.keys.get(name)
+    }
+}
+
+/// The prefix of the in-process tool set that `name` is reserved for, as a
+/// built-in tool of Harmony, e.g. `browser` for `browser_search`. A tool of
+/// any other source, such as an MCP server named `browser`, is named as if
+/// the name were taken.
+fn reserved_for(name: &str) -> Option<&'static str> {
+    if name == PYTHON_TOOL {
+        Some(PYTHON_PREFIX)
+    } else if browser_method(name).is_some() {
+        Some(BROWSER_PREFIX)
+    } else {
+        None
+    }
+}
+
+/// `<prefix>_<tool>` made acceptable to providers, before collisions are
+/// resolved.
+fn preferred_name(key: &ToolKey) -> String {
+    let full = format!("{}_{}", key.prefix, key.tool);
+    let name: String = full
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.len() > MAX_NAME_LEN {
+        with_hash(&name, &full)
+    } else {
+        name
+    }
+}
+
+/// Shortens `name` as needed and appends a hash of `seed`.
+fn with_hash(name: &str, seed: &str) -> String {
+    // Names are ASCII after sanitizing, so any byte offset is a char boundary.
+    let base = &name[..name.len().min(MAX_NAME_LEN - HASH_SUFFIX_LEN)];
+    format!("{base}_{:08x}", fnv1a(seed))
+}
+
+/// A hash that is stable across runs and Rust versions.
+fn fnv1a(data: &str) -> u32 {
+    data.bytes().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x01000193)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(name: &str, tool: &str) -> ToolKey {
+        ToolKey {
+            source: ToolSource::Server(name.into()),
+            prefix: name.into(),
+            tool: tool.into(),
+        }
+    }
+
+    #[test]
+    fn sanitizes_and_shortens_names() {
+        let dotted = server("my.server", "read file");
+        let long = server("files", &"x".repeat(80));
+        let mut names = ToolNames::default();
+        assert!(names.update(vec![dotted.clone(), long.clone()]).is_empty());
+        assert_eq!(names.name(&dotted), Some("my_server_read_file"));
+        let shortened = names.name(&long).unwrap();
+        assert_eq!(shortened.len(), 64);
+        assert!(shortened.starts_with("files_xxx"));
+        assert_eq!(names.resolve(shortened), Some(&long));
+        assert_eq!(names.resolve("files_x"), None);
+    }
+
+    #[test]
+    fn resolves_collisions_and_keeps_names() {
+        let a = server("a", "b_c");
+        let b = server("a_b", "c");
+        let mut names = ToolNames::default();
+        let collisions = names.update(vec![b.clone(), a.clone()]);
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].key, b);
+        assert_eq!(collisions[0].preferred, "a_b_c");
+        assert_eq!(names.name(&a), Some("a_b_c"));
+        let renamed = names.name(&b).unwrap().to_string();
+        assert!(renamed.starts_with("a_b_c_") && renamed.len() == 14);
+        assert_eq!(names.resolve(&renamed), Some(&b));
+
+        // The first tool goes away, the other keeps its name until it does too.
+        assert!(names.update(vec![b.clone()]).is_empty());
+        assert_eq!(names.name(&b), Some(renamed.as_str()));
+        assert_eq!(names.resolve("a_b_c"), None);
+        names.update(Vec::new());
+        names.update(vec![b.clone()]);
+        assert_eq!(names.name(&b), Some("a_b_c"));
+    }
+
+    #[test]
+    fn keeps_builtin_names_for_their_tool_sets() {
+        let search = server("browser", "search");
+        let exec = server("python", "exec");
+        let other = server("browser", "history");
+        let mut names = ToolNames::default();
+        let collisions = names.update(vec![search.clone(), exec.clone(), other.clone()]);
+        assert_eq!(collisions.len(), 2);
+        assert!(names.name(&search).unwrap().starts_with("browser_search_"));
+        assert!(names.name(&exec).unwrap().starts_with("python_exec_"));
+        assert_eq!(names.name(&other), Some("browser_history"));
+
+        let builtin = ToolKey {
+            source: ToolSource::Functions("browser".into()),
+            prefix: "browser".into(),
+            tool: "search".into(),
+        };
+        assert!(names.update(vec![builtin.clone(), search]).is_empty());
+        assert_eq!(names.name(&builtin), Some("browser_search"));
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::{ToolOutput, ToolOutputPart, Truncation};
+
+/// Caps the text of the tool results added to the history.
+///
+/// Longer outputs keep the head and tail of their text around a notice, and
+/// the full text is written to a file in `dir` so it can be paged through
+/// with [`ToolOutputLimit::read`]. The files are removed by
+/// [`ToolOutputLimit::clear`], and when the last clone of the limit is
+/// dropped.
+#[derive(Clone, Debug)]
+pub struct ToolOutputLimit {
+    max_chars: usize,
+    store: Arc<Store>,
+}
+
+/// The stored outputs, shared by the clones of a limit.
+#[derive(Debug)]
+struct Store {
+    dir: PathBuf,
+    state: Mutex<StoreState>,
+}
+
+#[derive(Debug, Default)]
+struct StoreState {
+    ids: Vec<String>,
+    /// Whether `dir` was created for the outputs, and is removed with them.
+    created_dir: bool,
+}
+
+impl Store {
+    fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        for id in state.ids.drain(..) {
+            let _ = std::fs::remove_file(self.dir.join(format!("{id}.txt")));
+        }
+        if state.created_dir && std::fs::remove_dir(&self.dir).is_ok() {
+            state.created_dir = false;
+        }
+    }
+}
+
+impl Drop for Store {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// A slice of a stored output, see [`ToolOutputLimit::read`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ToolOutputPage {
+    pub text: String,
+    /// The offset of the first character after the page.
+    pub next_offset: usize,
+    pub total_chars: usize,
+}
+
+impl ToolOutputLimit {
+    pub fn new(max_chars: usize, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            max_chars,
+            store: Arc::new(Store {
+                dir: dir.into(),
+                state: Mutex::new(StoreState::default()),
+            }),
+        }
+    }
+
+    pub fn max_chars(&self) -> usize {
+        self.max_chars
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.store.dir
+    }
+
+    /// Removes the stored outputs, after which their ids no longer resolve.
+    pub fn clear(&self) {
+        self.store.clear();
+    }
+
+    /// Truncates `output` if its text is longer than the limit. Images are
+    /// kept, structured content is dropped in favour of the text.
+    pub fn apply(&self, output: &mut ToolOutput) {
+        let (images, rest): (Vec<_>, Vec<_>) = output
+            .parts
+            .iter()
+            .cloned()
+            .partition(|part| matches!(part, ToolOutputPart::Image { .. }));
+        let text = ToolOutput {
+            parts: rest,
+            structured_content: output.structured_content.clone(),
+            ..ToolOutput::default()
+        }
+        .to_text();
+        let total_chars = text.chars().count();
+        if total_chars <= self.max_chars {
+            return;
+        }
+        let id = uuid::Uuid::new_v4().simple().to_string()[..12].to_string();
+        let notice = match self.store(&id, &text) {
+            Ok(()) => format!(
+                "[... {} of {total_chars} characters omitted, the full output is stored with id \"{id}\" ...]",
+                total_chars - self.max_chars
+            ),
+            Err(err) => format!(
+                "[... {} of {total_chars} characters omitted, the full output could not be stored: {err} ...]",
+                total_chars - self.max_chars
+            ),
+        };
+        let head_chars = self.max_chars / 2;
+        let tail_chars = self.max_chars - head_chars;
+        let head: String = text.chars().take(head_chars).collect();
+        let tail: String = text.chars().skip(total_chars - tail_chars).collect();
+        output.parts = std::iter::once(ToolOutputPart::Text {
+            text: format!("{head}\n{notice}\n{tail}"),
+        })
+        .chain(images)
+        .collect();
+        output.structured_content = None;
+        output.truncated = Some(Truncation { id, total_chars });
+    }
+
+    fn path(&self, id: &str) -> io::Result<PathBuf> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid output id \"{id}\""),
+            ));
+        }
+        Ok(self.store.dir.join(format!("{id}.txt")))
+    }
+
+    fn store(&self, id: &str, text: &str) -> io::Result<()> {
+        let path = self.path(id)?;
+        let mut state = self.store.state.lock().unwrap();
+        if !self.store.dir.exists() {
+            std::fs::create_dir_all(&self.store.dir)?;
+            state.created_dir = true;
+        }
+        std::fs::write(path, text)?;
+        state.ids.push(id.to_string());
+        Ok(())
+    }
+
+    /// Reads up to `max_chars` characters of a stored output, starting at
+    /// character `offset`.
+    pub fn read(&self, id: &str, offset: usize, max_chars: usize) -> io::Result<ToolOutputPage> {
+        let text = std::fs::read_to_string(self.path(id)?)?;
+        let total_chars = text.chars().count();
+        let page: String = text.chars().skip(offset).take(max_chars).collect();
+        Ok(ToolOutputPage {
+            next_offset: (offset + max_chars).min(total_chars),
+            text: page,
+            total_chars,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncates_and_pages_through_long_outputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let limit = ToolOutputLimit::new(10, dir.path());
+
+        let mut short = ToolOutput::text("0123456789");
+        limit.apply(&mut short);
+        assert_eq!(short, ToolOutput::text("0123456789"));
+
+        let mut output = ToolOutput::text("héllo wörld, this is long");
+        output.parts.push(ToolOutputPart::Image {
+            data: "aGk=".into(),
+            mime_type: "image/png".into(),
+        });
+        limit.apply(&mut output);
+        let truncation = output.truncated.clone().unwrap();
+        assert_eq!(truncation.total_chars, 25);
+        assert_eq!(
+            output.to_text(),
+            format!(
+                "héllo\n[... 15 of 25 characters omitted, the full output is stored with id \"{}\" ...]\n long\n[image: image/png]",
+                truncation.id
+            )
+        );
+
+        let page = limit.read(&truncation.id, 6, 5).unwrap();
+        assert_eq!(
+            page,
+            ToolOutputPage {
+                text: "wörld".into(),
+                next_offset: 11,
+                total_chars: 25,
+            }
+        );
+        assert_eq!(limit.read(&truncation.id, 20, 100).unwrap().next_offset, 25);
+        assert!(limit.read("../secret", 0, 10).is_err());
+    }
+
+    #[test]
+    fn removes_stored_outputs() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "mine").unwrap();
+        let limit = ToolOutputLimit::new(4, dir.path());
+        let mut output = ToolOutput::text("too long");
+        limit.apply(&mut output);
+        let id = output.truncated.unwrap().id;
+        assert!(limit.read(&id, 0, 4).is_ok());
+        limit.clear();
+        assert!(limit.read(&id, 0, 4).is_err());
+        assert!(dir.path().join("notes.txt").exists());
+
+        let outputs = dir.path().join("outputs");
+        let limit = ToolOutputLimit::new(4, &outputs);
+        limit.clone().apply(&mut ToolOutput::text("too long"));
+        assert!(outputs.exists());
+        drop(limit);
+        assert!(!outputs.exists());
+    }
+}
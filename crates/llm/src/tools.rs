@@ -11,15 +11,54 @@ use tokio::{
 };
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tokio_stream::{Stream, StreamExt};
+use tokio_util::sync::CancellationToken;
 
 use crate::{
     AssistantMessage, AssistantPart, ChatMessage, ChatMessageRequest, JsonResult, LlmClient,
-    ResponseChunk,
+    ResponseChunk, ToolInfo, ToolOutput,
+    agent::{AgentHooks, Budget, BudgetExceeded, NoHooks},
+    tool_output_limit::ToolOutputLimit,
 };
 
 #[async_trait]
 pub trait ToolExecutor: Send + Sync {
-    async fn call(&self, name: &str, args: Value) -> Result<String, Box<dyn Error + Send + Sync>>;
+    async fn call(
+        &self,
+        name: &str,
+        args: Value,
+    ) -> Result<ToolOutput, Box<dyn Error + Send + Sync>>;
+
+    /// Like [`ToolExecutor::call`], and sends any progress reported by the
+    /// tool to `progress`.
+    async fn call_with_progress(
+        &self,
+        name: &str,
+        args: Value,
+        progress: UnboundedSender<ToolProgress>,
+    ) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+        let _ = progress;
+        self.call(name, args).await
+    }
+
+    /// The tools this executor can call, as advertised to the model.
+    fn tool_infos(&self) -> Vec<ToolInfo> {
+        Vec::new()
+    }
+
+    /// The name that the tool `tool` of the MCP server `server` is called
+    /// by, if the executor has it.
+    fn server_tool_name(&self, server: &str, tool: &str) -> Option<String> {
+        let _ = (server, tool);
+        None
+    }
+}
+
+/// Progress of a running tool, e.g. from MCP `notifications/progress`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ToolProgress {
+    pub progress: f64,
+    pub total: Option<f64>,
+    pub message: Option<String>,
 }
 
 pub enum ToolEvent {
@@ -33,7 +72,11 @@ pub enum ToolEvent {
     ToolResult {
         call_id: String,
         name: String,
-        result: Result<String, Box<dyn Error + Send + Sync>>,
+        result: Result<ToolOutput, Box<dyn Error + Send + Sync>>,
+    },
+    ToolProgress {
+        call_id: String,
+        progress: ToolProgress,
     },
 }
 
@@ -58,20 +101,85 @@ pub fn tool_event_stream(
 }
 
 pub async fn run_tool_loop(
+    client: Arc<dyn LlmClient>,
+    request: ChatMessageRequest,
+    tool_executor: Arc<dyn ToolExecutor>,
+    chat_history: Arc<Mutex<Vec<ChatMessage>>>,
+    tx: UnboundedSender<ToolEvent>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    run_tool_loop_with(
+        client,
+        request,
+        tool_executor,
+        chat_history,
+        tx,
+        Arc::new(NoHooks),
+        Budget::default(),
+        None,
+        CancellationToken::new(),
+    )
+    .await
+}
+
+/// Like [`run_tool_loop`], calling `hooks` around each request and tool call
+/// and stopping with [`BudgetExceeded`] once `budget` is used up. Tool
+/// results are cut down to `output_limit` after the hooks have seen them.
+///
+/// When `cancel` fires the partial response is committed to the history,
+/// in-flight tool calls are dropped and recorded as cancelled, and the loop
+/// returns `Ok`.
+#[allow(clippy::too_many_arguments)]
+pub async fn run_tool_loop_with(
     client: Arc<dyn LlmClient>,
     mut request: ChatMessageRequest,
     tool_executor: Arc<dyn ToolExecutor>,
     chat_history: Arc<Mutex<Vec<ChatMessage>>>,
     tx: UnboundedSender<ToolEvent>,
+    hooks: Arc<dyn AgentHooks>,
+    budget: Budget,
+    output_limit: Option<ToolOutputLimit>,
+    cancel: CancellationToken,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
-    loop {
-        let mut stream = client.send_chat_messages_stream(request.clone()).await?;
+    let mut requests = 0;
+    let mut tokens = 0;
+    let mut cancelled = false;
+    while !cancelled {
+        if let Some(max) = budget.max_requests
+            && requests >= max
+        {
+            return Err(Box::new(BudgetExceeded::Requests(max)));
+        }
+        if let Some(max) = budget.max_tokens
+            && tokens >= max
+        {
+            return Err(Box::new(BudgetExceeded::Tokens(max)));
+        }
+        requests += 1;
+        hooks.before_request(&mut request);
+        let mut stream = tokio::select! {
+            stream = client.send_chat_messages_stream(request.clone()) => stream?,
+            _ = cancel.cancelled() => break,
+        };
         tx.send(ToolEvent::RequestStarted).ok();
-        let mut handles: JoinSet<(String, String, Result<String, Box<dyn Error + Send + Sync>>)> =
-            JoinSet::new();
+        let mut handles: JoinSet<(
+            String,
+            String,
+            Result<ToolOutput, Box<dyn Error + Send + Sync>>,
+        )> = JoinSet::new();
+        let mut pending_calls: Vec<(String, String)> = Vec::new();
         let mut parts: Vec<AssistantPart> = Vec::new();
         let mut current_part: Option<AssistantPart> = None;
-        while let Some(chunk) = stream.next().await {
+        loop {
+            let chunk = tokio::select! {
+                chunk = stream.next() => chunk,
+                _ = cancel.cancelled() => {
+                    cancelled = true;
+                    None
+                }
+            };
+            let Some(chunk) = chunk else {
+                break;
+            };
             let chunk = chunk?;
             let mut done = false;
             match chunk.clone() {
@@ -145,17 +253,38 @@ pub async fn run_tool_loop(
                         let name = call.name.clone();
                         let args = call.arguments.clone();
                         let call_id = call.id.clone();
+                        let rejected = hooks.before_tool_call(&call).err();
+                        pending_calls.push((call_id.clone(), name.clone()));
                         current_part = Some(part);
+                        let events = tx.clone();
                         handles.spawn(async move {
+                            if let Some(reason) = rejected {
+                                return (call_id, name, Err(reason.into()));
+                            }
                             match args {
                                 JsonResult::Content { content } => {
-                                    let res = executor.call(&name, content).await;
+                                    let (progress_tx, mut progress_rx) =
+                                        tokio::sync::mpsc::unbounded_channel();
+                                    let progress_id = call_id.clone();
+                                    tokio::spawn(async move {
+                                        while let Some(progress) = progress_rx.recv().await {
+                                            events
+                                                .send(ToolEvent::ToolProgress {
+                                                    call_id: progress_id.clone(),
+                                                    progress,
+                                                })
+                                                .ok();
+                                        }
+                                    });
+                                    let res = executor
+                                        .call_with_progress(&name, content, progress_tx)
+                                        .await;
                                     (call_id, name, res)
                                 }
                                 JsonResult::Error { .. } => (
                                     call_id,
                                     name,
-                                    Err::<String, Box<dyn Error + Send + Sync>>(Box::new(
+                                    Err::<ToolOutput, Box<dyn Error + Send + Sync>>(Box::new(
                                         std::io::Error::new(
                                             std::io::ErrorKind::Other,
                                             "Could not parse arguments as JSON",
@@ -166,7 +295,13 @@ pub async fn run_tool_loop(
                         });
                     }
                 },
-                ResponseChunk::Usage { .. } => {}
+                ResponseChunk::Usage {
+                    input_tokens,
+                    output_tokens,
+                } => {
+                    tokens += input_tokens + output_tokens;
+                }
+                ResponseChunk::Tokens(_) => {}
                 ResponseChunk::Done => {
                     done = true;
                 }
@@ -179,35 +314,49 @@ pub async fn run_tool_loop(
         if let Some(part) = current_part.take() {
             parts.push(part);
         }
-        if !parts.is_empty() {
+        let response = AssistantMessage { content: parts };
+        hooks.after_request(&response);
+        if !response.content.is_empty() {
             chat_history
                 .lock()
                 .unwrap()
-                .push(ChatMessage::Assistant(AssistantMessage { content: parts }));
+                .push(ChatMessage::Assistant(response));
         }
         if handles.is_empty() {
             break;
         }
-        while let Some(res) = handles.join_next().await {
-            if let Ok((call_id, name, result)) = res {
-                match &result {
-                    Ok(text) => {
-                        let content = serde_json::from_str::<Value>(&text)
-                            .unwrap_or_else(|_| Value::String(text.clone()));
-                        chat_history.lock().unwrap().push(ChatMessage::tool(
-                            call_id.clone(),
-                            JsonResult::Content { content },
-                            name.clone(),
-                        ));
+        if cancelled {
+            handles.abort_all();
+        }
+        while !handles.is_empty() {
+            let res = if cancelled {
+                handles.join_next().await
+            } else {
+                tokio::select! {
+                    res = handles.join_next() => res,
+                    _ = cancel.cancelled() => {
+                        cancelled = true;
+                        handles.abort_all();
+                        continue;
                     }
-                    Err(err) => chat_history.lock().unwrap().push(ChatMessage::tool(
-                        call_id.clone(),
-                        JsonResult::Error {
-                            error: format!("Tool Failed: {}", err),
-                        },
-                        name.clone(),
-                    )),
                 }
+            };
+            if let Some(Ok((call_id, name, result))) = res {
+                pending_calls.retain(|(id, _)| *id != call_id);
+                let mut output = match &result {
+                    Ok(output) => output.clone(),
+                    Err(err) => ToolOutput::error(format!("Tool Failed: {}", err)),
+                };
+                hooks.after_tool_call(&name, &mut output);
+                if let Some(limit) = &output_limit {
+                    limit.apply(&mut output);
+                }
+                let result = result.map(|_| output.clone());
+                chat_history.lock().unwrap().push(ChatMessage::tool(
+                    call_id.clone(),
+                    output,
+                    name.clone(),
+                ));
                 tx.send(ToolEvent::ToolResult {
                     call_id,
                     name,
@@ -216,10 +365,26 @@ pub async fn run_tool_loop(
                 .ok();
             }
         }
-        let history_clone = { chat_history.lock().unwrap().clone() };
-        request = ChatMessageRequest::new(request.model_name.clone(), history_clone)
-            .tools(request.tools.clone())
-            .think(true);
+        // Every tool call needs a result, or the next request will be rejected.
+        for (call_id, name) in pending_calls {
+            let reason = if cancelled {
+                "cancelled by user"
+            } else {
+                "Tool Failed: tool call did not complete"
+            };
+            chat_history.lock().unwrap().push(ChatMessage::tool(
+                call_id.clone(),
+                ToolOutput::error(reason),
+                name.clone(),
+            ));
+            tx.send(ToolEvent::ToolResult {
+                call_id,
+                name,
+                result: Err(reason.into()),
+            })
+            .ok();
+        }
+        request.messages = chat_history.lock().unwrap().clone();
     }
     Ok(())
 }
@@ -257,6 +422,7 @@ mod tests {
                             arguments: JsonResult::Content {
                                 content: Value::Null,
                             },
+                            repaired_from: None,
                         },
                         encrypted_content: None,
                     })),
@@ -287,8 +453,8 @@ mod tests {
             &self,
             name: &str,
             _args: Value,
-        ) -> Result<String, Box<dyn Error + Send + Sync>> {
-            Ok(format!("called {name}"))
+        ) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+            Ok(ToolOutput::text(format!("called {name}")))
         }
     }
 
@@ -377,6 +543,7 @@ mod tests {
                             arguments: JsonResult::Error {
                                 error: "nope".into(),
                             },
+                            repaired_from: None,
                         },
                         encrypted_content: None,
                     })),
@@ -409,10 +576,10 @@ mod tests {
             &self,
             _name: &str,
             _args: Value,
-        ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        ) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
             let mut calls = self.calls.lock().unwrap();
             *calls += 1;
-            Ok("should not be called".into())
+            Ok(ToolOutput::text("should not be called"))
         }
     }
 
@@ -435,12 +602,11 @@ mod tests {
         let updated = history.lock().unwrap().clone();
         assert_eq!(updated.len(), 4);
         if let ChatMessage::Tool(t) = &updated[2] {
-            match &t.content {
-                JsonResult::Error { error } => {
-                    assert_eq!(error, "Tool Failed: Could not parse arguments as JSON")
-                }
-                _ => panic!("expected tool failure message"),
-            }
+            assert!(t.output.is_error);
+            assert_eq!(
+                t.output.to_text(),
+                "Tool Failed: Could not parse arguments as JSON"
+            );
         } else {
             panic!("expected tool failure message");
         }
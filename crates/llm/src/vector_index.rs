@@ -0,0 +1,286 @@
+//! A small vector index for semantic search, stored as a JSON file.
+//!
+//! Documents are embedded with [`LlmClient::embed`] and ranked by cosine
+//! similarity to the query. Vectors of different models don't compare, so an
+//! index belongs to one model and is started afresh when the model changes.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{EmbeddingRequest, LlmClient};
+
+/// Number of texts embedded per request.
+const BATCH: usize = 32;
+
+/// An embedded document.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: String,
+    pub text: String,
+    /// Normalised to unit length.
+    pub vector: Vec<f32>,
+}
+
+/// A search result.
+#[derive(Debug, PartialEq)]
+pub struct Hit<'a> {
+    /// Cosine similarity to the query, from -1 to 1.
+    pub score: f32,
+    pub entry: &'a Entry,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(from = "IndexFile")]
+pub struct VectorIndex {
+    model: String,
+    entries: Vec<Entry>,
+    /// The position of each entry in `entries`, by id.
+    #[serde(skip)]
+    positions: HashMap<String, usize>,
+}
+
+/// The saved form of a [`VectorIndex`], without the positions.
+#[derive(Deserialize)]
+struct IndexFile {
+    model: String,
+    entries: Vec<Entry>,
+}
+
+impl From<IndexFile> for VectorIndex {
+    fn from(file: IndexFile) -> Self {
+        let mut index = Self {
+            model: file.model,
+            entries: file.entries,
+            positions: HashMap::new(),
+        };
+        index.index_positions();
+        index
+    }
+}
+
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+impl VectorIndex {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            ..Self::default()
+        }
+    }
+
+    fn index_positions(&mut self) {
+        self.positions = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.id.clone(), i))
+            .collect();
+    }
+
+    /// Loads the index at `path`. Returns an empty index if there is no file
+    /// or it was built with another model.
+    pub fn load(
+        path: impl AsRef<Path>,
+        model: impl Into<String>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let model = model.into();
+        let data = match std::fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::new(model)),
+            Err(e) => return Err(e.into()),
+        };
+        let index: Self = serde_json::from_str(&data)?;
+        Ok(if index.model == model {
+            index
+        } else {
+            Self::new(model)
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Entry> {
+        self.positions.get(id).map(|&i| &self.entries[i])
+    }
+
+    /// Adds a document, replacing any with the same id.
+    pub fn insert(&mut self, id: String, text: String, vector: Vec<f32>) {
+        let entry = Entry {
+            id,
+            text,
+            vector: normalize(vector),
+        };
+        match self.positions.get(&entry.id) {
+            Some(&i) => self.entries[i] = entry,
+            None => {
+                self.positions.insert(entry.id.clone(), self.entries.len());
+                self.entries.push(entry);
+            }
+        }
+    }
+
+    /// Removes the documents for which `keep` returns false.
+    pub fn retain(&mut self, keep: impl FnMut(&Entry) -> bool) {
+        let len = self.entries.len();
+        self.entries.retain(keep);
+        if self.entries.len() != len {
+            self.index_positions();
+        }
+    }
+
+    /// Embeds `documents`, pairs of id and text, with `client` and adds them.
+    pub async fn add<C: LlmClient + ?Sized>(
+        &mut self,
+        client: &C,
+        documents: Vec<(String, String)>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        for batch in documents.chunks(BATCH) {
+            let texts = batch.iter().map(|(_, text)| text.clone()).collect();
+            let vectors = client
+                .embed(EmbeddingRequest::new(self.model.clone(), texts))
+                .await?;
+            if vectors.len() != batch.len() {
+                return Err(
+                    format!("expected {} embeddings, got {}", batch.len(), vectors.len()).into(),
+                );
+            }
+            for ((id, text), vector) in batch.iter().cloned().zip(vectors) {
+                self.insert(id, text, vector);
+            }
+        }
+        Ok(())
+    }
+
+    /// The `limit` documents most similar to `vector`, best first.
+    pub fn search(&self, vector: &[f32], limit: usize) -> Vec<Hit<'_>> {
+        let query = normalize(vector.to_vec());
+        let mut hits: Vec<Hit> = self
+            .entries
+            .iter()
+            .filter(|e| e.vector.len() == query.len())
+            .map(|entry| Hit {
+                score: entry.vector.iter().zip(&query).map(|(a, b)| a * b).sum(),
+                entry,
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(limit);
+        hits
+    }
+
+    /// Embeds `text` with `client` and returns the `limit` most similar
+    /// documents.
+    pub async fn query<C: LlmClient + ?Sized>(
+        &self,
+        client: &C,
+        text: &str,
+        limit: usize,
+    ) -> Result<Vec<Hit<'_>>, Box<dyn Error + Send + Sync>> {
+        let vectors = client
+            .embed(EmbeddingRequest::new(self.model.clone(), vec![text.into()]))
+            .await?;
+        let vector = vectors.first().ok_or("no embedding returned")?;
+        Ok(self.search(vector, limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ollama::OllamaClient, test_server::serve};
+    use axum::{Json, Router, routing::post};
+    use serde_json::{Value, json};
+
+    /// Embeds texts as their counts of the letters a, b and c.
+    async fn embed(Json(body): Json<Value>) -> Json<Value> {
+        assert_eq!(body["model"], "letters");
+        let embeddings: Vec<Vec<f32>> = body["input"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|text| {
+                let text = text.as_str().unwrap();
+                "abc"
+                    .chars()
+                    .map(|c| text.matches(c).count() as f32)
+                    .collect()
+            })
+            .collect();
+        Json(json!({ "model": "letters", "embeddings": embeddings }))
+    }
+
+    #[tokio::test]
+    async fn indexes_saves_and_searches() {
+        let host = serve(Router::new().route("/api/embed", post(embed))).await;
+        let client = OllamaClient::new(Some(&host)).unwrap();
+
+        let mut index = VectorIndex::new("letters");
+        let documents = [("a", "aaa"), ("b", "bb"), ("ab", "ab"), ("c", "cc")]
+            .into_iter()
+            .map(|(id, text)| (id.to_string(), text.to_string()))
+            .collect();
+        index.add(&client, documents).await.unwrap();
+        index
+            .add(&client, vec![("c".into(), "ccc".into())])
+            .await
+            .unwrap();
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.get("c").unwrap().text, "ccc");
+        index.retain(|e| e.id != "b");
+        assert_eq!(index.get("b"), None);
+        assert_eq!(index.get("c").unwrap().text, "ccc");
+        index
+            .add(&client, vec![("b".into(), "bb".into())])
+            .await
+            .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index/letters.json");
+        index.save(&path).unwrap();
+        let index = VectorIndex::load(&path, "letters").unwrap();
+        assert_eq!(index.get("ab").unwrap().text, "ab");
+        let hits = index.query(&client, "aab", 3).await.unwrap();
+        let ids: Vec<&str> = hits.iter().map(|h| h.entry.id.as_str()).collect();
+        assert_eq!(ids, ["ab", "a", "b"]);
+        assert!((hits[0].score - 0.9487).abs() < 1e-3);
+
+        assert!(VectorIndex::load(&path, "other").unwrap().is_empty());
+        assert!(
+            VectorIndex::load(dir.path().join("missing.json"), "letters")
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
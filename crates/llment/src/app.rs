@@ -1,41 +1,70 @@
 use std::{
+    collections::HashSet,
     path::PathBuf,
     sync::{Arc, Mutex},
 };
 
 use crate::{
     Args, Component,
-    builtins::setup_builtin_tools,
+    builtins::builtin_tools,
     commands::{
-        AgentModeCommand, ClearCommand, ContinueCommand, LoadCommand, ModelCommand, PopCommand,
-        PromptCommand, ProviderCommand, QuitCommand, RedoCommand, ResponseCommand, RoleCommand,
-        SaveCommand, ThoughtCommand,
+        AgentModeCommand, ClearCommand, ContinueCommand, LoadCommand, LogCommand,
+        McpPromptCommands, ModelCommand, PopCommand, PromptCommand, ProviderCommand, QuitCommand,
+        RedoCommand, ResponseCommand, RoleCommand, SaveCommand, SearchCommand, ThoughtCommand,
+        TokensCommand,
+    },
+    components::{
+        ApprovalPopup, ElicitationPopup, ErrorPopup, LogPane, Prompt, TokenPane,
+        input::PromptModel, log_pane::LogFilter,
     },
-    components::{ErrorPopup, Prompt, input::PromptModel},
     conversation::{Conversation, ToolStep},
+    elicitation::AskElicitation,
     history_edits::{HistoryEdit, HistoryEditResult},
+    mcp_config::untrusted_project_config,
     modes::AgentMode,
     prompts,
+    pruning::PrunePolicy,
+    resources::{
+        ResourceMentions, ResourceRef, parse_mentions, read_attachments, resource_servers,
+    },
+    sampling::{SamplingApproval, sampling_policy},
+    search::Search,
 };
-use crossterm::event::Event;
+use crossterm::event::{Event, KeyCode, KeyModifiers};
 use llm::{
-    AssistantPart, ChatMessage, ChatMessageRequest, JsonResult, Provider, ResponseChunk,
-    mcp::{McpContext, McpService},
-    tools::{ToolEvent, ToolExecutor, tool_event_stream},
+    AssistantPart, ChatMessage, GrammarConstraint, JsonResult, Provider, ResponseChunk,
+    agent::Agent,
+    browser::{Browser, browser_tools},
+    function_tool::FunctionToolSet,
+    mcp::{
+        ElicitationRequest, ElicitationResponse, McpContext, McpLogMessage, Sampling,
+        SamplingPolicy, SamplingRequest,
+    },
+    python::{Python, python_tools},
+    tool_output_limit::ToolOutputLimit,
+    tools::{ToolEvent, ToolExecutor},
 };
 use ratatui::{prelude::*, widgets::Paragraph};
-use rmcp::service::{RoleClient, RunningService};
 use tokio::{
     sync::{
+        broadcast,
         mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel},
-        watch,
+        oneshot, watch,
     },
-    task::JoinSet,
+    task::{AbortHandle, JoinSet},
 };
 use tokio_stream::{StreamExt, wrappers::WatchStream};
+use tokio_util::sync::CancellationToken;
 use tui_realm_stdlib::states::SpinnerStates;
 use unicode_width::UnicodeWidthStr;
 
+/// Where the browser tools search and fetch pages.
+struct BrowserConfig {
+    search_url: Option<String>,
+    search_tool: Option<String>,
+    fetch_tool: Option<String>,
+}
+
 enum ConversationState {
     Idle,
     Thinking,
@@ -49,6 +78,17 @@ pub struct App {
     prompt: Prompt,
 
     prompt_dir: Option<PathBuf>,
+    repair_tool_calls: bool,
+    grammar: GrammarConstraint,
+    resource_tools: bool,
+    browser: Option<BrowserConfig>,
+    /// Enables the python tool, run through the named mcp-shell server if
+    /// set.
+    python: Option<Option<String>>,
+    mcp_config: Vec<PathBuf>,
+    trust_project_mcp: bool,
+    tool_output_limit: Option<ToolOutputLimit>,
+    prune_policy: Arc<PrunePolicy>,
 
     client: Arc<Mutex<llm::Client>>,
     mcp_context: McpContext,
@@ -63,13 +103,24 @@ pub struct App {
 
     tasks: JoinSet<()>,
     request_tasks: JoinSet<()>,
+    request_loop: Option<AbortHandle>,
+    request_cancel: CancellationToken,
     update_tx: UnboundedSender<Update>,
     update_rx: UnboundedReceiver<Update>,
     ignore_responses: bool,
     error: ErrorPopup,
+    approval: ApprovalPopup,
+    elicitation: ElicitationPopup,
+    log: LogPane,
+    tokens: TokenPane,
+    sampling_policy: Arc<dyn SamplingPolicy>,
+    sampling_max_tokens: u32,
     selected_prompt: Option<String>,
     selected_role: Option<String>,
     mode: Option<Box<dyn AgentMode>>,
+    subscribed_resources: HashSet<ResourceRef>,
+    /// Subscribed resources that changed since they were last attached.
+    updated_resources: Arc<Mutex<Vec<ResourceRef>>>,
 }
 
 pub struct AppModel {
@@ -80,34 +131,55 @@ pub struct AppModel {
 
 pub(crate) enum Update {
     Prompt(String),
+    /// A prompt along with the contents of the resources it mentions.
+    PromptWithAttachments(String, String, Vec<ResourceRef>),
     Response(ToolEvent),
     ResponseComplete,
     Error(String),
+    Info(String),
     SetModel(String),
     SetProvider(Provider, Option<String>),
     SetPrompt(String),
     SetRole(Option<String>),
     Continue,
     EditHistory(HistoryEdit),
-    SetMode(
-        Option<Box<dyn AgentMode>>,
-        Option<RunningService<RoleClient, McpService>>,
-    ),
+    SetMode(Option<Box<dyn AgentMode>>, Option<FunctionToolSet>),
+    /// An MCP server asks to sample the model and waits for the user's reply.
+    SamplingApproval(SamplingRequest, oneshot::Sender<SamplingApproval>),
+    /// An MCP server asks for input and waits for the user's answers.
+    Elicitation(ElicitationRequest, oneshot::Sender<ElicitationResponse>),
+    McpLog(McpLogMessage),
+    /// Shows the MCP log with the given filter, or toggles it.
+    ShowLog(Option<LogFilter>),
+    /// Toggles the token inspector.
+    ToggleTokens,
 }
 
 impl App {
-    pub fn new(model: AppModel, args: Args) -> Self {
+    pub fn new(model: AppModel, args: Args, prune_policy: PrunePolicy) -> Self {
         let (update_tx, update_rx) = unbounded_channel();
         let mcp_context = McpContext::default();
         let prompt_dir = args.prompt_dir.clone();
         let client =
             llm::client_from(args.provider, args.model.clone(), args.host.as_deref()).unwrap();
         let client = Arc::new(Mutex::new(client));
+        let search = args.embed_model.clone().map(|model| {
+            let host = args.embed_host.as_deref().or(args.host.as_deref());
+            let provider = args.embed_provider.unwrap_or(args.provider);
+            let client = llm::client_from(provider, model, host).unwrap();
+            Arc::new(Search::new(
+                client,
+                args.search_index.clone(),
+                PathBuf::from("."),
+            ))
+        });
         let tasks = JoinSet::new();
         let request_tasks = JoinSet::new();
         let mut spinner = SpinnerStates::default();
         spinner.reset("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏");
         let needs_redraw = model.needs_redraw.clone();
+        let sampling_policy =
+            sampling_policy(args.sampling, update_tx.clone(), model.needs_update.clone());
         App {
             conversation: Conversation::default(),
             prompt: Prompt::new(
@@ -166,6 +238,19 @@ impl App {
                         needs_update: model.needs_update.clone(),
                         update_tx: update_tx.clone(),
                     }),
+                    Box::new(SearchCommand {
+                        search,
+                        needs_update: model.needs_update.clone(),
+                        update_tx: update_tx.clone(),
+                    }),
+                    Box::new(LogCommand {
+                        needs_update: model.needs_update.clone(),
+                        update_tx: update_tx.clone(),
+                    }),
+                    Box::new(TokensCommand {
+                        needs_update: model.needs_update.clone(),
+                        update_tx: update_tx.clone(),
+                    }),
                     Box::new(ClearCommand {
                         needs_update: model.needs_update.clone(),
                         update_tx: update_tx.clone(),
@@ -177,6 +262,24 @@ impl App {
             ),
             model,
             prompt_dir,
+            repair_tool_calls: args.repair_tool_calls,
+            grammar: args.grammar,
+            resource_tools: args.resource_tools,
+            browser: args.browser.then(|| BrowserConfig {
+                search_url: args.browser_search_url.clone(),
+                search_tool: args.browser_search_tool.clone(),
+                fetch_tool: args.browser_fetch_tool.clone(),
+            }),
+            python: (args.python || args.python_shell.is_some()).then(|| args.python_shell.clone()),
+            mcp_config: args.mcp.clone(),
+            trust_project_mcp: args.trust_project_mcp,
+            tool_output_limit: (args.max_tool_output > 0).then(|| {
+                let dir = args.tool_output_dir.clone().unwrap_or_else(|| {
+                    std::env::temp_dir().join(format!("llment-{}", std::process::id()))
+                });
+                ToolOutputLimit::new(args.max_tool_output, dir)
+            }),
+            prune_policy: Arc::new(prune_policy),
             client,
             session_in_tokens: 0,
             session_out_tokens: 0,
@@ -189,22 +292,183 @@ impl App {
             spinner: spinner,
             tasks,
             request_tasks,
+            request_loop: None,
+            request_cancel: CancellationToken::new(),
             update_tx,
             update_rx,
             ignore_responses: false,
-            error: ErrorPopup::new(needs_redraw),
+            error: ErrorPopup::new(needs_redraw.clone()),
+            approval: ApprovalPopup::new(needs_redraw.clone()),
+            elicitation: ElicitationPopup::new(needs_redraw.clone()),
+            log: LogPane::new(needs_redraw.clone()),
+            tokens: TokenPane::new(needs_redraw),
+            sampling_policy,
+            sampling_max_tokens: args.sampling_max_tokens,
             selected_prompt: Some("default".to_string()),
             selected_role: None,
             mode: None,
+            subscribed_resources: HashSet::new(),
+            updated_resources: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
-    pub async fn init(&mut self, mcp_context: McpContext) {
+    /// Sets up the tools and commands around `mcp_context`. `mcp_errors` are
+    /// the errors of the configuration files and servers that failed to
+    /// load, which are shown to the user.
+    pub async fn init(&mut self, mcp_context: McpContext, mcp_errors: Vec<String>) {
         self.mcp_context = mcp_context;
-        let builtin_service = setup_builtin_tools(self.chat_history.clone()).await;
+        if let Some(path) = untrusted_project_config(self.trust_project_mcp) {
+            let _ = self.update_tx.send(Update::Info(format!(
+                "{} was not loaded, start with --trust-project-mcp to run its servers",
+                path.display()
+            )));
+        }
+        if !mcp_errors.is_empty() {
+            let _ = self.update_tx.send(Update::Error(mcp_errors.join("\n")));
+        }
+        self.mcp_context.insert_functions(builtin_tools(
+            self.chat_history.clone(),
+            self.tool_output_limit.clone(),
+        ));
+        if self.resource_tools {
+            self.mcp_context
+                .insert_functions(self.mcp_context.resource_tools());
+        }
+        if let Some(config) = &self.browser {
+            let tools: Arc<dyn ToolExecutor> = Arc::new(self.mcp_context.clone());
+            let mut browser = Browser::new();
+            if let Some(url) = &config.search_url {
+                browser = browser.search_url(url.clone());
+            }
+            if let Some(tool) = &config.search_tool {
+                browser = browser.search_tool(tools.clone(), tool.clone());
+            }
+            if let Some(tool) = &config.fetch_tool {
+                browser = browser.fetch_tool(tools, tool.clone());
+            }
+            self.mcp_context
+                .insert_functions(browser_tools(Arc::new(browser)));
+        }
+        if let Some(shell) = &self.python {
+            let mut python = Python::new();
+            if let Some(server) = shell {
+                python = python.shell_tool(Arc::new(self.mcp_context.clone()), server.clone());
+            }
+            self.mcp_context
+                .insert_functions(python_tools(Arc::new(python)));
+        }
+        self.prompt.set_mentions(Box::new(ResourceMentions {
+            ctx: self.mcp_context.clone(),
+        }));
+        self.prompt.add_command_source(Box::new(McpPromptCommands {
+            ctx: self.mcp_context.clone(),
+            needs_update: self.model.needs_update.clone(),
+            update_tx: self.update_tx.clone(),
+        }));
+        self.prompt.add_command_source(Box::new(McpCommands {
+            ctx: self.mcp_context.clone(),
+            config: self.mcp_config.clone(),
+            trust_project: self.trust_project_mcp,
+            needs_update: self.model.needs_update.clone(),
+            update_tx: self.update_tx.clone(),
+        }));
+        self.install_sampling();
         self.mcp_context
-            .insert(builtin_service)
-            .expect("builtin MCP prefix must not contain '_'");
+            .set_elicitation(Some(Box::new(AskElicitation {
+                update_tx: self.update_tx.clone(),
+                needs_update: self.model.needs_update.clone(),
+            })));
+        let mut logs = self.mcp_context.subscribe_logs();
+        let update_tx = self.update_tx.clone();
+        let needs_update = self.model.needs_update.clone();
+        self.tasks.spawn(async move {
+            loop {
+                match logs.recv().await {
+                    Ok(message) => {
+                        let _ = update_tx.send(Update::McpLog(message));
+                        let _ = needs_update.send(true);
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// Serves MCP sampling requests with the current client and model.
+    fn install_sampling(&self) {
+        let client = { self.client.lock().unwrap().clone() };
+        self.mcp_context.set_sampling(Some(Sampling {
+            model: client.model().to_string(),
+            client: Arc::new(client),
+            policy: self.sampling_policy.clone(),
+            max_tokens: self.sampling_max_tokens,
+        }));
+    }
+
+    /// Sends `prompt` after attaching the resources it mentions and any
+    /// subscribed resources that changed since they were attached.
+    fn submit_prompt(&mut self, prompt: String) {
+        let mentioned = parse_mentions(&prompt, &resource_servers(&self.mcp_context));
+        let updated: Vec<ResourceRef> =
+            std::mem::take(&mut *self.updated_resources.lock().unwrap())
+                .into_iter()
+                .filter(|r| !mentioned.contains(r))
+                .collect();
+        if mentioned.is_empty() && updated.is_empty() {
+            self.send_request(Some(prompt));
+            return;
+        }
+        let refs: Vec<(ResourceRef, bool)> = mentioned
+            .into_iter()
+            .map(|r| (r, false))
+            .chain(updated.into_iter().map(|r| (r, true)))
+            .collect();
+        let ctx = self.mcp_context.clone();
+        let update_tx = self.update_tx.clone();
+        let needs_update = self.model.needs_update.clone();
+        self.tasks.spawn(async move {
+            let update = match read_attachments(&ctx, &refs).await {
+                Ok(attachments) => Update::PromptWithAttachments(
+                    prompt,
+                    attachments,
+                    refs.into_iter().map(|(r, _)| r).collect(),
+                ),
+                Err(err) => Update::Error(err.to_string()),
+            };
+            let _ = update_tx.send(update);
+            let _ = needs_update.send(true);
+        });
+    }
+
+    /// Subscribes to changes of attached resources so they can be attached
+    /// again once they change.
+    fn subscribe_resources(&mut self, refs: Vec<ResourceRef>) {
+        for r in refs {
+            if !self.subscribed_resources.insert(r.clone()) {
+                continue;
+            }
+            let ctx = self.mcp_context.clone();
+            let updated = self.updated_resources.clone();
+            self.tasks.spawn(async move {
+                // Servers that do not support subscriptions reject the request.
+                let Ok(mut updates) = ctx.subscribe_resource(&r.server, &r.uri).await else {
+                    return;
+                };
+                loop {
+                    match updates.recv().await {
+                        Ok(uri) if uri == r.uri => {
+                            let mut updated = updated.lock().unwrap();
+                            if !updated.contains(&r) {
+                                updated.push(r.clone());
+                            }
+                        }
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+        }
     }
 
     fn handle_tool_event(&mut self, ev: ToolEvent) {
@@ -239,6 +503,7 @@ impl App {
                     self.request_out_tokens += output_tokens;
                     let _ = self.model.needs_redraw.send(true);
                 }
+                ResponseChunk::Tokens(tokens) => self.tokens.push(tokens),
                 ResponseChunk::Done => {}
             },
             ToolEvent::ToolStarted {
@@ -263,59 +528,64 @@ impl App {
             ToolEvent::ToolResult {
                 call_id, result, ..
             } => {
-                let (text, failed) = match result {
-                    Ok(t) => (t, false),
-                    Err(e) => (format!("Tool Failed: {}", e), true),
+                let (text, failed, truncated) = match result {
+                    Ok(output) => (output.to_text(), output.is_error, output.truncated),
+                    Err(e) => (format!("Tool Failed: {}", e), true, None),
                 };
-                self.conversation.update_tool_result(&call_id, text, failed);
+                self.conversation
+                    .update_tool_result(&call_id, text, failed, truncated);
+            }
+            ToolEvent::ToolProgress { call_id, progress } => {
+                self.conversation.update_tool_progress(&call_id, progress);
             }
         }
     }
 
-    fn apply_prompt(&mut self) {
-        if let Some(name) = &self.selected_prompt {
-            let tool_names = self.mcp_context.tool_names();
-            let role = self.selected_role.as_deref();
-            if let Some(content) =
-                prompts::load_prompt(name, role, tool_names, self.prompt_dir.as_deref())
-            {
-                let mut history = self.chat_history.lock().unwrap();
-                while matches!(history.first(), Some(ChatMessage::System(_))) {
-                    history.remove(0);
-                }
-                history.insert(0, ChatMessage::system(content));
-            }
-        }
+    fn system_prompt(&self) -> Option<String> {
+        let name = self.selected_prompt.as_ref()?;
+        let tool_names = self.mcp_context.tool_names();
+        let role = self.selected_role.as_deref();
+        prompts::load_prompt(name, role, tool_names, self.prompt_dir.as_deref())
     }
 
     fn send_request(&mut self, prompt: Option<String>) {
-        self.apply_prompt();
+        self.send_request_with_attachments(prompt, String::new());
+    }
+
+    /// Like `send_request`, but the model also receives `attachments` after
+    /// the prompt. Only the prompt is shown in the conversation.
+    fn send_request_with_attachments(&mut self, prompt: Option<String>, attachments: String) {
         self.state = ConversationState::Thinking;
         let _ = self.model.needs_redraw.send(true);
-        if let Some(prompt) = prompt {
-            self.conversation.push_user(prompt.clone());
-            {
-                let mut history = self.chat_history.lock().unwrap();
-                history.push(ChatMessage::user(prompt));
-            }
+        let client = { self.client.lock().unwrap().clone() };
+        self.request_cancel = CancellationToken::new();
+        let mut agent = Agent::new(Arc::new(client.clone()), client.model())
+            .tools(Arc::new(self.mcp_context.clone()))
+            .history(self.chat_history.clone())
+            .think(true)
+            .repair_tool_calls(self.repair_tool_calls)
+            .grammar(self.grammar)
+            .inspect_tokens(self.tokens.visible())
+            .hooks(self.prune_policy.clone())
+            .cancel_token(self.request_cancel.clone());
+        if let Some(system_prompt) = self.system_prompt() {
+            agent = agent.system_prompt(system_prompt);
+        }
+        if let Some(limit) = self.tool_output_limit.clone() {
+            agent = agent.max_tool_output(limit);
         }
+        let (mut stream, handle) = if let Some(prompt) = prompt {
+            self.conversation.push_user(prompt.clone());
+            agent.run(prompt + &attachments)
+        } else {
+            agent.resume()
+        };
+        self.request_loop = Some(handle.abort_handle());
 
         self.ignore_responses = false;
         let update_tx = self.update_tx.clone();
         let needs_update = self.model.needs_update.clone();
-        let history = self.chat_history.clone();
-        let mcp_context = Arc::new(self.mcp_context.clone());
-        let client = { Arc::new(self.client.lock().unwrap().clone()) };
         self.request_tasks.spawn(async move {
-            let tool_infos = mcp_context.tool_infos();
-            let model_name = { client.model().to_string() };
-            let request_history = { history.lock().unwrap().clone() };
-            let request = ChatMessageRequest::new(model_name, request_history)
-                .tools(tool_infos)
-                .think(true);
-            let tool_executor = mcp_context.clone() as Arc<dyn ToolExecutor>;
-            let (mut stream, handle) =
-                tool_event_stream(client, request, tool_executor, history.clone());
             while let Some(event) = stream.next().await {
                 let _ = update_tx.send(Update::Response(event));
                 let _ = needs_update.send(true);
@@ -335,7 +605,17 @@ impl App {
         });
     }
 
+    /// Stops the running request, keeping its partial output in the history.
+    fn cancel_requests(&mut self) {
+        self.request_cancel.cancel();
+    }
+
+    /// Stops the running request immediately, e.g. before the history is replaced.
     fn abort_requests(&mut self) {
+        self.request_cancel.cancel();
+        if let Some(request_loop) = self.request_loop.take() {
+            request_loop.abort();
+        }
         self.request_tasks.abort_all();
         self.request_tasks = JoinSet::new();
         self.ignore_responses = true;
@@ -347,6 +627,9 @@ impl App {
         self.chat_history.lock().unwrap().clear();
         self.conversation.clear();
         self.state = ConversationState::Idle;
+        if let Some(limit) = &self.tool_output_limit {
+            limit.clear();
+        }
     }
 }
 
@@ -368,7 +651,27 @@ impl Component for App {
     }
     fn handle_event(&mut self, event: Event) {
         self.error.handle_event(event.clone());
+        if self.elicitation.is_active() && matches!(event, Event::Key(_) | Event::Paste(_)) {
+            if let Event::Paste(text) = event {
+                for c in text.chars() {
+                    self.elicitation
+                        .handle_event(Event::Key(KeyCode::Char(c).into()));
+                }
+            } else {
+                self.elicitation.handle_event(event);
+            }
+            return;
+        }
+        if self.approval.is_active() && matches!(event, Event::Key(_)) {
+            self.approval.handle_event(event);
+            return;
+        }
         match event {
+            Event::Key(key)
+                if key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL =>
+            {
+                self.cancel_requests();
+            }
             Event::Key(key) => {
                 self.prompt.handle_event(Event::Key(key));
             }
@@ -393,9 +696,13 @@ impl Component for App {
             match self.update_rx.try_recv() {
                 Ok(Update::Prompt(prompt)) => {
                     if !prompt.is_empty() {
-                        self.send_request(Some(prompt));
+                        self.submit_prompt(prompt);
                     }
                 }
+                Ok(Update::PromptWithAttachments(prompt, attachments, refs)) => {
+                    self.subscribe_resources(refs);
+                    self.send_request_with_attachments(Some(prompt), attachments);
+                }
                 Ok(Update::Continue) => {
                     self.send_request(None);
                 }
@@ -409,7 +716,9 @@ impl Component for App {
                 Ok(Update::ResponseComplete) => {
                     self.state = ConversationState::Idle;
                     let last_message = { self.chat_history.lock().unwrap().last().cloned() };
-                    let step = if let Some(mode) = self.mode.as_mut() {
+                    let step = if self.request_cancel.is_cancelled() {
+                        None
+                    } else if let Some(mode) = self.mode.as_mut() {
                         Some(mode.step(last_message.as_ref()))
                     } else {
                         None
@@ -433,22 +742,27 @@ impl Component for App {
                     self.state = ConversationState::Idle;
                     let _ = self.model.needs_redraw.send(true);
                 }
+                Ok(Update::Info(message)) => {
+                    self.error.set_info(message);
+                }
                 Ok(Update::SetModel(model_name)) => {
-                    self.abort_requests();
+                    self.cancel_requests();
                     {
                         let mut client = self.client.lock().unwrap();
                         client.set_model(model_name);
                     }
+                    self.install_sampling();
                     let _ = self.model.needs_redraw.send(true);
                 }
                 Ok(Update::SetProvider(provider, host)) => {
-                    self.abort_requests();
+                    self.cancel_requests();
                     let model = { self.client.lock().unwrap().model().to_string() };
                     if let Ok(new_client) = llm::client_from(provider, model, host.as_deref()) {
                         {
                             let mut guard = self.client.lock().unwrap();
                             *guard = new_client;
                         }
+                        self.install_sampling();
                         let _ = self.model.needs_redraw.send(true);
                     }
                 }
@@ -481,6 +795,11 @@ impl Component for App {
                     }
                     drop(history_guard);
                     if reset_session {
+                        // The ids of the stored outputs only mean something to
+                        // the conversation that they were truncated in.
+                        if let Some(limit) = &self.tool_output_limit {
+                            limit.clear();
+                        }
                         self.session_in_tokens = 0;
                         self.session_out_tokens = 0;
                         self.session_requests = 0;
@@ -491,17 +810,12 @@ impl Component for App {
                     self.conversation.set_history(&history);
                     let _ = self.model.needs_redraw.send(true);
                 }
-                Ok(Update::SetMode(mode, service)) => {
+                Ok(Update::SetMode(mode, tools)) => {
                     self.mcp_context.remove("agent");
                     self.abort_requests();
                     self.mode = mode;
-                    if let Some(service) = service {
-                        if let Err(err) = self.mcp_context.insert(service) {
-                            self.mode = None;
-                            self.error.set(err.to_string());
-                            let _ = self.model.needs_redraw.send(true);
-                            continue;
-                        }
+                    if let Some(tools) = tools {
+                        self.mcp_context.insert_functions(tools);
                     }
                     let start = if let Some(mode) = self.mode.as_mut() {
                         Some(mode.start())
@@ -519,6 +833,21 @@ impl Component for App {
                     }
                     let _ = self.model.needs_redraw.send(true);
                 }
+                Ok(Update::SamplingApproval(request, reply)) => {
+                    self.approval.push(request, reply);
+                }
+                Ok(Update::Elicitation(request, reply)) => {
+                    self.elicitation.push(request, reply);
+                }
+                Ok(Update::McpLog(message)) => {
+                    self.log.push(message);
+                }
+                Ok(Update::ShowLog(filter)) => {
+                    self.log.show(filter);
+                }
+                Ok(Update::ToggleTokens) => {
+                    self.tokens.toggle();
+                }
                 Err(_) => break,
             }
         }
@@ -528,12 +857,20 @@ impl Component for App {
         let prompt_height = self.prompt.height();
         let inner_width = area.width.saturating_sub(2);
         let error_height = self.error.height(inner_width);
+        let approval_height = self.approval.height(inner_width);
+        let elicitation_height = self.elicitation.height(inner_width);
+        let log_height = self.log.height();
+        let tokens_height = self.tokens.height();
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints(
                 [
                     Constraint::Min(1),
+                    Constraint::Length(tokens_height),
+                    Constraint::Length(log_height),
                     Constraint::Length(error_height),
+                    Constraint::Length(approval_height),
+                    Constraint::Length(elicitation_height),
                     Constraint::Length(prompt_height),
                     Constraint::Length(1),
                 ]
@@ -542,8 +879,12 @@ impl Component for App {
             .split(area);
 
         self.conversation.render(frame, chunks[0]);
-        self.error.render(frame, chunks[1]);
-        self.prompt.render(frame, chunks[2]);
+        self.tokens.render(frame, chunks[1]);
+        self.log.render(frame, chunks[2]);
+        self.error.render(frame, chunks[3]);
+        self.approval.render(frame, chunks[4]);
+        self.elicitation.render(frame, chunks[5]);
+        self.prompt.render(frame, chunks[6]);
         let ctx_tokens = self.request_in_tokens + self.request_out_tokens;
         let status_right = format!(
             "ctx {}t, Σ {}r {}t=>{}t",
@@ -553,7 +894,7 @@ impl Component for App {
         let status_chunks = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Min(0), Constraint::Length(right_width)].as_ref())
-            .split(chunks[3]);
+            .split(chunks[7]);
         let state_text = match &self.state {
             ConversationState::Idle => String::new(),
             ConversationState::Thinking => format!("thinking… {}", self.spinner.step()),
@@ -1,18 +1,16 @@
-use std::sync::{Arc, Mutex};
-
-use arc_swap::ArcSwap;
-use llm::{ChatMessage, JsonResult, ToolInfo, mcp::McpService};
-use rmcp::{
-    ServerHandler,
-    handler::server::{router::tool::ToolRouter, wrapper::Parameters},
-    model::{ServerCapabilities, ServerInfo},
-    service::{RoleClient, RunningService, ServiceExt},
-    tool, tool_handler, tool_router,
+use std::{
+    error::Error,
+    sync::{Arc, Mutex},
 };
-use schemars::{JsonSchema, schema_for};
+
+use async_trait::async_trait;
+use llm::{
+    ChatMessage, ToolOutput,
+    function_tool::{FunctionTool, FunctionToolSet},
+    tool_output_limit::ToolOutputLimit,
+};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use tokio::io::duplex;
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct GetMessageCountParams {}
@@ -23,89 +21,172 @@ pub struct DiscardFunctionResponseParams {
     pub id: String,
 }
 
-#[derive(Clone)]
-struct BuiltinTools {
+struct GetMessageCount {
     chat_history: Arc<Mutex<Vec<ChatMessage>>>,
-    tool_router: ToolRouter<Self>,
 }
 
-#[tool_router]
-impl BuiltinTools {
-    fn new(chat_history: Arc<Mutex<Vec<ChatMessage>>>) -> Self {
-        Self {
-            chat_history,
-            tool_router: Self::tool_router(),
-        }
-    }
+#[async_trait]
+impl FunctionTool for GetMessageCount {
+    const NAME: &'static str = "get_message_count";
+    const DESCRIPTION: &'static str = "Returns the number of chat messages";
+    type Params = GetMessageCountParams;
 
-    #[tool(
-        name = "get_message_count",
-        description = "Returns the number of chat messages"
-    )]
-    fn get_message_count(&self) -> String {
-        self.chat_history.lock().unwrap().len().to_string()
+    async fn call(
+        &self,
+        _params: GetMessageCountParams,
+    ) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+        let count = self.chat_history.lock().unwrap().len();
+        Ok(ToolOutput::text(count.to_string()))
     }
+}
+
+struct DiscardFunctionResponse {
+    chat_history: Arc<Mutex<Vec<ChatMessage>>>,
+}
 
-    #[tool(
-        name = "discard_function_response",
-        description = "Removes the content from a tool response in history by id"
-    )]
-    fn discard_function_response(
+#[async_trait]
+impl FunctionTool for DiscardFunctionResponse {
+    const NAME: &'static str = "discard_function_response";
+    const DESCRIPTION: &'static str = "Removes the content from a tool response in history by id";
+    type Params = DiscardFunctionResponseParams;
+
+    async fn call(
         &self,
-        Parameters(params): Parameters<DiscardFunctionResponseParams>,
-    ) -> String {
+        params: DiscardFunctionResponseParams,
+    ) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
         let mut history = self.chat_history.lock().unwrap();
         if let Some((idx, _)) = history.iter().enumerate().rev().find(|(_, m)| match m {
             ChatMessage::Tool(t) => t.id == params.id,
             _ => false,
         }) {
             if let ChatMessage::Tool(t) = &mut history[idx] {
-                t.content = JsonResult::Content {
-                    content: Value::String("<response discarded>".into()),
-                };
+                t.output = ToolOutput::text("<response discarded>");
             }
-            "ok".into()
+            Ok(ToolOutput::text("ok"))
         } else {
-            format!("Tool response with id '{}' not found", params.id)
+            Ok(ToolOutput::text(format!(
+                "Tool response with id '{}' not found",
+                params.id
+            )))
         }
     }
 }
 
-#[tool_handler(router = self.tool_router)]
-impl ServerHandler for BuiltinTools {
-    fn get_info(&self) -> ServerInfo {
-        ServerInfo::new(ServerCapabilities::builder().enable_tools().build())
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ReadToolOutputParams {
+    /// The id of the truncated tool response
+    pub id: String,
+    /// The character offset to start reading from
+    #[serde(default)]
+    pub offset: usize,
+}
+
+struct ReadToolOutput {
+    limit: ToolOutputLimit,
+}
+
+#[async_trait]
+impl FunctionTool for ReadToolOutput {
+    const NAME: &'static str = "read_tool_output";
+    const DESCRIPTION: &'static str =
+        "Reads the full content of a truncated tool response by id, one page at a time";
+    type Params = ReadToolOutputParams;
+
+    async fn call(
+        &self,
+        params: ReadToolOutputParams,
+    ) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
+        let page = match self
+            .limit
+            .read(&params.id, params.offset, self.limit.max_chars())
+        {
+            Ok(page) => page,
+            Err(err) => {
+                return Ok(ToolOutput::error(format!(
+                    "Tool response with id '{}' could not be read: {err}",
+                    params.id
+                )));
+            }
+        };
+        if page.next_offset == page.total_chars {
+            return Ok(ToolOutput::text(page.text));
+        }
+        let footer = |next_offset: usize| {
+            format!(
+                "\n[characters {}-{next_offset} of {}; continue with offset {next_offset}]",
+                params.offset, page.total_chars
+            )
+        };
+        // The page and its footer have to fit in the limit, which is applied
+        // to this output too, or the page would be truncated and stored again.
+        let page_chars = self
+            .limit
+            .max_chars()
+            .saturating_sub(footer(page.total_chars).chars().count())
+            .max(1);
+        let text: String = page.text.chars().take(page_chars).collect();
+        let next_offset = params.offset + text.chars().count();
+        Ok(ToolOutput::text(text + &footer(next_offset)))
     }
 }
 
-pub async fn setup_builtin_tools(
+pub fn builtin_tools(
     chat_history: Arc<Mutex<Vec<ChatMessage>>>,
-) -> RunningService<RoleClient, McpService> {
-    let builtins = BuiltinTools::new(chat_history);
-    let (server_transport, client_transport) = duplex(64);
-    let (server_res, client_res) = tokio::join!(
-        builtins.clone().serve(server_transport),
-        McpService {
-            prefix: "chat".into(),
-            tools: ArcSwap::new(Arc::new(vec![
-                ToolInfo {
-                    name: "get_message_count".into(),
-                    description: "Returns the number of chat messages".into(),
-                    parameters: schema_for!(GetMessageCountParams),
-                },
-                ToolInfo {
-                    name: "discard_function_response".into(),
-                    description: "Removes the content from a tool response in history by id".into(),
-                    parameters: schema_for!(DiscardFunctionResponseParams),
-                },
-            ])),
+    output_limit: Option<ToolOutputLimit>,
+) -> FunctionToolSet {
+    let tools = FunctionToolSet::new("chat")
+        .with(GetMessageCount {
+            chat_history: chat_history.clone(),
+        })
+        .with(DiscardFunctionResponse { chat_history });
+    match output_limit {
+        Some(limit) => tools.with(ReadToolOutput { limit }),
+        None => tools,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reads_a_truncated_output_back_in_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let limit = ToolOutputLimit::new(60, dir.path());
+        let full: String = (0..40).map(|i| format!("line {i} é\n")).collect();
+        let mut output = ToolOutput::text(full.clone());
+        limit.apply(&mut output);
+        let id = output.truncated.unwrap().id;
+
+        let tool = ReadToolOutput {
+            limit: limit.clone(),
+        };
+        let mut read = String::new();
+        let mut offset = 0;
+        loop {
+            let mut page = tool
+                .call(ReadToolOutputParams {
+                    id: id.clone(),
+                    offset,
+                })
+                .await
+                .unwrap();
+            let untouched = page.clone();
+            limit.apply(&mut page);
+            assert_eq!(page, untouched);
+            let text = page.to_text();
+            match text.rsplit_once("\n[characters ") {
+                Some((text, footer)) => {
+                    read.push_str(text);
+                    let next = footer.rsplit_once("offset ").unwrap().1;
+                    offset = next.trim_end_matches(']').parse().unwrap();
+                }
+                None => {
+                    read.push_str(&text);
+                    break;
+                }
+            }
         }
-        .serve(client_transport)
-    );
-    let server = server_res.expect("builtin server");
-    let client_service = client_res.expect("builtin client");
-    tokio::spawn(async move {
-        let _ = server.waiting().await;
-    });
-    client_service
+        assert_eq!(read, full);
+    }
 }
@@ -71,15 +71,9 @@ impl CommandInstance for AgentModeCommandInstance {
             });
             Ok(())
         } else {
-            let mode_name = self.param.clone();
-            let tx = self.update_tx.clone();
-            let needs_update = self.needs_update.clone();
-            tokio::spawn(async move {
-                if let Some((mode, service)) = modes::create_agent_mode(&mode_name).await {
-                    let _ = tx.send(Update::SetMode(Some(mode), service));
-                    let _ = needs_update.send(true);
-                }
-            });
+            let (mode, tools) = modes::create_agent_mode(&self.param).ok_or("unknown mode")?;
+            let _ = self.update_tx.send(Update::SetMode(Some(mode), tools));
+            let _ = self.needs_update.send(true);
             Ok(())
         }
     }
@@ -0,0 +1,112 @@
+use llm::mcp::LoggingLevel;
+use serde_json::Value;
+use tokio::sync::{mpsc::UnboundedSender, watch};
+
+use crate::{
+    app::Update,
+    components::{
+        completion::{Command, CommandInstance, Completion, CompletionResult},
+        log_pane::LogFilter,
+    },
+};
+
+const LEVELS: [&str; 8] = [
+    "debug",
+    "info",
+    "notice",
+    "warning",
+    "error",
+    "critical",
+    "alert",
+    "emergency",
+];
+
+pub struct LogCommand {
+    pub(crate) needs_update: watch::Sender<bool>,
+    pub(crate) update_tx: UnboundedSender<Update>,
+}
+
+impl Command for LogCommand {
+    fn name(&self) -> &'static str {
+        "log"
+    }
+    fn description(&self) -> &'static str {
+        "Toggle the MCP log, or show it as [level] [server]"
+    }
+    fn has_params(&self) -> bool {
+        true
+    }
+    fn instance(&self) -> Box<dyn CommandInstance> {
+        Box::new(LogCommandInstance {
+            needs_update: self.needs_update.clone(),
+            update_tx: self.update_tx.clone(),
+            param: String::new(),
+        })
+    }
+}
+
+struct LogCommandInstance {
+    needs_update: watch::Sender<bool>,
+    update_tx: UnboundedSender<Update>,
+    param: String,
+}
+
+/// Parses `[level] [server]`, where the level defaults to `info`.
+fn parse_filter(input: &str) -> Result<Option<LogFilter>, String> {
+    let mut words = input.split_whitespace();
+    let Some(level) = words.next() else {
+        return Ok(None);
+    };
+    let min_level: LoggingLevel = serde_json::from_value(Value::String(level.to_string()))
+        .map_err(|_| format!("unknown log level {level}"))?;
+    Ok(Some(LogFilter {
+        min_level,
+        server: words.next().map(str::to_string),
+    }))
+}
+
+impl CommandInstance for LogCommandInstance {
+    fn update(&mut self, input: &str) -> CompletionResult {
+        self.param = input.to_string();
+        if input.contains(char::is_whitespace) {
+            return CompletionResult::Options {
+                at: 0,
+                options: Vec::new(),
+            };
+        }
+        let options = LEVELS
+            .iter()
+            .filter(|level| level.starts_with(input))
+            .map(|level| Completion {
+                name: level.to_string(),
+                description: String::new(),
+                str: format!("{level} "),
+            })
+            .collect();
+        CompletionResult::Options { at: 0, options }
+    }
+    fn commit(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let filter = parse_filter(&self.param)?;
+        let _ = self.update_tx.send(Update::ShowLog(filter));
+        let _ = self.needs_update.send(true);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_level_and_server() {
+        assert_eq!(parse_filter("  "), Ok(None));
+        assert_eq!(
+            parse_filter("warning files"),
+            Ok(Some(LogFilter {
+                min_level: LoggingLevel::Warning,
+                server: Some("files".into()),
+            }))
+        );
+        assert!(parse_filter("loud").is_err());
+    }
+}
@@ -0,0 +1,237 @@
+use std::path::PathBuf;
+
+use llm::mcp::{McpContext, McpServer};
+use tokio::sync::{mpsc::UnboundedSender, watch};
+
+use crate::{
+    app::Update,
+    components::completion::{
+        Command, CommandInstance, CommandSource, Completion, CompletionResult,
+    },
+    mcp_config::mcp_config_paths,
+};
+
+const ACTIONS: [(&str, &str); 7] = [
+    ("list", "Show the servers and their status"),
+    ("start", "Start a stopped server"),
+    ("stop", "Stop a server"),
+    ("restart", "Restart a server"),
+    ("add", "Add a server: <name> <command|url> [args...]"),
+    ("remove", "Stop a server and forget it"),
+    ("reload", "Apply changes to the mcp.json files"),
+];
+
+/// Provides the `/mcp` command once the MCP servers are loaded. `config`
+/// holds the files given with `--mcp`, and `trust_project` whether
+/// `./.mcp.json` is loaded too.
+pub struct McpCommands {
+    pub(crate) ctx: McpContext,
+    pub(crate) config: Vec<PathBuf>,
+    pub(crate) trust_project: bool,
+    pub(crate) needs_update: watch::Sender<bool>,
+    pub(crate) update_tx: UnboundedSender<Update>,
+}
+
+impl CommandSource for McpCommands {
+    fn commands(&self) -> Vec<Box<dyn Command>> {
+        vec![Box::new(McpCommand {
+            ctx: self.ctx.clone(),
+            config: self.config.clone(),
+            trust_project: self.trust_project,
+            needs_update: self.needs_update.clone(),
+            update_tx: self.update_tx.clone(),
+        })]
+    }
+}
+
+struct McpCommand {
+    ctx: McpContext,
+    config: Vec<PathBuf>,
+    trust_project: bool,
+    needs_update: watch::Sender<bool>,
+    update_tx: UnboundedSender<Update>,
+}
+
+impl Command for McpCommand {
+    fn name(&self) -> &str {
+        "mcp"
+    }
+    fn description(&self) -> &str {
+        "Manage MCP servers"
+    }
+    fn has_params(&self) -> bool {
+        true
+    }
+    fn instance(&self) -> Box<dyn CommandInstance> {
+        Box::new(McpCommandInstance {
+            ctx: self.ctx.clone(),
+            config: self.config.clone(),
+            trust_project: self.trust_project,
+            needs_update: self.needs_update.clone(),
+            update_tx: self.update_tx.clone(),
+            param: String::new(),
+        })
+    }
+}
+
+struct McpCommandInstance {
+    ctx: McpContext,
+    config: Vec<PathBuf>,
+    trust_project: bool,
+    needs_update: watch::Sender<bool>,
+    update_tx: UnboundedSender<Update>,
+    param: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum McpAction {
+    List,
+    Start(String),
+    Stop(String),
+    Restart(String),
+    Add(String, McpServer),
+    Remove(String),
+    Reload,
+}
+
+fn parse_action(input: &str) -> Result<McpAction, String> {
+    let mut words = input.split_whitespace();
+    let action = words.next().unwrap_or("list");
+    let mut name = || {
+        words
+            .next()
+            .map(str::to_string)
+            .ok_or_else(|| format!("/mcp {action} needs a server name"))
+    };
+    Ok(match action {
+        "list" => McpAction::List,
+        "start" => McpAction::Start(name()?),
+        "stop" => McpAction::Stop(name()?),
+        "restart" => McpAction::Restart(name()?),
+        "remove" => McpAction::Remove(name()?),
+        "reload" => McpAction::Reload,
+        "add" => {
+            let name = name()?;
+            let target = words
+                .next()
+                .ok_or("/mcp add needs a command or url")?
+                .to_string();
+            let server = if target.starts_with("http://") || target.starts_with("https://") {
+                McpServer::url(target)
+            } else {
+                McpServer::command(target, words.map(str::to_string).collect())
+            };
+            McpAction::Add(name, server)
+        }
+        _ => return Err(format!("unknown /mcp action {action}")),
+    })
+}
+
+fn format_servers(ctx: &McpContext) -> String {
+    let servers = ctx.servers();
+    if servers.is_empty() {
+        return "No MCP servers".into();
+    }
+    servers
+        .iter()
+        .map(|s| format!("{}: {}, {} tools", s.name, s.status, s.tools))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl CommandInstance for McpCommandInstance {
+    fn update(&mut self, input: &str) -> CompletionResult {
+        self.param = input.to_string();
+        let words: Vec<&str> = input.split_whitespace().collect();
+        let typing = !input.ends_with(char::is_whitespace);
+        let (index, typed) = match (words.len(), typing) {
+            (0, _) => (0, ""),
+            (n, true) => (n - 1, words[n - 1]),
+            (n, false) => (n, ""),
+        };
+        let at = input.chars().count() - typed.chars().count();
+        let options = match index {
+            0 => ACTIONS
+                .iter()
+                .filter(|(action, _)| action.starts_with(typed))
+                .map(|(action, description)| Completion {
+                    name: action.to_string(),
+                    description: description.to_string(),
+                    str: format!("{action} "),
+                })
+                .collect(),
+            1 if matches!(words[0], "start" | "stop" | "restart" | "remove") => self
+                .ctx
+                .servers()
+                .into_iter()
+                .filter(|s| s.name.starts_with(typed))
+                .map(|s| Completion {
+                    description: s.status.to_string(),
+                    str: s.name.clone(),
+                    name: s.name,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        CompletionResult::Options { at, options }
+    }
+
+    fn commit(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let action = parse_action(&self.param)?;
+        let paths = mcp_config_paths(&self.config, self.trust_project);
+        if action == McpAction::Reload && paths.is_empty() {
+            return Err("no mcp.json file was found or given with --mcp".into());
+        }
+        let ctx = self.ctx.clone();
+        let needs_update = self.needs_update.clone();
+        let update_tx = self.update_tx.clone();
+        tokio::spawn(async move {
+            let result = match action {
+                McpAction::List => Ok(()),
+                McpAction::Start(name) => ctx.start_server(&name).await,
+                McpAction::Stop(name) => ctx.stop_server(&name),
+                McpAction::Restart(name) => ctx.restart_server(&name).await,
+                McpAction::Add(name, server) => ctx.add_server(&name, server).await,
+                McpAction::Remove(name) => ctx.remove_server(&name),
+                McpAction::Reload => ctx.reload_servers(&paths).await,
+            };
+            let update = match result {
+                Ok(()) => Update::Info(format_servers(&ctx)),
+                Err(err) => Update::Error(err.to_string()),
+            };
+            let _ = update_tx.send(update);
+            let _ = needs_update.send(true);
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_actions() {
+        assert_eq!(parse_action(""), Ok(McpAction::List));
+        assert_eq!(
+            parse_action("restart files"),
+            Ok(McpAction::Restart("files".into()))
+        );
+        assert!(parse_action("stop").is_err());
+        assert_eq!(
+            parse_action("add shell mcp-shell --workdir /tmp"),
+            Ok(McpAction::Add(
+                "shell".into(),
+                McpServer::command("mcp-shell", vec!["--workdir".into(), "/tmp".into()])
+            ))
+        );
+        assert_eq!(
+            parse_action("add search https://mcp.example.com/mcp"),
+            Ok(McpAction::Add(
+                "search".into(),
+                McpServer::url("https://mcp.example.com/mcp")
+            ))
+        );
+        assert!(parse_action("frobnicate").is_err());
+    }
+}
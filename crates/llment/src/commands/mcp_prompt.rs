@@ -0,0 +1,210 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use llm::{
+    ChatMessage,
+    mcp::{McpContext, McpPrompt},
+};
+use tokio::sync::{mpsc::UnboundedSender, oneshot, watch};
+
+use crate::{
+    app::Update,
+    components::completion::{
+        Command, CommandInstance, CommandSource, Completion, CompletionResult,
+    },
+    history_edits,
+};
+
+/// Exposes the prompts of connected MCP servers as `/<server>:<prompt>` commands.
+pub struct McpPromptCommands {
+    pub(crate) ctx: McpContext,
+    pub(crate) needs_update: watch::Sender<bool>,
+    pub(crate) update_tx: UnboundedSender<Update>,
+}
+
+impl CommandSource for McpPromptCommands {
+    fn commands(&self) -> Vec<Box<dyn Command>> {
+        self.ctx
+            .prompts()
+            .into_iter()
+            .map(|prompt| {
+                Box::new(McpPromptCommand {
+                    name: format!("{}:{}", prompt.server, prompt.name),
+                    description: prompt.description.clone().unwrap_or_default(),
+                    prompt,
+                    ctx: self.ctx.clone(),
+                    needs_update: self.needs_update.clone(),
+                    update_tx: self.update_tx.clone(),
+                }) as Box<dyn Command>
+            })
+            .collect()
+    }
+}
+
+struct McpPromptCommand {
+    name: String,
+    description: String,
+    prompt: McpPrompt,
+    ctx: McpContext,
+    needs_update: watch::Sender<bool>,
+    update_tx: UnboundedSender<Update>,
+}
+
+impl Command for McpPromptCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn description(&self) -> &str {
+        &self.description
+    }
+    fn has_params(&self) -> bool {
+        !self.prompt.arguments.is_empty()
+    }
+    fn instance(&self) -> Box<dyn CommandInstance> {
+        Box::new(McpPromptCommandInstance {
+            prompt: self.prompt.clone(),
+            ctx: self.ctx.clone(),
+            needs_update: self.needs_update.clone(),
+            update_tx: self.update_tx.clone(),
+            args: HashMap::new(),
+            values: Arc::default(),
+        })
+    }
+}
+
+/// Server completions for the value of an argument, keyed by the argument
+/// name and the value typed so far.
+type ValueCompletions = Arc<Mutex<Option<((String, String), Vec<String>)>>>;
+
+struct McpPromptCommandInstance {
+    prompt: McpPrompt,
+    ctx: McpContext,
+    needs_update: watch::Sender<bool>,
+    update_tx: UnboundedSender<Update>,
+    args: HashMap<String, String>,
+    values: ValueCompletions,
+}
+
+/// Splits `name=value` arguments. Values can not contain whitespace.
+fn parse_args(input: &str) -> HashMap<String, String> {
+    input
+        .split_whitespace()
+        .filter_map(|token| token.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+impl McpPromptCommandInstance {
+    fn complete_names(&self, typed: &str, at: usize) -> CompletionResult {
+        let options = self
+            .prompt
+            .arguments
+            .iter()
+            .filter(|arg| arg.name.starts_with(typed) && !self.args.contains_key(&arg.name))
+            .map(|arg| Completion {
+                name: arg.name.clone(),
+                description: format!(
+                    "{}{}",
+                    arg.description.as_deref().unwrap_or_default(),
+                    if arg.required { " (required)" } else { "" }
+                ),
+                str: format!("{}=", arg.name),
+            })
+            .collect();
+        CompletionResult::Options { at, options }
+    }
+
+    fn complete_value(&self, name: &str, value: &str, at: usize) -> CompletionResult {
+        let key = (name.to_string(), value.to_string());
+        if let Some((cached, values)) = &*self.values.lock().unwrap()
+            && *cached == key
+        {
+            let options = values
+                .iter()
+                .map(|v| Completion {
+                    name: v.clone(),
+                    description: String::new(),
+                    str: format!("{v} "),
+                })
+                .collect();
+            return CompletionResult::Options { at, options };
+        }
+        let ctx = self.ctx.clone();
+        let prompt = self.prompt.clone();
+        let context = self.args.clone();
+        let values = self.values.clone();
+        let (tx, rx) = oneshot::channel();
+        tokio::spawn(async move {
+            let completions = ctx
+                .complete_prompt_argument(&prompt.server, &prompt.name, &key.0, &key.1, &context)
+                .await
+                .unwrap_or_default();
+            *values.lock().unwrap() = Some((key, completions));
+            let _ = tx.send(());
+        });
+        CompletionResult::Loading { at, done: rx }
+    }
+}
+
+impl CommandInstance for McpPromptCommandInstance {
+    fn update(&mut self, input: &str) -> CompletionResult {
+        self.args = parse_args(input);
+        let start = input.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+        let token = &input[start..];
+        let at = input[..start].chars().count();
+        match token.split_once('=') {
+            None => self.complete_names(token, at),
+            Some((name, value)) => self.complete_value(name, value, at + name.chars().count() + 1),
+        }
+    }
+
+    fn commit(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(arg) = self
+            .prompt
+            .arguments
+            .iter()
+            .find(|arg| arg.required && !self.args.contains_key(&arg.name))
+        {
+            return Err(format!("missing argument {}", arg.name).into());
+        }
+        let ctx = self.ctx.clone();
+        let prompt = self.prompt.clone();
+        let args = self.args.clone();
+        let needs_update = self.needs_update.clone();
+        let update_tx = self.update_tx.clone();
+        tokio::spawn(async move {
+            match ctx.get_prompt(&prompt.server, &prompt.name, &args).await {
+                Ok(messages) => {
+                    let respond = matches!(messages.last(), Some(ChatMessage::User(_)));
+                    let _ = update_tx.send(Update::EditHistory(history_edits::append(messages)));
+                    if respond {
+                        let _ = update_tx.send(Update::Continue);
+                    }
+                }
+                Err(err) => {
+                    let _ = update_tx.send(Update::Error(format!(
+                        "Failed to get prompt {}:{}: {err}",
+                        prompt.server, prompt.name
+                    )));
+                }
+            }
+            let _ = needs_update.send(true);
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_name_value_arguments() {
+        let args = parse_args("path=src/main.rs  focus=perf stray");
+        assert_eq!(args.len(), 2);
+        assert_eq!(args["path"], "src/main.rs");
+        assert_eq!(args["focus"], "perf");
+    }
+}
@@ -2,6 +2,9 @@ pub mod agent_mode;
 pub mod clear;
 pub mod r#continue;
 pub mod load;
+pub mod log;
+pub mod mcp;
+pub mod mcp_prompt;
 pub mod model;
 pub mod pop;
 pub mod prompt;
@@ -11,12 +14,17 @@ pub mod redo;
 pub mod response;
 pub mod role;
 pub mod save;
+pub mod search;
 pub mod thought;
+pub mod tokens;
 
 pub use agent_mode::AgentModeCommand;
 pub use clear::ClearCommand;
 pub use r#continue::ContinueCommand;
 pub use load::LoadCommand;
+pub use log::LogCommand;
+pub use mcp::McpCommands;
+pub use mcp_prompt::McpPromptCommands;
 pub use model::ModelCommand;
 pub use pop::PopCommand;
 pub use prompt::PromptCommand;
@@ -26,4 +34,6 @@ pub use redo::RedoCommand;
 pub use response::ResponseCommand;
 pub use role::RoleCommand;
 pub use save::SaveCommand;
+pub use search::SearchCommand;
 pub use thought::ThoughtCommand;
+pub use tokens::TokensCommand;
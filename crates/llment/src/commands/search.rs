@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use tokio::sync::{mpsc::UnboundedSender, watch};
+
+use crate::{
+    app::Update,
+    components::completion::{Command, CommandInstance, CompletionResult},
+    search::Search,
+};
+
+/// Number of results shown.
+const LIMIT: usize = 8;
+/// Longest snippet shown for a result, in characters.
+const SNIPPET_CHARS: usize = 100;
+
+pub struct SearchCommand {
+    pub(crate) search: Option<Arc<Search>>,
+    pub(crate) needs_update: watch::Sender<bool>,
+    pub(crate) update_tx: UnboundedSender<Update>,
+}
+
+impl Command for SearchCommand {
+    fn name(&self) -> &'static str {
+        "search"
+    }
+    fn description(&self) -> &'static str {
+        "Semantic search over the workspace files and saved sessions"
+    }
+    fn has_params(&self) -> bool {
+        true
+    }
+    fn instance(&self) -> Box<dyn CommandInstance> {
+        Box::new(SearchCommandInstance {
+            search: self.search.clone(),
+            needs_update: self.needs_update.clone(),
+            update_tx: self.update_tx.clone(),
+            param: String::new(),
+        })
+    }
+}
+
+struct SearchCommandInstance {
+    search: Option<Arc<Search>>,
+    needs_update: watch::Sender<bool>,
+    update_tx: UnboundedSender<Update>,
+    param: String,
+}
+
+impl CommandInstance for SearchCommandInstance {
+    fn update(&mut self, input: &str) -> CompletionResult {
+        self.param = input.trim().to_string();
+        CompletionResult::Options {
+            at: 0,
+            options: vec![],
+        }
+    }
+
+    fn commit(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(search) = self.search.clone() else {
+            return Err("search needs an embedding model, see --embed-model".into());
+        };
+        if self.param.is_empty() {
+            return Err("no query".into());
+        }
+        let query = self.param.clone();
+        let update_tx = self.update_tx.clone();
+        let needs_update = self.needs_update.clone();
+        let _ = update_tx.send(Update::Info(format!("Searching for \"{query}\"...")));
+        tokio::spawn(async move {
+            let update = match search.search(&query, LIMIT).await {
+                Ok(hits) if hits.is_empty() => Update::Info(format!("No results for \"{query}\"")),
+                Ok(hits) => {
+                    let mut text = format!("Results for \"{query}\":");
+                    for hit in hits {
+                        let snippet = hit
+                            .text
+                            .lines()
+                            .map(str::trim)
+                            .find(|l| !l.is_empty())
+                            .unwrap_or_default();
+                        let mut snippet: String = snippet.chars().take(SNIPPET_CHARS).collect();
+                        if snippet.len() < hit.text.trim().len() {
+                            snippet.push('…');
+                        }
+                        text.push_str(&format!("\n{} ({:.2}) {}", hit.id, hit.score, snippet));
+                    }
+                    Update::Info(text)
+                }
+                Err(e) => Update::Error(format!("search failed: {e}")),
+            };
+            let _ = update_tx.send(update);
+            let _ = needs_update.send(true);
+        });
+        let _ = self.needs_update.send(true);
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use tokio::sync::{mpsc::UnboundedSender, watch};
+
+use crate::{
+    app::Update,
+    components::completion::{Command, CommandInstance, CompletionResult},
+};
+
+pub struct TokensCommand {
+    pub(crate) needs_update: watch::Sender<bool>,
+    pub(crate) update_tx: UnboundedSender<Update>,
+}
+
+impl Command for TokensCommand {
+    fn name(&self) -> &'static str {
+        "tokens"
+    }
+    fn description(&self) -> &'static str {
+        "Toggle the raw tokens and probabilities of the next requests"
+    }
+    fn instance(&self) -> Box<dyn CommandInstance> {
+        Box::new(TokensCommandInstance {
+            needs_update: self.needs_update.clone(),
+            update_tx: self.update_tx.clone(),
+        })
+    }
+}
+
+struct TokensCommandInstance {
+    needs_update: watch::Sender<bool>,
+    update_tx: UnboundedSender<Update>,
+}
+
+impl CommandInstance for TokensCommandInstance {
+    fn update(&mut self, _input: &str) -> CompletionResult {
+        CompletionResult::Options {
+            at: 0,
+            options: vec![],
+        }
+    }
+    fn commit(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let _ = self.update_tx.send(Update::ToggleTokens);
+        let _ = self.needs_update.send(true);
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::VecDeque;
+
+use crate::{component::Component, sampling::SamplingApproval};
+use crossterm::event::{Event, KeyCode};
+use llm::{ChatMessage, mcp::SamplingRequest};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+use textwrap::wrap;
+use tokio::sync::{oneshot, watch};
+
+/// Maximum number of characters of the last user message shown in the popup.
+const PREVIEW_CHARS: usize = 200;
+
+/// Asks the user to approve MCP sampling requests one at a time with
+/// `y` (once), `a` (always for the server) or `n`.
+pub struct ApprovalPopup {
+    pending: VecDeque<(SamplingRequest, oneshot::Sender<SamplingApproval>)>,
+    needs_redraw: watch::Sender<bool>,
+}
+
+impl ApprovalPopup {
+    pub fn new(needs_redraw: watch::Sender<bool>) -> Self {
+        Self {
+            pending: VecDeque::new(),
+            needs_redraw,
+        }
+    }
+
+    pub fn push(&mut self, request: SamplingRequest, reply: oneshot::Sender<SamplingApproval>) {
+        self.pending.push_back((request, reply));
+        let _ = self.needs_redraw.send(true);
+    }
+
+    /// Whether the popup is shown and takes key events.
+    pub fn is_active(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    fn message(&self) -> Option<String> {
+        let (request, _) = self.pending.front()?;
+        let preview = request
+            .messages
+            .iter()
+            .rev()
+            .find_map(|message| match message {
+                ChatMessage::User(u) => Some(u.content.clone()),
+                _ => None,
+            })
+            .unwrap_or_default();
+        let mut preview = preview.replace('\n', " ");
+        if let Some((end, _)) = preview.char_indices().nth(PREVIEW_CHARS) {
+            preview.truncate(end);
+            preview.push('…');
+        }
+        Some(format!(
+            "{} wants to sample the model (up to {} tokens): {preview}\n[y]es [a]lways [n]o",
+            request.server, request.max_tokens
+        ))
+    }
+
+    pub fn height(&self, width: u16) -> u16 {
+        if let Some(msg) = self.message() {
+            let inner = width.saturating_sub(2).max(1) as usize;
+            wrap(&msg, inner).len() as u16 + 2
+        } else {
+            0
+        }
+    }
+}
+
+impl Component for ApprovalPopup {
+    fn handle_event(&mut self, event: Event) {
+        let Event::Key(key) = event else {
+            return;
+        };
+        let approval = match key.code {
+            KeyCode::Char('y') => SamplingApproval::Once,
+            KeyCode::Char('a') => SamplingApproval::Always,
+            KeyCode::Char('n') => SamplingApproval::Deny,
+            _ => return,
+        };
+        if let Some((_, reply)) = self.pending.pop_front() {
+            let _ = reply.send(approval);
+            let _ = self.needs_redraw.send(true);
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        if let Some(msg) = self.message() {
+            let inner = area.width.saturating_sub(2).max(1) as usize;
+            let lines = wrap(&msg, inner)
+                .into_iter()
+                .map(|l| ratatui::text::Line::from(l.into_owned()))
+                .collect::<Vec<_>>();
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow));
+            frame.render_widget(Clear, area);
+            frame.render_widget(
+                Paragraph::new(lines)
+                    .block(block)
+                    .style(Style::default().fg(Color::Yellow)),
+                area,
+            );
+        }
+    }
+}
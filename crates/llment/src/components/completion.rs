@@ -141,8 +141,8 @@ pub enum CompletionResult {
 }
 
 pub trait Command {
-    fn name(&self) -> &'static str;
-    fn description(&self) -> &'static str;
+    fn name(&self) -> &str;
+    fn description(&self) -> &str;
     fn has_params(&self) -> bool {
         false
     }
@@ -156,9 +156,25 @@ pub trait CommandInstance {
     fn commit(&mut self) -> Result<(), Box<dyn std::error::Error>>;
 }
 
+/// Provides commands that can change while the app runs, e.g. prompts
+/// published by MCP servers.
+pub trait CommandSource {
+    fn commands(&self) -> Vec<Box<dyn Command>>;
+}
+
+/// Completes `@` mentions in prompts that are not commands.
+pub trait MentionCompleter {
+    /// Returns completions for the text typed after the `@`.
+    fn complete(&self, typed: &str) -> Vec<Completion>;
+}
+
 pub struct CommandRouter {
     commands: Vec<Box<dyn Command>>,
     active: Option<(String, Box<dyn CommandInstance>)>,
+    mentions: Option<Box<dyn MentionCompleter>>,
+    sources: Vec<Box<dyn CommandSource>>,
+    /// Commands from `sources`, refreshed on every update.
+    dynamic: Vec<Box<dyn Command>>,
 }
 
 impl CommandRouter {
@@ -166,13 +182,48 @@ impl CommandRouter {
         Self {
             commands,
             active: None,
+            mentions: None,
+            sources: Vec::new(),
+            dynamic: Vec::new(),
+        }
+    }
+
+    pub fn add_source(&mut self, source: Box<dyn CommandSource>) {
+        self.sources.push(source);
+    }
+
+    fn all_commands(&self) -> impl Iterator<Item = &dyn Command> {
+        self.commands
+            .iter()
+            .chain(self.dynamic.iter())
+            .map(|c| &**c)
+    }
+
+    pub fn set_mentions(&mut self, mentions: Box<dyn MentionCompleter>) {
+        self.mentions = Some(mentions);
+    }
+
+    /// Completes the mention being typed at the end of `input`, if any.
+    fn complete_mention(&self, input: &str) -> CompletionResult {
+        let start = input
+            .rfind(char::is_whitespace)
+            .map(|i| i + input[i..].chars().next().map_or(1, char::len_utf8))
+            .unwrap_or(0);
+        let (Some(mentions), Some(typed)) = (&self.mentions, input[start..].strip_prefix('@'))
+        else {
+            return CompletionResult::Invalid { at: 0 };
+        };
+        // completions apply after the '@'
+        let at = input[..start].chars().count() + 1;
+        CompletionResult::Options {
+            at,
+            options: mentions.complete(typed),
         }
     }
 
     fn complete_names(&self, typed: &str) -> CompletionResult {
         let mut options: Vec<Completion> = self
-            .commands
-            .iter()
+            .all_commands()
             .filter(|c| c.name().starts_with(typed))
             .map(|c| Completion {
                 name: c.name().to_string(),
@@ -186,10 +237,7 @@ impl CommandRouter {
     }
 
     fn find_command(&self, token: &str) -> Option<&dyn Command> {
-        self.commands
-            .iter()
-            .map(|c| &**c)
-            .find(|c| c.name() == token)
+        self.all_commands().find(|c| c.name() == token)
     }
 
     fn offset(res: CompletionResult, by: usize) -> CompletionResult {
@@ -210,9 +258,10 @@ impl CommandInstance for CommandRouter {
     fn update(&mut self, input: &str) -> CompletionResult {
         if !input.starts_with('/') {
             self.active = None;
-            return CompletionResult::Invalid { at: 0 };
+            return self.complete_mention(input);
         }
 
+        self.dynamic = self.sources.iter().flat_map(|s| s.commands()).collect();
         let rest = &input[1..];
         let (token, after_opt) = match rest.find(' ') {
             None => (rest, None),
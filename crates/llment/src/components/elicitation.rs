@@ -0,0 +1,328 @@
+use std::collections::VecDeque;
+
+use crate::component::Component;
+use crossterm::event::{Event, KeyCode, KeyModifiers};
+use llm::mcp::{ElicitationRequest, ElicitationResponse};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+use serde_json::{Map, Value};
+use textwrap::wrap;
+use tokio::sync::{oneshot, watch};
+
+#[derive(Clone, Debug, PartialEq)]
+enum FieldKind {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Enum(Vec<String>),
+}
+
+#[derive(Clone, Debug)]
+struct Field {
+    name: String,
+    label: String,
+    kind: FieldKind,
+    required: bool,
+    value: String,
+}
+
+impl Field {
+    /// Cycles through the choices of enum and boolean fields.
+    fn cycle(&mut self, step: isize) {
+        let choices = match &self.kind {
+            FieldKind::Enum(choices) => choices.clone(),
+            FieldKind::Boolean => vec!["true".to_string(), "false".to_string()],
+            _ => return,
+        };
+        let current = choices.iter().position(|c| *c == self.value);
+        let next = match current {
+            Some(i) => (i as isize + step).rem_euclid(choices.len() as isize) as usize,
+            None => 0,
+        };
+        self.value = choices[next].clone();
+    }
+
+    fn to_value(&self) -> Result<Option<Value>, String> {
+        if self.value.is_empty() {
+            return if self.required {
+                Err(format!("{} is required", self.label))
+            } else {
+                Ok(None)
+            };
+        }
+        let invalid = |kind: &str| format!("{} must be {kind}", self.label);
+        let value = match self.kind {
+            FieldKind::String | FieldKind::Enum(_) => Value::String(self.value.clone()),
+            FieldKind::Number => self
+                .value
+                .parse::<f64>()
+                .ok()
+                .and_then(|n| serde_json::Number::from_f64(n).map(Value::Number))
+                .ok_or_else(|| invalid("a number"))?,
+            FieldKind::Integer => Value::from(
+                self.value
+                    .parse::<i64>()
+                    .map_err(|_| invalid("an integer"))?,
+            ),
+            FieldKind::Boolean => Value::Bool(self.value == "true"),
+        };
+        Ok(Some(value))
+    }
+}
+
+/// Reads the fields of a flat elicitation schema.
+fn fields(schema: &Value) -> Vec<Field> {
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    let Some(properties) = schema["properties"].as_object() else {
+        return Vec::new();
+    };
+    properties
+        .iter()
+        .map(|(name, property)| {
+            let kind = if let Some(choices) = property["enum"].as_array() {
+                FieldKind::Enum(
+                    choices
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(str::to_string)
+                        .collect(),
+                )
+            } else {
+                match property["type"].as_str() {
+                    Some("number") => FieldKind::Number,
+                    Some("integer") => FieldKind::Integer,
+                    Some("boolean") => FieldKind::Boolean,
+                    _ => FieldKind::String,
+                }
+            };
+            let value = match &property["default"] {
+                Value::Null => String::new(),
+                Value::String(s) => s.clone(),
+                default => default.to_string(),
+            };
+            Field {
+                name: name.clone(),
+                label: property["title"].as_str().unwrap_or(name).to_string(),
+                kind,
+                required: required.contains(&name.as_str()),
+                value,
+            }
+        })
+        .collect()
+}
+
+/// Collects the answers as an object, or the first problem with them.
+fn answers(fields: &[Field]) -> Result<Value, String> {
+    let mut content = Map::new();
+    for field in fields {
+        if let Some(value) = field.to_value()? {
+            content.insert(field.name.clone(), value);
+        }
+    }
+    Ok(Value::Object(content))
+}
+
+struct Form {
+    request: ElicitationRequest,
+    fields: Vec<Field>,
+    focused: usize,
+    error: Option<String>,
+    reply: oneshot::Sender<ElicitationResponse>,
+}
+
+/// Asks for the input requested by MCP servers, one form at a time.
+/// `Tab` moves between fields, `←`/`→` choose between options, `Enter`
+/// submits, `Ctrl-D` declines and `Ctrl-C` cancels.
+pub struct ElicitationPopup {
+    forms: VecDeque<Form>,
+    needs_redraw: watch::Sender<bool>,
+}
+
+impl ElicitationPopup {
+    pub fn new(needs_redraw: watch::Sender<bool>) -> Self {
+        Self {
+            forms: VecDeque::new(),
+            needs_redraw,
+        }
+    }
+
+    pub fn push(
+        &mut self,
+        request: ElicitationRequest,
+        reply: oneshot::Sender<ElicitationResponse>,
+    ) {
+        self.forms.push_back(Form {
+            fields: fields(&request.schema),
+            request,
+            focused: 0,
+            error: None,
+            reply,
+        });
+        let _ = self.needs_redraw.send(true);
+    }
+
+    /// Whether the popup is shown and takes key events.
+    pub fn is_active(&self) -> bool {
+        !self.forms.is_empty()
+    }
+
+    fn respond(&mut self, response: ElicitationResponse) {
+        if let Some(form) = self.forms.pop_front() {
+            let _ = form.reply.send(response);
+        }
+    }
+
+    fn lines(&self, width: u16) -> Vec<Line<'static>> {
+        let Some(form) = self.forms.front() else {
+            return Vec::new();
+        };
+        let inner = width.saturating_sub(2).max(1) as usize;
+        let mut lines: Vec<Line> = wrap(
+            &format!("{} asks: {}", form.request.server, form.request.message),
+            inner,
+        )
+        .into_iter()
+        .map(|l| Line::from(l.into_owned()))
+        .collect();
+        for (i, field) in form.fields.iter().enumerate() {
+            let marker = if i == form.focused { "›" } else { " " };
+            let required = if field.required { "*" } else { "" };
+            let hint = match &field.kind {
+                FieldKind::Enum(choices) => format!(" ({})", choices.join("|")),
+                FieldKind::Boolean => " (true|false)".to_string(),
+                _ => String::new(),
+            };
+            let mut style = Style::default();
+            if i == form.focused {
+                style = style.add_modifier(Modifier::BOLD);
+            }
+            lines.push(Line::from(vec![
+                Span::styled(format!("{marker} {}{required}: ", field.label), style),
+                Span::raw(field.value.clone()),
+                Span::styled(hint, Style::default().fg(Color::DarkGray)),
+            ]));
+        }
+        if let Some(error) = &form.error {
+            lines.push(Line::styled(error.clone(), Style::default().fg(Color::Red)));
+        }
+        lines.push(Line::styled(
+            "[Enter] submit [Ctrl-D] decline [Ctrl-C] cancel",
+            Style::default().fg(Color::DarkGray),
+        ));
+        lines
+    }
+
+    pub fn height(&self, width: u16) -> u16 {
+        if self.is_active() {
+            self.lines(width).len() as u16 + 2
+        } else {
+            0
+        }
+    }
+}
+
+impl Component for ElicitationPopup {
+    fn handle_event(&mut self, event: Event) {
+        let Event::Key(key) = event else {
+            return;
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c') if ctrl => self.respond(ElicitationResponse::Cancel),
+            KeyCode::Char('d') if ctrl => self.respond(ElicitationResponse::Decline),
+            KeyCode::Enter => {
+                let Some(form) = self.forms.front_mut() else {
+                    return;
+                };
+                match answers(&form.fields) {
+                    Ok(content) => self.respond(ElicitationResponse::Accept(content)),
+                    Err(error) => form.error = Some(error),
+                }
+            }
+            code => {
+                let Some(form) = self.forms.front_mut() else {
+                    return;
+                };
+                let count = form.fields.len().max(1);
+                match code {
+                    KeyCode::Tab | KeyCode::Down => form.focused = (form.focused + 1) % count,
+                    KeyCode::BackTab | KeyCode::Up => {
+                        form.focused = (form.focused + count - 1) % count
+                    }
+                    _ => {
+                        let Some(field) = form.fields.get_mut(form.focused) else {
+                            return;
+                        };
+                        match code {
+                            KeyCode::Left => field.cycle(-1),
+                            KeyCode::Right => field.cycle(1),
+                            KeyCode::Backspace => {
+                                field.value.pop();
+                            }
+                            KeyCode::Char(c) if !ctrl => field.value.push(c),
+                            _ => return,
+                        }
+                    }
+                }
+            }
+        }
+        let _ = self.needs_redraw.send(true);
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        if !self.is_active() {
+            return;
+        }
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan));
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(self.lines(area.width)).block(block), area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn converts_answers_to_schema_types() {
+        let mut fields = fields(&json!({
+            "type": "object",
+            "properties": {
+                "env": { "type": "string", "enum": ["staging", "prod"] },
+                "replicas": { "type": "integer", "title": "Replicas", "default": 2 },
+                "dry_run": { "type": "boolean" },
+                "note": { "type": "string" }
+            },
+            "required": ["env"]
+        }));
+        let index = |name: &str| fields.iter().position(|f| f.name == name).unwrap();
+        let (env, replicas, dry_run) = (index("env"), index("replicas"), index("dry_run"));
+        assert_eq!(answers(&fields), Err("env is required".to_string()));
+        fields[env].cycle(1);
+        fields[replicas].value = "two".into();
+        assert_eq!(
+            answers(&fields),
+            Err("Replicas must be an integer".to_string())
+        );
+        fields[replicas].value = "3".into();
+        fields[dry_run].cycle(1);
+        assert_eq!(
+            answers(&fields),
+            Ok(json!({ "env": "staging", "replicas": 3, "dry_run": true }))
+        );
+    }
+}
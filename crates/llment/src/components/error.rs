@@ -9,9 +9,11 @@ use ratatui::{
 use textwrap::wrap;
 use tokio::sync::watch;
 
-/// Displays an error message in a dismissable box with an `x` button.
+/// Displays an error or informational message in a dismissable box with an
+/// `x` button.
 pub struct ErrorPopup {
     message: Option<String>,
+    is_error: bool,
     area: Rect,
     needs_redraw: watch::Sender<bool>,
 }
@@ -20,6 +22,7 @@ impl ErrorPopup {
     pub fn new(needs_redraw: watch::Sender<bool>) -> Self {
         Self {
             message: None,
+            is_error: true,
             area: Rect::default(),
             needs_redraw,
         }
@@ -27,6 +30,13 @@ impl ErrorPopup {
 
     pub fn set(&mut self, msg: String) {
         self.message = Some(msg);
+        self.is_error = true;
+        let _ = self.needs_redraw.send(true);
+    }
+
+    pub fn set_info(&mut self, msg: String) {
+        self.message = Some(msg);
+        self.is_error = false;
         let _ = self.needs_redraw.send(true);
     }
 
@@ -60,6 +70,11 @@ impl Component for ErrorPopup {
     fn render(&mut self, frame: &mut Frame, area: Rect) {
         self.area = area;
         if let Some(msg) = &self.message {
+            let color = if self.is_error {
+                Color::Red
+            } else {
+                Color::Reset
+            };
             let inner = area.width.saturating_sub(2).max(1) as usize;
             let lines = wrap(msg, inner)
                 .into_iter()
@@ -67,17 +82,17 @@ impl Component for ErrorPopup {
                 .collect::<Vec<_>>();
             let block = Block::default()
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Red));
+                .border_style(Style::default().fg(color));
             frame.render_widget(Clear, area);
             frame.render_widget(
                 Paragraph::new(lines)
                     .block(block)
-                    .style(Style::default().fg(Color::Red)),
+                    .style(Style::default().fg(color)),
                 area,
             );
             if area.width > 1 {
                 frame.render_widget(
-                    Paragraph::new("x").style(Style::default().fg(Color::Red)),
+                    Paragraph::new("x").style(Style::default().fg(color)),
                     Rect::new(area.x + area.width - 2, area.y, 1, 1),
                 );
             }
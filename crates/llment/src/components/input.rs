@@ -15,7 +15,8 @@ use tokio::sync::watch;
 use tui_textarea::{Input as TaInput, Key as TaKey, TextArea};
 
 use super::completion::{
-    Command, CommandInstance, CommandRouter, CompletionPopup, CompletionResult,
+    Command, CommandInstance, CommandRouter, CommandSource, CompletionPopup, CompletionResult,
+    MentionCompleter,
 };
 
 /// Multiline prompt input backed by [`tui_textarea`].
@@ -56,6 +57,14 @@ impl Prompt {
         }
     }
 
+    pub fn set_mentions(&mut self, mentions: Box<dyn MentionCompleter>) {
+        self.router.set_mentions(mentions);
+    }
+
+    pub fn add_command_source(&mut self, source: Box<dyn CommandSource>) {
+        self.router.add_source(source);
+    }
+
     pub fn submitted_prompt_rx(&self) -> watch::Receiver<String> {
         self.submitted_prompt_rx.clone()
     }
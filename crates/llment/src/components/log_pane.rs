@@ -0,0 +1,151 @@
+use std::collections::VecDeque;
+
+use crate::component::Component;
+use llm::mcp::{LoggingLevel, McpLogMessage};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::Line,
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+use tokio::sync::watch;
+
+/// Number of log messages kept for the pane.
+const MAX_ENTRIES: usize = 500;
+/// Number of log lines shown at once.
+const MAX_VISIBLE: usize = 8;
+
+/// Selects the log messages shown in the [`LogPane`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogFilter {
+    pub min_level: LoggingLevel,
+    pub server: Option<String>,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self {
+            min_level: LoggingLevel::Info,
+            server: None,
+        }
+    }
+}
+
+impl LogFilter {
+    fn matches(&self, message: &McpLogMessage) -> bool {
+        message.level as u8 >= self.min_level as u8
+            && self.server.as_ref().is_none_or(|s| *s == message.server)
+    }
+}
+
+/// Shows the most recent log messages sent by MCP servers.
+pub struct LogPane {
+    entries: VecDeque<McpLogMessage>,
+    filter: LogFilter,
+    visible: bool,
+    needs_redraw: watch::Sender<bool>,
+}
+
+impl LogPane {
+    pub fn new(needs_redraw: watch::Sender<bool>) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            filter: LogFilter::default(),
+            visible: false,
+            needs_redraw,
+        }
+    }
+
+    pub fn push(&mut self, message: McpLogMessage) {
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        let shown = self.visible && self.filter.matches(&message);
+        self.entries.push_back(message);
+        if shown {
+            let _ = self.needs_redraw.send(true);
+        }
+    }
+
+    /// Shows the pane with `filter`, or toggles it when `None`.
+    pub fn show(&mut self, filter: Option<LogFilter>) {
+        match filter {
+            Some(filter) => {
+                self.filter = filter;
+                self.visible = true;
+            }
+            None => self.visible = !self.visible,
+        }
+        let _ = self.needs_redraw.send(true);
+    }
+
+    fn visible_entries(&self) -> Vec<&McpLogMessage> {
+        let mut entries: Vec<_> = self
+            .entries
+            .iter()
+            .rev()
+            .filter(|m| self.filter.matches(m))
+            .take(MAX_VISIBLE)
+            .collect();
+        entries.reverse();
+        entries
+    }
+
+    pub fn height(&self) -> u16 {
+        if self.visible {
+            self.visible_entries().len().max(1) as u16 + 2
+        } else {
+            0
+        }
+    }
+}
+
+fn level_color(level: LoggingLevel) -> Color {
+    match level {
+        LoggingLevel::Debug => Color::DarkGray,
+        LoggingLevel::Info | LoggingLevel::Notice => Color::Reset,
+        LoggingLevel::Warning => Color::Yellow,
+        _ => Color::Red,
+    }
+}
+
+impl Component for LogPane {
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        if !self.visible {
+            return;
+        }
+        let lines: Vec<Line> = self
+            .visible_entries()
+            .into_iter()
+            .map(|m| {
+                let logger = m
+                    .logger
+                    .as_ref()
+                    .map(|l| format!("{l}: "))
+                    .unwrap_or_default();
+                Line::styled(
+                    format!(
+                        "{} {:?} {logger}{}",
+                        m.server,
+                        m.level,
+                        m.text().replace('\n', " ")
+                    ),
+                    Style::default().fg(level_color(m.level)),
+                )
+            })
+            .collect();
+        let server = self
+            .filter
+            .server
+            .as_ref()
+            .map(|s| format!(" from {s}"))
+            .unwrap_or_default();
+        let block = Block::default().borders(Borders::ALL).title(format!(
+            "MCP log: {:?} and above{server}",
+            self.filter.min_level
+        ));
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
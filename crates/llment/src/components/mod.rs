@@ -1,6 +1,14 @@
+pub mod approval;
 pub mod completion;
+pub mod elicitation;
 pub mod error;
 pub mod input;
+pub mod log_pane;
+pub mod token_pane;
 
+pub use approval::ApprovalPopup;
+pub use elicitation::ElicitationPopup;
 pub use error::ErrorPopup;
 pub use input::Prompt;
+pub use log_pane::LogPane;
+pub use token_pane::TokenPane;
@@ -0,0 +1,202 @@
+use crate::component::Component;
+use llm::{TokenDebug, TokenInfo};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+use tokio::sync::watch;
+use unicode_width::UnicodeWidthChar;
+
+/// Height of the pane, borders included.
+const HEIGHT: u16 = 16;
+/// Number of prompt lines shown above the response.
+const PROMPT_LINES: usize = 4;
+
+/// Shows the tokens of the last request: the end of the rendered prompt,
+/// then the response coloured by the probability of each token.
+pub struct TokenPane {
+    prompt: Vec<TokenInfo>,
+    response: Vec<TokenInfo>,
+    visible: bool,
+    needs_redraw: watch::Sender<bool>,
+}
+
+impl TokenPane {
+    pub fn new(needs_redraw: watch::Sender<bool>) -> Self {
+        Self {
+            prompt: Vec::new(),
+            response: Vec::new(),
+            visible: false,
+            needs_redraw,
+        }
+    }
+
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        let _ = self.needs_redraw.send(true);
+    }
+
+    pub fn push(&mut self, tokens: TokenDebug) {
+        match tokens {
+            TokenDebug::Prompt(prompt) => {
+                self.prompt = prompt;
+                self.response.clear();
+            }
+            TokenDebug::Response(tokens) => self.response.extend(tokens),
+        }
+        if self.visible {
+            let _ = self.needs_redraw.send(true);
+        }
+    }
+
+    pub fn height(&self) -> u16 {
+        if self.visible { HEIGHT } else { 0 }
+    }
+}
+
+fn is_special(token: &TokenInfo) -> bool {
+    token.text.starts_with("<|") && token.text.ends_with("|>")
+}
+
+fn prob_color(prob: Option<f32>) -> Color {
+    match prob {
+        None => Color::Reset,
+        Some(p) if p >= 0.9 => Color::Green,
+        Some(p) if p >= 0.5 => Color::Yellow,
+        Some(p) if p >= 0.2 => Color::LightRed,
+        Some(_) => Color::Red,
+    }
+}
+
+/// Lays out `tokens` in lines of at most `width` columns, styled by
+/// `style`. Newlines are shown as `↵` and end the line.
+fn wrap_tokens(
+    tokens: &[TokenInfo],
+    width: usize,
+    style: impl Fn(usize, &TokenInfo) -> Style,
+) -> Vec<Line<'static>> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line: Vec<Span<'static>> = Vec::new();
+    let mut line_width = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        let style = style(i, token);
+        let mut text = String::new();
+        for c in token.text.chars() {
+            let (shown, newline) = match c {
+                '\n' => ('↵', true),
+                '\t' => (' ', false),
+                c => (c, false),
+            };
+            let w = shown.width().unwrap_or(0);
+            if line_width + w > width {
+                if !text.is_empty() {
+                    line.push(Span::styled(std::mem::take(&mut text), style));
+                }
+                lines.push(Line::from(std::mem::take(&mut line)));
+                line_width = 0;
+            }
+            text.push(shown);
+            line_width += w;
+            if newline {
+                line.push(Span::styled(std::mem::take(&mut text), style));
+                lines.push(Line::from(std::mem::take(&mut line)));
+                line_width = 0;
+            }
+        }
+        if !text.is_empty() {
+            line.push(Span::styled(text, style));
+        }
+    }
+    if !line.is_empty() {
+        lines.push(Line::from(line));
+    }
+    lines
+}
+
+impl Component for TokenPane {
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        if !self.visible {
+            return;
+        }
+        let width = area.width.saturating_sub(2) as usize;
+        let height = area.height.saturating_sub(2) as usize;
+        // Alternating shades show where prompt tokens start and end.
+        let prompt = wrap_tokens(&self.prompt, width, |i, t| {
+            if is_special(t) {
+                Style::default().fg(Color::Cyan)
+            } else if i % 2 == 0 {
+                Style::default().fg(Color::Gray)
+            } else {
+                Style::default().fg(Color::DarkGray)
+            }
+        });
+        let response = wrap_tokens(&self.response, width, |i, t| {
+            let style = Style::default().fg(prob_color(t.prob));
+            let style = if i % 2 == 1 {
+                style.add_modifier(Modifier::UNDERLINED)
+            } else {
+                style
+            };
+            if is_special(t) {
+                style.add_modifier(Modifier::BOLD)
+            } else {
+                style
+            }
+        });
+        let prompt_lines = PROMPT_LINES.min(prompt.len());
+        let response_lines = height.saturating_sub(prompt_lines + 1);
+        let mut lines: Vec<Line> = prompt[prompt.len() - prompt_lines..].to_vec();
+        lines.push(Line::styled(
+            "─".repeat(width),
+            Style::default().fg(Color::DarkGray),
+        ));
+        lines.extend_from_slice(&response[response.len().saturating_sub(response_lines)..]);
+        let block = Block::default().borders(Borders::ALL).title(format!(
+            "Tokens: {} prompt, {} response",
+            self.prompt.len(),
+            self.response.len()
+        ));
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(text: &str, prob: Option<f32>) -> TokenInfo {
+        TokenInfo {
+            id: 0,
+            text: text.into(),
+            prob,
+            top: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn wraps_tokens_at_newlines_and_width() {
+        let tokens = [
+            token("ab", Some(0.95)),
+            token("c\nd", Some(0.1)),
+            token("efgh", None),
+        ];
+        let lines = wrap_tokens(&tokens, 4, |_, t| Style::default().fg(prob_color(t.prob)));
+        let text: Vec<String> = lines
+            .iter()
+            .map(|l| l.spans.iter().map(|s| s.content.as_ref()).collect())
+            .collect();
+        assert_eq!(text, ["abc↵", "defg", "h"]);
+        assert_eq!(lines[0].spans[0].style.fg, Some(Color::Green));
+        assert_eq!(lines[0].spans[1].style.fg, Some(Color::Red));
+        assert_eq!(lines[1].spans[1].style.fg, Some(Color::Reset));
+    }
+}
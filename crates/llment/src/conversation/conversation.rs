@@ -1,5 +1,5 @@
 use crossterm::event::{Event, MouseButton, MouseEventKind};
-use llm::{AssistantPart, ChatMessage, JsonResult};
+use llm::{AssistantPart, ChatMessage, JsonResult, Truncation, tools::ToolProgress};
 use ratatui::{Frame, layout::Rect};
 use serde_json::to_string;
 
@@ -242,7 +242,13 @@ impl Conversation {
         }
     }
 
-    pub fn update_tool_result(&mut self, step_id: &str, result: String, failed: bool) -> bool {
+    pub fn update_tool_result(
+        &mut self,
+        step_id: &str,
+        result: String,
+        failed: bool,
+        truncated: Option<Truncation>,
+    ) -> bool {
         let at_bottom = self.is_at_bottom();
         let block = self.ensure_last_assistant();
         let matching = block.steps.iter().enumerate().find(|(_i, step)| {
@@ -258,6 +264,7 @@ impl Conversation {
                 result: r,
                 done,
                 failed: f,
+                truncated: t,
                 content_rev,
                 ..
             })) = block.steps.get_mut(step_idx)
@@ -265,6 +272,7 @@ impl Conversation {
                 *r = result;
                 *done = true;
                 *f = failed;
+                *t = truncated;
                 *content_rev += 1;
                 block.record_activity();
                 block.content_rev += 1;
@@ -280,6 +288,25 @@ impl Conversation {
         }
     }
 
+    pub fn update_tool_progress(&mut self, step_id: &str, progress: ToolProgress) {
+        let at_bottom = self.is_at_bottom();
+        let block = self.ensure_last_assistant();
+        let Some(tool) = block.steps.iter_mut().find_map(|step| match step {
+            Node::Tool(tool) if tool.id == step_id && !tool.done => Some(tool),
+            _ => None,
+        }) else {
+            return;
+        };
+        tool.progress = Some(progress);
+        tool.content_rev += 1;
+        block.content_rev += 1;
+        self.needs_layout = true;
+        self.ensure_layout(self.width);
+        if at_bottom {
+            self.scroll_to_bottom();
+        }
+    }
+
     pub fn set_history(&mut self, history: &[ChatMessage]) {
         self.clear();
         for msg in history {
@@ -319,19 +346,20 @@ impl Conversation {
                     }
                 }
                 ChatMessage::Tool(tmsg) => {
-                    let result = match &tmsg.content {
-                        JsonResult::Content { content } => to_string(content).unwrap_or_default(),
-                        JsonResult::Error { error } => error.clone(),
-                    };
-                    if !self.update_tool_result(&tmsg.id, result.clone(), false) {
+                    let result = tmsg.output.to_text();
+                    let failed = tmsg.output.is_error;
+                    let truncated = tmsg.output.truncated.clone();
+                    if !self.update_tool_result(&tmsg.id, result.clone(), failed, truncated.clone())
+                    {
                         let mut step = ToolStep::new(
                             tmsg.tool_name.clone(),
                             tmsg.id.clone(),
                             String::new(),
                             result,
-                            false,
+                            failed,
                         );
                         step.done = true;
+                        step.truncated = truncated;
                         self.add_tool_step(step);
                     }
                 }
@@ -381,6 +409,31 @@ mod tests {
         out
     }
 
+    #[test]
+    fn shows_tool_progress_until_result() {
+        let mut conv = Conversation::new();
+        conv.add_tool_step(ToolStep::new(
+            "build".into(),
+            "1".into(),
+            "{}".into(),
+            String::new(),
+            true,
+        ));
+        conv.update_tool_progress(
+            "1",
+            ToolProgress {
+                progress: 1.0,
+                total: Some(2.0),
+                message: Some("compiling".into()),
+            },
+        );
+        let text = buffer_to_debug_string(&render_conv(&mut conv, 40, 6));
+        assert!(text.contains("│ [█████░░░░░] 1/2 compiling"));
+        conv.update_tool_result("1", "ok".into(), false, None);
+        let text = buffer_to_debug_string(&render_conv(&mut conv, 40, 6));
+        assert!(!text.contains("compiling"));
+    }
+
     #[test]
     fn collapsing_block_adjusts_scroll() {
         let mut conv = Conversation::new();
@@ -1,3 +1,4 @@
+use llm::{Truncation, tools::ToolProgress};
 use ratatui::{
     Frame,
     layout::Rect,
@@ -9,6 +10,9 @@ use textwrap::wrap;
 
 use super::node::ConvNode;
 
+/// Width of the progress bar shown while a tool reports its total.
+const PROGRESS_BAR_WIDTH: usize = 10;
+
 pub struct ToolStep {
     pub(crate) name: String,
     pub(crate) id: String,
@@ -17,6 +21,10 @@ pub struct ToolStep {
     pub(crate) collapsed: bool,
     pub(crate) done: bool,
     pub(crate) failed: bool,
+    /// The latest progress reported while the tool runs.
+    pub(crate) progress: Option<ToolProgress>,
+    /// Set when the result was cut down before it was added to the history.
+    pub(crate) truncated: Option<Truncation>,
     cache_width: u16,
     cache_rev: u64,
     pub(crate) content_rev: u64,
@@ -33,6 +41,8 @@ impl ToolStep {
             collapsed,
             done: false,
             failed: false,
+            progress: None,
+            truncated: None,
             cache_width: 0,
             cache_rev: 0,
             content_rev: 0,
@@ -52,11 +62,33 @@ impl ToolStep {
         if self.failed {
             name_style = name_style.fg(Color::Red);
         }
-        lines.push(Line::from(vec![
+        let mut header = vec![
             Span::raw("· "),
             Span::styled(self.name.clone(), name_style),
             Span::raw(format!(" {}", arrow)),
-        ]));
+        ];
+        if let Some(truncated) = &self.truncated {
+            header.push(Span::styled(
+                format!(
+                    " truncated from {} chars, id {}",
+                    truncated.total_chars, truncated.id
+                ),
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+        lines.push(Line::from(header));
+        if !self.done
+            && let Some(progress) = &self.progress
+        {
+            let line = progress_line(progress);
+            lines.push(Line::from(format!(
+                "│ {}",
+                wrap(&line, width.saturating_sub(2) as usize)
+                    .first()
+                    .map(|l| l.to_string())
+                    .unwrap_or_default()
+            )));
+        }
         if !self.collapsed {
             let a_wrap = wrap(&self.args, width.saturating_sub(8) as usize);
             for (i, w) in a_wrap.into_iter().enumerate() {
@@ -79,6 +111,30 @@ impl ToolStep {
     }
 }
 
+/// Formats progress as `[████░░░░░░] 2/5 message`, or `2 message` when the
+/// total is unknown.
+fn progress_line(progress: &ToolProgress) -> String {
+    let mut line = match progress.total {
+        Some(total) if total > 0.0 => {
+            let filled = ((progress.progress / total).clamp(0.0, 1.0) * PROGRESS_BAR_WIDTH as f64)
+                .round() as usize;
+            format!(
+                "[{}{}] {}/{}",
+                "█".repeat(filled),
+                "░".repeat(PROGRESS_BAR_WIDTH - filled),
+                progress.progress,
+                total
+            )
+        }
+        _ => progress.progress.to_string(),
+    };
+    if let Some(message) = &progress.message {
+        line.push(' ');
+        line.push_str(message);
+    }
+    line
+}
+
 impl ConvNode for ToolStep {
     fn height(&mut self, width: u16) -> u16 {
         self.ensure_cache(width);
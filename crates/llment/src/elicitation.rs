@@ -0,0 +1,21 @@
+use async_trait::async_trait;
+use llm::mcp::{Elicitation, ElicitationRequest, ElicitationResponse};
+use tokio::sync::{mpsc::UnboundedSender, oneshot, watch};
+
+use crate::app::Update;
+
+/// Asks the user for the requested input through the elicitation form.
+pub struct AskElicitation {
+    pub update_tx: UnboundedSender<Update>,
+    pub needs_update: watch::Sender<bool>,
+}
+
+#[async_trait]
+impl Elicitation for AskElicitation {
+    async fn elicit(&self, request: ElicitationRequest) -> ElicitationResponse {
+        let (reply, response) = oneshot::channel();
+        let _ = self.update_tx.send(Update::Elicitation(request, reply));
+        let _ = self.needs_update.send(true);
+        response.await.unwrap_or(ElicitationResponse::Cancel)
+    }
+}
@@ -51,6 +51,13 @@ pub fn append_response(text: String) -> HistoryEdit {
     })
 }
 
+pub fn append(messages: Vec<ChatMessage>) -> HistoryEdit {
+    Box::new(move |history: &mut Vec<ChatMessage>| {
+        history.extend(messages);
+        Ok(HistoryEditResult::default())
+    })
+}
+
 pub fn pop() -> HistoryEdit {
     Box::new(|history: &mut Vec<ChatMessage>| {
         let mut removed = false;
@@ -27,13 +27,21 @@ mod commands;
 mod component;
 mod components;
 mod conversation;
+mod elicitation;
 mod history_edits;
 mod markdown;
+mod mcp_config;
 mod modes;
 mod prompts;
+mod pruning;
+mod resources;
+mod sampling;
+mod search;
 
-use llm::mcp::{McpContext, load_mcp_servers};
-use llm::{self, Provider};
+use llm::mcp::{McpContext, load_mcp_servers_partial};
+use llm::{self, GrammarConstraint, Provider};
+use pruning::PrunePolicy;
+use sampling::SamplingMode;
 
 struct TerminalGuard;
 
@@ -82,12 +90,80 @@ pub struct Args {
     /// Optional LLM host URL, e.g. http://localhost:11434 for Ollama
     #[arg(long)]
     host: Option<String>,
-    /// Path to MCP configuration JSON
+    /// Path to an MCP configuration JSON, merged over the discovered
+    /// `~/.config/llment/mcp.json` and `./.mcp.json`. May be repeated
     #[arg(long)]
-    mcp: Option<String>,
+    mcp: Vec<PathBuf>,
+    /// Load `./.mcp.json`, which is ignored otherwise since its servers run
+    /// commands chosen by the project
+    #[arg(long)]
+    trust_project_mcp: bool,
     /// Directory containing custom prompt templates
     #[arg(long)]
     prompt_dir: Option<PathBuf>,
+    /// Attempt to repair malformed tool-call JSON instead of failing the call
+    #[arg(long)]
+    repair_tool_calls: bool,
+    /// How much of each response is constrained by a grammar, for providers
+    /// that decode with one
+    #[arg(long, value_enum, default_value_t = GrammarConstraint::Full)]
+    grammar: GrammarConstraint,
+    /// Give the model tools to list and read MCP resources
+    #[arg(long)]
+    resource_tools: bool,
+    /// How to handle requests from MCP servers to sample the model
+    #[arg(long, value_enum, default_value_t = SamplingMode::Ask)]
+    sampling: SamplingMode,
+    /// Maximum number of tokens generated for a single sampling request
+    #[arg(long, default_value_t = 1024)]
+    sampling_max_tokens: u32,
+    /// Maximum number of characters of a tool result added to the history.
+    /// Longer results are truncated, 0 disables the limit
+    #[arg(long, default_value_t = 20000)]
+    max_tool_output: usize,
+    /// Directory where the full text of truncated tool results is stored
+    /// until the conversation is cleared or llment exits. Defaults to a
+    /// per-process directory under the system temp directory
+    #[arg(long)]
+    tool_output_dir: Option<PathBuf>,
+    /// Path to a JSON file configuring when old tool results are replaced
+    /// with placeholders in requests
+    #[arg(long)]
+    prune_config: Option<PathBuf>,
+    /// Give the model the `browser` tools. Harmony declares them as the
+    /// built-in browser gpt-oss was trained on
+    #[arg(long)]
+    browser: bool,
+    /// Search URL of the browser, with `{query}` replaced by the query
+    #[arg(long)]
+    browser_search_url: Option<String>,
+    /// MCP tool the browser searches with, called with `{"query"}`
+    #[arg(long)]
+    browser_search_tool: Option<String>,
+    /// MCP tool the browser fetches pages with, called with `{"url"}`,
+    /// instead of fetching them over HTTP
+    #[arg(long)]
+    browser_fetch_tool: Option<String>,
+    /// Give the model a `python` tool that runs scripts with a local
+    /// `python3`. Harmony declares it as the built-in python tool
+    #[arg(long)]
+    python: bool,
+    /// Run the scripts of the `python` tool through this mcp-shell server,
+    /// e.g. in its container
+    #[arg(long)]
+    python_shell: Option<String>,
+    /// Embedding model of `/search`, which is off without one
+    #[arg(long)]
+    embed_model: Option<String>,
+    /// Provider of the embedding model, `--provider` by default
+    #[arg(long, value_enum)]
+    embed_provider: Option<Provider>,
+    /// Host of the embedding model, `--host` by default
+    #[arg(long)]
+    embed_host: Option<String>,
+    /// Index file of `/search`
+    #[arg(long, default_value = ".llment/index.json")]
+    search_index: PathBuf,
 }
 
 #[tokio::main]
@@ -97,10 +173,15 @@ async fn main() -> Result<(), Box<dyn Error>> {
 }
 
 async fn run(args: Args) -> Result<(), Box<dyn Error>> {
-    let mcp_ctx = if let Some(path) = &args.mcp {
-        load_mcp_servers(path).await.expect("mcp")
+    let mcp_paths = mcp_config::mcp_config_paths(&args.mcp, args.trust_project_mcp);
+    let (mcp_ctx, mcp_errors) = if mcp_paths.is_empty() {
+        (McpContext::default(), Vec::new())
     } else {
-        McpContext::default()
+        load_mcp_servers_partial(&mcp_paths).await
+    };
+    let prune_policy = match &args.prune_config {
+        Some(path) => PrunePolicy::load(path)?,
+        None => PrunePolicy::default(),
     };
 
     let _guard = TerminalGuard::new()?;
@@ -120,8 +201,9 @@ async fn run(args: Args) -> Result<(), Box<dyn Error>> {
             should_quit: should_quit_tx.clone(),
         },
         args,
+        prune_policy,
     );
-    app.init(mcp_ctx).await;
+    app.init(mcp_ctx, mcp_errors).await;
     Component::init(&mut app);
 
     tokio::spawn(event_loop(tx));
@@ -0,0 +1,64 @@
+use std::path::{Path, PathBuf};
+
+/// Name of the project-local configuration, looked up in the working
+/// directory.
+const PROJECT_CONFIG: &str = ".mcp.json";
+
+/// The user-level configuration: `$XDG_CONFIG_HOME/llment/mcp.json`, or
+/// `~/.config/llment/mcp.json`.
+fn user_config() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_dir.join("llment").join("mcp.json"))
+}
+
+/// The mcp.json files to load, in the order they are merged: the user-level
+/// file, then the project-local one, then those given with `--mcp`.
+/// Discovered files are skipped when they do not exist. The project-local
+/// file comes with the project and starts commands of its choosing, so it is
+/// only loaded when `trust_project` is set.
+pub fn mcp_config_paths(explicit: &[PathBuf], trust_project: bool) -> Vec<PathBuf> {
+    let project = trust_project.then(|| Path::new(PROJECT_CONFIG));
+    discover(user_config(), project, explicit)
+}
+
+/// The project-local configuration, if it exists but is not trusted.
+pub fn untrusted_project_config(trust_project: bool) -> Option<&'static Path> {
+    Some(Path::new(PROJECT_CONFIG)).filter(|path| !trust_project && path.is_file())
+}
+
+fn discover(user: Option<PathBuf>, project: Option<&Path>, explicit: &[PathBuf]) -> Vec<PathBuf> {
+    user.into_iter()
+        .chain(project.map(Path::to_path_buf))
+        .filter(|path| path.is_file())
+        .chain(explicit.iter().cloned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_discovered_files_before_explicit_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = dir.path().join("user.json");
+        let project = dir.path().join("project.json");
+        let explicit = vec![PathBuf::from("extra.json")];
+        std::fs::write(&project, "{}").unwrap();
+        assert_eq!(
+            discover(Some(user.clone()), Some(&project), &explicit),
+            vec![project.clone(), PathBuf::from("extra.json")]
+        );
+        std::fs::write(&user, "{}").unwrap();
+        assert_eq!(
+            discover(Some(user.clone()), Some(&project), &explicit),
+            vec![user.clone(), project, PathBuf::from("extra.json")]
+        );
+        assert_eq!(
+            discover(Some(user.clone()), None, &explicit),
+            vec![user, PathBuf::from("extra.json")]
+        );
+    }
+}
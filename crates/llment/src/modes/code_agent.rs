@@ -1,17 +1,15 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    error::Error,
+    sync::{Arc, Mutex},
+};
 
-use arc_swap::ArcSwap;
-use llm::{ChatMessage, ToolInfo, mcp::McpService};
-use rmcp::{
-    ServerHandler,
-    handler::server::{router::tool::ToolRouter, wrapper::Parameters},
-    model::{ServerCapabilities, ServerInfo},
-    service::{RoleClient, RunningService, ServiceExt},
-    tool, tool_handler, tool_router,
+use async_trait::async_trait;
+use llm::{
+    ChatMessage, ToolOutput,
+    function_tool::{FunctionTool, FunctionToolSet},
 };
-use schemars::{JsonSchema, Schema};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tokio::io::duplex;
 
 use super::{AgentMode, AgentModeStart, AgentModeStep};
 
@@ -50,37 +48,21 @@ struct NotifyParams {
     message: Option<String>,
 }
 
-#[derive(Clone)]
-struct CodeAgentTools {
+struct Notify {
     state: Arc<Mutex<NotifyState>>,
-    tool_router: ToolRouter<Self>,
 }
 
-#[tool_router]
-impl CodeAgentTools {
-    fn new(state: Arc<Mutex<NotifyState>>) -> Self {
-        Self {
-            state,
-            tool_router: Self::tool_router(),
-        }
-    }
+#[async_trait]
+impl FunctionTool for Notify {
+    const NAME: &'static str = "notify";
+    const DESCRIPTION: &'static str = "Switch to another code-agent role with an optional message";
+    type Params = NotifyParams;
 
-    #[tool(
-        name = "notify",
-        description = "Switch to another code-agent role with an optional message"
-    )]
-    fn notify(&self, Parameters(params): Parameters<NotifyParams>) -> String {
+    async fn call(&self, params: NotifyParams) -> Result<ToolOutput, Box<dyn Error + Send + Sync>> {
         let mut state = self.state.lock().unwrap();
         state.role = Some(params.role);
         state.message = params.message;
-        "ok".into()
-    }
-}
-
-#[tool_handler(router = self.tool_router)]
-impl ServerHandler for CodeAgentTools {
-    fn get_info(&self) -> ServerInfo {
-        ServerInfo::new(ServerCapabilities::builder().enable_tools().build())
+        Ok(ToolOutput::text("ok"))
     }
 }
 
@@ -90,43 +72,17 @@ pub struct CodeAgentMode {
 }
 
 impl CodeAgentMode {
-    pub async fn new() -> (Self, RunningService<RoleClient, McpService>) {
+    pub fn new() -> (Self, FunctionToolSet) {
         let state = Arc::new(Mutex::new(NotifyState::default()));
-        let tools = CodeAgentTools::new(state.clone());
-        let tool_infos: Vec<ToolInfo> = tools
-            .tool_router
-            .list_all()
-            .into_iter()
-            .filter_map(|tool| {
-                let schema: Schema = serde_json::from_value(tool.schema_as_json_value()).ok()?;
-                let description = tool.description.unwrap_or_default().to_string();
-                Some(ToolInfo {
-                    name: tool.name.to_string(),
-                    description,
-                    parameters: schema,
-                })
-            })
-            .collect();
-        let (server_transport, client_transport) = duplex(64);
-        let (server_res, client_res) = tokio::join!(
-            tools.clone().serve(server_transport),
-            McpService {
-                prefix: "agent".into(),
-                tools: ArcSwap::new(Arc::new(tool_infos)),
-            }
-            .serve(client_transport)
-        );
-        let server = server_res.expect("code agent server");
-        let client_service = client_res.expect("code agent client");
-        tokio::spawn(async move {
-            let _ = server.waiting().await;
+        let tools = FunctionToolSet::new("agent").with(Notify {
+            state: state.clone(),
         });
         (
             Self {
                 current_role: CodeAgentRole::Director,
                 state,
             },
-            client_service,
+            tools,
         )
     }
 }
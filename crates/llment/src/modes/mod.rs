@@ -1,5 +1,4 @@
-use llm::{ChatMessage, mcp::McpService};
-use rmcp::service::{RoleClient, RunningService};
+use llm::{ChatMessage, function_tool::FunctionToolSet};
 
 pub struct AgentModeStart {
     pub role: Option<String>,
@@ -19,16 +18,11 @@ pub trait AgentMode: Send {
     fn step(&mut self, last_message: Option<&ChatMessage>) -> AgentModeStep;
 }
 
-pub async fn create_agent_mode(
-    name: &str,
-) -> Option<(
-    Box<dyn AgentMode>,
-    Option<RunningService<RoleClient, McpService>>,
-)> {
+pub fn create_agent_mode(name: &str) -> Option<(Box<dyn AgentMode>, Option<FunctionToolSet>)> {
     match name {
         "code-agent" => {
-            let (mode, service) = code_agent::CodeAgentMode::new().await;
-            Some((Box::new(mode), Some(service)))
+            let (mode, tools) = code_agent::CodeAgentMode::new();
+            Some((Box::new(mode), Some(tools)))
         }
         "example" => Some((Box::new(example::ExampleAgentMode::new()), None)),
         _ => None,
@@ -0,0 +1,277 @@
+use std::{collections::HashMap, error::Error, path::Path};
+
+use globset::{Glob, GlobMatcher};
+use llm::{
+    AssistantPart, ChatMessage, ChatMessageRequest, ToolCall, ToolOutput, agent::AgentHooks,
+};
+use serde::Deserialize;
+
+/// How the results of a tool are pruned, see [`PrunePolicy`].
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct PruneRule {
+    /// Replace results once this many user turns followed them. Overrides
+    /// the policy's `maxAge`. Results of the current turn are always kept.
+    pub max_age: Option<usize>,
+    /// Argument holding the path of the file the tool reads or edits.
+    pub path: Option<String>,
+    /// Whether the tool edits the file at `path` instead of reading it.
+    #[serde(default)]
+    pub edits: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct PruneConfig {
+    max_age: Option<usize>,
+    #[serde(default)]
+    tools: HashMap<String, PruneRule>,
+}
+
+/// Replaces stale tool results in requests with short placeholders.
+///
+/// A result is stale once `maxAge` user turns followed it, or when it read a
+/// file that a later call read again or edited. Only the requests are
+/// changed: the history keeps the original results, so they are still shown
+/// and saved.
+pub struct PrunePolicy {
+    max_age: Option<usize>,
+    rules: Vec<(String, GlobMatcher, PruneRule)>,
+}
+
+impl Default for PrunePolicy {
+    /// Supersedes the reads of the mcp-edit tools, whatever their prefix.
+    fn default() -> Self {
+        let rule = |path: &str, edits| PruneRule {
+            max_age: None,
+            path: Some(path.into()),
+            edits,
+        };
+        Self::new(
+            None,
+            HashMap::from([
+                ("*read_file".to_string(), rule("path", false)),
+                ("*replace".to_string(), rule("file_path", true)),
+                ("*create_file".to_string(), rule("file_path", true)),
+            ]),
+        )
+        .expect("valid default patterns")
+    }
+}
+
+impl PrunePolicy {
+    /// Creates a policy from rules keyed by tool name, or by a glob pattern
+    /// over tool names. When several patterns match, the longest one wins.
+    pub fn new(
+        max_age: Option<usize>,
+        tools: HashMap<String, PruneRule>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut rules = tools
+            .into_iter()
+            .map(|(pattern, rule)| {
+                let matcher = Glob::new(&pattern)
+                    .map_err(|e| format!("invalid tool pattern '{pattern}': {e}"))?
+                    .compile_matcher();
+                Ok((pattern, matcher, rule))
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error + Send + Sync>>>()?;
+        rules.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.0.cmp(&b.0)));
+        Ok(Self { max_age, rules })
+    }
+
+    /// Loads a policy from a JSON file of the form
+    /// `{"maxAge": 10, "tools": {"edit_read_file": {"path": "path"}}}`.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        let config: PruneConfig = serde_json::from_str(&data)
+            .map_err(|e| format!("failed to parse {}: {e}", path.display()))?;
+        Self::new(config.max_age, config.tools)
+    }
+
+    fn rule(&self, tool: &str) -> Option<&PruneRule> {
+        self.rules
+            .iter()
+            .find(|(_, matcher, _)| matcher.is_match(tool))
+            .map(|(_, _, rule)| rule)
+    }
+
+    fn path<'a>(&self, call: &'a ToolCall) -> Option<(&'a str, bool)> {
+        let rule = self.rule(&call.name)?;
+        let path = call.arguments.as_result().ok()?.get(rule.path.as_ref()?)?;
+        Some((path.as_str()?, rule.edits))
+    }
+
+    /// Replaces the stale tool results in `messages`.
+    pub fn apply(&self, messages: &mut [ChatMessage]) {
+        let calls: HashMap<&str, &ToolCall> = messages
+            .iter()
+            .filter_map(|m| match m {
+                ChatMessage::Assistant(a) => Some(&a.content),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|part| match part {
+                AssistantPart::ToolCall { call, .. } => Some((call.id.as_str(), call)),
+                _ => None,
+            })
+            .collect();
+        let mut placeholders = Vec::new();
+        // Walk backwards so the later reads and edits of each file are known.
+        let mut turns = 0;
+        let mut later: HashMap<&str, bool> = HashMap::new();
+        for (idx, message) in messages.iter().enumerate().rev() {
+            match message {
+                ChatMessage::User(_) => turns += 1,
+                ChatMessage::Assistant(a) => {
+                    for part in &a.content {
+                        if let AssistantPart::ToolCall { call, .. } = part
+                            && let Some((path, edits)) = self.path(call)
+                        {
+                            let edited = later.entry(path).or_default();
+                            *edited |= edits;
+                        }
+                    }
+                }
+                ChatMessage::Tool(t) => {
+                    let max_age = self
+                        .rule(&t.tool_name)
+                        .and_then(|rule| rule.max_age)
+                        .or(self.max_age);
+                    let read = calls
+                        .get(t.id.as_str())
+                        .and_then(|call| self.path(call))
+                        .filter(|(_, edits)| !edits);
+                    let reason = if let Some(max) = max_age
+                        && turns > 0
+                        && turns >= max
+                    {
+                        Some(format!("aged out after {max} turns"))
+                    } else if let Some((path, _)) = read
+                        && let Some(edited) = later.get(path)
+                    {
+                        Some(if *edited {
+                            format!("{path} was edited later")
+                        } else {
+                            format!("{path} was read again later")
+                        })
+                    } else {
+                        None
+                    };
+                    if let Some(reason) = reason {
+                        placeholders.push((idx, reason));
+                    }
+                }
+                ChatMessage::System(_) => {}
+            }
+        }
+        for (idx, reason) in placeholders {
+            if let ChatMessage::Tool(t) = &mut messages[idx] {
+                t.output = ToolOutput::text(format!("<response pruned: {reason}>"));
+            }
+        }
+    }
+}
+
+impl AgentHooks for PrunePolicy {
+    fn before_request(&self, request: &mut ChatMessageRequest) {
+        self.apply(&mut request.messages);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use llm::{AssistantMessage, JsonResult};
+    use serde_json::{Value, json};
+
+    fn call(id: &str, name: &str, arguments: Value) -> ChatMessage {
+        ChatMessage::Assistant(AssistantMessage {
+            content: vec![AssistantPart::ToolCall {
+                call: ToolCall {
+                    id: id.into(),
+                    name: name.into(),
+                    arguments: JsonResult::Content { content: arguments },
+                    repaired_from: None,
+                },
+                encrypted_content: None,
+            }],
+        })
+    }
+
+    fn result(id: &str, name: &str) -> ChatMessage {
+        ChatMessage::tool(
+            id.into(),
+            ToolOutput::text(format!("{id} output")),
+            name.into(),
+        )
+    }
+
+    fn outputs(messages: &[ChatMessage]) -> Vec<String> {
+        messages
+            .iter()
+            .filter_map(|m| match m {
+                ChatMessage::Tool(t) => Some(t.output.to_text()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn prunes_superseded_and_old_results() {
+        let mut messages = vec![
+            ChatMessage::user("go".into()),
+            call("1", "edit_read_file", json!({"path": "a.rs"})),
+            result("1", "edit_read_file"),
+            call("2", "edit_read_file", json!({"path": "b.rs"})),
+            result("2", "edit_read_file"),
+            call("3", "shell_run", json!({"command": "ls"})),
+            result("3", "shell_run"),
+            ChatMessage::user("again".into()),
+            call("4", "edit_read_file", json!({"path": "a.rs"})),
+            result("4", "edit_read_file"),
+            call("5", "edit_replace", json!({"file_path": "b.rs"})),
+            result("5", "edit_replace"),
+            call("6", "shell_run", json!({"command": "ls"})),
+            result("6", "shell_run"),
+        ];
+        let mut tools = HashMap::new();
+        tools.insert("shell_*".to_string(), PruneRule::default());
+        tools.insert(
+            "shell_run".to_string(),
+            PruneRule {
+                max_age: Some(1),
+                ..PruneRule::default()
+            },
+        );
+        let policy = PrunePolicy::new(None, tools).unwrap();
+        let original = messages.clone();
+        policy.apply(&mut messages);
+        assert_eq!(
+            outputs(&messages),
+            vec![
+                "1 output",
+                "2 output",
+                "<response pruned: aged out after 1 turns>",
+                "4 output",
+                "5 output",
+                "6 output",
+            ]
+        );
+
+        messages = original;
+        PrunePolicy::default().apply(&mut messages);
+        policy.apply(&mut messages);
+        assert_eq!(
+            outputs(&messages),
+            vec![
+                "<response pruned: a.rs was read again later>",
+                "<response pruned: b.rs was edited later>",
+                "<response pruned: aged out after 1 turns>",
+                "4 output",
+                "5 output",
+                "6 output",
+            ]
+        );
+    }
+}
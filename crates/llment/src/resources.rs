@@ -0,0 +1,119 @@
+use std::{collections::HashSet, error::Error};
+
+use llm::{ToolOutput, mcp::McpContext};
+
+use crate::components::completion::{Completion, MentionCompleter};
+
+/// Maximum number of resources offered when completing a mention.
+const MAX_COMPLETIONS: usize = 20;
+
+/// A resource referenced from a prompt as `@server:uri`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ResourceRef {
+    pub server: String,
+    pub uri: String,
+}
+
+/// Completes `@server:uri` mentions from the resources listed by MCP servers.
+pub struct ResourceMentions {
+    pub ctx: McpContext,
+}
+
+impl MentionCompleter for ResourceMentions {
+    fn complete(&self, typed: &str) -> Vec<Completion> {
+        self.ctx
+            .resources()
+            .into_iter()
+            .filter(|r| {
+                format!("{}:{}", r.server, r.uri).starts_with(typed) || r.name.contains(typed)
+            })
+            .take(MAX_COMPLETIONS)
+            .map(|r| Completion {
+                name: format!("{}:{}", r.server, r.uri),
+                description: r.description.unwrap_or(r.name),
+                str: format!("{}:{} ", r.server, r.uri),
+            })
+            .collect()
+    }
+}
+
+/// Finds the `@server:uri` mentions in `prompt` that refer to one of `servers`.
+/// URIs need not be listed, so resource templates can be filled in by hand.
+pub fn parse_mentions(prompt: &str, servers: &HashSet<String>) -> Vec<ResourceRef> {
+    let mut refs = Vec::new();
+    for token in prompt.split_whitespace() {
+        let Some((server, uri)) = token.strip_prefix('@').and_then(|t| t.split_once(':')) else {
+            continue;
+        };
+        if uri.is_empty() || !servers.contains(server) {
+            continue;
+        }
+        let r = ResourceRef {
+            server: server.to_string(),
+            uri: uri.to_string(),
+        };
+        if !refs.contains(&r) {
+            refs.push(r);
+        }
+    }
+    refs
+}
+
+/// Names of the servers that expose resources or resource templates.
+pub fn resource_servers(ctx: &McpContext) -> HashSet<String> {
+    let mut servers: HashSet<String> = ctx.resources().into_iter().map(|r| r.server).collect();
+    servers.extend(ctx.resource_templates().into_iter().map(|t| t.server));
+    servers
+}
+
+/// Reads each resource and formats it to be appended to a prompt.
+pub async fn read_attachments(
+    ctx: &McpContext,
+    refs: &[(ResourceRef, bool)],
+) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let mut attachments = String::new();
+    for (r, updated) in refs {
+        let parts = ctx
+            .read_resource(&r.server, &r.uri)
+            .await
+            .map_err(|err| format!("Failed to read @{}:{}: {err}", r.server, r.uri))?;
+        let text = ToolOutput {
+            parts,
+            ..ToolOutput::default()
+        }
+        .to_text();
+        let updated = if *updated { " updated=\"true\"" } else { "" };
+        attachments.push_str(&format!(
+            "\n\n<resource server=\"{}\" uri=\"{}\"{updated}>\n{text}\n</resource>",
+            r.server, r.uri
+        ));
+    }
+    Ok(attachments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_mentions_of_known_servers() {
+        let servers = HashSet::from(["files".to_string()]);
+        let refs = parse_mentions(
+            "compare @files:file:///a.rs with @files:file:///b.rs and @other:x, mail a@b.c @files:file:///a.rs @files:",
+            &servers,
+        );
+        assert_eq!(
+            refs,
+            vec![
+                ResourceRef {
+                    server: "files".into(),
+                    uri: "file:///a.rs".into(),
+                },
+                ResourceRef {
+                    server: "files".into(),
+                    uri: "file:///b.rs".into(),
+                },
+            ]
+        );
+    }
+}
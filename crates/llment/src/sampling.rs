@@ -0,0 +1,90 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use clap::ValueEnum;
+use llm::mcp::{SamplingPolicy, SamplingRequest};
+use tokio::sync::{mpsc::UnboundedSender, oneshot, watch};
+
+use crate::app::Update;
+
+/// How sampling requests from MCP servers are handled.
+#[derive(Default, Copy, Clone, Debug, ValueEnum)]
+pub enum SamplingMode {
+    /// Ask before each request, unless the server was always allowed
+    #[default]
+    Ask,
+    Allow,
+    Deny,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SamplingApproval {
+    Once,
+    /// Approve this and all later requests from the same server.
+    Always,
+    Deny,
+}
+
+/// Rejects every request.
+pub struct DenySampling;
+
+#[async_trait]
+impl SamplingPolicy for DenySampling {
+    async fn approve(&self, _request: &SamplingRequest) -> Result<(), String> {
+        Err("sampling is disabled".into())
+    }
+}
+
+/// Asks the user to approve requests through the approval popup.
+pub struct AskSampling {
+    pub update_tx: UnboundedSender<Update>,
+    pub needs_update: watch::Sender<bool>,
+    allowed: Mutex<HashSet<String>>,
+}
+
+impl AskSampling {
+    pub fn new(update_tx: UnboundedSender<Update>, needs_update: watch::Sender<bool>) -> Self {
+        Self {
+            update_tx,
+            needs_update,
+            allowed: Mutex::new(HashSet::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl SamplingPolicy for AskSampling {
+    async fn approve(&self, request: &SamplingRequest) -> Result<(), String> {
+        if self.allowed.lock().unwrap().contains(&request.server) {
+            return Ok(());
+        }
+        let (reply, approval) = oneshot::channel();
+        let _ = self
+            .update_tx
+            .send(Update::SamplingApproval(request.clone(), reply));
+        let _ = self.needs_update.send(true);
+        match approval.await {
+            Ok(SamplingApproval::Once) => Ok(()),
+            Ok(SamplingApproval::Always) => {
+                self.allowed.lock().unwrap().insert(request.server.clone());
+                Ok(())
+            }
+            Ok(SamplingApproval::Deny) | Err(_) => Err("denied by user".into()),
+        }
+    }
+}
+
+pub fn sampling_policy(
+    mode: SamplingMode,
+    update_tx: UnboundedSender<Update>,
+    needs_update: watch::Sender<bool>,
+) -> Arc<dyn SamplingPolicy> {
+    match mode {
+        SamplingMode::Ask => Arc::new(AskSampling::new(update_tx, needs_update)),
+        SamplingMode::Allow => Arc::new(llm::mcp::AllowSampling),
+        SamplingMode::Deny => Arc::new(DenySampling),
+    }
+}
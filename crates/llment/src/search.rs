@@ -0,0 +1,222 @@
+//! Semantic search over the files of the workspace, see `/search`.
+//!
+//! Files are embedded in chunks of lines. Saved sessions, the files written
+//! by `/save`, are embedded one message at a time. The vectors are kept in
+//! an index file and only new or changed chunks are embedded again.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use llm::{ChatMessage, vector_index::VectorIndex};
+use tokio::sync::Mutex;
+
+/// Maximum length of a chunk of a file, in bytes.
+const CHUNK_BYTES: usize = 1200;
+/// Larger files are not indexed.
+const MAX_FILE_BYTES: u64 = 256 * 1024;
+/// Directories that are not indexed, besides hidden ones.
+const SKIP_DIRS: [&str; 2] = ["target", "node_modules"];
+
+pub struct Search {
+    client: llm::Client,
+    index_path: PathBuf,
+    root: PathBuf,
+    /// Loaded from `index_path` by the first search. Held for the whole of
+    /// a search, so that searches update the index one at a time.
+    index: Mutex<Option<VectorIndex>>,
+}
+
+/// A chunk that matches a query.
+pub struct SearchHit {
+    /// `path:line` for files, `path#message` for sessions.
+    pub id: String,
+    pub score: f32,
+    pub text: String,
+}
+
+impl Search {
+    /// Searches the files under `root` with the embedding model of `client`,
+    /// keeping the index at `index_path`.
+    pub fn new(client: llm::Client, index_path: PathBuf, root: PathBuf) -> Self {
+        Self {
+            client,
+            index_path,
+            root,
+            index: Mutex::new(None),
+        }
+    }
+
+    /// Brings the index up to date with the workspace and returns the
+    /// `limit` chunks most similar to `query`.
+    pub async fn search(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, Box<dyn Error + Send + Sync>> {
+        let root = self.root.clone();
+        let documents = tokio::task::spawn_blocking(move || collect_documents(&root)).await?;
+        let mut index = self.index.lock().await;
+        let index = match &mut *index {
+            Some(index) => index,
+            None => index.insert(VectorIndex::load(&self.index_path, self.client.model())?),
+        };
+        let ids: HashSet<&str> = documents.iter().map(|(id, _)| id.as_str()).collect();
+        let before = index.len();
+        index.retain(|e| ids.contains(e.id.as_str()));
+        let changed: Vec<(String, String)> = documents
+            .iter()
+            .filter(|(id, text)| index.get(id).is_none_or(|e| &e.text != text))
+            .cloned()
+            .collect();
+        if !changed.is_empty() || index.len() != before {
+            index.add(&self.client, changed).await?;
+            index.save(&self.index_path)?;
+        }
+        let hits = index.query(&self.client, query, limit).await?;
+        Ok(hits
+            .into_iter()
+            .map(|hit| SearchHit {
+                id: hit.entry.id.clone(),
+                score: hit.score,
+                text: hit.entry.text.clone(),
+            })
+            .collect())
+    }
+}
+
+/// Chunks of the files under `root`, as pairs of id and text.
+fn collect_documents(root: &Path) -> Vec<(String, String)> {
+    let mut documents = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut entries: Vec<_> = entries.flatten().collect();
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.is_dir() {
+                if !SKIP_DIRS.contains(&name.as_ref()) {
+                    dirs.push(path);
+                }
+                continue;
+            }
+            if !meta.is_file() || meta.len() > MAX_FILE_BYTES {
+                continue;
+            }
+            let Ok(text) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let id = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .into_owned();
+            match serde_json::from_str::<Vec<ChatMessage>>(&text) {
+                Ok(session) if name.ends_with(".json") => {
+                    documents.extend(session_documents(&id, &session))
+                }
+                _ => documents.extend(file_chunks(&id, &text)),
+            }
+        }
+    }
+    documents
+}
+
+/// One document per user, assistant and system message of a session.
+fn session_documents(id: &str, session: &[ChatMessage]) -> Vec<(String, String)> {
+    session
+        .iter()
+        .enumerate()
+        .filter_map(|(i, message)| {
+            let (role, text) = match message {
+                ChatMessage::User(m) => ("user", m.content.clone()),
+                ChatMessage::Assistant(m) => ("assistant", m.text()),
+                ChatMessage::System(m) => ("system", m.content.clone()),
+                ChatMessage::Tool(_) => return None,
+            };
+            (!text.trim().is_empty()).then(|| (format!("{id}#{i}"), format!("{role}: {text}")))
+        })
+        .collect()
+}
+
+/// Splits `text` into chunks of whole lines, ids ending with the number of
+/// their first line.
+fn file_chunks(id: &str, text: &str) -> Vec<(String, String)> {
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+    let mut first_line = 1;
+    for (i, line) in text.lines().enumerate() {
+        if !chunk.is_empty() && chunk.len() + line.len() > CHUNK_BYTES {
+            if !chunk.trim().is_empty() {
+                chunks.push((format!("{id}:{first_line}"), std::mem::take(&mut chunk)));
+            }
+            chunk.clear();
+            first_line = i + 1;
+        }
+        chunk.push_str(line);
+        chunk.push('\n');
+    }
+    if !chunk.trim().is_empty() {
+        chunks.push((format!("{id}:{first_line}"), chunk));
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_files_and_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join(".llment")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        let long_line = "x".repeat(CHUNK_BYTES - 10);
+        std::fs::write(
+            root.join("src/main.rs"),
+            format!("fn main() {{}}\n{long_line}\n\nlast\n"),
+        )
+        .unwrap();
+        std::fs::write(root.join(".llment/index.json"), "{}").unwrap();
+        std::fs::write(root.join("target/out"), "built").unwrap();
+        let session = vec![
+            ChatMessage::user("find the bug".into()),
+            ChatMessage::assistant("it is in main".into()),
+        ];
+        std::fs::write(
+            root.join("chat.json"),
+            serde_json::to_string(&session).unwrap(),
+        )
+        .unwrap();
+
+        let documents = collect_documents(root);
+        assert_eq!(
+            documents,
+            vec![
+                ("chat.json#0".to_string(), "user: find the bug".to_string()),
+                (
+                    "chat.json#1".to_string(),
+                    "assistant: it is in main".to_string()
+                ),
+                ("src/main.rs:1".to_string(), "fn main() {}\n".to_string()),
+                (
+                    "src/main.rs:2".to_string(),
+                    format!("{long_line}\n\nlast\n")
+                ),
+            ]
+        );
+    }
+}
@@ -6,10 +6,15 @@ use grep::{
 };
 use ignore::WalkBuilder;
 use rmcp::{
-    ErrorData as McpError, ServerHandler,
+    ErrorData as McpError, RoleServer, ServerHandler,
     handler::server::tool::ToolRouter,
     handler::server::wrapper::Parameters,
-    model::{CallToolResult, Content, ServerCapabilities, ServerInfo},
+    model::{
+        AnnotateAble, CallToolResult, Content, ListResourceTemplatesResult, ListResourcesResult,
+        PaginatedRequestParams, RawResource, RawResourceTemplate, ReadResourceRequestParams,
+        ReadResourceResult, Resource, ResourceContents, ServerCapabilities, ServerInfo,
+    },
+    service::RequestContext,
     tool, tool_handler, tool_router,
 };
 use std::{
@@ -24,6 +29,27 @@ use replace_in_content::replace_in_content;
 
 use rmcp::{schemars::JsonSchema, serde::Deserialize};
 
+/// Number of workspace files returned per `resources/list` page.
+const RESOURCE_PAGE_SIZE: usize = 200;
+
+fn mime_type(path: &Path) -> Option<&'static str> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    match ext.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "svg" => Some("image/svg+xml"),
+        "bmp" => Some("image/bmp"),
+        "pdf" => Some("application/pdf"),
+        _ => None,
+    }
+}
+
 fn default_expected_replacements() -> Option<usize> {
     Some(1)
 }
@@ -206,6 +232,79 @@ impl FsServer {
     fn tool_error(msg: impl Into<String>) -> CallToolResult {
         CallToolResult::error(vec![Content::text(msg.into())])
     }
+
+    fn resource_uri(&self, path: &Path) -> String {
+        format!("file://{}", self.display_path(path))
+    }
+
+    /// Lists the files in the workspace, respecting ignore files, as resources.
+    fn workspace_resources(&self) -> Vec<Resource> {
+        let mut builder = WalkBuilder::new(&self.workspace_root);
+        builder.git_ignore(true);
+        builder.standard_filters(true);
+        let mut files: Vec<PathBuf> = builder
+            .build()
+            .flatten()
+            .filter(|entry| entry.file_type().is_some_and(|ft| ft.is_file()))
+            .filter_map(|entry| fs::canonicalize(entry.path()).ok())
+            .filter(|path| path.starts_with(&self.workspace_root))
+            .collect();
+        files.sort();
+        files
+            .iter()
+            .map(|path| {
+                let name = path
+                    .strip_prefix(&self.workspace_root)
+                    .unwrap_or(path)
+                    .display()
+                    .to_string();
+                let mut resource = RawResource::new(self.resource_uri(path), name);
+                resource.mime_type = mime_type(path).map(str::to_string);
+                resource.size = fs::metadata(path)
+                    .ok()
+                    .and_then(|m| u32::try_from(m.len()).ok());
+                resource.no_annotation()
+            })
+            .collect()
+    }
+
+    fn read_workspace_resource(&self, uri: &str) -> Result<ReadResourceResult, McpError> {
+        let path = uri
+            .strip_prefix("file://")
+            .ok_or_else(|| McpError::invalid_params(format!("unsupported uri {uri}"), None))?;
+        let canonical_path = self
+            .resolve(path)
+            .map_err(|msg| McpError::resource_not_found(msg, None))?;
+        if !canonical_path.is_file() {
+            return Err(McpError::resource_not_found(
+                format!("{} is not a file", self.display_path(&canonical_path)),
+                None,
+            ));
+        }
+        let data = fs::read(&canonical_path).map_err(|e| {
+            McpError::internal_error(
+                format!(
+                    "failed to read file {}: {e}",
+                    self.display_path(&canonical_path)
+                ),
+                None,
+            )
+        })?;
+        let contents = match String::from_utf8(data) {
+            Ok(text) => ResourceContents::text(text, uri),
+            Err(err) => ResourceContents::BlobResourceContents {
+                uri: uri.to_string(),
+                mime_type: Some(
+                    mime_type(&canonical_path)
+                        .unwrap_or("application/octet-stream")
+                        .to_string(),
+                ),
+                blob: BASE64.encode(err.into_bytes()),
+                meta: None,
+            },
+        };
+        Ok(ReadResourceResult::new(vec![contents]))
+    }
 }
 
 #[tool_router]
@@ -403,22 +502,7 @@ impl FsServer {
             };
             Ok(CallToolResult::success(vec![Content::text(result)]))
         } else {
-            let ext = canonical_path
-                .extension()
-                .and_then(|e| e.to_str())
-                .unwrap_or("")
-                .to_lowercase();
-            let mime = match ext.as_str() {
-                "png" => Some("image/png"),
-                "jpg" | "jpeg" => Some("image/jpeg"),
-                "gif" => Some("image/gif"),
-                "webp" => Some("image/webp"),
-                "svg" => Some("image/svg+xml"),
-                "bmp" => Some("image/bmp"),
-                "pdf" => Some("application/pdf"),
-                _ => None,
-            };
-            if let Some(mime) = mime {
+            if let Some(mime) = mime_type(&canonical_path) {
                 let encoded = BASE64.encode(data);
                 Ok(CallToolResult::success(vec![Content::image(
                     encoded,
@@ -821,7 +905,56 @@ impl FsServer {
 #[tool_handler]
 impl ServerHandler for FsServer {
     fn get_info(&self) -> ServerInfo {
-        ServerInfo::new(ServerCapabilities::builder().enable_tools().build())
+        ServerInfo::new(
+            ServerCapabilities::builder()
+                .enable_tools()
+                .enable_resources()
+                .build(),
+        )
+    }
+
+    async fn list_resources(
+        &self,
+        request: Option<PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        let start = match request.and_then(|r| r.cursor) {
+            Some(cursor) => cursor
+                .parse::<usize>()
+                .map_err(|_| McpError::invalid_params("invalid cursor", None))?,
+            None => 0,
+        };
+        let resources = self.workspace_resources();
+        let end = (start + RESOURCE_PAGE_SIZE).min(resources.len());
+        let page = resources.get(start..end).unwrap_or_default().to_vec();
+        let mut result = ListResourcesResult::with_all_items(page);
+        if end < resources.len() {
+            result.next_cursor = Some(end.to_string());
+        }
+        Ok(result)
+    }
+
+    async fn list_resource_templates(
+        &self,
+        _request: Option<PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourceTemplatesResult, McpError> {
+        let mut template = RawResourceTemplate::new(
+            format!("file://{}/{{path}}", self.mount_point.display()),
+            "workspace file",
+        );
+        template.description = Some("Any file within the workspace".to_string());
+        Ok(ListResourceTemplatesResult::with_all_items(vec![
+            template.no_annotation(),
+        ]))
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParams,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        self.read_workspace_resource(&request.uri)
     }
 }
 
@@ -1325,6 +1458,47 @@ mod tests {
         assert_eq!(msg_existing, "path must be within the workspace");
     }
 
+    #[test]
+    fn workspace_resources_lists_files() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/a.txt"), "abc").unwrap();
+        fs::write(dir.path().join("logo.png"), [0x89, 0x50]).unwrap();
+        let server = FsServer::new_with_mount_point(dir.path(), "/ws");
+        let resources = server.workspace_resources();
+        let uris: Vec<_> = resources.iter().map(|r| r.raw.uri.as_str()).collect();
+        assert_eq!(uris, vec!["file:///ws/logo.png", "file:///ws/sub/a.txt"]);
+        assert_eq!(resources[0].raw.mime_type.as_deref(), Some("image/png"));
+        assert_eq!(resources[1].raw.name, "sub/a.txt");
+        assert_eq!(resources[1].raw.size, Some(3));
+    }
+
+    #[test]
+    fn read_workspace_resource_reads_text_and_blobs() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "abc").unwrap();
+        fs::write(dir.path().join("logo.png"), [0x89, 0x50, 0xff]).unwrap();
+        let server = FsServer::new_with_mount_point(dir.path(), "/ws");
+        let result = server.read_workspace_resource("file:///ws/a.txt").unwrap();
+        assert!(matches!(
+            &result.contents[..],
+            [ResourceContents::TextResourceContents { text, .. }] if text == "abc"
+        ));
+        let result = server
+            .read_workspace_resource("file:///ws/logo.png")
+            .unwrap();
+        assert!(matches!(
+            &result.contents[..],
+            [ResourceContents::BlobResourceContents { mime_type: Some(m), .. }] if m == "image/png"
+        ));
+        assert!(
+            server
+                .read_workspace_resource("file:///etc/passwd")
+                .is_err()
+        );
+        assert!(server.read_workspace_resource("http://ws/a.txt").is_err());
+    }
+
     #[test]
     fn disable_modification_tools_removes_routes() {
         let dir = tempdir().unwrap();
@@ -18,6 +18,7 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::process::{Child, Command};
 use tokio::sync::{Mutex, mpsc, oneshot};
 use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
 
 const OUTPUT_LIMIT: usize = 10_000;
 const TIME_LIMIT: Duration = Duration::from_secs(10);
@@ -314,6 +315,7 @@ Only a maximum of 10k bytes or 200 lines will be returned."#
     pub async fn run(
         &self,
         Parameters(params): Parameters<RunParams>,
+        ct: CancellationToken,
     ) -> Result<CallToolResult, McpError> {
         let RunParams {
             command,
@@ -330,7 +332,10 @@ Only a maximum of 10k bytes or 200 lines will be returned."#
             Err(e) => return Ok(Self::tool_error(format!("spawn failed: {e}"))),
         };
         let mut state = CommandState::new(handle);
-        let timed_out = collect_output(&mut state, self.time_limit).await;
+        let Some(timed_out) = collect_output_until(&mut state, self.time_limit, &ct).await else {
+            let _ = kill(Pid::from_raw(state.pid), Signal::SIGTERM);
+            return Ok(Self::tool_error("cancelled"));
+        };
         let stdout = state.stdout.clone();
         let stderr = state.stderr.clone();
         let exit_code = state.exit_code;
@@ -369,13 +374,18 @@ Only a maximum of 10k bytes or 200 lines will be returned."#
     pub async fn wait(
         &self,
         Parameters(_p): Parameters<WaitParams>,
+        ct: CancellationToken,
     ) -> Result<CallToolResult, McpError> {
         let mut run_slot = self.run.lock().await;
         let state = match run_slot.as_mut() {
             Some(s) => s,
             None => return Ok(Self::tool_error("no running command")),
         };
-        let timed_out = collect_output(state, self.time_limit).await;
+        let Some(timed_out) = collect_output_until(state, self.time_limit, &ct).await else {
+            let _ = kill(Pid::from_raw(state.pid), Signal::SIGTERM);
+            *run_slot = None;
+            return Ok(Self::tool_error("cancelled"));
+        };
         let stdout = state.stdout[state.stdout_pos..].to_string();
         let stderr = state.stderr[state.stderr_pos..].to_string();
         state.stdout_pos = state.stdout.len();
@@ -466,6 +476,18 @@ async fn collect_output(state: &mut CommandState, limit: Duration) -> bool {
     }
 }
 
+/// Like [`collect_output`], but returns `None` if the client cancels the request.
+async fn collect_output_until(
+    state: &mut CommandState,
+    limit: Duration,
+    ct: &CancellationToken,
+) -> Option<bool> {
+    tokio::select! {
+        timed_out = collect_output(state, limit) => Some(timed_out),
+        _ = ct.cancelled() => None,
+    }
+}
+
 fn handle_chunk(state: &mut CommandState, is_stdout: bool, chunk: String) {
     if state.truncated {
         if !chunk.is_empty() {
@@ -508,7 +530,10 @@ mod tests {
             stdin: None,
             workdir: None,
         };
-        let res: CallToolResult = server.run(Parameters(params)).await.unwrap();
+        let res: CallToolResult = server
+            .run(Parameters(params), CancellationToken::new())
+            .await
+            .unwrap();
         let value: WaitResult =
             serde_json::from_str(&res.content[0].as_text().unwrap().text).unwrap();
         assert_eq!(value.stdout.trim(), "hi");
@@ -530,7 +555,10 @@ mod tests {
             stdin: None,
             workdir: Some(path.join("sub").to_string_lossy().into()),
         };
-        let res: CallToolResult = server.run(Parameters(params)).await.unwrap();
+        let res: CallToolResult = server
+            .run(Parameters(params), CancellationToken::new())
+            .await
+            .unwrap();
         let value: WaitResult =
             serde_json::from_str(&res.content[0].as_text().unwrap().text).unwrap();
         assert_eq!(value.stdout.trim(), "ok");
@@ -552,7 +580,10 @@ mod tests {
             stdin: None,
             workdir: None,
         };
-        let run_res: CallToolResult = server.run(Parameters(params)).await.unwrap();
+        let run_res: CallToolResult = server
+            .run(Parameters(params), CancellationToken::new())
+            .await
+            .unwrap();
         let run_value: WaitResult =
             serde_json::from_str(&run_res.content[0].as_text().unwrap().text).unwrap();
         assert_eq!(run_value.status, "still running, call wait or terminate");
@@ -563,6 +594,30 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn cancel_kills_command() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let server = ShellServer::new_local(dir.path().to_string_lossy().to_string()).await?;
+        let marker = dir.path().join("done");
+        let params = RunParams {
+            command: format!("sleep 1 && touch {}", marker.display()),
+            stdin: None,
+            workdir: None,
+        };
+        let ct = CancellationToken::new();
+        let cancel = ct.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(100)).await;
+            cancel.cancel();
+        });
+        let res: CallToolResult = server.run(Parameters(params), ct).await.unwrap();
+        assert_eq!(res.is_error, Some(true));
+        assert_eq!(res.content[0].as_text().unwrap().text, "cancelled");
+        sleep(Duration::from_secs(2)).await;
+        assert!(!marker.exists());
+        Ok(())
+    }
+
     #[tokio::test]
     async fn lists_tools() -> Result<()> {
         use rmcp::ServiceExt;